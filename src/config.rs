@@ -1,5 +1,10 @@
-use crate::Result;
+use crate::dns::{DnsTransport, RecordType};
+use crate::tests::network_jitter::PingMode;
+use crate::{NetworkTestError, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -49,20 +54,119 @@ pub struct ConnectionPerfConfig {
     pub total_connections: usize,
     pub connection_timeout_ms: u64,
     pub targets: Vec<String>,
+    #[serde(default)]
+    pub concurrency_ramp: Option<ConcurrencyRamp>,
+    #[serde(default)]
+    pub level_hold_ms: u64,
+    #[serde(default = "default_knee_min_success_rate")]
+    pub knee_min_success_rate: f64,
+    #[serde(default = "default_knee_p99_factor")]
+    pub knee_p99_factor: f64,
+    #[serde(default)]
+    pub open_loop_rates: Vec<f64>,
+    #[serde(default = "default_rate_step_duration_sec")]
+    pub rate_step_duration_sec: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConcurrencyRamp {
+    Levels(Vec<usize>),
+    Linear {
+        start: usize,
+        end: usize,
+        step: usize,
+    },
+    Geometric {
+        start: usize,
+        end: usize,
+        factor: f64,
+    },
+}
+
+fn default_knee_min_success_rate() -> f64 {
+    95.0
+}
+
+fn default_knee_p99_factor() -> f64 {
+    3.0
+}
+
+fn default_rate_step_duration_sec() -> u64 {
+    10
+}
+
+impl ConcurrencyRamp {
+    pub fn up_to(max_level: usize) -> Self {
+        ConcurrencyRamp::Geometric {
+            start: 1,
+            end: max_level.max(1),
+            factor: 2.0,
+        }
+    }
+
+    pub fn levels(&self) -> Vec<usize> {
+        let mut levels = match self {
+            ConcurrencyRamp::Levels(levels) => levels.clone(),
+            ConcurrencyRamp::Linear { start, end, step } => {
+                let start = (*start).max(1).min(*end);
+                let mut levels: Vec<usize> = (start..=*end).step_by((*step).max(1)).collect();
+                if levels.last().is_some_and(|last| last < end) {
+                    levels.push(*end);
+                }
+                levels
+            }
+            ConcurrencyRamp::Geometric { start, end, factor } => {
+                let mut levels = Vec::new();
+                let mut level = (*start).max(1);
+                while level < *end {
+                    levels.push(level);
+                    level = ((level as f64 * factor).round() as usize).max(level + 1);
+                }
+                levels.push(*end);
+                levels
+            }
+        };
+
+        levels.retain(|&level| level > 0);
+        levels.dedup();
+        levels
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DnsStabilityConfig {
     pub domains: Vec<String>,
+    #[serde(default = "default_dns_resolvers")]
+    pub resolvers: Vec<String>,
+    #[serde(default)]
+    pub expected_ips: HashMap<String, Vec<IpAddr>>,
+    #[serde(default)]
+    pub record_types: HashMap<String, Vec<RecordType>>,
+    #[serde(default)]
+    pub transport: DnsTransport,
+    #[serde(default)]
+    pub tls_insecure: bool,
+    #[serde(default)]
+    pub cache_analysis: bool,
     pub query_interval_ms: u64,
     pub test_duration_sec: u64,
 }
 
+fn default_dns_resolvers() -> Vec<String> {
+    vec!["8.8.8.8".to_string()]
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkJitterConfig {
     pub ping_interval_ms: u64,
     pub test_duration_sec: u64,
     pub targets: Vec<String>,
+    #[serde(default = "default_ping_modes")]
+    pub modes: Vec<PingMode>,
+}
+
+fn default_ping_modes() -> Vec<PingMode> {
+    vec![PingMode::Connect, PingMode::Tunnel]
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -71,13 +175,50 @@ pub struct ReportingConfig {
     pub output_file: Option<String>,
     pub real_time_metrics: bool,
     pub detailed_logs: bool,
+    #[serde(default)]
+    pub assertions: ReportAssertions,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Pass/fail thresholds for the JUnit report. Each one is checked against
+/// every test that reports the matching figure; unset ones are skipped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReportAssertions {
+    pub min_score: Option<f64>,
+    pub max_p99_ms: Option<f64>,
+    pub max_loss_percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OutputFormat {
     Json,
     Csv,
+    Html,
     Text,
+    Influx,
+    OpenMetrics,
+    Junit,
+    Markdown,
+}
+
+impl FromStr for OutputFormat {
+    type Err = NetworkTestError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().replace('-', "").as_str() {
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "html" => Ok(OutputFormat::Html),
+            "text" => Ok(OutputFormat::Text),
+            "influx" => Ok(OutputFormat::Influx),
+            "openmetrics" => Ok(OutputFormat::OpenMetrics),
+            "junit" => Ok(OutputFormat::Junit),
+            "markdown" => Ok(OutputFormat::Markdown),
+            _ => Err(NetworkTestError::Config(format!(
+                "Unknown report format: {s} (expected json, csv, html, text, influx, \
+                 openmetrics, junit or markdown)"
+            ))),
+        }
+    }
 }
 
 impl Default for Config {
@@ -109,6 +250,12 @@ impl Default for Config {
                     total_connections: 100,
                     connection_timeout_ms: 5000,
                     targets: vec!["8.8.8.8:53".to_string()],
+                    concurrency_ramp: None,
+                    level_hold_ms: 0,
+                    knee_min_success_rate: default_knee_min_success_rate(),
+                    knee_p99_factor: default_knee_p99_factor(),
+                    open_loop_rates: Vec::new(),
+                    rate_step_duration_sec: default_rate_step_duration_sec(),
                 },
                 dns_stability: DnsStabilityConfig {
                     domains: vec![
@@ -116,6 +263,12 @@ impl Default for Config {
                         "github.com".to_string(),
                         "cloudflare.com".to_string(),
                     ],
+                    resolvers: default_dns_resolvers(),
+                    expected_ips: HashMap::new(),
+                    record_types: HashMap::new(),
+                    transport: DnsTransport::default(),
+                    tls_insecure: false,
+                    cache_analysis: false,
                     query_interval_ms: 1000,
                     test_duration_sec: 60,
                 },
@@ -123,13 +276,15 @@ impl Default for Config {
                     ping_interval_ms: 1000,
                     test_duration_sec: 60,
                     targets: vec!["8.8.8.8:53".to_string(), "1.1.1.1:53".to_string()],
+                    modes: default_ping_modes(),
                 },
             },
             reporting: ReportingConfig {
                 output_format: OutputFormat::Json,
                 output_file: None,
-                real_time_metrics: true,
+                real_time_metrics: false,
                 detailed_logs: false,
+                assertions: ReportAssertions::default(),
             },
         }
     }
@@ -160,3 +315,36 @@ impl Config {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrency_ramp_levels() {
+        assert_eq!(
+            ConcurrencyRamp::Linear {
+                start: 5,
+                end: 22,
+                step: 5
+            }
+            .levels(),
+            vec![5, 10, 15, 20, 22]
+        );
+        assert_eq!(
+            ConcurrencyRamp::Linear {
+                start: 20,
+                end: 8,
+                step: 20
+            }
+            .levels(),
+            vec![8]
+        );
+        assert_eq!(ConcurrencyRamp::up_to(10).levels(), vec![1, 2, 4, 8, 10]);
+        assert_eq!(ConcurrencyRamp::up_to(1).levels(), vec![1]);
+        assert_eq!(
+            ConcurrencyRamp::Levels(vec![0, 3, 3, 7]).levels(),
+            vec![3, 7]
+        );
+    }
+}
@@ -0,0 +1,1434 @@
+use crate::socks5::Socks5UdpRelay;
+use crate::tls::{self, TlsHandshake};
+use crate::{NetworkTestError, Phase, Result, Socks5Client};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use tracing::{debug, warn};
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_CNAME: u16 = 5;
+pub const TYPE_MX: u16 = 15;
+pub const TYPE_TXT: u16 = 16;
+pub const TYPE_AAAA: u16 = 28;
+pub const TYPE_SRV: u16 = 33;
+pub const TYPE_HTTPS: u16 = 65;
+pub const CLASS_IN: u16 = 1;
+
+pub const RCODE_NOERROR: u8 = 0;
+pub const RCODE_NXDOMAIN: u8 = 3;
+
+const HEADER_LEN: usize = 12;
+const MAX_CNAME_CHAIN: usize = 16;
+const MAX_HTTP_HEADER: usize = 16 * 1024;
+const MAX_DNS_MESSAGE: usize = 65535;
+const DNS_MESSAGE: &str = "application/dns-message";
+
+#[derive(Debug, Clone)]
+pub struct DnsMessage {
+    pub id: u16,
+    pub flags: u16,
+    pub questions: Vec<DnsQuestion>,
+    pub answers: Vec<DnsRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: u16,
+    pub qclass: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsRecord {
+    pub name: String,
+    pub rtype: u16,
+    pub class: u16,
+    pub ttl: u32,
+    pub data: RecordData,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Cname(String),
+    Mx {
+        preference: u16,
+        exchange: String,
+    },
+    Txt(Vec<String>),
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    /// SvcParams are kept out so rotating ECH keys don't look like churn
+    Https {
+        priority: u16,
+        target: String,
+    },
+    Other(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    pub cname_chain: Vec<String>,
+    pub addresses: Vec<IpAddr>,
+    /// Presentation form of every record of the queried type
+    pub records: Vec<String>,
+    pub min_ttl: Option<u32>,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RecordType {
+    #[default]
+    A,
+    Aaaa,
+    Cname,
+    Mx,
+    Txt,
+    Srv,
+    Https,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsTransport {
+    /// UDP ASSOCIATE, falling back to TCP when the proxy refuses it
+    #[default]
+    Auto,
+    Udp,
+    Tcp,
+    /// DNS-over-TLS (RFC 7858)
+    Dot,
+    /// DNS-over-HTTPS (RFC 8484) using HTTP/1.1 POST
+    Doh,
+}
+
+/// One long-lived UDP ASSOCIATE shared by many concurrent queries. Responses
+/// are matched back to their query by transaction ID and source resolver.
+#[derive(Debug)]
+pub struct DnsUdpSession {
+    relay: Arc<Socks5UdpRelay>,
+    pending: Arc<PendingQueries>,
+    stray_responses: Arc<AtomicU64>,
+    receiver: JoinHandle<()>,
+    pub association_time: Duration,
+}
+
+/// One CONNECT tunnel to a single resolver carrying length-prefixed DNS
+/// messages (RFC 1035 4.2.2), optionally inside TLS for DoT. Queries are
+/// pipelined and matched by ID.
+pub struct DnsTcpSession {
+    server: String,
+    writer: tokio::sync::Mutex<Box<dyn AsyncWrite + Send + Unpin>>,
+    pending: Arc<PendingQueries>,
+    stray_responses: Arc<AtomicU64>,
+    receiver: JoinHandle<()>,
+    pub connect_time: Duration,
+    pub tls: Option<TlsHandshake>,
+}
+
+/// DNS-over-HTTPS over one kept-alive HTTP/1.1 connection. HTTP/1.1 can't
+/// multiplex, so queries sharing a connection take turns.
+pub struct DnsHttpsSession {
+    endpoint: DohEndpoint,
+    stream: tokio::sync::Mutex<TlsStream<TcpStream>>,
+    closed: AtomicBool,
+    pub connect_time: Duration,
+    pub tls: TlsHandshake,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DohEndpoint {
+    pub authority: String,
+    pub path: String,
+}
+
+#[derive(Clone)]
+pub enum DnsSession {
+    Udp(Arc<DnsUdpSession>),
+    Tcp(Arc<DnsTcpSession>),
+    Https(Arc<DnsHttpsSession>),
+}
+
+#[derive(Debug, Default)]
+struct PendingQueries {
+    queries: Mutex<HashMap<u16, PendingQuery>>,
+}
+
+#[derive(Debug)]
+struct PendingQuery {
+    server: String,
+    reply: oneshot::Sender<Vec<u8>>,
+}
+
+pub fn rcode_name(rcode: u8) -> &'static str {
+    match rcode {
+        0 => "NOERROR",
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        _ => "RCODE",
+    }
+}
+
+pub fn build_query(id: u16, domain: &str, qtype: u16) -> Result<Vec<u8>> {
+    let mut packet = Vec::with_capacity(HEADER_LEN + domain.len() + 6);
+
+    packet.extend_from_slice(&id.to_be_bytes());
+    // Standard query with recursion desired, one question
+    packet.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+    for part in domain.trim_end_matches('.').split('.') {
+        if part.is_empty() || part.len() > 63 {
+            return Err(NetworkTestError::Config(format!(
+                "Invalid domain label in {domain}"
+            )));
+        }
+        packet.push(part.len() as u8);
+        packet.extend_from_slice(part.as_bytes());
+    }
+    packet.push(0);
+
+    packet.extend_from_slice(&qtype.to_be_bytes());
+    packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    Ok(packet)
+}
+
+impl RecordType {
+    pub fn code(self) -> u16 {
+        match self {
+            RecordType::A => TYPE_A,
+            RecordType::Aaaa => TYPE_AAAA,
+            RecordType::Cname => TYPE_CNAME,
+            RecordType::Mx => TYPE_MX,
+            RecordType::Txt => TYPE_TXT,
+            RecordType::Srv => TYPE_SRV,
+            RecordType::Https => TYPE_HTTPS,
+        }
+    }
+
+    pub fn is_address(self) -> bool {
+        matches!(self, RecordType::A | RecordType::Aaaa)
+    }
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RecordType::A => "A",
+            RecordType::Aaaa => "AAAA",
+            RecordType::Cname => "CNAME",
+            RecordType::Mx => "MX",
+            RecordType::Txt => "TXT",
+            RecordType::Srv => "SRV",
+            RecordType::Https => "HTTPS",
+        };
+        f.pad(name)
+    }
+}
+
+impl FromStr for RecordType {
+    type Err = NetworkTestError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "A" => Ok(RecordType::A),
+            "AAAA" => Ok(RecordType::Aaaa),
+            "CNAME" => Ok(RecordType::Cname),
+            "MX" => Ok(RecordType::Mx),
+            "TXT" => Ok(RecordType::Txt),
+            "SRV" => Ok(RecordType::Srv),
+            "HTTPS" => Ok(RecordType::Https),
+            _ => Err(NetworkTestError::Config(format!(
+                "Unsupported record type '{s}', expected A, AAAA, CNAME, MX, TXT, SRV or HTTPS"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for RecordData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordData::A(ip) => write!(f, "{ip}"),
+            RecordData::Aaaa(ip) => write!(f, "{ip}"),
+            RecordData::Cname(target) => write!(f, "{target}"),
+            RecordData::Mx {
+                preference,
+                exchange,
+            } => write!(f, "{preference} {exchange}"),
+            RecordData::Txt(strings) => {
+                let quoted: Vec<String> = strings.iter().map(|s| format!("{s:?}")).collect();
+                f.write_str(&quoted.join(" "))
+            }
+            RecordData::Srv {
+                priority,
+                weight,
+                port,
+                target,
+            } => write!(f, "{priority} {weight} {port} {target}"),
+            RecordData::Https { priority, target } if target.is_empty() => {
+                write!(f, "{priority} .")
+            }
+            RecordData::Https { priority, target } => write!(f, "{priority} {target}"),
+            // RFC 3597 generic form
+            RecordData::Other(rdata) => {
+                write!(f, "\\# {}", rdata.len())?;
+                if !rdata.is_empty() {
+                    f.write_str(" ")?;
+                    for byte in rdata {
+                        write!(f, "{byte:02x}")?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for DnsTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DnsTransport::Auto => "auto",
+            DnsTransport::Udp => "udp",
+            DnsTransport::Tcp => "tcp",
+            DnsTransport::Dot => "dot",
+            DnsTransport::Doh => "doh",
+        };
+        f.write_str(name)
+    }
+}
+
+impl DnsTransport {
+    /// Adds the transport's standard port to a resolver given without one.
+    pub fn resolver_address(self, resolver: &str) -> String {
+        match self {
+            // DoH resolvers may be URLs; DohEndpoint fills in 443 itself
+            DnsTransport::Doh => resolver.to_string(),
+            DnsTransport::Dot => with_default_port(resolver, 853),
+            _ => with_default_port(resolver, 53),
+        }
+    }
+}
+
+impl FromStr for DnsTransport {
+    type Err = NetworkTestError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(DnsTransport::Auto),
+            "udp" => Ok(DnsTransport::Udp),
+            "tcp" => Ok(DnsTransport::Tcp),
+            "dot" => Ok(DnsTransport::Dot),
+            "doh" => Ok(DnsTransport::Doh),
+            _ => Err(NetworkTestError::Config(format!(
+                "Unknown DNS transport '{s}', expected auto, udp, tcp, dot or doh"
+            ))),
+        }
+    }
+}
+
+impl PendingQueries {
+    fn register(&self, server: &str) -> (u16, oneshot::Receiver<Vec<u8>>) {
+        let (reply, response) = oneshot::channel();
+        let mut queries = self.queries.lock().unwrap();
+        let mut id = rand::random::<u16>();
+        while queries.contains_key(&id) {
+            id = rand::random::<u16>();
+        }
+        queries.insert(
+            id,
+            PendingQuery {
+                server: server.to_string(),
+                reply,
+            },
+        );
+        (id, response)
+    }
+
+    fn cancel(&self, id: u16) {
+        self.queries.lock().unwrap().remove(&id);
+    }
+
+    /// Hands a response to its waiting query; returns false for strays.
+    fn complete(&self, response: &[u8], source: &str) -> bool {
+        if response.len() < 2 {
+            return false;
+        }
+
+        let id = u16::from_be_bytes([response[0], response[1]]);
+        let mut queries = self.queries.lock().unwrap();
+        match queries.get(&id) {
+            Some(query) if same_server(&query.server, source) => {
+                if let Some(query) = queries.remove(&id) {
+                    let _ = query.reply.send(response.to_vec());
+                }
+                true
+            }
+            _ => {
+                debug!("Stray DNS response {:#06x} from {}", id, source);
+                false
+            }
+        }
+    }
+
+    /// Dropping the senders fails every outstanding query immediately.
+    fn clear(&self) {
+        self.queries.lock().unwrap().clear();
+    }
+
+    async fn wait(
+        &self,
+        id: u16,
+        response: oneshot::Receiver<Vec<u8>>,
+        sent_at: Instant,
+        domain: &str,
+        qtype: u16,
+        timeout_after: Duration,
+    ) -> Result<(Duration, DnsMessage)> {
+        let response = match tokio::time::timeout(timeout_after, response).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => {
+                return Err(NetworkTestError::protocol(
+                    Phase::Exchange,
+                    "DNS session closed before the response arrived",
+                ));
+            }
+            Err(_) => {
+                // A late response will now be counted as stray
+                self.cancel(id);
+                return Err(NetworkTestError::timeout(
+                    Phase::Exchange,
+                    format!("DNS query timeout for {domain}"),
+                ));
+            }
+        };
+        let rtt = sent_at.elapsed();
+
+        let message = DnsMessage::parse(&response)?;
+        message.validate_for(id, domain, qtype)?;
+        Ok((rtt, message))
+    }
+}
+
+impl DnsUdpSession {
+    pub async fn open(client: &Socks5Client) -> Result<Self> {
+        let association_start = Instant::now();
+        let relay = Arc::new(client.udp_associate().await?);
+        let association_time = association_start.elapsed();
+
+        let pending: Arc<PendingQueries> = Arc::default();
+        let stray_responses = Arc::new(AtomicU64::new(0));
+        let receiver = tokio::spawn(Self::dispatch_responses(
+            Arc::clone(&relay),
+            Arc::clone(&pending),
+            Arc::clone(&stray_responses),
+        ));
+
+        Ok(Self {
+            relay,
+            pending,
+            stray_responses,
+            receiver,
+            association_time,
+        })
+    }
+
+    pub fn is_alive(&self) -> bool {
+        !self.receiver.is_finished()
+    }
+
+    pub fn stray_responses(&self) -> u64 {
+        self.stray_responses.load(Ordering::Relaxed)
+    }
+
+    /// Sends one query and waits for its matching response, returning the
+    /// round trip measured from send to receive.
+    pub async fn query(
+        &self,
+        server: &str,
+        domain: &str,
+        qtype: u16,
+        timeout_after: Duration,
+    ) -> Result<(Duration, DnsMessage)> {
+        let (id, response) = self.pending.register(server);
+
+        let sent_at = Instant::now();
+        let sent = match build_query(id, domain, qtype) {
+            Ok(packet) => self.relay.send_to(&packet, server).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = sent {
+            self.pending.cancel(id);
+            return Err(e);
+        }
+
+        self.pending
+            .wait(id, response, sent_at, domain, qtype, timeout_after)
+            .await
+    }
+
+    async fn dispatch_responses(
+        relay: Arc<Socks5UdpRelay>,
+        pending: Arc<PendingQueries>,
+        stray_responses: Arc<AtomicU64>,
+    ) {
+        let mut buffer = [0u8; 1024];
+
+        loop {
+            let (n, source) = match relay.recv_from(&mut buffer).await {
+                Ok(received) => received,
+                Err(e @ NetworkTestError::PhaseIo { .. }) => {
+                    warn!("UDP relay receive failed, closing DNS session: {}", e);
+                    break;
+                }
+                Err(e) => {
+                    stray_responses.fetch_add(1, Ordering::Relaxed);
+                    debug!("Discarding malformed relay packet: {}", e);
+                    continue;
+                }
+            };
+
+            if !pending.complete(&buffer[..n], &source) {
+                stray_responses.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        pending.clear();
+    }
+}
+
+impl Drop for DnsUdpSession {
+    fn drop(&mut self) {
+        self.receiver.abort();
+    }
+}
+
+impl DnsTcpSession {
+    pub async fn open(client: &Socks5Client, server: &str) -> Result<Self> {
+        let connect_start = Instant::now();
+        let stream = client.connect(server).await?;
+        let connect_time = connect_start.elapsed();
+
+        let (reader, writer) = stream.into_split();
+        Ok(Self::start(
+            server,
+            Box::new(reader),
+            Box::new(writer),
+            connect_time,
+            None,
+        ))
+    }
+
+    pub async fn open_tls(
+        client: &Socks5Client,
+        server: &str,
+        connector: &TlsConnector,
+    ) -> Result<Self> {
+        let connect_start = Instant::now();
+        let stream = client.connect(server).await?;
+        let connect_time = connect_start.elapsed();
+
+        let (stream, handshake) = tls::handshake(connector, server, stream).await?;
+        let (reader, writer) = tokio::io::split(stream);
+        Ok(Self::start(
+            server,
+            Box::new(reader),
+            Box::new(writer),
+            connect_time,
+            Some(handshake),
+        ))
+    }
+
+    fn start(
+        server: &str,
+        reader: Box<dyn AsyncRead + Send + Unpin>,
+        writer: Box<dyn AsyncWrite + Send + Unpin>,
+        connect_time: Duration,
+        tls: Option<TlsHandshake>,
+    ) -> Self {
+        let pending: Arc<PendingQueries> = Arc::default();
+        let stray_responses = Arc::new(AtomicU64::new(0));
+        let receiver = tokio::spawn(Self::dispatch_responses(
+            reader,
+            server.to_string(),
+            Arc::clone(&pending),
+            Arc::clone(&stray_responses),
+        ));
+
+        Self {
+            server: server.to_string(),
+            writer: tokio::sync::Mutex::new(writer),
+            pending,
+            stray_responses,
+            receiver,
+            connect_time,
+            tls,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        !self.receiver.is_finished()
+    }
+
+    pub fn stray_responses(&self) -> u64 {
+        self.stray_responses.load(Ordering::Relaxed)
+    }
+
+    pub async fn query(
+        &self,
+        domain: &str,
+        qtype: u16,
+        timeout_after: Duration,
+    ) -> Result<(Duration, DnsMessage)> {
+        let (id, response) = self.pending.register(&self.server);
+
+        let packet = match build_query(id, domain, qtype) {
+            Ok(packet) => packet,
+            Err(e) => {
+                self.pending.cancel(id);
+                return Err(e);
+            }
+        };
+        let mut framed = Vec::with_capacity(packet.len() + 2);
+        framed.extend_from_slice(&(packet.len() as u16).to_be_bytes());
+        framed.extend_from_slice(&packet);
+
+        let sent_at = Instant::now();
+        let written = {
+            let mut writer = self.writer.lock().await;
+            match writer.write_all(&framed).await {
+                // TLS buffers records until flushed
+                Ok(()) => writer.flush().await,
+                Err(e) => Err(e),
+            }
+        };
+        if let Err(e) = written {
+            self.pending.cancel(id);
+            return Err(NetworkTestError::io(Phase::Exchange, e));
+        }
+
+        self.pending
+            .wait(id, response, sent_at, domain, qtype, timeout_after)
+            .await
+    }
+
+    async fn dispatch_responses(
+        mut reader: Box<dyn AsyncRead + Send + Unpin>,
+        server: String,
+        pending: Arc<PendingQueries>,
+        stray_responses: Arc<AtomicU64>,
+    ) {
+        loop {
+            let mut length = [0u8; 2];
+            if let Err(e) = reader.read_exact(&mut length).await {
+                debug!("DNS TCP connection to {} closed: {}", server, e);
+                break;
+            }
+
+            let mut message = vec![0u8; u16::from_be_bytes(length) as usize];
+            if let Err(e) = reader.read_exact(&mut message).await {
+                warn!("DNS TCP connection to {} closed mid-message: {}", server, e);
+                break;
+            }
+
+            if !pending.complete(&message, &server) {
+                stray_responses.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        pending.clear();
+    }
+}
+
+impl Drop for DnsTcpSession {
+    fn drop(&mut self) {
+        self.receiver.abort();
+    }
+}
+
+impl DohEndpoint {
+    /// Accepts `https://host[:port][/path]` or a bare `host:port`; the path
+    /// defaults to `/dns-query`.
+    pub fn parse(resolver: &str) -> Result<Self> {
+        let rest = resolver.strip_prefix("https://").unwrap_or(resolver);
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/dns-query"),
+        };
+        if authority.is_empty() {
+            return Err(NetworkTestError::Config(format!(
+                "Invalid DoH resolver '{resolver}'"
+            )));
+        }
+
+        Ok(Self {
+            authority: with_default_port(authority, 443),
+            path: path.to_string(),
+        })
+    }
+}
+
+impl DnsHttpsSession {
+    pub async fn open(
+        client: &Socks5Client,
+        resolver: &str,
+        connector: &TlsConnector,
+    ) -> Result<Self> {
+        let endpoint = DohEndpoint::parse(resolver)?;
+
+        let connect_start = Instant::now();
+        let stream = client.connect(&endpoint.authority).await?;
+        let connect_time = connect_start.elapsed();
+
+        let (stream, tls) = tls::handshake(connector, &endpoint.authority, stream).await?;
+
+        Ok(Self {
+            endpoint,
+            stream: tokio::sync::Mutex::new(stream),
+            closed: AtomicBool::new(false),
+            connect_time,
+            tls,
+        })
+    }
+
+    pub fn is_alive(&self) -> bool {
+        !self.closed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the time from sending the request to reading the full
+    /// response, excluding any wait for the shared connection.
+    pub async fn query(
+        &self,
+        domain: &str,
+        qtype: u16,
+        timeout_after: Duration,
+    ) -> Result<(Duration, DnsMessage)> {
+        let id = rand::random::<u16>();
+        let packet = build_query(id, domain, qtype)?;
+
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {DNS_MESSAGE}\r\nAccept: {DNS_MESSAGE}\r\nContent-Length: {}\r\n\r\n",
+            self.endpoint.path,
+            tls::server_host(&self.endpoint.authority),
+            packet.len()
+        )
+        .into_bytes();
+        request.extend_from_slice(&packet);
+
+        let mut stream = self.stream.lock().await;
+        if !self.is_alive() {
+            return Err(NetworkTestError::protocol(
+                Phase::Exchange,
+                "DoH connection already closed",
+            ));
+        }
+
+        let sent_at = Instant::now();
+        let exchange = async {
+            stream
+                .write_all(&request)
+                .await
+                .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+            stream
+                .flush()
+                .await
+                .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+            read_http_response(&mut *stream).await
+        };
+        let response = match tokio::time::timeout(timeout_after, exchange).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(e)) => {
+                self.closed.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
+            Err(_) => {
+                // A half-read response leaves the connection unusable
+                self.closed.store(true, Ordering::Relaxed);
+                return Err(NetworkTestError::timeout(
+                    Phase::Exchange,
+                    format!("DNS query timeout for {domain}"),
+                ));
+            }
+        };
+        let rtt = sent_at.elapsed();
+
+        if response.close {
+            self.closed.store(true, Ordering::Relaxed);
+        }
+        if response.status != 200 {
+            return Err(NetworkTestError::protocol(
+                Phase::Exchange,
+                format!("DoH server returned HTTP {}", response.status),
+            ));
+        }
+
+        let message = DnsMessage::parse(&response.body)?;
+        message.validate_for(id, domain, qtype)?;
+        Ok((rtt, message))
+    }
+}
+
+struct HttpResponse {
+    status: u16,
+    close: bool,
+    body: Vec<u8>,
+}
+
+async fn read_http_response<S: AsyncRead + Unpin>(stream: &mut S) -> Result<HttpResponse> {
+    let mut buffer = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    let header_end = loop {
+        if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+        if buffer.len() > MAX_HTTP_HEADER {
+            return Err(NetworkTestError::protocol(
+                Phase::Exchange,
+                "DoH response headers too large",
+            ));
+        }
+        let n = stream
+            .read(&mut chunk)
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+        if n == 0 {
+            return Err(NetworkTestError::protocol(
+                Phase::Exchange,
+                "DoH connection closed before response headers",
+            ));
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| NetworkTestError::protocol(Phase::Exchange, "Malformed DoH status line"))?;
+
+    let mut content_length = None;
+    let mut close = false;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse::<usize>().ok();
+        } else if name.eq_ignore_ascii_case("connection") {
+            close = value.eq_ignore_ascii_case("close");
+        }
+    }
+    let content_length = content_length.ok_or_else(|| {
+        NetworkTestError::protocol(Phase::Exchange, "DoH response without Content-Length")
+    })?;
+    if content_length > MAX_DNS_MESSAGE {
+        return Err(NetworkTestError::protocol(
+            Phase::Exchange,
+            format!("DoH response body of {content_length} bytes exceeds the DNS message limit"),
+        ));
+    }
+
+    let mut body = buffer.split_off(header_end);
+    if body.len() < content_length {
+        let already = body.len();
+        body.resize(content_length, 0);
+        stream
+            .read_exact(&mut body[already..])
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+    }
+    body.truncate(content_length);
+
+    Ok(HttpResponse {
+        status,
+        close,
+        body,
+    })
+}
+
+impl DnsSession {
+    pub fn transport(&self) -> DnsTransport {
+        match self {
+            DnsSession::Udp(_) => DnsTransport::Udp,
+            DnsSession::Tcp(session) if session.tls.is_some() => DnsTransport::Dot,
+            DnsSession::Tcp(_) => DnsTransport::Tcp,
+            DnsSession::Https(_) => DnsTransport::Doh,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        match self {
+            DnsSession::Udp(session) => session.is_alive(),
+            DnsSession::Tcp(session) => session.is_alive(),
+            DnsSession::Https(session) => session.is_alive(),
+        }
+    }
+
+    pub fn stray_responses(&self) -> u64 {
+        match self {
+            DnsSession::Udp(session) => session.stray_responses(),
+            DnsSession::Tcp(session) => session.stray_responses(),
+            DnsSession::Https(_) => 0,
+        }
+    }
+
+    pub fn tls_handshake(&self) -> Option<TlsHandshake> {
+        match self {
+            DnsSession::Udp(_) => None,
+            DnsSession::Tcp(session) => session.tls,
+            DnsSession::Https(session) => Some(session.tls),
+        }
+    }
+
+    pub async fn query(
+        &self,
+        server: &str,
+        domain: &str,
+        qtype: u16,
+        timeout_after: Duration,
+    ) -> Result<(Duration, DnsMessage)> {
+        match self {
+            DnsSession::Udp(session) => session.query(server, domain, qtype, timeout_after).await,
+            DnsSession::Tcp(session) => session.query(domain, qtype, timeout_after).await,
+            DnsSession::Https(session) => session.query(domain, qtype, timeout_after).await,
+        }
+    }
+}
+
+fn with_default_port(server: &str, port: u16) -> String {
+    if let Ok(ip) = server.parse::<Ipv6Addr>() {
+        return format!("[{ip}]:{port}");
+    }
+    let has_port = server
+        .rsplit_once(':')
+        .is_some_and(|(host, port)| port.parse::<u16>().is_ok() && !host.ends_with(':'));
+    if has_port {
+        server.to_string()
+    } else {
+        format!("{server}:{port}")
+    }
+}
+
+fn same_server(server: &str, source: &str) -> bool {
+    match (server.parse::<SocketAddr>(), source.parse::<SocketAddr>()) {
+        (Ok(server), Ok(source)) => server == source,
+        // Hostname resolvers can't be compared without resolving them
+        _ => true,
+    }
+}
+
+impl DnsMessage {
+    pub fn parse(packet: &[u8]) -> Result<Self> {
+        if packet.len() < HEADER_LEN {
+            return Err(malformed("response shorter than DNS header"));
+        }
+
+        let id = u16::from_be_bytes([packet[0], packet[1]]);
+        let flags = u16::from_be_bytes([packet[2], packet[3]]);
+        let qdcount = u16::from_be_bytes([packet[4], packet[5]]);
+        let ancount = u16::from_be_bytes([packet[6], packet[7]]);
+
+        let mut offset = HEADER_LEN;
+
+        let mut questions = Vec::with_capacity(qdcount as usize);
+        for _ in 0..qdcount {
+            let name = read_name(packet, &mut offset)?;
+            let fixed = read_bytes(packet, &mut offset, 4)?;
+            questions.push(DnsQuestion {
+                name,
+                qtype: u16::from_be_bytes([fixed[0], fixed[1]]),
+                qclass: u16::from_be_bytes([fixed[2], fixed[3]]),
+            });
+        }
+
+        let mut answers = Vec::with_capacity(ancount as usize);
+        for _ in 0..ancount {
+            let name = read_name(packet, &mut offset)?;
+            let fixed = read_bytes(packet, &mut offset, 10)?;
+            let rtype = u16::from_be_bytes([fixed[0], fixed[1]]);
+            let class = u16::from_be_bytes([fixed[2], fixed[3]]);
+            let ttl = u32::from_be_bytes([fixed[4], fixed[5], fixed[6], fixed[7]]);
+            let rdlength = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+
+            let rdata_start = offset;
+            let rdata = read_bytes(packet, &mut offset, rdlength)?;
+            let data = match (rtype, rdlength) {
+                (TYPE_A, 4) => RecordData::A(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3])),
+                (TYPE_AAAA, 16) => {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(rdata);
+                    RecordData::Aaaa(Ipv6Addr::from(octets))
+                }
+                (TYPE_A, _) | (TYPE_AAAA, _) => {
+                    return Err(malformed(&format!(
+                        "address record for {name} has length {rdlength}"
+                    )));
+                }
+                (TYPE_CNAME, _) => {
+                    let mut target_offset = rdata_start;
+                    RecordData::Cname(read_name(packet, &mut target_offset)?)
+                }
+                (TYPE_MX, 3..) => {
+                    let mut exchange_offset = rdata_start + 2;
+                    RecordData::Mx {
+                        preference: u16::from_be_bytes([rdata[0], rdata[1]]),
+                        exchange: read_name(packet, &mut exchange_offset)?,
+                    }
+                }
+                (TYPE_TXT, _) => RecordData::Txt(read_character_strings(rdata)?),
+                (TYPE_SRV, 7..) => {
+                    let mut target_offset = rdata_start + 6;
+                    RecordData::Srv {
+                        priority: u16::from_be_bytes([rdata[0], rdata[1]]),
+                        weight: u16::from_be_bytes([rdata[2], rdata[3]]),
+                        port: u16::from_be_bytes([rdata[4], rdata[5]]),
+                        target: read_name(packet, &mut target_offset)?,
+                    }
+                }
+                (TYPE_HTTPS, 3..) => {
+                    let mut target_offset = rdata_start + 2;
+                    RecordData::Https {
+                        priority: u16::from_be_bytes([rdata[0], rdata[1]]),
+                        target: read_name(packet, &mut target_offset)?,
+                    }
+                }
+                (TYPE_MX, _) | (TYPE_SRV, _) | (TYPE_HTTPS, _) => {
+                    return Err(malformed(&format!(
+                        "type {rtype} record for {name} has length {rdlength}"
+                    )));
+                }
+                _ => RecordData::Other(rdata.to_vec()),
+            };
+
+            answers.push(DnsRecord {
+                name,
+                rtype,
+                class,
+                ttl,
+                data,
+            });
+        }
+
+        Ok(Self {
+            id,
+            flags,
+            questions,
+            answers,
+        })
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.flags & 0x8000 != 0
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.flags & 0x0200 != 0
+    }
+
+    pub fn rcode(&self) -> u8 {
+        (self.flags & 0x000F) as u8
+    }
+
+    /// Checks that this message answers the query we sent rather than some
+    /// other (late, misrouted or spoofed) one.
+    pub fn validate_for(&self, id: u16, domain: &str, qtype: u16) -> Result<()> {
+        if !self.is_response() {
+            return Err(malformed("QR bit not set in response"));
+        }
+        if self.id != id {
+            return Err(malformed(&format!(
+                "response ID {:#06x} does not match query ID {:#06x}",
+                self.id, id
+            )));
+        }
+        if self.is_truncated() {
+            return Err(malformed("truncated response (TC bit set)"));
+        }
+
+        let Some(question) = self.questions.first() else {
+            return Err(malformed("response does not echo the question"));
+        };
+        if !names_equal(&question.name, domain) || question.qtype != qtype {
+            return Err(malformed(&format!(
+                "response is for {} type {}, expected {} type {}",
+                question.name, question.qtype, domain, qtype
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Follows the CNAME chain from `domain` and collects the records of
+    /// `qtype` at its end. CNAME queries stop at the first hop.
+    pub fn resolve(&self, domain: &str, qtype: u16) -> Result<Resolution> {
+        let mut current = domain.trim_end_matches('.').to_string();
+        let mut cname_chain = Vec::new();
+        let mut min_ttl: Option<u32> = None;
+
+        loop {
+            // A CNAME query wants the alias itself, not what it points to
+            if qtype == TYPE_CNAME {
+                break;
+            }
+            let cname = self.answers.iter().find_map(|record| match &record.data {
+                RecordData::Cname(target) if names_equal(&record.name, &current) => {
+                    Some((target.clone(), record.ttl))
+                }
+                _ => None,
+            });
+
+            let Some((target, ttl)) = cname else {
+                break;
+            };
+            if cname_chain.len() >= MAX_CNAME_CHAIN || names_equal(&target, domain) {
+                return Err(malformed(&format!("CNAME loop while resolving {domain}")));
+            }
+
+            min_ttl = Some(min_ttl.map_or(ttl, |m| m.min(ttl)));
+            cname_chain.push(target.clone());
+            current = target;
+        }
+
+        let mut addresses = Vec::new();
+        let mut records = Vec::new();
+        for record in &self.answers {
+            if record.rtype != qtype || !names_equal(&record.name, &current) {
+                continue;
+            }
+            match record.data {
+                RecordData::A(ip) => addresses.push(IpAddr::V4(ip)),
+                RecordData::Aaaa(ip) => addresses.push(IpAddr::V6(ip)),
+                _ => {}
+            }
+            min_ttl = Some(min_ttl.map_or(record.ttl, |m| m.min(record.ttl)));
+            records.push(record.data.to_string());
+        }
+        addresses.sort();
+        addresses.dedup();
+        records.sort();
+        records.dedup();
+
+        Ok(Resolution {
+            cname_chain,
+            addresses,
+            records,
+            min_ttl,
+        })
+    }
+}
+
+fn malformed(reason: &str) -> NetworkTestError {
+    NetworkTestError::protocol(Phase::Exchange, format!("Invalid DNS response: {reason}"))
+}
+
+fn names_equal(a: &str, b: &str) -> bool {
+    a.trim_end_matches('.')
+        .eq_ignore_ascii_case(b.trim_end_matches('.'))
+}
+
+fn read_bytes<'a>(packet: &'a [u8], offset: &mut usize, len: usize) -> Result<&'a [u8]> {
+    let end = offset
+        .checked_add(len)
+        .filter(|&end| end <= packet.len())
+        .ok_or_else(|| malformed("message truncated"))?;
+    let bytes = &packet[*offset..end];
+    *offset = end;
+    Ok(bytes)
+}
+
+fn read_character_strings(rdata: &[u8]) -> Result<Vec<String>> {
+    let mut strings = Vec::new();
+    let mut position = 0;
+    while position < rdata.len() {
+        let len = rdata[position] as usize;
+        let text = rdata
+            .get(position + 1..position + 1 + len)
+            .ok_or_else(|| malformed("TXT string runs past end of record"))?;
+        strings.push(String::from_utf8_lossy(text).into_owned());
+        position += 1 + len;
+    }
+    Ok(strings)
+}
+
+fn read_name(packet: &[u8], offset: &mut usize) -> Result<String> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut position = *offset;
+    let mut jumped = false;
+    let mut jumps = 0;
+
+    loop {
+        let len = *packet
+            .get(position)
+            .ok_or_else(|| malformed("name runs past end of message"))?;
+
+        match len & 0xC0 {
+            0x00 => {
+                position += 1;
+                if len == 0 {
+                    break;
+                }
+                let label = packet
+                    .get(position..position + len as usize)
+                    .ok_or_else(|| malformed("label runs past end of message"))?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                position += len as usize;
+            }
+            0xC0 => {
+                let low = *packet
+                    .get(position + 1)
+                    .ok_or_else(|| malformed("compression pointer truncated"))?;
+                if !jumped {
+                    *offset = position + 2;
+                    jumped = true;
+                }
+                jumps += 1;
+                if jumps > 32 {
+                    return Err(malformed("compression pointer loop"));
+                }
+                position = (((len & 0x3F) as usize) << 8) | low as usize;
+            }
+            _ => return Err(malformed("unsupported label type")),
+        }
+    }
+
+    if !jumped {
+        *offset = position;
+    }
+
+    Ok(labels.join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cname_chain() {
+        let mut packet = build_query(0xBEEF, "www.example.com", TYPE_A).unwrap();
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[7] = 2;
+        // www.example.com CNAME edge.example.com, both names compressed
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x05, 0x00, 0x01, 0, 0, 0x0E, 0x10]);
+        packet.extend_from_slice(&[0x00, 0x07, 4, b'e', b'd', b'g', b'e', 0xC0, 0x10]);
+        // edge.example.com A 192.0.2.7
+        let edge_offset = packet.len() as u8 - 7;
+        packet.extend_from_slice(&[0xC0, edge_offset, 0x00, 0x01, 0x00, 0x01, 0, 0, 0, 60]);
+        packet.extend_from_slice(&[0x00, 0x04, 192, 0, 2, 7]);
+
+        let message = DnsMessage::parse(&packet).unwrap();
+        message
+            .validate_for(0xBEEF, "www.example.com", TYPE_A)
+            .unwrap();
+        assert!(message
+            .validate_for(0x1234, "www.example.com", TYPE_A)
+            .is_err());
+
+        let resolution = message.resolve("www.example.com", TYPE_A).unwrap();
+        assert_eq!(resolution.cname_chain, vec!["edge.example.com".to_string()]);
+        assert_eq!(
+            resolution.addresses,
+            vec!["192.0.2.7".parse::<IpAddr>().unwrap()]
+        );
+        assert_eq!(resolution.min_ttl, Some(60));
+
+        let alias = message.resolve("www.example.com", TYPE_CNAME).unwrap();
+        assert!(alias.cname_chain.is_empty());
+        assert_eq!(alias.records, vec!["edge.example.com".to_string()]);
+        assert!(message
+            .resolve("www.example.com", TYPE_AAAA)
+            .unwrap()
+            .records
+            .is_empty());
+
+        assert!(DnsMessage::parse(&packet[..packet.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn test_parse_typed_rdata() {
+        fn push_record(packet: &mut Vec<u8>, rtype: u16, rdata: &[u8]) {
+            packet.extend_from_slice(&[0xC0, 0x0C]);
+            packet.extend_from_slice(&rtype.to_be_bytes());
+            packet.extend_from_slice(&[0x00, 0x01, 0, 0, 0x01, 0x2C]);
+            packet.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            packet.extend_from_slice(rdata);
+        }
+
+        let mut packet = build_query(0xBEEF, "example.com", TYPE_MX).unwrap();
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[7] = 4;
+        push_record(
+            &mut packet,
+            TYPE_MX,
+            &[0, 10, 4, b'm', b'a', b'i', b'l', 0xC0, 0x0C],
+        );
+        push_record(
+            &mut packet,
+            TYPE_TXT,
+            &[5, b'h', b'e', b'l', b'l', b'o', 0, 2, b'o', b'k'],
+        );
+        push_record(
+            &mut packet,
+            TYPE_SRV,
+            &[0, 1, 0, 5, 0x01, 0xBB, 3, b's', b'i', b'p', 0xC0, 0x0C],
+        );
+        // SvcParams (alpn=h2) follow the target and are dropped
+        push_record(
+            &mut packet,
+            TYPE_HTTPS,
+            &[0, 1, 0xC0, 0x0C, 0, 1, 0, 3, 2, b'h', b'2'],
+        );
+
+        let message = DnsMessage::parse(&packet).unwrap();
+        let data: Vec<_> = message.answers.iter().map(|record| &record.data).collect();
+        assert_eq!(
+            data,
+            vec![
+                &RecordData::Mx {
+                    preference: 10,
+                    exchange: "mail.example.com".to_string(),
+                },
+                &RecordData::Txt(vec!["hello".to_string(), String::new(), "ok".to_string()]),
+                &RecordData::Srv {
+                    priority: 1,
+                    weight: 5,
+                    port: 443,
+                    target: "sip.example.com".to_string(),
+                },
+                &RecordData::Https {
+                    priority: 1,
+                    target: "example.com".to_string(),
+                },
+            ]
+        );
+
+        // A TXT string whose length byte runs past the record
+        let mut truncated = build_query(0xBEEF, "example.com", TYPE_TXT).unwrap();
+        truncated[2] = 0x81;
+        truncated[3] = 0x80;
+        truncated[7] = 1;
+        push_record(&mut truncated, TYPE_TXT, &[5, b'a', b'b']);
+        assert!(DnsMessage::parse(&truncated).is_err());
+
+        // An MX record too short to hold an exchange name
+        truncated.truncate(truncated.len() - 15);
+        push_record(&mut truncated, TYPE_MX, &[0, 10]);
+        assert!(DnsMessage::parse(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_read_character_strings() {
+        assert!(read_character_strings(&[]).unwrap().is_empty());
+        assert_eq!(
+            read_character_strings(&[1, b'a', 0, 2, b'b', b'c']).unwrap(),
+            vec!["a".to_string(), String::new(), "bc".to_string()]
+        );
+        assert!(read_character_strings(&[3, b'a', b'b']).is_err());
+        assert!(read_character_strings(&[1, b'a', 4]).is_err());
+    }
+
+    #[test]
+    fn test_doh_endpoint() {
+        let endpoint = DohEndpoint::parse("https://dns.example/dns-query").unwrap();
+        assert_eq!(endpoint.authority, "dns.example:443");
+        assert_eq!(endpoint.path, "/dns-query");
+
+        let endpoint = DohEndpoint::parse("127.0.0.1:8006").unwrap();
+        assert_eq!(endpoint.authority, "127.0.0.1:8006");
+        assert_eq!(endpoint.path, "/dns-query");
+
+        let endpoint = DohEndpoint::parse("https://[::1]:8443/resolve").unwrap();
+        assert_eq!(endpoint.authority, "[::1]:8443");
+        assert_eq!(endpoint.path, "/resolve");
+
+        assert!(DohEndpoint::parse("https:///dns-query").is_err());
+    }
+
+    #[test]
+    fn test_resolver_default_ports() {
+        assert_eq!(DnsTransport::Udp.resolver_address("8.8.8.8"), "8.8.8.8:53");
+        assert_eq!(DnsTransport::Dot.resolver_address("8.8.8.8"), "8.8.8.8:853");
+        assert_eq!(
+            DnsTransport::Dot.resolver_address("1.1.1.1:8853"),
+            "1.1.1.1:8853"
+        );
+        assert_eq!(DnsTransport::Dot.resolver_address("::1"), "[::1]:853");
+        assert_eq!(
+            DnsTransport::Tcp.resolver_address("dns.example"),
+            "dns.example:53"
+        );
+        assert_eq!(
+            DnsTransport::Doh.resolver_address("https://dns.example/dns-query"),
+            "https://dns.example/dns-query"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_doh_response_length_limit() {
+        let mut response: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nabc";
+        let parsed = read_http_response(&mut response).await.unwrap();
+        assert_eq!(parsed.status, 200);
+        assert_eq!(parsed.body, b"abc");
+
+        let mut response: &[u8] =
+            b"HTTP/1.1 200 OK\r\nContent-Length: 18446744073709551615\r\n\r\n";
+        assert!(read_http_response(&mut response).await.is_err());
+
+        let mut response: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 65536\r\n\r\n";
+        assert!(read_http_response(&mut response).await.is_err());
+    }
+
+    #[test]
+    fn test_pending_queries_match_by_id_and_server() {
+        let pending = PendingQueries::default();
+        let (first_id, mut first) = pending.register("127.0.0.1:53");
+        let (second_id, mut second) = pending.register("127.0.0.1:53");
+        assert_ne!(first_id, second_id);
+
+        let stray_id = (0..=u16::MAX)
+            .find(|id| *id != first_id && *id != second_id)
+            .unwrap();
+        let responses = [
+            (second_id, "127.0.0.1:53"),
+            (stray_id, "127.0.0.1:53"),
+            (first_id, "127.0.0.2:53"),
+            (first_id, "127.0.0.1:53"),
+            (second_id, "127.0.0.1:53"),
+        ];
+        let stray = responses
+            .iter()
+            .filter(|(id, source)| !pending.complete(&id.to_be_bytes(), source))
+            .count();
+
+        // Unknown ID, wrong resolver and a duplicate of an answered query
+        assert_eq!(stray, 3);
+        assert_eq!(first.try_recv().unwrap(), first_id.to_be_bytes());
+        assert_eq!(second.try_recv().unwrap(), second_id.to_be_bytes());
+        assert!(pending.queries.lock().unwrap().is_empty());
+    }
+}
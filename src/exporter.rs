@@ -0,0 +1,905 @@
+use crate::config::{ConcurrencyRamp, Config};
+use crate::metrics::{FailureBreakdown, Metrics, MetricsCollector, Outage};
+use crate::samples::SampleSink;
+use crate::tests::bandwidth::BandwidthTest;
+use crate::tests::connection_perf::ConnectionPerfTest;
+use crate::tests::dns_stability::DnsStabilityTest;
+use crate::tests::network_jitter::NetworkJitterTest;
+use crate::tests::tcp_stability::TcpStabilityTest;
+use crate::{NetworkTestError, Result};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, timeout};
+use tracing::{debug, info, warn};
+
+/// Upper bounds, in seconds, of the latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 14] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Tests the exporter probes in a loop, each configured from `Config.tests`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportedTest {
+    TcpStability,
+    Bandwidth,
+    ConnectionPerf,
+    DnsStability,
+    NetworkJitter,
+}
+
+/// Runs tests against every proxy in a loop and serves the accumulated
+/// results on `/metrics` in the Prometheus text format.
+#[derive(Debug)]
+pub struct Exporter {
+    config: Config,
+    proxies: Vec<String>,
+    tests: Vec<ExportedTest>,
+    interval: Duration,
+    samples: Option<SampleSink>,
+    registry: Arc<Mutex<MetricRegistry>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+type Labels = Vec<(&'static str, String)>;
+
+/// Counters, gauges and histograms keyed by name and label set.
+#[derive(Debug, Default)]
+pub struct MetricRegistry {
+    families: BTreeMap<&'static str, MetricFamily>,
+}
+
+#[derive(Debug)]
+struct MetricFamily {
+    kind: MetricKind,
+    help: &'static str,
+    series: BTreeMap<Labels, MetricValue>,
+}
+
+#[derive(Debug)]
+enum MetricValue {
+    Value(f64),
+    Histogram(Histogram),
+}
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative; the last slot is `+Inf`.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl ExportedTest {
+    pub const ALL: [ExportedTest; 5] = [
+        ExportedTest::TcpStability,
+        ExportedTest::Bandwidth,
+        ExportedTest::ConnectionPerf,
+        ExportedTest::DnsStability,
+        ExportedTest::NetworkJitter,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportedTest::TcpStability => "tcp_stability",
+            ExportedTest::Bandwidth => "bandwidth",
+            ExportedTest::ConnectionPerf => "connection_perf",
+            ExportedTest::DnsStability => "dns_stability",
+            ExportedTest::NetworkJitter => "network_jitter",
+        }
+    }
+}
+
+impl FromStr for ExportedTest {
+    type Err = NetworkTestError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.trim().to_ascii_lowercase().replace('-', "_");
+        ExportedTest::ALL
+            .into_iter()
+            .find(|test| test.name() == name)
+            .ok_or_else(|| {
+                NetworkTestError::Config(format!(
+                    "Unknown exporter test: {s} (expected tcp-stability, bandwidth, \
+                     connection-perf, dns-stability or network-jitter)"
+                ))
+            })
+    }
+}
+
+impl fmt::Display for ExportedTest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Exporter {
+    pub fn new(config: Config, proxies: Vec<String>) -> Self {
+        Self {
+            config,
+            proxies,
+            tests: ExportedTest::ALL.to_vec(),
+            interval: Duration::from_secs(60),
+            samples: None,
+            registry: Arc::new(Mutex::new(MetricRegistry::default())),
+        }
+    }
+
+    pub fn with_tests(mut self, tests: Vec<ExportedTest>) -> Self {
+        if !tests.is_empty() {
+            self.tests = tests;
+        }
+        self
+    }
+
+    pub fn with_interval(mut self, interval_sec: u64) -> Self {
+        self.interval = Duration::from_secs(interval_sec);
+        self
+    }
+
+    pub fn with_samples(mut self, samples: Option<SampleSink>) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub async fn run(&self, listen: SocketAddr) -> Result<()> {
+        if self.proxies.is_empty() {
+            return Err(NetworkTestError::Config(
+                "Exporter needs at least one proxy".to_string(),
+            ));
+        }
+
+        let listener = TcpListener::bind(listen).await?;
+        info!(
+            "Serving Prometheus metrics on http://{}/metrics",
+            listener.local_addr()?
+        );
+        tokio::spawn(serve(listener, self.registry.clone()));
+
+        let tests: Vec<String> = self.tests.iter().map(ToString::to_string).collect();
+        info!(
+            "Probing {} with {}, {:?} between cycles",
+            self.proxies.join(", "),
+            tests.join(", "),
+            self.interval
+        );
+
+        loop {
+            for proxy in &self.proxies {
+                for &test in &self.tests {
+                    for target in self.targets(test) {
+                        self.probe(proxy, test, &target).await;
+                    }
+                }
+            }
+
+            self.lock_registry()
+                .add("nst_probe_cycles", "Completed probe cycles", vec![], 1.0);
+            sleep(self.interval).await;
+        }
+    }
+
+    fn lock_registry(&self) -> std::sync::MutexGuard<'_, MetricRegistry> {
+        self.registry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn targets(&self, test: ExportedTest) -> Vec<String> {
+        let tests = &self.config.tests;
+        match test {
+            ExportedTest::TcpStability => tests.tcp_stability.targets.clone(),
+            ExportedTest::Bandwidth => tests.bandwidth.targets.clone(),
+            ExportedTest::ConnectionPerf => tests.connection_perf.targets.clone(),
+            ExportedTest::DnsStability => tests.dns_stability.resolvers.clone(),
+            ExportedTest::NetworkJitter => tests.network_jitter.targets.clone(),
+        }
+    }
+
+    async fn probe(&self, proxy: &str, test: ExportedTest, target: &str) {
+        debug!("Probing {} via {} against {}", test, proxy, target);
+
+        match self.collect(proxy, test, target).await {
+            Ok(metrics) => self.lock_registry().observe(proxy, Some(target), &metrics),
+            Err(e) => {
+                warn!("{} via {} against {} failed: {}", test, proxy, target, e);
+                self.lock_registry().run_failed(proxy, test.name(), target);
+            }
+        }
+    }
+
+    /// Runs one test against a single target and collects its `Metrics`.
+    async fn collect(&self, proxy: &str, test: ExportedTest, target: &str) -> Result<Metrics> {
+        let tests = &self.config.tests;
+        let samples = self
+            .samples
+            .as_ref()
+            .map(|sink| sink.recorder(test.name(), 1))
+            .unwrap_or_default();
+        let mut collector = MetricsCollector::new(proxy.to_string());
+
+        match test {
+            ExportedTest::TcpStability => {
+                let config = &tests.tcp_stability;
+                let interval = Duration::from_millis(config.heartbeat_interval_ms).as_secs();
+                let test =
+                    TcpStabilityTest::new(proxy, target, interval.max(1), config.test_duration_sec)
+                        .with_samples(samples);
+                let result = test.execute().await?;
+                collector.set_tcp_stability_metrics(test.to_metrics(&result));
+                collector.add_events(result.events);
+            }
+            ExportedTest::Bandwidth => {
+                let config = &tests.bandwidth;
+                let test =
+                    BandwidthTest::new(proxy, target, config.chunk_size, config.test_duration_sec)
+                        .with_samples(samples);
+                let result = test.execute().await?;
+                collector.set_bandwidth_metrics(test.to_metrics(&result));
+                collector.add_events(result.events);
+            }
+            ExportedTest::ConnectionPerf => {
+                let config = &tests.connection_perf;
+                let ramp = config
+                    .concurrency_ramp
+                    .clone()
+                    .unwrap_or_else(|| ConcurrencyRamp::up_to(config.concurrent_connections));
+                let test = ConnectionPerfTest::new(
+                    proxy,
+                    target,
+                    config.concurrent_connections,
+                    config.total_connections,
+                )
+                .with_ramp(ramp)
+                .with_level_hold(config.level_hold_ms)
+                .with_knee_thresholds(config.knee_min_success_rate, config.knee_p99_factor)
+                .with_open_loop(
+                    config.open_loop_rates.clone(),
+                    config.rate_step_duration_sec,
+                )
+                .with_samples(samples);
+                let result = test.execute().await?;
+                collector.set_connection_perf_metrics(test.to_metrics(&result));
+                collector.add_events(result.events);
+            }
+            ExportedTest::DnsStability => {
+                let config = &tests.dns_stability;
+                let test = DnsStabilityTest::new(
+                    proxy,
+                    config.domains.clone(),
+                    config.query_interval_ms,
+                    config.test_duration_sec,
+                )
+                .with_resolvers(vec![target.to_string()])
+                .with_expected_ips(config.expected_ips.clone())
+                .with_record_types(config.record_types.clone())
+                .with_transport(config.transport)
+                .with_tls_insecure(config.tls_insecure)
+                .with_cache_analysis(config.cache_analysis)
+                .with_samples(samples);
+                let result = test.execute().await?;
+                collector.set_dns_stability_metrics(test.to_metrics(&result));
+                collector.add_events(result.events);
+            }
+            ExportedTest::NetworkJitter => {
+                let config = &tests.network_jitter;
+                let test = NetworkJitterTest::new(
+                    proxy,
+                    vec![target.to_string()],
+                    config.ping_interval_ms,
+                    config.test_duration_sec,
+                )
+                .with_modes(config.modes.clone())
+                .with_samples(samples);
+                let result = test.execute().await?;
+                collector.set_network_jitter_metrics(test.to_metrics(&result));
+                collector.add_events(result.events);
+            }
+        }
+
+        Ok(collector.finalize())
+    }
+}
+
+impl MetricRegistry {
+    /// Folds finished test results into the counters, gauges and histograms,
+    /// labelled by `proxy`, the test and, when given, `target`.
+    pub fn observe(&mut self, proxy: &str, target: Option<&str>, metrics: &Metrics) {
+        let labels = |test: &str| {
+            let mut labels: Labels = vec![("proxy", proxy.to_string()), ("test", test.to_string())];
+            if let Some(target) = target {
+                labels.push(("target", target.to_string()));
+            }
+            labels
+        };
+        let with = |labels: &Labels, name: &'static str, value: &str| {
+            let mut labels = labels.clone();
+            labels.push((name, value.to_string()));
+            labels
+        };
+        let mut finished = Vec::new();
+
+        if let Some(ref tcp) = metrics.tcp_stability {
+            let labels = labels("tcp_stability");
+            self.attempts(&labels, tcp.total_heartbeats, &tcp.failures);
+            self.score(&labels, tcp.stability_score);
+            // Heartbeats only keep min/average/max, so they land at the average
+            self.observe_percentiles(
+                &labels,
+                tcp.successful_heartbeats,
+                tcp.average_rtt,
+                &[(1.0, tcp.average_rtt)],
+            );
+            finished.push(("tcp_stability", labels));
+        }
+
+        if let Some(ref bandwidth) = metrics.bandwidth {
+            let test = if bandwidth.fairness.is_some() {
+                "fairness"
+            } else {
+                "bandwidth"
+            };
+            let labels = labels(test);
+            self.failures(&labels, &bandwidth.failures);
+            self.score(&labels, bandwidth.bandwidth_score);
+            for (direction, bytes, speed) in [
+                (
+                    "sent",
+                    bandwidth.total_bytes_sent,
+                    bandwidth.average_upload_speed,
+                ),
+                (
+                    "received",
+                    bandwidth.total_bytes_received,
+                    bandwidth.average_download_speed,
+                ),
+            ] {
+                self.add(
+                    "nst_bytes",
+                    "Bytes transferred through the proxy",
+                    with(&labels, "direction", direction),
+                    bytes as f64,
+                );
+                self.set(
+                    "nst_throughput_bytes_per_second",
+                    "Average throughput of the last run",
+                    with(&labels, "direction", direction),
+                    speed,
+                );
+            }
+            finished.push((test, labels));
+        }
+
+        if let Some(ref perf) = metrics.connection_perf {
+            let labels = labels("connection_perf");
+            self.attempts(&labels, perf.total_attempts as u64, &perf.failures);
+            self.score(&labels, perf.performance_score);
+            self.observe_percentiles(
+                &labels,
+                perf.successful_connections as u64,
+                perf.average_connection_time,
+                &[
+                    (0.5, perf.median_connection_time),
+                    (0.95, perf.p95_connection_time),
+                    (0.99, perf.p99_connection_time),
+                    (1.0, perf.max_connection_time),
+                ],
+            );
+            finished.push(("connection_perf", labels));
+        }
+
+        if let Some(ref dns) = metrics.dns_stability {
+            let labels = labels("dns_stability");
+            self.attempts(&labels, dns.total_queries, &dns.failures);
+            self.score(&labels, dns.dns_score);
+            // Per-resolver percentiles only describe the whole run with one resolver
+            let points = match dns.per_resolver_metrics.as_slice() {
+                [resolver] => vec![
+                    (0.5, resolver.median_query_time),
+                    (0.95, resolver.p95_query_time),
+                    (0.99, resolver.p99_query_time),
+                    (1.0, dns.max_query_time),
+                ],
+                _ => vec![(1.0, dns.average_query_time)],
+            };
+            self.observe_percentiles(
+                &labels,
+                dns.successful_queries,
+                dns.average_query_time,
+                &points,
+            );
+            finished.push(("dns_stability", labels));
+        }
+
+        if let Some(ref jitter) = metrics.network_jitter {
+            let labels = labels("network_jitter");
+            // Failures cover every ping mode, the top-level totals only the primary one
+            let attempts = match jitter.modes.iter().map(|mode| mode.total_pings).sum() {
+                0 => jitter.total_pings,
+                total => total,
+            };
+            self.attempts(&labels, attempts, &jitter.failures);
+            self.score(&labels, jitter.network_quality_score);
+            self.set(
+                "nst_packet_loss_ratio",
+                "Share of pings lost in the last run",
+                labels.clone(),
+                jitter.packet_loss_rate / 100.0,
+            );
+            self.set(
+                "nst_jitter_seconds",
+                "RFC 3550 interarrival jitter of the last run",
+                labels.clone(),
+                jitter.rfc3550_jitter.as_secs_f64(),
+            );
+            self.observe_percentiles(
+                &labels,
+                jitter.successful_pings,
+                jitter.average_rtt,
+                &[
+                    (0.5, jitter.median_rtt),
+                    (0.95, jitter.p95_rtt),
+                    (0.99, jitter.p99_rtt),
+                    (1.0, jitter.max_rtt),
+                ],
+            );
+            finished.push(("network_jitter", labels));
+        }
+
+        if let Some(ref lul) = metrics.latency_under_load {
+            let labels = labels("latency_under_load");
+            let phases = [
+                ("upload", &lul.upload),
+                ("download", &lul.download),
+                ("duplex", &lul.duplex),
+            ];
+            let probes = phases.iter().map(|(_, phase)| phase.probes).sum();
+            self.attempts(&labels, probes, &lul.failures);
+            for (name, phase) in phases {
+                self.set(
+                    "nst_latency_inflation_seconds",
+                    "Median RTT increase under load",
+                    with(&labels, "phase", name),
+                    phase.median_inflation.as_secs_f64(),
+                );
+            }
+            finished.push(("latency_under_load", labels));
+        }
+
+        if let Some(ref tunnels) = metrics.max_tunnels {
+            let labels = labels("max_tunnels");
+            self.failures(&labels, &tunnels.failures);
+            for phase in &tunnels.phases {
+                self.set(
+                    "nst_tunnel_limit",
+                    "Tunnels held open before the proxy refused more",
+                    with(&labels, "scope", &phase.scope),
+                    phase.limit as f64,
+                );
+            }
+            finished.push(("max_tunnels", labels));
+        }
+
+        if let Some(ref proxy_dns) = metrics.proxy_dns {
+            let labels = labels("proxy_dns");
+            let attempts = proxy_dns.paths.iter().map(|path| path.attempts).sum();
+            self.attempts(&labels, attempts, &proxy_dns.failures);
+            finished.push(("proxy_dns", labels));
+        }
+
+        let outages = metrics.outages();
+        let end = metrics.test_end_time.unwrap_or_else(Utc::now);
+        for (test, labels) in finished {
+            let outages: Vec<&Outage> = outages.iter().filter(|o| o.test == test).collect();
+            self.run_finished(labels, end, &outages);
+        }
+    }
+
+    fn run_finished(&mut self, labels: Labels, end: DateTime<Utc>, outages: &[&Outage]) {
+        let mut succeeded = labels.clone();
+        succeeded.push(("result", "success".to_string()));
+        let outage_time: f64 = outages
+            .iter()
+            .map(|outage| match outage.duration {
+                Some(duration) => duration.as_secs_f64(),
+                None => (end - outage.start)
+                    .to_std()
+                    .unwrap_or_default()
+                    .as_secs_f64(),
+            })
+            .sum();
+
+        self.add("nst_runs", "Test runs by result", succeeded, 1.0);
+        self.set(
+            "nst_last_run_timestamp_seconds",
+            "Unix time the last run finished",
+            labels.clone(),
+            end.timestamp() as f64,
+        );
+        self.add(
+            "nst_outages",
+            "Outages seen, from first failure to recovery",
+            labels.clone(),
+            outages.len() as f64,
+        );
+        self.add(
+            "nst_outage_seconds",
+            "Time spent in outages",
+            labels.clone(),
+            outage_time,
+        );
+        self.set(
+            "nst_outage_active",
+            "1 if the last run ended with a target still failing",
+            labels,
+            if outages.iter().any(|outage| outage.end.is_none()) {
+                1.0
+            } else {
+                0.0
+            },
+        );
+    }
+
+    /// Records a run that could not finish, which counts as an active outage.
+    pub fn run_failed(&mut self, proxy: &str, test: &str, target: &str) {
+        let labels: Labels = vec![
+            ("proxy", proxy.to_string()),
+            ("test", test.to_string()),
+            ("target", target.to_string()),
+        ];
+        let mut failed = labels.clone();
+        failed.push(("result", "error".to_string()));
+
+        self.add("nst_runs", "Test runs by result", failed, 1.0);
+        self.set(
+            "nst_outage_active",
+            "1 if the last run ended with a target still failing",
+            labels,
+            1.0,
+        );
+    }
+
+    fn attempts(&mut self, labels: &Labels, attempts: u64, failures: &FailureBreakdown) {
+        self.add(
+            "nst_attempts",
+            "Probe attempts: heartbeats, connections, queries or pings",
+            labels.clone(),
+            attempts as f64,
+        );
+        self.failures(labels, failures);
+    }
+
+    fn failures(&mut self, labels: &Labels, failures: &FailureBreakdown) {
+        for category in &failures.categories {
+            let mut labels = labels.clone();
+            labels.push(("category", category.category.clone()));
+            self.add(
+                "nst_failures",
+                "Failed attempts by failure category",
+                labels,
+                category.count as f64,
+            );
+        }
+    }
+
+    fn score(&mut self, labels: &Labels, score: f64) {
+        self.set(
+            "nst_score",
+            "Score of the last run, 0 to 100",
+            labels.clone(),
+            score,
+        );
+    }
+
+    /// Adds `count` latencies to the histogram using the run's percentiles:
+    /// each `(fraction, value)` point takes the share of samples between the
+    /// previous fraction and its own, so the last point must be `1.0`.
+    fn observe_percentiles(
+        &mut self,
+        labels: &Labels,
+        count: u64,
+        average: Duration,
+        points: &[(f64, Duration)],
+    ) {
+        let family = self.family(
+            "nst_latency_seconds",
+            MetricKind::Histogram,
+            "Latency of successful attempts",
+        );
+        let MetricValue::Histogram(histogram) = family
+            .series
+            .entry(labels.clone())
+            .or_insert_with(|| MetricValue::Histogram(Histogram::default()))
+        else {
+            return;
+        };
+
+        let mut assigned = 0;
+        for &(fraction, value) in points {
+            let upto = (count as f64 * fraction).round() as u64;
+            histogram.add(value.as_secs_f64(), upto.saturating_sub(assigned));
+            assigned = assigned.max(upto);
+        }
+        histogram.sum += average.as_secs_f64() * count as f64;
+        histogram.count += count;
+    }
+
+    fn family(
+        &mut self,
+        name: &'static str,
+        kind: MetricKind,
+        help: &'static str,
+    ) -> &mut MetricFamily {
+        self.families.entry(name).or_insert_with(|| MetricFamily {
+            kind,
+            help,
+            series: BTreeMap::new(),
+        })
+    }
+
+    fn add(&mut self, name: &'static str, help: &'static str, labels: Labels, by: f64) {
+        let family = self.family(name, MetricKind::Counter, help);
+        if let MetricValue::Value(value) = family
+            .series
+            .entry(labels)
+            .or_insert(MetricValue::Value(0.0))
+        {
+            *value += by;
+        }
+    }
+
+    fn set(&mut self, name: &'static str, help: &'static str, labels: Labels, value: f64) {
+        let family = self.family(name, MetricKind::Gauge, help);
+        family.series.insert(labels, MetricValue::Value(value));
+    }
+
+    /// Renders every series in the Prometheus text exposition format 0.0.4.
+    pub fn render(&self) -> String {
+        self.render_exposition(false)
+    }
+
+    /// Renders every series in the OpenMetrics text format, which names
+    /// counter families without `_total` and ends with `# EOF`.
+    pub fn render_openmetrics(&self) -> String {
+        self.render_exposition(true)
+    }
+
+    fn render_exposition(&self, openmetrics: bool) -> String {
+        let mut out = String::new();
+
+        for (&family_name, family) in &self.families {
+            let (name, kind) = match family.kind {
+                MetricKind::Counter => (format!("{family_name}_total"), "counter"),
+                MetricKind::Gauge => (family_name.to_string(), "gauge"),
+                MetricKind::Histogram => (family_name.to_string(), "histogram"),
+            };
+            let family_name = if openmetrics { family_name } else { &name };
+            out.push_str(&format!("# HELP {} {}\n", family_name, family.help));
+            out.push_str(&format!("# TYPE {} {}\n", family_name, kind));
+
+            for (labels, value) in &family.series {
+                match value {
+                    MetricValue::Value(value) => {
+                        out.push_str(&format!(
+                            "{}{} {}\n",
+                            name,
+                            format_labels(labels, None),
+                            format_value(*value)
+                        ));
+                    }
+                    MetricValue::Histogram(histogram) => {
+                        let mut cumulative = 0;
+                        let bounds = LATENCY_BUCKETS.iter().map(|b| b.to_string());
+                        for (bound, count) in bounds
+                            .chain(std::iter::once("+Inf".to_string()))
+                            .zip(histogram.buckets.iter())
+                        {
+                            cumulative += count;
+                            out.push_str(&format!(
+                                "{}_bucket{} {}\n",
+                                name,
+                                format_labels(labels, Some(&bound)),
+                                cumulative
+                            ));
+                        }
+                        out.push_str(&format!(
+                            "{}_sum{} {}\n",
+                            name,
+                            format_labels(labels, None),
+                            format_value(histogram.sum)
+                        ));
+                        out.push_str(&format!(
+                            "{}_count{} {}\n",
+                            name,
+                            format_labels(labels, None),
+                            histogram.count
+                        ));
+                    }
+                }
+            }
+        }
+
+        if openmetrics {
+            out.push_str("# EOF\n");
+        }
+        out
+    }
+}
+
+impl Histogram {
+    fn add(&mut self, seconds: f64, count: u64) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket] += count;
+    }
+}
+
+fn format_labels(labels: &Labels, le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label(value)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{le}\""));
+    }
+
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+async fn serve(listener: TcpListener, registry: Arc<Mutex<MetricRegistry>>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                let registry = registry.clone();
+                tokio::spawn(async move {
+                    match timeout(REQUEST_TIMEOUT, handle_request(stream, &registry)).await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => debug!("Scrape from {} failed: {}", peer, e),
+                        Err(_) => debug!("Scrape from {} timed out", peer),
+                    }
+                });
+            }
+            Err(e) => warn!("Failed to accept scrape connection: {}", e),
+        }
+    }
+}
+
+async fn handle_request(mut stream: TcpStream, registry: &Mutex<MetricRegistry>) -> Result<()> {
+    let mut request_line = String::new();
+    {
+        let mut reader = BufReader::new(&mut stream);
+        reader.read_line(&mut request_line).await?;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+                break;
+            }
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+
+    let (status, body) = match (method, path) {
+        ("GET", "/metrics") => (
+            "200 OK",
+            registry.lock().unwrap_or_else(|e| e.into_inner()).render(),
+        ),
+        ("GET", _) => (
+            "404 Not Found",
+            "Not found, metrics are at /metrics\n".to_string(),
+        ),
+        _ => (
+            "405 Method Not Allowed",
+            "Only GET is supported\n".to_string(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exported_test_parsing() {
+        assert_eq!(
+            "tcp-stability".parse::<ExportedTest>().unwrap(),
+            ExportedTest::TcpStability
+        );
+        assert_eq!(
+            "network_jitter".parse::<ExportedTest>().unwrap(),
+            ExportedTest::NetworkJitter
+        );
+        assert!("fairness".parse::<ExportedTest>().is_err());
+    }
+
+    #[test]
+    fn test_histogram_from_percentiles() {
+        let mut registry = MetricRegistry::default();
+        let labels: Labels = vec![("test", "connection_perf".to_string())];
+        registry.observe_percentiles(
+            &labels,
+            100,
+            Duration::from_millis(20),
+            &[
+                (0.5, Duration::from_millis(8)),
+                (0.95, Duration::from_millis(40)),
+                (0.99, Duration::from_millis(200)),
+                (1.0, Duration::from_secs(60)),
+            ],
+        );
+
+        let rendered = registry.render();
+        assert!(rendered.contains("# TYPE nst_latency_seconds histogram"));
+        assert!(rendered
+            .contains("nst_latency_seconds_bucket{test=\"connection_perf\",le=\"0.01\"} 50"));
+        assert!(rendered
+            .contains("nst_latency_seconds_bucket{test=\"connection_perf\",le=\"0.05\"} 95"));
+        assert!(
+            rendered.contains("nst_latency_seconds_bucket{test=\"connection_perf\",le=\"30\"} 99")
+        );
+        assert!(rendered
+            .contains("nst_latency_seconds_bucket{test=\"connection_perf\",le=\"+Inf\"} 100"));
+        assert!(rendered.contains("nst_latency_seconds_count{test=\"connection_perf\"} 100"));
+    }
+
+    #[test]
+    fn test_counters_accumulate_and_escape_labels() {
+        let mut registry = MetricRegistry::default();
+        let labels: Labels = vec![("target", "a \"quoted\" host".to_string())];
+        registry.add("nst_attempts", "Attempts", labels.clone(), 3.0);
+        registry.add("nst_attempts", "Attempts", labels, 2.0);
+
+        let rendered = registry.render();
+        assert!(rendered.contains("# TYPE nst_attempts_total counter"));
+        assert!(rendered.contains("nst_attempts_total{target=\"a \\\"quoted\\\" host\"} 5"));
+    }
+}
@@ -1,26 +1,126 @@
 pub mod config;
+pub mod dns;
+pub mod exporter;
+pub mod live;
 pub mod metrics;
 pub mod report;
+pub mod samples;
 pub mod socks5;
 pub mod tests;
+pub mod tls;
 
 pub use config::Config;
 pub use metrics::Metrics;
 pub use report::Report;
-pub use socks5::Socks5Client;
+pub use socks5::{HostResolution, Socks5Client};
+
+use std::fmt;
 
 #[derive(Debug, thiserror::Error)]
 pub enum NetworkTestError {
     #[error("Connection error: {0}")]
     Connection(String),
-    #[error("SOCKS5 error: {0}")]
-    Socks5(String),
+    #[error("SOCKS5 reply {code:#04x} ({}) at {phase}", reply_description(*.phase, *.code))]
+    SocksReply { phase: Phase, code: u8 },
+    #[error("IO error during {phase}: {source}")]
+    PhaseIo {
+        phase: Phase,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Protocol violation during {phase}: {reason}")]
+    Protocol { phase: Phase, reason: String },
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
-    #[error("Timeout error: {0}")]
-    Timeout(String),
+    #[error("Timeout during {phase}: {context}")]
+    Timeout { phase: Phase, context: String },
     #[error("Configuration error: {0}")]
     Config(String),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Phase {
+    Resolve,
+    ProxyConnect,
+    MethodNegotiation,
+    Authentication,
+    Connect,
+    UdpAssociate,
+    TlsHandshake,
+    Exchange,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Phase::Resolve => "local resolution",
+            Phase::ProxyConnect => "proxy connect",
+            Phase::MethodNegotiation => "method negotiation",
+            Phase::Authentication => "authentication",
+            Phase::Connect => "CONNECT",
+            Phase::UdpAssociate => "UDP ASSOCIATE",
+            Phase::TlsHandshake => "TLS handshake",
+            Phase::Exchange => "data exchange",
+        };
+        f.write_str(name)
+    }
+}
+
+pub fn reply_description(phase: Phase, code: u8) -> &'static str {
+    match (phase, code) {
+        (Phase::MethodNegotiation, 0xFF) => "no acceptable authentication methods",
+        (Phase::MethodNegotiation, _) => "unsupported authentication method",
+        (Phase::Authentication, _) => "authentication rejected",
+        (_, 0x01) => "general SOCKS server failure",
+        (_, 0x02) => "connection not allowed by ruleset",
+        (_, 0x03) => "network unreachable",
+        (_, 0x04) => "host unreachable",
+        (_, 0x05) => "connection refused",
+        (_, 0x06) => "TTL expired",
+        (_, 0x07) => "command not supported",
+        (_, 0x08) => "address type not supported",
+        _ => "unassigned reply code",
+    }
+}
+
+impl NetworkTestError {
+    pub fn timeout(phase: Phase, context: impl Into<String>) -> Self {
+        NetworkTestError::Timeout {
+            phase,
+            context: context.into(),
+        }
+    }
+
+    pub fn io(phase: Phase, source: std::io::Error) -> Self {
+        NetworkTestError::PhaseIo { phase, source }
+    }
+
+    pub fn protocol(phase: Phase, reason: impl Into<String>) -> Self {
+        NetworkTestError::Protocol {
+            phase,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn phase(&self) -> Option<Phase> {
+        match self {
+            NetworkTestError::SocksReply { phase, .. }
+            | NetworkTestError::PhaseIo { phase, .. }
+            | NetworkTestError::Protocol { phase, .. }
+            | NetworkTestError::Timeout { phase, .. } => Some(*phase),
+            _ => None,
+        }
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            NetworkTestError::Timeout { .. } => true,
+            NetworkTestError::Io(e) | NetworkTestError::PhaseIo { source: e, .. } => {
+                e.kind() == std::io::ErrorKind::TimedOut
+            }
+            _ => false,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, NetworkTestError>;
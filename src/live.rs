@@ -0,0 +1,485 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio::time::interval;
+
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+/// Without a terminal the dashboard prints one line per test this often.
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+const SPARKLINE_WIDTH: usize = 30;
+const MAX_OUTAGE_LINES: usize = 2;
+const LINE_WIDTH: usize = 78;
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Live view of running tests. On a terminal the dashboard is redrawn in
+/// place below the log; otherwise it degrades to periodic summary lines.
+#[derive(Debug, Clone)]
+pub struct LiveMonitor {
+    state: Arc<Mutex<MonitorState>>,
+    tty: bool,
+}
+
+#[derive(Debug, Default)]
+struct MonitorState {
+    panels: Vec<PanelState>,
+    drawn_lines: usize,
+    /// Set while a finished test prints its results, so they are not overdrawn.
+    paused: usize,
+    last_summary: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct PanelState {
+    name: String,
+    started: Option<Instant>,
+    duration: Option<Duration>,
+    finished: Option<(Instant, bool)>,
+    successes: u64,
+    failures: u64,
+    last_rtt: Option<Duration>,
+    rtts: VecDeque<f64>,
+    bytes: u64,
+    sampled_bytes: u64,
+    sampled_at: Option<Instant>,
+    throughputs: VecDeque<f64>,
+    outages: HashMap<String, (Instant, String)>,
+}
+
+/// Handle a test reports into; the default handle is disabled and every
+/// call on it is a no-op.
+#[derive(Debug, Clone, Default)]
+pub struct LivePanel {
+    monitor: Option<LiveMonitor>,
+    index: usize,
+}
+
+/// Keeps the dashboard from redrawing until dropped.
+#[must_use]
+pub struct LivePause {
+    monitor: Option<LiveMonitor>,
+}
+
+pub struct LiveDashboard {
+    monitor: LiveMonitor,
+    renderer: JoinHandle<()>,
+}
+
+/// Log sink that clears the dashboard before each write, so log lines
+/// scroll above it instead of through it.
+pub struct LogWriter {
+    monitor: LiveMonitor,
+}
+
+impl LiveMonitor {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MonitorState::default())),
+            tty: io::stdout().is_terminal(),
+        }
+    }
+
+    pub fn panel(&self, name: impl Into<String>) -> LivePanel {
+        let mut state = self.lock();
+        state.panels.push(PanelState::new(name.into()));
+        LivePanel {
+            monitor: Some(self.clone()),
+            index: state.panels.len() - 1,
+        }
+    }
+
+    pub fn log_writer(&self) -> LogWriter {
+        LogWriter {
+            monitor: self.clone(),
+        }
+    }
+
+    pub fn spawn(&self) -> LiveDashboard {
+        self.lock().last_summary = Some(Instant::now());
+        let monitor = self.clone();
+        let renderer = tokio::spawn(async move {
+            let mut refresh = interval(REFRESH_INTERVAL);
+            loop {
+                refresh.tick().await;
+                monitor.refresh(false);
+            }
+        });
+        LiveDashboard {
+            monitor: self.clone(),
+            renderer,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MonitorState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn refresh(&self, last: bool) {
+        let mut state = self.lock();
+        let now = Instant::now();
+        for panel in state.panels.iter_mut() {
+            panel.sample_throughput(now);
+        }
+
+        let mut stdout = io::stdout().lock();
+        if self.tty {
+            if state.paused > 0 {
+                return;
+            }
+            let frame = state.frame(now);
+            state.erase(&mut stdout);
+            let _ = write!(stdout, "{frame}");
+            state.drawn_lines = if last { 0 } else { frame.lines().count() };
+        } else {
+            let due = state
+                .last_summary
+                .is_none_or(|at| now.duration_since(at) >= SUMMARY_INTERVAL);
+            if !due && !last {
+                return;
+            }
+            state.last_summary = Some(now);
+            for panel in &state.panels {
+                let _ = writeln!(stdout, "[live] {}", panel.summary_line(now));
+            }
+        }
+        let _ = stdout.flush();
+    }
+}
+
+impl Default for LiveMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MonitorState {
+    fn erase(&mut self, out: &mut impl Write) {
+        if self.drawn_lines > 0 {
+            let _ = write!(out, "\r\x1b[{}A\x1b[J", self.drawn_lines);
+            self.drawn_lines = 0;
+        }
+    }
+
+    fn frame(&self, now: Instant) -> String {
+        let running = self
+            .panels
+            .iter()
+            .filter(|p| p.started.is_some() && p.finished.is_none())
+            .count();
+        let mut frame = format!(
+            "=== Live Dashboard: {} running, {} finished ===\n",
+            running,
+            self.panels.iter().filter(|p| p.finished.is_some()).count()
+        );
+        for panel in &self.panels {
+            for line in panel.lines(now) {
+                frame.push_str(&truncate(&line, LINE_WIDTH));
+                frame.push('\n');
+            }
+        }
+        frame
+    }
+}
+
+impl PanelState {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            started: None,
+            duration: None,
+            finished: None,
+            successes: 0,
+            failures: 0,
+            last_rtt: None,
+            rtts: VecDeque::with_capacity(SPARKLINE_WIDTH),
+            bytes: 0,
+            sampled_bytes: 0,
+            sampled_at: None,
+            throughputs: VecDeque::with_capacity(SPARKLINE_WIDTH),
+            outages: HashMap::new(),
+        }
+    }
+
+    fn sample_throughput(&mut self, now: Instant) {
+        if self.finished.is_some() || self.bytes == 0 {
+            return;
+        }
+        if let Some(sampled_at) = self.sampled_at {
+            let window = now.duration_since(sampled_at).as_secs_f64();
+            if window > 0.0 {
+                let rate = (self.bytes - self.sampled_bytes) as f64 / window;
+                push_sample(&mut self.throughputs, rate);
+            }
+        }
+        self.sampled_bytes = self.bytes;
+        self.sampled_at = Some(now);
+    }
+
+    fn elapsed(&self, now: Instant) -> Duration {
+        match (self.started, self.finished) {
+            (Some(started), Some((finished, _))) => finished.duration_since(started),
+            (Some(started), None) => now.duration_since(started),
+            _ => Duration::ZERO,
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        match (self.started, self.finished) {
+            (None, _) => "waiting",
+            (Some(_), None) => "running",
+            (Some(_), Some((_, true))) => "done",
+            (Some(_), Some((_, false))) => "failed",
+        }
+    }
+
+    fn progress(&self, now: Instant) -> String {
+        let elapsed = self.elapsed(now);
+        match self.duration {
+            Some(duration) if self.finished.is_none() => format!(
+                "{} / {} ({} left)",
+                clock(elapsed),
+                clock(duration),
+                clock(duration.saturating_sub(elapsed))
+            ),
+            _ => format!("{} elapsed", clock(elapsed)),
+        }
+    }
+
+    fn lines(&self, now: Instant) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{:<24} {:<8} {}",
+            self.name,
+            self.status(),
+            self.progress(now)
+        )];
+        if let Some(rtt) = self.last_rtt {
+            lines.push(format!(
+                "  RTT  {:<width$} last {rtt:.1?}",
+                sparkline(&self.rtts),
+                width = SPARKLINE_WIDTH
+            ));
+        }
+        if !self.throughputs.is_empty() {
+            lines.push(format!(
+                "  Thru {:<width$} {}",
+                sparkline(&self.throughputs),
+                format_rate(self.throughputs.back().copied().unwrap_or_default()),
+                width = SPARKLINE_WIDTH
+            ));
+        }
+        lines.push(format!(
+            "  OK {}  Failed {}  Outages {}",
+            self.successes,
+            self.failures,
+            self.outages.len()
+        ));
+
+        let until = self.finished.map_or(now, |(finished, _)| finished);
+        let mut outages: Vec<_> = self.outages.iter().collect();
+        outages.sort_by_key(|(_, (since, _))| *since);
+        for (target, (since, cause)) in outages.iter().take(MAX_OUTAGE_LINES) {
+            lines.push(format!(
+                "  ! {} down {:.1?}: {}",
+                target,
+                until.duration_since(*since),
+                cause
+            ));
+        }
+        if outages.len() > MAX_OUTAGE_LINES {
+            lines.push(format!("  ! ... {} more", outages.len() - MAX_OUTAGE_LINES));
+        }
+        lines
+    }
+
+    fn summary_line(&self, now: Instant) -> String {
+        let mut line = format!(
+            "{}: {}, {}, ok={} failed={} outages={}",
+            self.name,
+            self.status(),
+            self.progress(now),
+            self.successes,
+            self.failures,
+            self.outages.len()
+        );
+        if let Some(rtt) = self.last_rtt {
+            line.push_str(&format!(" rtt={rtt:.1?}"));
+        }
+        if let Some(&rate) = self.throughputs.back() {
+            line.push_str(&format!(" throughput={}", format_rate(rate)));
+        }
+        line
+    }
+}
+
+impl LivePanel {
+    pub fn start(&self, duration: Option<Duration>) {
+        self.update(|panel| {
+            panel.started = Some(Instant::now());
+            panel.duration = duration;
+        });
+    }
+
+    pub fn success(&self) {
+        self.update(|panel| panel.successes += 1);
+    }
+
+    pub fn rtt(&self, rtt: Duration) {
+        self.update(|panel| {
+            panel.successes += 1;
+            panel.last_rtt = Some(rtt);
+            push_sample(&mut panel.rtts, rtt.as_secs_f64());
+        });
+    }
+
+    pub fn bytes(&self, bytes: u64) {
+        self.update(|panel| panel.bytes += bytes);
+    }
+
+    pub fn failure(&self) {
+        self.update(|panel| panel.failures += 1);
+    }
+
+    pub fn outage(&self, target: &str, cause: &str) {
+        self.update(|panel| {
+            panel
+                .outages
+                .entry(target.to_string())
+                .or_insert_with(|| (Instant::now(), cause.to_string()));
+        });
+    }
+
+    pub fn recovered(&self, target: &str) {
+        self.update(|panel| {
+            panel.outages.remove(target);
+        });
+    }
+
+    /// Marks the test finished and clears the dashboard so the caller can
+    /// print its results; drawing resumes when the pause is dropped.
+    pub fn finish(&self, success: bool) -> LivePause {
+        let Some(monitor) = self.monitor.as_ref() else {
+            return LivePause { monitor: None };
+        };
+
+        let mut state = monitor.lock();
+        if let Some(panel) = state.panels.get_mut(self.index) {
+            panel.finished = Some((Instant::now(), success));
+        }
+        if monitor.tty {
+            state.erase(&mut io::stdout().lock());
+        }
+        state.paused += 1;
+        LivePause {
+            monitor: Some(monitor.clone()),
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut PanelState)) {
+        if let Some(monitor) = self.monitor.as_ref() {
+            if let Some(panel) = monitor.lock().panels.get_mut(self.index) {
+                f(panel);
+            }
+        }
+    }
+}
+
+impl Drop for LivePause {
+    fn drop(&mut self) {
+        if let Some(monitor) = self.monitor.as_ref() {
+            let mut state = monitor.lock();
+            state.paused = state.paused.saturating_sub(1);
+        }
+    }
+}
+
+impl LiveDashboard {
+    /// Stops redrawing and leaves the final state on screen.
+    pub fn stop(self) {
+        self.renderer.abort();
+        self.monitor.refresh(true);
+    }
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.monitor.lock();
+        let mut stdout = io::stdout().lock();
+        if self.monitor.tty {
+            state.erase(&mut stdout);
+        }
+        stdout.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+fn push_sample(samples: &mut VecDeque<f64>, value: f64) {
+    if samples.len() == SPARKLINE_WIDTH {
+        samples.pop_front();
+    }
+    samples.push_back(value);
+}
+
+/// Scales samples between their own minimum and maximum.
+pub fn sparkline(samples: &VecDeque<f64>) -> String {
+    let min = samples.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = samples.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let span = max - min;
+    samples
+        .iter()
+        .map(|&value| {
+            let level = if span > 0.0 {
+                ((value - min) / span * (SPARK_LEVELS.len() - 1) as f64).round() as usize
+            } else {
+                0
+            };
+            SPARK_LEVELS[level.min(SPARK_LEVELS.len() - 1)]
+        })
+        .collect()
+}
+
+fn format_rate(bytes_per_sec: f64) -> String {
+    if bytes_per_sec >= 1024.0 * 1024.0 {
+        format!("{:.2} MB/s", bytes_per_sec / (1024.0 * 1024.0))
+    } else {
+        format!("{:.2} KB/s", bytes_per_sec / 1024.0)
+    }
+}
+
+fn clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+fn truncate(line: &str, width: usize) -> String {
+    line.chars().take(width).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparkline_scales_to_range() {
+        let samples: VecDeque<f64> = [1.0, 2.0, 3.0, 8.0].into_iter().collect();
+        assert_eq!(sparkline(&samples), "▁▂▃█");
+
+        let flat: VecDeque<f64> = [5.0, 5.0].into_iter().collect();
+        assert_eq!(sparkline(&flat), "▁▁");
+        assert_eq!(sparkline(&VecDeque::new()), "");
+    }
+
+    #[test]
+    fn test_clock_format() {
+        assert_eq!(clock(Duration::from_secs(75)), "01:15");
+        assert_eq!(clock(Duration::from_secs(3725)), "1:02:05");
+    }
+}
@@ -1,5 +1,21 @@
 use clap::{Parser, Subcommand};
-use network_stable_test::{Config, Result};
+use network_stable_test::config::{ConcurrencyRamp, OutputFormat, ReportingConfig};
+use network_stable_test::dns::{DnsTransport, RecordType};
+use network_stable_test::exporter::{ExportedTest, Exporter};
+use network_stable_test::live::{LiveMonitor, LivePanel};
+use network_stable_test::metrics::MetricsCollector;
+use network_stable_test::samples::{SampleRecorder, SampleSink, SampleSinkSpec};
+use network_stable_test::tests::connection_perf::ConnectionPerfTest;
+use network_stable_test::tests::dns_stability::DnsStabilityTest;
+use network_stable_test::tests::fairness::FairnessTest;
+use network_stable_test::tests::max_tunnels::MaxTunnelsTest;
+use network_stable_test::tests::network_jitter::{NetworkJitterTest, PingMode};
+use network_stable_test::tests::proxy_dns::ProxyDnsTest;
+use network_stable_test::{Config, HostResolution, NetworkTestError, Report, Result};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::Mutex;
 use tracing::info;
 
 #[derive(Parser)]
@@ -18,6 +34,18 @@ struct Cli {
 
     #[arg(short = 'j', long, default_value = "1")]
     parallel: usize,
+
+    #[arg(long)]
+    live: bool,
+
+    #[arg(long, value_name = "ndjson[:PATH]")]
+    events: Option<SampleSinkSpec>,
+
+    #[arg(long, value_name = "FORMAT")]
+    report: Option<OutputFormat>,
+
+    #[arg(long, value_name = "FILE")]
+    output: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -62,31 +90,318 @@ enum Commands {
 
         #[arg(short = 'n', long, default_value = "100")]
         total: usize,
+
+        #[arg(long, value_delimiter = ',')]
+        levels: Vec<usize>,
+
+        #[arg(long)]
+        ramp_step: Option<usize>,
+
+        #[arg(long)]
+        ramp_factor: Option<f64>,
+
+        #[arg(long, default_value = "0")]
+        hold_ms: u64,
+
+        #[arg(long)]
+        knee_min_success_rate: Option<f64>,
+
+        #[arg(long)]
+        knee_p99_factor: Option<f64>,
+
+        #[arg(long, value_delimiter = ',')]
+        rates: Vec<f64>,
+
+        #[arg(long, default_value = "10")]
+        rate_step_sec: u64,
+    },
+
+    DnsStability {
+        #[arg(short, long, default_value = "127.0.0.1:1080")]
+        proxy: String,
+
+        #[arg(
+            short = 'r',
+            long = "resolver",
+            value_delimiter = ',',
+            default_value = "8.8.8.8"
+        )]
+        resolvers: Vec<String>,
+
+        #[arg(
+            short = 'n',
+            long = "domain",
+            value_delimiter = ',',
+            default_value = "google.com,github.com,cloudflare.com"
+        )]
+        domains: Vec<String>,
+
+        #[arg(short = 'e', long = "expect", value_name = "DOMAIN=IP")]
+        expected: Vec<String>,
+
+        #[arg(short = 'q', long = "query-type", value_name = "DOMAIN=TYPE[,TYPE]")]
+        query_types: Vec<String>,
+
+        #[arg(short = 't', long, default_value = "auto")]
+        transport: DnsTransport,
+
+        #[arg(short = 'k', long)]
+        insecure: bool,
+
+        #[arg(short = 'a', long)]
+        cache_analysis: bool,
+
+        #[arg(short, long, default_value = "1000")]
+        interval: u64,
+
+        #[arg(short, long, default_value = "60")]
+        duration: u64,
+    },
+
+    LatencyUnderLoad {
+        #[arg(short, long, default_value = "127.0.0.1:1080")]
+        proxy: String,
+
+        #[arg(short = 'l', long, default_value = "127.0.0.1:8002")]
+        load_target: String,
+
+        #[arg(short = 'r', long, default_value = "127.0.0.1:8005")]
+        probe_target: String,
+
+        #[arg(short, long, default_value = "4")]
+        streams: usize,
+
+        #[arg(short, long, default_value = "15")]
+        duration: u64,
+    },
+
+    Fairness {
+        #[arg(short, long, default_value = "127.0.0.1:1080")]
+        proxy: String,
+
+        #[arg(short, long, default_value = "127.0.0.1:8002")]
+        target: String,
+
+        #[arg(short = 'n', long, default_value = "8")]
+        streams: usize,
+
+        #[arg(short, long, default_value = "65536")]
+        size: usize,
+
+        #[arg(short, long, default_value = "30")]
+        duration: u64,
+
+        #[arg(short = 'u', long = "credential", value_name = "USER:PASS")]
+        credentials: Vec<String>,
+    },
+
+    MaxTunnels {
+        #[arg(short, long, default_value = "127.0.0.1:1080")]
+        proxy: String,
+
+        #[arg(short, long, default_value = "127.0.0.1:8003")]
+        target: String,
+
+        #[arg(short, long, default_value = "1000")]
+        max: usize,
+
+        #[arg(short, long, default_value = "10")]
+        interval_ms: u64,
+
+        #[arg(short = 'b', long, default_value = "5")]
+        heartbeat: u64,
+
+        #[arg(short = 'u', long = "credential", value_name = "USER:PASS")]
+        credentials: Vec<String>,
+    },
+
+    ProxyDns {
+        #[arg(short, long, default_value = "127.0.0.1:1080")]
+        proxy: String,
+
+        #[arg(short, long, default_value = "localhost:8001")]
+        target: String,
+
+        #[arg(short = 'a', long = "literal", value_name = "IP:PORT")]
+        literal: Option<String>,
+
+        #[arg(
+            short = 'm',
+            long = "resolution",
+            value_delimiter = ',',
+            default_value = "remote,local"
+        )]
+        resolutions: Vec<HostResolution>,
+
+        #[arg(short = 'n', long, default_value = "20")]
+        rounds: usize,
+
+        #[arg(short, long, default_value = "500")]
+        interval: u64,
+    },
+
+    NetworkJitter {
+        #[arg(short, long, default_value = "127.0.0.1:1080")]
+        proxy: String,
+
+        #[arg(
+            short,
+            long = "target",
+            value_delimiter = ',',
+            default_value = "127.0.0.1:8005"
+        )]
+        targets: Vec<String>,
+
+        #[arg(
+            short = 'm',
+            long = "mode",
+            value_delimiter = ',',
+            default_value = "connect,tunnel"
+        )]
+        modes: Vec<PingMode>,
+
+        #[arg(short, long, default_value = "1000")]
+        interval: u64,
+
+        #[arg(short, long, default_value = "60")]
+        duration: u64,
     },
 
     All {
         #[arg(short, long, default_value = "127.0.0.1:1080")]
         proxy: String,
     },
+
+    Exporter {
+        #[arg(short, long, default_value = "0.0.0.0:9464")]
+        listen: SocketAddr,
+
+        #[arg(short, long = "proxy", value_delimiter = ',')]
+        proxies: Vec<String>,
+
+        #[arg(short, long = "test", value_delimiter = ',')]
+        tests: Vec<ExportedTest>,
+
+        #[arg(short, long, default_value = "60")]
+        interval: u64,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    tracing_subscriber::fmt()
-        .with_env_filter(if cli.verbose { "debug" } else { "info" })
-        .init();
-
-    let _config = if let Some(config_path) = cli.config {
-        Config::from_file(&config_path)?
+    let config = if let Some(ref config_path) = cli.config {
+        Config::from_file(config_path)?
     } else {
         Config::default()
     };
 
+    let mut reporting = config.reporting.clone();
+    if let Some(format) = cli.report {
+        reporting.output_format = format;
+    }
+    if let Some(ref output) = cli.output {
+        reporting.output_file = Some(output.clone());
+    }
+    // A report is written when asked for, either on the command line or through a config file
+    let reports = cli
+        .command
+        .proxy()
+        .filter(|_| cli.config.is_some() || cli.report.is_some() || cli.output.is_some())
+        .map(|proxy| {
+            Mutex::new(
+                (0..cli.parallel)
+                    .map(|_| MetricsCollector::new(proxy.to_string()))
+                    .collect::<Vec<_>>(),
+            )
+        });
+    let report_to_stdout = reports.is_some() && reporting.output_file.is_none();
+    if report_to_stdout && cli.events == Some(SampleSinkSpec::NdjsonStdout) {
+        return Err(NetworkTestError::Config(
+            "The report cannot share stdout with --events ndjson, set --output".to_string(),
+        ));
+    }
+    if report_to_stdout && cli.parallel > 1 {
+        return Err(NetworkTestError::Config(
+            "Parallel instances write one report each, set --output".to_string(),
+        ));
+    }
+
+    let live = (cli.live || config.reporting.real_time_metrics).then(LiveMonitor::new);
+    if live.is_some() && cli.events == Some(SampleSinkSpec::NdjsonStdout) {
+        return Err(NetworkTestError::Config(
+            "The live dashboard cannot share stdout with --events ndjson, use ndjson:PATH"
+                .to_string(),
+        ));
+    }
+    if live.is_some() && report_to_stdout {
+        return Err(NetworkTestError::Config(
+            "The live dashboard cannot share stdout with the report, set --output".to_string(),
+        ));
+    }
+    let filter = if cli.verbose { "debug" } else { "info" };
+    match live {
+        // Log lines must go through the dashboard so they scroll above it
+        Some(ref monitor) => {
+            let monitor = monitor.clone();
+            tracing_subscriber::fmt()
+                .with_env_filter(filter)
+                .with_writer(move || monitor.log_writer())
+                .init();
+        }
+        // Keep stdout for the sample stream or the report
+        None if report_to_stdout || cli.events == Some(SampleSinkSpec::NdjsonStdout) => {
+            tracing_subscriber::fmt()
+                .with_env_filter(filter)
+                .with_writer(std::io::stderr)
+                .init()
+        }
+        None => tracing_subscriber::fmt().with_env_filter(filter).init(),
+    }
+
+    let samples = cli.events.as_ref().map(SampleSink::open).transpose()?;
+    if let (Some(spec), Some(sink)) = (cli.events.as_ref(), samples.as_ref()) {
+        info!(
+            "Streaming samples to {} (session {})",
+            spec,
+            sink.session_id()
+        );
+    }
+    let dashboard = live.as_ref().map(LiveMonitor::spawn);
+    let observers = &Observers {
+        live,
+        samples,
+        reports,
+        report_to_stdout,
+    };
+
     info!("Starting network stability test");
 
-    match cli.command {
+    let mut outcome = run_command(cli.command, config, cli.parallel, observers).await;
+
+    if let Some(dashboard) = dashboard {
+        dashboard.stop();
+    }
+
+    if let Some(ref reports) = observers.reports {
+        let collectors = std::mem::take(&mut *reports.lock().unwrap_or_else(|e| e.into_inner()));
+        // Written even when a test failed, so the report records the error
+        outcome = outcome.and(save_reports(&reporting, collectors));
+    }
+    outcome?;
+
+    info!("Test completed successfully");
+    Ok(())
+}
+
+async fn run_command(
+    command: Commands,
+    config: Config,
+    parallel: usize,
+    observers: &Observers,
+) -> Result<()> {
+    match command {
         Commands::TcpStability {
             proxy,
             target,
@@ -95,10 +410,12 @@ async fn main() -> Result<()> {
         } => {
             info!(
                 "Running TCP stability test with {} parallel instances",
-                cli.parallel
+                parallel
             );
-            run_tcp_stability_test_parallel(&proxy, &target, interval, duration, cli.parallel)
-                .await?;
+            run_tcp_stability_test_parallel(
+                &proxy, &target, interval, duration, parallel, observers,
+            )
+            .await?;
         }
         Commands::Bandwidth {
             proxy,
@@ -108,46 +425,306 @@ async fn main() -> Result<()> {
         } => {
             info!(
                 "Running bandwidth test with {} parallel instances",
-                cli.parallel
+                parallel
             );
-            run_bandwidth_test_parallel(&proxy, &target, size, duration, cli.parallel).await?;
+            run_bandwidth_test_parallel(&proxy, &target, size, duration, parallel, observers)
+                .await?;
         }
         Commands::ConnectionPerf {
             proxy,
             target,
             concurrent,
             total,
+            levels,
+            ramp_step,
+            ramp_factor,
+            hold_ms,
+            knee_min_success_rate,
+            knee_p99_factor,
+            rates,
+            rate_step_sec,
         } => {
             info!(
                 "Running connection performance test with {} parallel instances",
-                cli.parallel
+                parallel
             );
-            run_connection_perf_test_parallel(&proxy, &target, concurrent, total, cli.parallel)
-                .await?;
+            if let Some(rate) = rates.iter().find(|rate| !rate.is_finite() || **rate <= 0.0) {
+                return Err(NetworkTestError::Config(format!(
+                    "Open-loop rates must be positive and finite, got {rate}"
+                )));
+            }
+            let defaults = &config.tests.connection_perf;
+            let ramp = build_concurrency_ramp(
+                concurrent,
+                levels,
+                ramp_step,
+                ramp_factor,
+                defaults.concurrency_ramp.as_ref(),
+            )?;
+            let test = ConnectionPerfTest::new(&proxy, &target, concurrent, total)
+                .with_ramp(ramp)
+                .with_level_hold(hold_ms)
+                .with_knee_thresholds(
+                    knee_min_success_rate.unwrap_or(defaults.knee_min_success_rate),
+                    knee_p99_factor.unwrap_or(defaults.knee_p99_factor),
+                )
+                .with_open_loop(rates, rate_step_sec);
+            run_connection_perf_test_parallel(test, parallel, observers).await?;
+        }
+        Commands::DnsStability {
+            proxy,
+            resolvers,
+            domains,
+            expected,
+            query_types,
+            transport,
+            insecure,
+            cache_analysis,
+            interval,
+            duration,
+        } => {
+            let expected_ips = parse_expected_ips(&expected)?;
+            let record_types = parse_record_types(&query_types)?;
+            info!(
+                "Running DNS stability test with {} parallel instances",
+                parallel
+            );
+            let test = DnsStabilityTest::new(&proxy, domains, interval, duration)
+                .with_resolvers(resolvers)
+                .with_expected_ips(expected_ips)
+                .with_record_types(record_types)
+                .with_transport(transport)
+                .with_tls_insecure(insecure)
+                .with_cache_analysis(cache_analysis);
+            run_dns_stability_test_parallel(test, parallel, observers).await?;
+        }
+        Commands::LatencyUnderLoad {
+            proxy,
+            load_target,
+            probe_target,
+            streams,
+            duration,
+        } => {
+            info!(
+                "Running latency under load test with {} parallel instances",
+                parallel
+            );
+            run_latency_under_load_test_parallel(
+                &proxy,
+                &load_target,
+                &probe_target,
+                streams,
+                duration,
+                parallel,
+                observers,
+            )
+            .await?;
+        }
+        Commands::Fairness {
+            proxy,
+            target,
+            streams,
+            size,
+            duration,
+            credentials,
+        } => {
+            let credentials = parse_credentials(&credentials)?;
+            info!("Running fairness test with {} parallel instances", parallel);
+            let test = FairnessTest::new(&proxy, &target, streams, size, duration)
+                .with_credentials(credentials);
+            run_fairness_test_parallel(test, parallel, observers).await?;
+        }
+        Commands::MaxTunnels {
+            proxy,
+            target,
+            max,
+            interval_ms,
+            heartbeat,
+            credentials,
+        } => {
+            let credentials = parse_credentials(&credentials)?;
+            info!(
+                "Running max tunnels test with {} parallel instances",
+                parallel
+            );
+            let test = MaxTunnelsTest::new(&proxy, &target, max)
+                .with_open_interval(interval_ms)
+                .with_heartbeat_interval(heartbeat)
+                .with_credentials(credentials);
+            run_max_tunnels_test_parallel(test, parallel, observers).await?;
+        }
+        Commands::ProxyDns {
+            proxy,
+            target,
+            literal,
+            resolutions,
+            rounds,
+            interval,
+        } => {
+            info!(
+                "Running proxy DNS test with {} parallel instances",
+                parallel
+            );
+            let test = ProxyDnsTest::new(&proxy, &target, rounds, interval)
+                .with_literal_addr(literal)
+                .with_resolutions(resolutions);
+            run_proxy_dns_test_parallel(test, parallel, observers).await?;
+        }
+        Commands::NetworkJitter {
+            proxy,
+            targets,
+            modes,
+            interval,
+            duration,
+        } => {
+            info!(
+                "Running network jitter test with {} parallel instances",
+                parallel
+            );
+            let test =
+                NetworkJitterTest::new(&proxy, targets, interval, duration).with_modes(modes);
+            run_network_jitter_test_parallel(test, parallel, observers).await?;
         }
         Commands::All { proxy } => {
-            info!("Running all tests with {} parallel instances", cli.parallel);
-            run_all_tests_parallel(&proxy, cli.parallel).await?;
+            info!("Running all tests with {} parallel instances", parallel);
+            run_all_tests_parallel(&proxy, parallel, observers).await?;
+        }
+        Commands::Exporter {
+            listen,
+            mut proxies,
+            tests,
+            interval,
+        } => {
+            if proxies.is_empty() {
+                proxies.push(format!("{}:{}", config.proxy.host, config.proxy.port));
+            }
+            Exporter::new(config, proxies)
+                .with_tests(tests)
+                .with_interval(interval)
+                .with_samples(observers.samples.clone())
+                .run(listen)
+                .await?;
         }
     }
 
-    info!("Test completed successfully");
     Ok(())
 }
 
+impl Commands {
+    /// The proxy under test, for commands that finish with a report.
+    fn proxy(&self) -> Option<&str> {
+        match self {
+            Commands::TcpStability { proxy, .. }
+            | Commands::Bandwidth { proxy, .. }
+            | Commands::ConnectionPerf { proxy, .. }
+            | Commands::DnsStability { proxy, .. }
+            | Commands::LatencyUnderLoad { proxy, .. }
+            | Commands::Fairness { proxy, .. }
+            | Commands::MaxTunnels { proxy, .. }
+            | Commands::ProxyDns { proxy, .. }
+            | Commands::NetworkJitter { proxy, .. }
+            | Commands::All { proxy } => Some(proxy),
+            Commands::Exporter { .. } => None,
+        }
+    }
+}
+
+struct Observers {
+    live: Option<LiveMonitor>,
+    samples: Option<SampleSink>,
+    /// One collector per parallel instance, present when a report is written.
+    reports: Option<Mutex<Vec<MetricsCollector>>>,
+    report_to_stdout: bool,
+}
+
+impl Observers {
+    fn panel(&self, test: &str, instance: usize, parallel: usize) -> LivePanel {
+        match self.live.as_ref() {
+            Some(monitor) if parallel > 1 => monitor.panel(format!("{test} #{}", instance + 1)),
+            Some(monitor) => monitor.panel(test),
+            None => LivePanel::default(),
+        }
+    }
+
+    fn recorder(&self, test: &'static str, instance: usize) -> SampleRecorder {
+        self.samples
+            .as_ref()
+            .map(|sink| sink.recorder(test, instance + 1))
+            .unwrap_or_default()
+            .with_stdout_reserved(self.report_to_stdout)
+    }
+
+    /// Records a test that aborted, so its report still shows the run.
+    fn fail(&self, instance: usize, test: &str, error: NetworkTestError) -> NetworkTestError {
+        self.collect(instance, |collector| collector.add_error(test, &error));
+        error
+    }
+
+    fn collect(&self, instance: usize, add: impl FnOnce(&mut MetricsCollector)) {
+        if let Some(reports) = self.reports.as_ref() {
+            let mut collectors = reports.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(collector) = collectors.get_mut(instance) {
+                add(collector);
+            }
+        }
+    }
+}
+
+fn save_reports(reporting: &ReportingConfig, collectors: Vec<MetricsCollector>) -> Result<()> {
+    let parallel = collectors.len();
+    for (i, collector) in collectors.into_iter().enumerate() {
+        let mut report = Report::new(collector.finalize())
+            .with_format(reporting.output_format)
+            .with_assertions(reporting.assertions.clone());
+        if let Some(ref path) = reporting.output_file {
+            report = report.with_output_file(instance_report_path(path, i, parallel));
+        }
+        report.generate_and_save()?;
+    }
+    Ok(())
+}
+
+/// With parallel instances each one gets its own file, `report-1.json` and so on.
+fn instance_report_path(path: &str, instance: usize, parallel: usize) -> String {
+    if parallel <= 1 {
+        return path.to_string();
+    }
+    let path = Path::new(path);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}-{}.{}", instance + 1, extension.to_string_lossy()),
+        None => format!("{stem}-{}", instance + 1),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
 async fn run_tcp_stability_test_parallel(
     proxy: &str,
     target: &str,
     interval: u64,
     duration: u64,
     parallel: usize,
+    observers: &Observers,
 ) -> Result<()> {
     use network_stable_test::tests::tcp_stability::TcpStabilityTest;
     use tokio::task::JoinSet;
 
     if parallel == 1 {
-        let test = TcpStabilityTest::new(proxy, target, interval, duration);
-        return test.run().await;
+        let test = TcpStabilityTest::new(proxy, target, interval, duration)
+            .with_live(observers.panel("tcp_stability", 0, 1))
+            .with_samples(observers.recorder("tcp_stability", 0));
+        let result = test
+            .run()
+            .await
+            .map_err(|e| observers.fail(0, "tcp_stability", e))?;
+        observers.collect(0, |collector| {
+            collector.set_tcp_stability_metrics(test.to_metrics(&result));
+            collector.add_events(result.events);
+        });
+        return Ok(());
     }
 
     let mut join_set = JoinSet::new();
@@ -155,17 +732,32 @@ async fn run_tcp_stability_test_parallel(
     for i in 0..parallel {
         let proxy = proxy.to_string();
         let target = target.to_string();
+        let panel = observers.panel("tcp_stability", i, parallel);
+        let samples = observers.recorder("tcp_stability", i);
 
         join_set.spawn(async move {
             info!("Starting TCP stability test instance {}", i + 1);
-            let test = TcpStabilityTest::new(&proxy, &target, interval, duration);
-            test.run().await
+            let test = TcpStabilityTest::new(&proxy, &target, interval, duration)
+                .with_live(panel)
+                .with_samples(samples);
+            let result = test
+                .run()
+                .await
+                .map(|result| (test.to_metrics(&result), result.events));
+            (i, result)
         });
     }
 
     while let Some(result) = join_set.join_next().await {
         match result {
-            Ok(test_result) => test_result?,
+            Ok((i, test_result)) => {
+                let (metrics, events) =
+                    test_result.map_err(|e| observers.fail(i, "tcp_stability", e))?;
+                observers.collect(i, |collector| {
+                    collector.set_tcp_stability_metrics(metrics);
+                    collector.add_events(events);
+                });
+            }
             Err(join_error) => {
                 return Err(network_stable_test::NetworkTestError::Connection(format!(
                     "Task join error: {join_error}"
@@ -183,13 +775,24 @@ async fn run_bandwidth_test_parallel(
     size: usize,
     duration: u64,
     parallel: usize,
+    observers: &Observers,
 ) -> Result<()> {
     use network_stable_test::tests::bandwidth::BandwidthTest;
     use tokio::task::JoinSet;
 
     if parallel == 1 {
-        let test = BandwidthTest::new(proxy, target, size, duration);
-        return test.run().await;
+        let test = BandwidthTest::new(proxy, target, size, duration)
+            .with_live(observers.panel("bandwidth", 0, 1))
+            .with_samples(observers.recorder("bandwidth", 0));
+        let result = test
+            .run()
+            .await
+            .map_err(|e| observers.fail(0, "bandwidth", e))?;
+        observers.collect(0, |collector| {
+            collector.set_bandwidth_metrics(test.to_metrics(&result));
+            collector.add_events(result.events);
+        });
+        return Ok(());
     }
 
     let mut join_set = JoinSet::new();
@@ -197,17 +800,32 @@ async fn run_bandwidth_test_parallel(
     for i in 0..parallel {
         let proxy = proxy.to_string();
         let target = target.to_string();
+        let panel = observers.panel("bandwidth", i, parallel);
+        let samples = observers.recorder("bandwidth", i);
 
         join_set.spawn(async move {
             info!("Starting bandwidth test instance {}", i + 1);
-            let test = BandwidthTest::new(&proxy, &target, size, duration);
-            test.run().await
+            let test = BandwidthTest::new(&proxy, &target, size, duration)
+                .with_live(panel)
+                .with_samples(samples);
+            let result = test
+                .run()
+                .await
+                .map(|result| (test.to_metrics(&result), result.events));
+            (i, result)
         });
     }
 
     while let Some(result) = join_set.join_next().await {
         match result {
-            Ok(test_result) => test_result?,
+            Ok((i, test_result)) => {
+                let (metrics, events) =
+                    test_result.map_err(|e| observers.fail(i, "bandwidth", e))?;
+                observers.collect(i, |collector| {
+                    collector.set_bandwidth_metrics(metrics);
+                    collector.add_events(events);
+                });
+            }
             Err(join_error) => {
                 return Err(network_stable_test::NetworkTestError::Connection(format!(
                     "Task join error: {join_error}"
@@ -219,37 +837,258 @@ async fn run_bandwidth_test_parallel(
     Ok(())
 }
 
+fn build_concurrency_ramp(
+    concurrent: usize,
+    levels: Vec<usize>,
+    ramp_step: Option<usize>,
+    ramp_factor: Option<f64>,
+    configured: Option<&ConcurrencyRamp>,
+) -> Result<ConcurrencyRamp> {
+    let ramp = if !levels.is_empty() {
+        ConcurrencyRamp::Levels(levels)
+    } else if let Some(step) = ramp_step {
+        ConcurrencyRamp::Linear {
+            start: step,
+            end: concurrent,
+            step,
+        }
+    } else if let Some(factor) = ramp_factor {
+        if !factor.is_finite() || factor <= 1.0 {
+            return Err(NetworkTestError::Config(format!(
+                "Ramp factor must be greater than 1, got {factor}"
+            )));
+        }
+        ConcurrencyRamp::Geometric {
+            start: 1,
+            end: concurrent.max(1),
+            factor,
+        }
+    } else if let Some(ramp) = configured {
+        ramp.clone()
+    } else {
+        ConcurrencyRamp::up_to(concurrent)
+    };
+    Ok(ramp)
+}
+
 async fn run_connection_perf_test_parallel(
+    test: ConnectionPerfTest,
+    parallel: usize,
+    observers: &Observers,
+) -> Result<()> {
+    use tokio::task::JoinSet;
+
+    if parallel == 1 {
+        let test = test
+            .with_live(observers.panel("connection_perf", 0, 1))
+            .with_samples(observers.recorder("connection_perf", 0));
+        let result = test
+            .run()
+            .await
+            .map_err(|e| observers.fail(0, "connection_perf", e))?;
+        observers.collect(0, |collector| {
+            collector.set_connection_perf_metrics(test.to_metrics(&result));
+            collector.add_events(result.events);
+        });
+        return Ok(());
+    }
+
+    let mut join_set = JoinSet::new();
+
+    for i in 0..parallel {
+        let test = test
+            .clone()
+            .with_live(observers.panel("connection_perf", i, parallel))
+            .with_samples(observers.recorder("connection_perf", i));
+
+        join_set.spawn(async move {
+            info!("Starting connection performance test instance {}", i + 1);
+            let result = test
+                .run()
+                .await
+                .map(|result| (test.to_metrics(&result), result.events));
+            (i, result)
+        });
+    }
+
+    while let Some(result) = join_set.join_next().await {
+        match result {
+            Ok((i, test_result)) => {
+                let (metrics, events) =
+                    test_result.map_err(|e| observers.fail(i, "connection_perf", e))?;
+                observers.collect(i, |collector| {
+                    collector.set_connection_perf_metrics(metrics);
+                    collector.add_events(events);
+                });
+            }
+            Err(join_error) => {
+                return Err(network_stable_test::NetworkTestError::Connection(format!(
+                    "Task join error: {join_error}"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_expected_ips(expected: &[String]) -> Result<HashMap<String, Vec<IpAddr>>> {
+    let mut expected_ips: HashMap<String, Vec<IpAddr>> = HashMap::new();
+    for entry in expected {
+        let (domain, ip) = entry
+            .split_once('=')
+            .and_then(|(domain, ip)| Some((domain, ip.parse::<IpAddr>().ok()?)))
+            .ok_or_else(|| {
+                network_stable_test::NetworkTestError::Config(format!(
+                    "Invalid expected address, expected DOMAIN=IP: {entry}"
+                ))
+            })?;
+        expected_ips.entry(domain.to_string()).or_default().push(ip);
+    }
+    Ok(expected_ips)
+}
+
+fn parse_record_types(query_types: &[String]) -> Result<HashMap<String, Vec<RecordType>>> {
+    let mut record_types: HashMap<String, Vec<RecordType>> = HashMap::new();
+    for entry in query_types {
+        let (domain, types) = entry.split_once('=').ok_or_else(|| {
+            network_stable_test::NetworkTestError::Config(format!(
+                "Invalid query type, expected DOMAIN=TYPE[,TYPE]: {entry}"
+            ))
+        })?;
+        let types = types
+            .split(',')
+            .map(|t| t.trim().parse::<RecordType>())
+            .collect::<Result<Vec<_>>>()?;
+        record_types
+            .entry(domain.to_string())
+            .or_default()
+            .extend(types);
+    }
+    Ok(record_types)
+}
+
+async fn run_dns_stability_test_parallel(
+    test: DnsStabilityTest,
+    parallel: usize,
+    observers: &Observers,
+) -> Result<()> {
+    use tokio::task::JoinSet;
+
+    if parallel == 1 {
+        let test = test
+            .with_live(observers.panel("dns_stability", 0, 1))
+            .with_samples(observers.recorder("dns_stability", 0));
+        let result = test
+            .run()
+            .await
+            .map_err(|e| observers.fail(0, "dns_stability", e))?;
+        observers.collect(0, |collector| {
+            collector.set_dns_stability_metrics(test.to_metrics(&result));
+            collector.add_events(result.events);
+        });
+        return Ok(());
+    }
+
+    let mut join_set = JoinSet::new();
+
+    for i in 0..parallel {
+        let test = test
+            .clone()
+            .with_live(observers.panel("dns_stability", i, parallel))
+            .with_samples(observers.recorder("dns_stability", i));
+
+        join_set.spawn(async move {
+            info!("Starting DNS stability test instance {}", i + 1);
+            let result = test
+                .run()
+                .await
+                .map(|result| (test.to_metrics(&result), result.events));
+            (i, result)
+        });
+    }
+
+    while let Some(result) = join_set.join_next().await {
+        match result {
+            Ok((i, test_result)) => {
+                let (metrics, events) =
+                    test_result.map_err(|e| observers.fail(i, "dns_stability", e))?;
+                observers.collect(i, |collector| {
+                    collector.set_dns_stability_metrics(metrics);
+                    collector.add_events(events);
+                });
+            }
+            Err(join_error) => {
+                return Err(network_stable_test::NetworkTestError::Connection(format!(
+                    "Task join error: {join_error}"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_latency_under_load_test_parallel(
     proxy: &str,
-    target: &str,
-    concurrent: usize,
-    total: usize,
+    load_target: &str,
+    probe_target: &str,
+    streams: usize,
+    duration: u64,
     parallel: usize,
+    observers: &Observers,
 ) -> Result<()> {
-    use network_stable_test::tests::connection_perf::ConnectionPerfTest;
+    use network_stable_test::tests::latency_under_load::LatencyUnderLoadTest;
     use tokio::task::JoinSet;
 
     if parallel == 1 {
-        let test = ConnectionPerfTest::new(proxy, target, concurrent, total);
-        return test.run().await;
+        let test = LatencyUnderLoadTest::new(proxy, load_target, probe_target, streams, duration)
+            .with_live(observers.panel("latency_under_load", 0, 1))
+            .with_samples(observers.recorder("latency_under_load", 0));
+        let result = test
+            .run()
+            .await
+            .map_err(|e| observers.fail(0, "latency_under_load", e))?;
+        observers.collect(0, |collector| {
+            collector.set_latency_under_load_metrics(test.to_metrics(&result));
+            collector.add_events(result.events);
+        });
+        return Ok(());
     }
 
     let mut join_set = JoinSet::new();
 
     for i in 0..parallel {
         let proxy = proxy.to_string();
-        let target = target.to_string();
+        let load_target = load_target.to_string();
+        let probe_target = probe_target.to_string();
+        let panel = observers.panel("latency_under_load", i, parallel);
+        let samples = observers.recorder("latency_under_load", i);
 
         join_set.spawn(async move {
-            info!("Starting connection performance test instance {}", i + 1);
-            let test = ConnectionPerfTest::new(&proxy, &target, concurrent, total);
-            test.run().await
+            info!("Starting latency under load test instance {}", i + 1);
+            let test =
+                LatencyUnderLoadTest::new(&proxy, &load_target, &probe_target, streams, duration)
+                    .with_live(panel)
+                    .with_samples(samples);
+            let result = test
+                .run()
+                .await
+                .map(|result| (test.to_metrics(&result), result.events));
+            (i, result)
         });
     }
 
     while let Some(result) = join_set.join_next().await {
         match result {
-            Ok(test_result) => test_result?,
+            Ok((i, test_result)) => {
+                let (metrics, events) =
+                    test_result.map_err(|e| observers.fail(i, "latency_under_load", e))?;
+                observers.collect(i, |collector| {
+                    collector.set_latency_under_load_metrics(metrics);
+                    collector.add_events(events);
+                });
+            }
             Err(join_error) => {
                 return Err(network_stable_test::NetworkTestError::Connection(format!(
                     "Task join error: {join_error}"
@@ -261,12 +1100,268 @@ async fn run_connection_perf_test_parallel(
     Ok(())
 }
 
-async fn run_all_tests_parallel(proxy: &str, parallel: usize) -> Result<()> {
+fn parse_credentials(credentials: &[String]) -> Result<Vec<(String, String)>> {
+    credentials
+        .iter()
+        .map(|credential| {
+            credential
+                .split_once(':')
+                .map(|(user, pass)| (user.to_string(), pass.to_string()))
+                .ok_or_else(|| {
+                    network_stable_test::NetworkTestError::Config(format!(
+                        "Invalid credential, expected USER:PASS: {credential}"
+                    ))
+                })
+        })
+        .collect()
+}
+
+async fn run_fairness_test_parallel(
+    test: FairnessTest,
+    parallel: usize,
+    observers: &Observers,
+) -> Result<()> {
+    use tokio::task::JoinSet;
+
+    if parallel == 1 {
+        let test = test
+            .with_live(observers.panel("fairness", 0, 1))
+            .with_samples(observers.recorder("fairness", 0));
+        let result = test
+            .run()
+            .await
+            .map_err(|e| observers.fail(0, "fairness", e))?;
+        observers.collect(0, |collector| {
+            collector.set_bandwidth_metrics(test.to_metrics(&result));
+            collector.add_events(result.events);
+        });
+        return Ok(());
+    }
+
+    let mut join_set = JoinSet::new();
+
+    for i in 0..parallel {
+        let test = test
+            .clone()
+            .with_live(observers.panel("fairness", i, parallel))
+            .with_samples(observers.recorder("fairness", i));
+
+        join_set.spawn(async move {
+            info!("Starting fairness test instance {}", i + 1);
+            let result = test
+                .run()
+                .await
+                .map(|result| (test.to_metrics(&result), result.events));
+            (i, result)
+        });
+    }
+
+    while let Some(result) = join_set.join_next().await {
+        match result {
+            Ok((i, test_result)) => {
+                let (metrics, events) =
+                    test_result.map_err(|e| observers.fail(i, "fairness", e))?;
+                observers.collect(i, |collector| {
+                    collector.set_bandwidth_metrics(metrics);
+                    collector.add_events(events);
+                });
+            }
+            Err(join_error) => {
+                return Err(network_stable_test::NetworkTestError::Connection(format!(
+                    "Task join error: {join_error}"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_all_tests_parallel(proxy: &str, parallel: usize, observers: &Observers) -> Result<()> {
     info!("Running comprehensive network stability tests");
 
-    run_tcp_stability_test_parallel(proxy, "8.8.8.8:53", 30, 300, parallel).await?;
-    run_bandwidth_test_parallel(proxy, "httpbin.org:80", 1024, 60, parallel).await?;
-    run_connection_perf_test_parallel(proxy, "8.8.8.8:53", 10, 100, parallel).await?;
+    run_tcp_stability_test_parallel(proxy, "8.8.8.8:53", 30, 300, parallel, observers).await?;
+    run_bandwidth_test_parallel(proxy, "httpbin.org:80", 1024, 60, parallel, observers).await?;
+    run_connection_perf_test_parallel(
+        ConnectionPerfTest::new(proxy, "8.8.8.8:53", 10, 100),
+        parallel,
+        observers,
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn run_max_tunnels_test_parallel(
+    test: MaxTunnelsTest,
+    parallel: usize,
+    observers: &Observers,
+) -> Result<()> {
+    use tokio::task::JoinSet;
+
+    if parallel == 1 {
+        let test = test
+            .with_live(observers.panel("max_tunnels", 0, 1))
+            .with_samples(observers.recorder("max_tunnels", 0));
+        let result = test
+            .run()
+            .await
+            .map_err(|e| observers.fail(0, "max_tunnels", e))?;
+        observers.collect(0, |collector| {
+            collector.set_max_tunnels_metrics(test.to_metrics(&result));
+        });
+        return Ok(());
+    }
+
+    let mut join_set = JoinSet::new();
+
+    for i in 0..parallel {
+        let test = test
+            .clone()
+            .with_live(observers.panel("max_tunnels", i, parallel))
+            .with_samples(observers.recorder("max_tunnels", i));
+
+        join_set.spawn(async move {
+            info!("Starting max tunnels test instance {}", i + 1);
+            (i, test.run().await.map(|result| test.to_metrics(&result)))
+        });
+    }
+
+    while let Some(result) = join_set.join_next().await {
+        match result {
+            Ok((i, test_result)) => {
+                let metrics = test_result.map_err(|e| observers.fail(i, "max_tunnels", e))?;
+                observers.collect(i, |collector| collector.set_max_tunnels_metrics(metrics));
+            }
+            Err(join_error) => {
+                return Err(network_stable_test::NetworkTestError::Connection(format!(
+                    "Task join error: {join_error}"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_proxy_dns_test_parallel(
+    test: ProxyDnsTest,
+    parallel: usize,
+    observers: &Observers,
+) -> Result<()> {
+    use tokio::task::JoinSet;
+
+    if parallel == 1 {
+        let test = test
+            .with_live(observers.panel("proxy_dns", 0, 1))
+            .with_samples(observers.recorder("proxy_dns", 0));
+        let result = test
+            .run()
+            .await
+            .map_err(|e| observers.fail(0, "proxy_dns", e))?;
+        observers.collect(0, |collector| {
+            collector.set_proxy_dns_metrics(test.to_metrics(&result));
+            collector.add_events(result.events);
+        });
+        return Ok(());
+    }
+
+    let mut join_set = JoinSet::new();
+
+    for i in 0..parallel {
+        let test = test
+            .clone()
+            .with_live(observers.panel("proxy_dns", i, parallel))
+            .with_samples(observers.recorder("proxy_dns", i));
+
+        join_set.spawn(async move {
+            info!("Starting proxy DNS test instance {}", i + 1);
+            let result = test
+                .run()
+                .await
+                .map(|result| (test.to_metrics(&result), result.events));
+            (i, result)
+        });
+    }
+
+    while let Some(result) = join_set.join_next().await {
+        match result {
+            Ok((i, test_result)) => {
+                let (metrics, events) =
+                    test_result.map_err(|e| observers.fail(i, "proxy_dns", e))?;
+                observers.collect(i, |collector| {
+                    collector.set_proxy_dns_metrics(metrics);
+                    collector.add_events(events);
+                });
+            }
+            Err(join_error) => {
+                return Err(network_stable_test::NetworkTestError::Connection(format!(
+                    "Task join error: {join_error}"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_network_jitter_test_parallel(
+    test: NetworkJitterTest,
+    parallel: usize,
+    observers: &Observers,
+) -> Result<()> {
+    use tokio::task::JoinSet;
+
+    if parallel == 1 {
+        let test = test
+            .with_live(observers.panel("network_jitter", 0, 1))
+            .with_samples(observers.recorder("network_jitter", 0));
+        let result = test
+            .run()
+            .await
+            .map_err(|e| observers.fail(0, "network_jitter", e))?;
+        observers.collect(0, |collector| {
+            collector.set_network_jitter_metrics(test.to_metrics(&result));
+            collector.add_events(result.events);
+        });
+        return Ok(());
+    }
+
+    let mut join_set = JoinSet::new();
+
+    for i in 0..parallel {
+        let test = test
+            .clone()
+            .with_live(observers.panel("network_jitter", i, parallel))
+            .with_samples(observers.recorder("network_jitter", i));
+
+        join_set.spawn(async move {
+            info!("Starting network jitter test instance {}", i + 1);
+            let result = test
+                .run()
+                .await
+                .map(|result| (test.to_metrics(&result), result.events));
+            (i, result)
+        });
+    }
+
+    while let Some(result) = join_set.join_next().await {
+        match result {
+            Ok((i, test_result)) => {
+                let (metrics, events) =
+                    test_result.map_err(|e| observers.fail(i, "network_jitter", e))?;
+                observers.collect(i, |collector| {
+                    collector.set_network_jitter_metrics(metrics);
+                    collector.add_events(events);
+                });
+            }
+            Err(join_error) => {
+                return Err(network_stable_test::NetworkTestError::Connection(format!(
+                    "Task join error: {join_error}"
+                )));
+            }
+        }
+    }
 
     Ok(())
 }
@@ -1,7 +1,13 @@
+use crate::dns::{rcode_name, DnsTransport, RecordType};
+use crate::live::LivePanel;
+use crate::tests::network_jitter::PingMode;
+use crate::{reply_description, HostResolution, NetworkTestError, Phase};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::time::Duration;
+use std::fmt;
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metrics {
@@ -14,9 +20,27 @@ pub struct Metrics {
     pub connection_perf: Option<ConnectionPerfMetrics>,
     pub dns_stability: Option<DnsStabilityMetrics>,
     pub network_jitter: Option<NetworkJitterMetrics>,
+    pub latency_under_load: Option<LatencyUnderLoadMetrics>,
+    pub max_tunnels: Option<MaxTunnelsMetrics>,
+    #[serde(default)]
+    pub proxy_dns: Option<ProxyDnsMetrics>,
+    /// Timestamped failures and recoveries from every test, oldest first.
+    #[serde(default)]
+    pub events: Vec<TestEvent>,
+    /// Tests that aborted before producing any results.
+    #[serde(default)]
+    pub errors: Vec<TestError>,
     pub overall_score: Option<f64>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestError {
+    pub test: String,
+    pub timestamp: DateTime<Utc>,
+    pub category: String,
+    pub message: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProxyMetrics {
     pub proxy_address: String,
@@ -41,11 +65,15 @@ pub struct TcpStabilityMetrics {
     pub rtt_variance: f64,
     pub stability_score: f64,
     pub connection_drops: Vec<ConnectionDropMetrics>,
+    #[serde(default)]
+    pub failures: FailureBreakdown,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectionDropMetrics {
     pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub offset: Duration,
     pub duration: Duration,
     pub reason: String,
 }
@@ -66,6 +94,34 @@ pub struct BandwidthMetrics {
     pub connection_interruptions: u32,
     pub data_integrity_errors: u32,
     pub bandwidth_score: f64,
+    pub fairness: Option<FairnessMetrics>,
+    #[serde(default)]
+    pub failures: FailureBreakdown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FairnessMetrics {
+    pub streams: usize,
+    pub sample_interval: Duration,
+    pub starvation_threshold: f64,
+    pub jains_index: f64,
+    /// `None` when no sampling window completed.
+    pub min_window_jains_index: Option<f64>,
+    pub starvation_events: u64,
+    pub per_stream: Vec<StreamFairnessMetrics>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamFairnessMetrics {
+    pub stream_id: usize,
+    pub username: Option<String>,
+    pub bytes_received: u64,
+    pub average_throughput: f64,
+    pub min_throughput: f64,
+    pub max_throughput: f64,
+    pub share_of_total: f64,
+    pub starvation_events: u64,
+    pub reconnections: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -84,18 +140,46 @@ pub struct ConnectionPerfMetrics {
     pub max_concurrent_successful: usize,
     pub performance_score: f64,
     pub concurrent_results: Vec<ConcurrentMetrics>,
+    pub saturation_knee: Option<usize>,
+    pub last_healthy_level: Option<usize>,
+    pub knee_reason: Option<String>,
+    pub max_sustained_rate: Option<f64>,
+    pub open_loop_results: Vec<OpenLoopMetrics>,
+    #[serde(default)]
+    pub failures: FailureBreakdown,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConcurrentMetrics {
     pub concurrent_level: usize,
+    pub attempts: usize,
     pub successful_connections: usize,
     pub failed_connections: usize,
     pub success_rate: f64,
     pub average_time: Duration,
+    pub median_time: Duration,
+    pub p95_time: Duration,
+    pub p99_time: Duration,
     pub total_time: Duration,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenLoopMetrics {
+    pub offered_rate: f64,
+    pub achieved_rate: f64,
+    pub step_duration: Duration,
+    pub scheduled: usize,
+    pub successful_connections: usize,
+    pub failed_connections: usize,
+    pub max_backlog: usize,
+    pub backlog_at_step_end: usize,
+    pub max_launch_lag: Duration,
+    pub median_latency: Duration,
+    pub p95_latency: Duration,
+    pub p99_latency: Duration,
+    pub max_latency: Duration,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DnsStabilityMetrics {
     pub test_duration: Duration,
@@ -114,11 +198,55 @@ pub struct DnsStabilityMetrics {
     pub domain_consistency_score: f64,
     pub dns_score: f64,
     pub per_domain_metrics: HashMap<String, DomainMetrics>,
+    #[serde(default)]
+    pub per_resolver_metrics: Vec<ResolverMetrics>,
+    #[serde(default)]
+    pub per_type_metrics: Vec<RecordTypeMetrics>,
+    #[serde(default)]
+    pub cache: Option<DnsCacheMetrics>,
+    #[serde(default)]
+    pub answer_changes: u64,
+    #[serde(default)]
+    pub unexpected_answers: u64,
+    #[serde(default)]
+    pub nxdomain_responses: u64,
+    #[serde(default)]
+    pub nodata_responses: u64,
+    #[serde(default)]
+    pub associations: usize,
+    #[serde(default)]
+    pub average_association_time: Duration,
+    #[serde(default)]
+    pub max_association_time: Duration,
+    #[serde(default)]
+    pub stray_responses: u64,
+    #[serde(default)]
+    pub transport: DnsTransport,
+    #[serde(default)]
+    pub udp_fallback: bool,
+    #[serde(default)]
+    pub udp_queries: u64,
+    #[serde(default)]
+    pub tcp_queries: u64,
+    #[serde(default)]
+    pub tcp_connections: usize,
+    #[serde(default)]
+    pub average_tcp_connect_time: Duration,
+    #[serde(default)]
+    pub tls_handshakes: usize,
+    #[serde(default)]
+    pub average_tls_handshake_time: Duration,
+    #[serde(default)]
+    pub resumed_tls_sessions: u64,
+    #[serde(default)]
+    pub failures: FailureBreakdown,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DomainMetrics {
     pub domain: String,
+    #[serde(default)]
+    pub record_type: RecordType,
     pub total_queries: u64,
     pub successful_queries: u64,
     pub failed_queries: u64,
@@ -126,6 +254,86 @@ pub struct DomainMetrics {
     pub average_query_time: Duration,
     pub min_query_time: Duration,
     pub max_query_time: Duration,
+    #[serde(default)]
+    pub answer_sets: Vec<AnswerSetMetrics>,
+    #[serde(default)]
+    pub answer_changes: u64,
+    #[serde(default)]
+    pub expected_addresses: Vec<String>,
+    #[serde(default)]
+    pub unexpected_answers: u64,
+    #[serde(default)]
+    pub unexpected_addresses: Vec<String>,
+    #[serde(default)]
+    pub nxdomain_responses: u64,
+    #[serde(default)]
+    pub nodata_responses: u64,
+    #[serde(default)]
+    pub min_ttl: Option<u32>,
+    #[serde(default)]
+    pub max_ttl: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnswerSetMetrics {
+    pub addresses: Vec<String>,
+    #[serde(default)]
+    pub records: Vec<String>,
+    pub cname_chain: Vec<String>,
+    pub first_seen: Duration,
+    pub last_seen: Duration,
+    pub observations: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolverMetrics {
+    pub resolver: String,
+    pub total_queries: u64,
+    pub successful_queries: u64,
+    pub failed_queries: u64,
+    pub timeout_queries: u64,
+    pub success_rate: f64,
+    pub timeout_rate: f64,
+    pub average_query_time: Duration,
+    pub median_query_time: Duration,
+    pub p95_query_time: Duration,
+    pub p99_query_time: Duration,
+    #[serde(default)]
+    pub failures: FailureBreakdown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsCacheMetrics {
+    pub cold_queries: u64,
+    pub cold_failures: u64,
+    pub cold_median_query_time: Duration,
+    pub warm_queries: u64,
+    pub estimated_hits: u64,
+    pub estimated_hit_ratio: f64,
+    pub hit_median_query_time: Duration,
+    pub miss_median_query_time: Duration,
+    pub hit_latency_advantage: Duration,
+    pub decremented_ttl_responses: u64,
+    pub early_ttl_resets: u64,
+    pub expiry_probes: u64,
+    pub expiry_refetches: u64,
+    pub expiry_stale_answers: u64,
+    pub expiry_median_query_time: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordTypeMetrics {
+    pub record_type: RecordType,
+    pub domains: usize,
+    pub total_queries: u64,
+    pub successful_queries: u64,
+    pub failed_queries: u64,
+    pub success_rate: f64,
+    pub average_query_time: Duration,
+    pub p95_query_time: Duration,
+    pub answer_changes: u64,
+    pub nxdomain_responses: u64,
+    pub nodata_responses: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -133,6 +341,9 @@ pub struct NetworkJitterMetrics {
     pub test_duration: Duration,
     pub ping_interval: Duration,
     pub targets_tested: usize,
+    /// Ping mode the top-level figures below come from.
+    #[serde(default)]
+    pub primary_mode: PingMode,
     pub total_pings: u64,
     pub successful_pings: u64,
     pub failed_pings: u64,
@@ -145,11 +356,55 @@ pub struct NetworkJitterMetrics {
     pub p95_rtt: Duration,
     pub p99_rtt: Duration,
     pub jitter: Duration,
+    #[serde(default)]
+    pub rfc3550_jitter: Duration,
+    /// Percentiles of absolute RTT change between consecutive replies.
+    #[serde(default)]
+    pub ipdv_p50: Duration,
+    #[serde(default)]
+    pub ipdv_p95: Duration,
+    #[serde(default)]
+    pub ipdv_p99: Duration,
+    /// ITU-T G.107 E-model rating for a G.711 call over this path.
+    #[serde(default)]
+    pub r_factor: f64,
+    #[serde(default)]
+    pub mos: f64,
     pub rtt_variance: f64,
     pub latency_consistency_score: f64,
     pub target_consistency_score: f64,
     pub network_quality_score: f64,
     pub per_target_metrics: HashMap<String, TargetMetrics>,
+    #[serde(default)]
+    pub modes: Vec<PingModeMetrics>,
+    /// Median connect-per-ping RTT minus median in-tunnel RTT.
+    #[serde(default)]
+    pub setup_overhead: Option<Duration>,
+    #[serde(default)]
+    pub loss_correlation: Vec<LossCorrelationMetrics>,
+    #[serde(default)]
+    pub one_way_delay: Option<OneWayDelayMetrics>,
+    #[serde(default)]
+    pub failures: FailureBreakdown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PingModeMetrics {
+    pub mode: PingMode,
+    pub total_pings: u64,
+    pub successful_pings: u64,
+    pub packet_loss_rate: f64,
+    pub average_rtt: Duration,
+    pub median_rtt: Duration,
+    pub p95_rtt: Duration,
+    pub p99_rtt: Duration,
+    pub jitter: Duration,
+    #[serde(default)]
+    pub rfc3550_jitter: Duration,
+    #[serde(default)]
+    pub mos: f64,
+    pub reconnects: u64,
+    pub late_replies: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -160,7 +415,529 @@ pub struct TargetMetrics {
     pub failed_pings: u64,
     pub packet_loss_rate: f64,
     pub average_rtt: Duration,
+    #[serde(default)]
+    pub p50_rtt: Duration,
+    #[serde(default)]
+    pub p95_rtt: Duration,
+    #[serde(default)]
+    pub p99_rtt: Duration,
     pub jitter: Duration,
+    /// Estimated server clock minus client clock, from tunnel pings.
+    #[serde(default)]
+    pub clock_offset_ms: Option<f64>,
+}
+
+/// Upstream (client to server) and downstream delay from server timestamps,
+/// with clock offset taken from the least-delayed ping.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OneWayDelayMetrics {
+    pub samples: u64,
+    pub upstream_median: Duration,
+    pub upstream_p95: Duration,
+    pub upstream_jitter: Duration,
+    pub downstream_median: Duration,
+    pub downstream_p95: Duration,
+    pub downstream_jitter: Duration,
+    pub slower_direction: String,
+}
+
+/// Whether two targets tend to lose pings in the same schedule slots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LossCorrelationMetrics {
+    pub target_a: String,
+    pub target_b: String,
+    pub slots: u64,
+    pub losses_a: u64,
+    pub losses_b: u64,
+    pub joint_losses: u64,
+    /// Phi coefficient of the two loss series; `None` when undefined.
+    pub correlation: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaxTunnelsMetrics {
+    pub max_tunnels: usize,
+    pub heartbeat_interval: Duration,
+    pub phases: Vec<TunnelLimitMetrics>,
+    #[serde(default)]
+    pub failures: FailureBreakdown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunnelLimitMetrics {
+    pub scope: String,
+    pub username: Option<String>,
+    pub limit: usize,
+    pub reached_cap: bool,
+    pub failure_mode: Option<String>,
+    pub failed_attempts: usize,
+    pub time_to_limit: Duration,
+    pub average_open_time: Duration,
+    pub tunnels_survived: usize,
+    pub tunnels_dropped: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyDnsMetrics {
+    pub hostname_target: String,
+    pub literal_target: String,
+    pub rounds: usize,
+    pub round_interval: Duration,
+    pub paths: Vec<ResolutionPathMetrics>,
+    #[serde(default)]
+    pub failures: FailureBreakdown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolutionPathMetrics {
+    pub path: String,
+    pub resolution: Option<HostResolution>,
+    pub attempts: u64,
+    pub successful: u64,
+    pub success_rate: f64,
+    pub median_connect_time: Duration,
+    pub p95_connect_time: Duration,
+    pub cold_overhead: Option<Duration>,
+    pub warm_overhead: Option<Duration>,
+    pub cache_misses: u64,
+    pub resolution_failures: u64,
+    pub verdict: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyUnderLoadMetrics {
+    pub phase_duration: Duration,
+    pub load_streams: usize,
+    pub idle_median_rtt: Duration,
+    pub idle_p90_rtt: Duration,
+    pub upload: LoadedLatencyMetrics,
+    pub download: LoadedLatencyMetrics,
+    pub duplex: LoadedLatencyMetrics,
+    pub bufferbloat_grade: String,
+    #[serde(default)]
+    pub failures: FailureBreakdown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadedLatencyMetrics {
+    pub probes: u64,
+    pub lost_probes: u64,
+    pub median_rtt: Duration,
+    pub p90_rtt: Duration,
+    pub p99_rtt: Duration,
+    pub median_inflation: Duration,
+    pub p90_inflation: Duration,
+    pub p99_inflation: Duration,
+    pub throughput: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FailureBreakdown {
+    pub total_failures: u64,
+    pub categories: Vec<FailureCategoryCount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailureCategoryCount {
+    pub category: String,
+    pub phase: Option<String>,
+    pub count: u64,
+    pub percentage: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FailureCategory {
+    SocksReply {
+        phase: Phase,
+        code: u8,
+    },
+    Timeout {
+        phase: Phase,
+    },
+    Io {
+        phase: Option<Phase>,
+        kind: ErrorKind,
+    },
+    Protocol {
+        phase: Phase,
+    },
+    Connection,
+    Config,
+    DnsRcode {
+        rcode: u8,
+    },
+    DnsNoData,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FailureTally {
+    counts: HashMap<FailureCategory, u64>,
+}
+
+impl FailureCategory {
+    pub fn phase(&self) -> Option<Phase> {
+        match self {
+            FailureCategory::SocksReply { phase, .. }
+            | FailureCategory::Timeout { phase }
+            | FailureCategory::Protocol { phase } => Some(*phase),
+            FailureCategory::Io { phase, .. } => *phase,
+            FailureCategory::DnsRcode { .. } | FailureCategory::DnsNoData => Some(Phase::Exchange),
+            FailureCategory::Connection | FailureCategory::Config => None,
+        }
+    }
+}
+
+impl From<&NetworkTestError> for FailureCategory {
+    fn from(error: &NetworkTestError) -> Self {
+        match error {
+            NetworkTestError::SocksReply { phase, code } => FailureCategory::SocksReply {
+                phase: *phase,
+                code: *code,
+            },
+            NetworkTestError::Timeout { phase, .. } => FailureCategory::Timeout { phase: *phase },
+            NetworkTestError::PhaseIo { phase, source } if source.kind() == ErrorKind::TimedOut => {
+                FailureCategory::Timeout { phase: *phase }
+            }
+            NetworkTestError::PhaseIo { phase, source } => FailureCategory::Io {
+                phase: Some(*phase),
+                kind: source.kind(),
+            },
+            NetworkTestError::Io(source) => FailureCategory::Io {
+                phase: None,
+                kind: source.kind(),
+            },
+            NetworkTestError::Protocol { phase, .. } => FailureCategory::Protocol { phase: *phase },
+            NetworkTestError::Connection(_) => FailureCategory::Connection,
+            NetworkTestError::Config(_) => FailureCategory::Config,
+        }
+    }
+}
+
+impl fmt::Display for FailureCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureCategory::SocksReply { phase, code } => write!(
+                f,
+                "{code:#04x} {} at {phase}",
+                reply_description(*phase, *code)
+            ),
+            FailureCategory::Timeout { phase } => write!(f, "timeout at {phase}"),
+            FailureCategory::Io {
+                phase: Some(phase),
+                kind,
+            } => write!(f, "{kind} at {phase}"),
+            FailureCategory::Io { phase: None, kind } => write!(f, "{kind}"),
+            FailureCategory::Protocol { phase } => write!(f, "protocol violation at {phase}"),
+            FailureCategory::Connection => write!(f, "connection error"),
+            FailureCategory::Config => write!(f, "configuration error"),
+            FailureCategory::DnsRcode { rcode } => write!(f, "DNS {}", rcode_name(*rcode)),
+            FailureCategory::DnsNoData => write!(f, "DNS NODATA"),
+        }
+    }
+}
+
+impl FailureTally {
+    pub fn record(&mut self, error: &NetworkTestError) {
+        self.record_category(FailureCategory::from(error));
+    }
+
+    pub fn record_category(&mut self, category: FailureCategory) {
+        *self.counts.entry(category).or_insert(0) += 1;
+    }
+
+    pub fn merge(&mut self, other: &FailureTally) {
+        for (category, count) in &other.counts {
+            *self.counts.entry(*category).or_insert(0) += count;
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    pub fn breakdown(&self) -> FailureBreakdown {
+        let total_failures = self.total();
+        let mut entries: Vec<(&FailureCategory, &u64)> = self.counts.iter().collect();
+        entries.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        FailureBreakdown {
+            total_failures,
+            categories: entries
+                .into_iter()
+                .map(|(category, count)| FailureCategoryCount {
+                    category: category.to_string(),
+                    phase: category.phase().map(|phase| phase.to_string()),
+                    count: *count,
+                    percentage: *count as f64 / total_failures as f64 * 100.0,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl FailureBreakdown {
+    pub fn summary_lines(&self) -> Vec<String> {
+        self.categories
+            .iter()
+            .map(|c| {
+                format!(
+                    "{:.1}% of failures were {} ({} of {})",
+                    c.percentage, c.category, c.count, self.total_failures
+                )
+            })
+            .collect()
+    }
+
+    fn push_csv(&self, csv: &mut String, metric_type: &str) {
+        for c in &self.categories {
+            let name: String = c
+                .category
+                .chars()
+                .map(|ch| {
+                    if ch.is_ascii_alphanumeric() {
+                        ch.to_ascii_lowercase()
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            csv.push_str(&format!(
+                "{metric_type},failures_{name},{},count\n",
+                c.count
+            ));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Timeout,
+    ConnectionDrop,
+    IntegrityError,
+    DnsFailure,
+    Failure,
+    Reconnect,
+    Recovered,
+}
+
+impl EventKind {
+    /// Whether the event starts or continues an outage rather than ending one.
+    pub fn is_failure(&self) -> bool {
+        !matches!(self, EventKind::Reconnect | EventKind::Recovered)
+    }
+}
+
+impl From<FailureCategory> for EventKind {
+    fn from(category: FailureCategory) -> Self {
+        match category {
+            FailureCategory::Timeout { .. } => EventKind::Timeout,
+            FailureCategory::DnsRcode { .. } | FailureCategory::DnsNoData => EventKind::DnsFailure,
+            _ => EventKind::Failure,
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EventKind::Timeout => "timeout",
+            EventKind::ConnectionDrop => "connection drop",
+            EventKind::IntegrityError => "integrity error",
+            EventKind::DnsFailure => "DNS failure",
+            EventKind::Failure => "failure",
+            EventKind::Reconnect => "reconnect",
+            EventKind::Recovered => "recovered",
+        };
+        f.pad(name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestEvent {
+    pub timestamp: DateTime<Utc>,
+    /// Monotonic time since the recording test started.
+    pub offset: Duration,
+    pub test: String,
+    pub target: String,
+    pub kind: EventKind,
+    pub detail: String,
+    /// Identical back-to-back occurrences folded into this event.
+    pub count: u64,
+}
+
+/// Pairs a wall-clock start with a monotonic one, so event times stay
+/// ordered even if the system clock is stepped during a test.
+#[derive(Debug, Clone, Copy)]
+pub struct EventClock {
+    started: Instant,
+    started_at: DateTime<Utc>,
+}
+
+impl EventClock {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            started_at: Utc::now(),
+        }
+    }
+
+    pub fn now(&self) -> (DateTime<Utc>, Duration) {
+        let offset = self.started.elapsed();
+        let timestamp = self.started_at
+            + chrono::Duration::from_std(offset).unwrap_or_else(|_| chrono::Duration::zero());
+        (timestamp, offset)
+    }
+}
+
+/// Event stream of one test. Failures on a target that is already down
+/// are folded into the previous event when they repeat it exactly.
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    test: &'static str,
+    clock: EventClock,
+    events: Vec<TestEvent>,
+    down: HashMap<String, usize>,
+    live: LivePanel,
+}
+
+impl EventLog {
+    pub fn new(test: &'static str) -> Self {
+        Self::with_clock(test, EventClock::start())
+    }
+
+    /// For concurrent workers of one test, merged back with `merge`.
+    pub fn with_clock(test: &'static str, clock: EventClock) -> Self {
+        Self {
+            test,
+            clock,
+            events: Vec::new(),
+            down: HashMap::new(),
+            live: LivePanel::default(),
+        }
+    }
+
+    /// Mirrors failures and recoveries onto a live dashboard panel.
+    pub fn with_live(mut self, live: LivePanel) -> Self {
+        self.live = live;
+        self
+    }
+
+    pub fn clock(&self) -> EventClock {
+        self.clock
+    }
+
+    pub fn live(&self) -> &LivePanel {
+        &self.live
+    }
+
+    pub fn failure(&mut self, target: &str, kind: EventKind, detail: impl Into<String>) {
+        let detail = detail.into();
+        self.live.failure();
+        self.live.outage(target, &format!("{kind}: {detail}"));
+        if let Some(&index) = self.down.get(target) {
+            let last = &mut self.events[index];
+            if last.kind == kind && last.detail == detail {
+                last.count += 1;
+                return;
+            }
+        }
+
+        self.push(target, kind, detail);
+        self.down.insert(target.to_string(), self.events.len() - 1);
+    }
+
+    pub fn category(&mut self, target: &str, category: FailureCategory) {
+        self.failure(target, category.into(), category.to_string());
+    }
+
+    pub fn error(&mut self, target: &str, error: &NetworkTestError) {
+        self.category(target, FailureCategory::from(error));
+    }
+
+    /// Records the first success after a failure; a no-op while healthy.
+    pub fn recovered(&mut self, target: &str) {
+        if self.down.remove(target).is_some() {
+            self.live.recovered(target);
+            self.push(target, EventKind::Recovered, String::new());
+        }
+    }
+
+    pub fn reconnected(&mut self, target: &str, detail: impl Into<String>) {
+        self.down.remove(target);
+        self.live.recovered(target);
+        self.push(target, EventKind::Reconnect, detail.into());
+    }
+
+    pub fn merge(&mut self, other: EventLog) {
+        self.events.extend(other.events);
+        self.events.sort_by_key(|event| event.offset);
+        self.down.clear();
+    }
+
+    pub fn into_events(self) -> Vec<TestEvent> {
+        self.events
+    }
+
+    fn push(&mut self, target: &str, kind: EventKind, detail: String) {
+        let (timestamp, offset) = self.clock.now();
+        self.events.push(TestEvent {
+            timestamp,
+            offset,
+            test: self.test.to_string(),
+            target: target.to_string(),
+            kind,
+            detail,
+            count: 1,
+        });
+    }
+}
+
+/// A span during which a test saw a target failing, from the first failure
+/// to the next recovery or reconnect; `end` is `None` if it never recovered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Outage {
+    pub test: String,
+    pub target: String,
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+    pub duration: Option<Duration>,
+    pub cause: String,
+    pub failures: u64,
+}
+
+pub fn outages(events: &[TestEvent]) -> Vec<Outage> {
+    let mut open: HashMap<(&str, &str), Outage> = HashMap::new();
+    let mut outages = Vec::new();
+
+    for event in events {
+        let key = (event.test.as_str(), event.target.as_str());
+        if event.kind.is_failure() {
+            open.entry(key)
+                .or_insert_with(|| Outage {
+                    test: event.test.clone(),
+                    target: event.target.clone(),
+                    start: event.timestamp,
+                    end: None,
+                    duration: None,
+                    cause: format!("{}: {}", event.kind, event.detail),
+                    failures: 0,
+                })
+                .failures += event.count;
+        } else if let Some(mut outage) = open.remove(&key) {
+            outage.end = Some(event.timestamp);
+            outage.duration = (event.timestamp - outage.start).to_std().ok();
+            outages.push(outage);
+        }
+    }
+
+    outages.extend(open.into_values());
+    outages.sort_by_key(|outage| outage.start);
+    outages
 }
 
 impl Metrics {
@@ -180,6 +957,11 @@ impl Metrics {
             connection_perf: None,
             dns_stability: None,
             network_jitter: None,
+            latency_under_load: None,
+            max_tunnels: None,
+            proxy_dns: None,
+            events: Vec::new(),
+            errors: Vec::new(),
             overall_score: None,
         }
     }
@@ -189,6 +971,10 @@ impl Metrics {
         self.calculate_overall_score();
     }
 
+    pub fn outages(&self) -> Vec<Outage> {
+        outages(&self.events)
+    }
+
     pub fn calculate_overall_score(&mut self) {
         let mut scores = Vec::new();
         let mut weights = Vec::new();
@@ -274,6 +1060,15 @@ impl Metrics {
         if self.network_jitter.is_some() {
             count += 1;
         }
+        if self.latency_under_load.is_some() {
+            count += 1;
+        }
+        if self.max_tunnels.is_some() {
+            count += 1;
+        }
+        if self.proxy_dns.is_some() {
+            count += 1;
+        }
         count
     }
 
@@ -313,6 +1108,7 @@ impl Metrics {
                 "tcp_stability,reconnections,{},count\n",
                 tcp.reconnections
             ));
+            tcp.failures.push_csv(&mut csv, "tcp_stability");
         }
 
         if let Some(ref bandwidth) = self.bandwidth {
@@ -332,6 +1128,23 @@ impl Metrics {
                 "bandwidth,connection_interruptions,{},count\n",
                 bandwidth.connection_interruptions
             ));
+            if let Some(ref fairness) = bandwidth.fairness {
+                csv.push_str(&format!(
+                    "bandwidth,jains_fairness_index,{:.4},ratio\n",
+                    fairness.jains_index
+                ));
+                csv.push_str(&format!(
+                    "bandwidth,starvation_events,{},count\n",
+                    fairness.starvation_events
+                ));
+                for stream in &fairness.per_stream {
+                    csv.push_str(&format!(
+                        "bandwidth,stream_{}_average_throughput,{:.2},bytes_per_sec\n",
+                        stream.stream_id, stream.average_throughput
+                    ));
+                }
+            }
+            bandwidth.failures.push_csv(&mut csv, "bandwidth");
         }
 
         if let Some(ref conn_perf) = self.connection_perf {
@@ -351,6 +1164,41 @@ impl Metrics {
                 "connection_perf,max_concurrent_successful,{},count\n",
                 conn_perf.max_concurrent_successful
             ));
+            if let Some(knee) = conn_perf.saturation_knee {
+                csv.push_str(&format!("connection_perf,saturation_knee,{knee},count\n"));
+            }
+            for level in &conn_perf.concurrent_results {
+                csv.push_str(&format!(
+                    "connection_perf,level_{}_success_rate,{:.2},percent\n",
+                    level.concurrent_level, level.success_rate
+                ));
+                csv.push_str(&format!(
+                    "connection_perf,level_{}_p99_time,{},ms\n",
+                    level.concurrent_level,
+                    level.p99_time.as_millis()
+                ));
+            }
+            if let Some(rate) = conn_perf.max_sustained_rate {
+                csv.push_str(&format!(
+                    "connection_perf,max_sustained_rate,{rate:.2},connections_per_sec\n"
+                ));
+            }
+            for step in &conn_perf.open_loop_results {
+                csv.push_str(&format!(
+                    "connection_perf,rate_{}_achieved_rate,{:.2},connections_per_sec\n",
+                    step.offered_rate, step.achieved_rate
+                ));
+                csv.push_str(&format!(
+                    "connection_perf,rate_{}_max_backlog,{},count\n",
+                    step.offered_rate, step.max_backlog
+                ));
+                csv.push_str(&format!(
+                    "connection_perf,rate_{}_p99_latency,{},ms\n",
+                    step.offered_rate,
+                    step.p99_latency.as_millis()
+                ));
+            }
+            conn_perf.failures.push_csv(&mut csv, "connection_perf");
         }
 
         if let Some(ref dns) = self.dns_stability {
@@ -370,6 +1218,104 @@ impl Metrics {
                 "dns_stability,timeout_rate,{:.2},percent\n",
                 dns.timeout_rate
             ));
+            for resolver in &dns.per_resolver_metrics {
+                csv.push_str(&format!(
+                    "dns_stability,resolver_{}_success_rate,{:.2},percent\n",
+                    resolver.resolver, resolver.success_rate
+                ));
+                csv.push_str(&format!(
+                    "dns_stability,resolver_{}_p95_query_time,{},ms\n",
+                    resolver.resolver,
+                    resolver.p95_query_time.as_millis()
+                ));
+                csv.push_str(&format!(
+                    "dns_stability,resolver_{}_timeout_rate,{:.2},percent\n",
+                    resolver.resolver, resolver.timeout_rate
+                ));
+            }
+            csv.push_str(&format!(
+                "dns_stability,answer_changes,{},count\n",
+                dns.answer_changes
+            ));
+            csv.push_str(&format!(
+                "dns_stability,unexpected_answers,{},count\n",
+                dns.unexpected_answers
+            ));
+            csv.push_str(&format!(
+                "dns_stability,nxdomain_responses,{},count\n",
+                dns.nxdomain_responses
+            ));
+            csv.push_str(&format!(
+                "dns_stability,nodata_responses,{},count\n",
+                dns.nodata_responses
+            ));
+            csv.push_str(&format!(
+                "dns_stability,udp_associations,{},count\n",
+                dns.associations
+            ));
+            csv.push_str(&format!(
+                "dns_stability,average_association_time,{:.2},ms\n",
+                dns.average_association_time.as_secs_f64() * 1000.0
+            ));
+            csv.push_str(&format!(
+                "dns_stability,stray_responses,{},count\n",
+                dns.stray_responses
+            ));
+            csv.push_str(&format!(
+                "dns_stability,udp_queries,{},count\n",
+                dns.udp_queries
+            ));
+            csv.push_str(&format!(
+                "dns_stability,tcp_queries,{},count\n",
+                dns.tcp_queries
+            ));
+            csv.push_str(&format!(
+                "dns_stability,average_tcp_connect_time,{:.2},ms\n",
+                dns.average_tcp_connect_time.as_secs_f64() * 1000.0
+            ));
+            csv.push_str(&format!(
+                "dns_stability,tls_handshakes,{},count\n",
+                dns.tls_handshakes
+            ));
+            csv.push_str(&format!(
+                "dns_stability,average_tls_handshake_time,{:.2},ms\n",
+                dns.average_tls_handshake_time.as_secs_f64() * 1000.0
+            ));
+            csv.push_str(&format!(
+                "dns_stability,resumed_tls_sessions,{},count\n",
+                dns.resumed_tls_sessions
+            ));
+            if let Some(ref cache) = dns.cache {
+                csv.push_str(&format!(
+                    "dns_stability,cache_hit_ratio,{:.2},percent\n",
+                    cache.estimated_hit_ratio
+                ));
+                csv.push_str(&format!(
+                    "dns_stability,cold_median_query_time,{:.2},ms\n",
+                    cache.cold_median_query_time.as_secs_f64() * 1000.0
+                ));
+                csv.push_str(&format!(
+                    "dns_stability,cache_hit_median_query_time,{:.2},ms\n",
+                    cache.hit_median_query_time.as_secs_f64() * 1000.0
+                ));
+                csv.push_str(&format!(
+                    "dns_stability,cache_hit_advantage,{:.2},ms\n",
+                    cache.hit_latency_advantage.as_secs_f64() * 1000.0
+                ));
+                csv.push_str(&format!(
+                    "dns_stability,decremented_ttl_responses,{},count\n",
+                    cache.decremented_ttl_responses
+                ));
+                csv.push_str(&format!(
+                    "dns_stability,ttl_expiry_probes,{},count\n",
+                    cache.expiry_probes
+                ));
+                csv.push_str(&format!(
+                    "dns_stability,ttl_expiry_refetches,{},count\n",
+                    cache.expiry_refetches
+                ));
+            }
+            dns.failures.push_csv(&mut csv, "dns_stability");
         }
 
         if let Some(ref jitter) = self.network_jitter {
@@ -389,6 +1335,179 @@ impl Metrics {
                 "network_jitter,jitter,{},ms\n",
                 jitter.jitter.as_millis()
             ));
+            csv.push_str(&format!(
+                "network_jitter,rfc3550_jitter,{:.3},ms\n",
+                jitter.rfc3550_jitter.as_secs_f64() * 1000.0
+            ));
+            csv.push_str(&format!(
+                "network_jitter,ipdv_p95,{:.3},ms\n",
+                jitter.ipdv_p95.as_secs_f64() * 1000.0
+            ));
+            csv.push_str(&format!(
+                "network_jitter,r_factor,{:.1},points\n",
+                jitter.r_factor
+            ));
+            csv.push_str(&format!("network_jitter,mos,{:.2},mos\n", jitter.mos));
+            for mode in &jitter.modes {
+                let prefix = mode.mode;
+                csv.push_str(&format!(
+                    "network_jitter,{prefix}_median_rtt,{:.3},ms\n",
+                    mode.median_rtt.as_secs_f64() * 1000.0
+                ));
+                csv.push_str(&format!(
+                    "network_jitter,{prefix}_jitter,{:.3},ms\n",
+                    mode.jitter.as_secs_f64() * 1000.0
+                ));
+                csv.push_str(&format!(
+                    "network_jitter,{prefix}_rfc3550_jitter,{:.3},ms\n",
+                    mode.rfc3550_jitter.as_secs_f64() * 1000.0
+                ));
+                csv.push_str(&format!(
+                    "network_jitter,{prefix}_packet_loss_rate,{:.2},percent\n",
+                    mode.packet_loss_rate
+                ));
+            }
+            if let Some(overhead) = jitter.setup_overhead {
+                csv.push_str(&format!(
+                    "network_jitter,setup_overhead,{:.3},ms\n",
+                    overhead.as_secs_f64() * 1000.0
+                ));
+            }
+            let mut targets: Vec<_> = jitter.per_target_metrics.values().collect();
+            targets.sort_by_key(|target| target.target.as_str());
+            for target in targets {
+                for (name, rtt) in [
+                    ("p50_rtt", target.p50_rtt),
+                    ("p95_rtt", target.p95_rtt),
+                    ("p99_rtt", target.p99_rtt),
+                ] {
+                    csv.push_str(&format!(
+                        "network_jitter,{}_{name},{:.3},ms\n",
+                        target.target,
+                        rtt.as_secs_f64() * 1000.0
+                    ));
+                }
+            }
+            if let Some(ref one_way) = jitter.one_way_delay {
+                for (direction, median, jitter) in [
+                    ("upstream", one_way.upstream_median, one_way.upstream_jitter),
+                    (
+                        "downstream",
+                        one_way.downstream_median,
+                        one_way.downstream_jitter,
+                    ),
+                ] {
+                    csv.push_str(&format!(
+                        "network_jitter,{direction}_median_delay,{:.3},ms\n",
+                        median.as_secs_f64() * 1000.0
+                    ));
+                    csv.push_str(&format!(
+                        "network_jitter,{direction}_jitter,{:.3},ms\n",
+                        jitter.as_secs_f64() * 1000.0
+                    ));
+                }
+            }
+            for pair in &jitter.loss_correlation {
+                if let Some(correlation) = pair.correlation {
+                    csv.push_str(&format!(
+                        "network_jitter,{}_{}_loss_correlation,{:.3},phi\n",
+                        pair.target_a, pair.target_b, correlation
+                    ));
+                }
+            }
+            jitter.failures.push_csv(&mut csv, "network_jitter");
+        }
+
+        if let Some(ref lul) = self.latency_under_load {
+            csv.push_str(&format!(
+                "latency_under_load,idle_median_rtt,{},ms\n",
+                lul.idle_median_rtt.as_millis()
+            ));
+            for (phase, loaded) in [
+                ("upload", &lul.upload),
+                ("download", &lul.download),
+                ("duplex", &lul.duplex),
+            ] {
+                csv.push_str(&format!(
+                    "latency_under_load,{phase}_p90_inflation,{},ms\n",
+                    loaded.p90_inflation.as_millis()
+                ));
+                csv.push_str(&format!(
+                    "latency_under_load,{phase}_p99_inflation,{},ms\n",
+                    loaded.p99_inflation.as_millis()
+                ));
+            }
+            csv.push_str(&format!(
+                "latency_under_load,bufferbloat_grade,{},grade\n",
+                lul.bufferbloat_grade
+            ));
+            lul.failures.push_csv(&mut csv, "latency_under_load");
+        }
+
+        if let Some(ref max_tunnels) = self.max_tunnels {
+            for phase in &max_tunnels.phases {
+                let scope = phase.username.as_deref().unwrap_or(&phase.scope);
+                csv.push_str(&format!(
+                    "max_tunnels,{scope}_limit,{},count\n",
+                    phase.limit
+                ));
+                csv.push_str(&format!(
+                    "max_tunnels,{scope}_tunnels_dropped,{},count\n",
+                    phase.tunnels_dropped
+                ));
+            }
+            max_tunnels.failures.push_csv(&mut csv, "max_tunnels");
+        }
+
+        if let Some(ref proxy_dns) = self.proxy_dns {
+            for path in &proxy_dns.paths {
+                let name = path
+                    .resolution
+                    .map(|r| r.to_string())
+                    .unwrap_or_else(|| "literal".to_string());
+                csv.push_str(&format!(
+                    "proxy_dns,{name}_success_rate,{:.2},percent\n",
+                    path.success_rate
+                ));
+                csv.push_str(&format!(
+                    "proxy_dns,{name}_median_connect_time,{},ms\n",
+                    path.median_connect_time.as_millis()
+                ));
+                if let Some(cold) = path.cold_overhead {
+                    csv.push_str(&format!(
+                        "proxy_dns,{name}_cold_overhead,{:.3},ms\n",
+                        cold.as_secs_f64() * 1000.0
+                    ));
+                }
+                if let Some(warm) = path.warm_overhead {
+                    csv.push_str(&format!(
+                        "proxy_dns,{name}_warm_overhead,{:.3},ms\n",
+                        warm.as_secs_f64() * 1000.0
+                    ));
+                }
+                if path.resolution.is_some() {
+                    csv.push_str(&format!(
+                        "proxy_dns,{name}_resolution_failures,{},count\n",
+                        path.resolution_failures
+                    ));
+                }
+            }
+            proxy_dns.failures.push_csv(&mut csv, "proxy_dns");
+        }
+
+        if !self.events.is_empty() {
+            let outages = self.outages();
+            let total_outage: Duration = outages.iter().filter_map(|o| o.duration).sum();
+            csv.push_str(&format!("events,total,{},count\n", self.events.len()));
+            csv.push_str(&format!("events,outages,{},count\n", outages.len()));
+            csv.push_str(&format!(
+                "events,ongoing_outages,{},count\n",
+                outages.iter().filter(|o| o.end.is_none()).count()
+            ));
+            csv.push_str(&format!(
+                "events,total_outage_time,{:.3},ms\n",
+                total_outage.as_secs_f64() * 1000.0
+            ));
         }
 
         csv
@@ -487,6 +1606,32 @@ impl MetricsCollector {
         self.metrics.network_jitter = Some(metrics);
     }
 
+    pub fn set_latency_under_load_metrics(&mut self, metrics: LatencyUnderLoadMetrics) {
+        self.metrics.latency_under_load = Some(metrics);
+    }
+
+    pub fn set_max_tunnels_metrics(&mut self, metrics: MaxTunnelsMetrics) {
+        self.metrics.max_tunnels = Some(metrics);
+    }
+
+    pub fn set_proxy_dns_metrics(&mut self, metrics: ProxyDnsMetrics) {
+        self.metrics.proxy_dns = Some(metrics);
+    }
+
+    pub fn add_events(&mut self, events: Vec<TestEvent>) {
+        self.metrics.events.extend(events);
+        self.metrics.events.sort_by_key(|event| event.timestamp);
+    }
+
+    pub fn add_error(&mut self, test: &str, error: &NetworkTestError) {
+        self.metrics.errors.push(TestError {
+            test: test.to_string(),
+            timestamp: Utc::now(),
+            category: FailureCategory::from(error).to_string(),
+            message: error.to_string(),
+        });
+    }
+
     pub fn finalize(mut self) -> Metrics {
         self.metrics.finalize();
         self.metrics
@@ -500,3 +1645,60 @@ impl MetricsCollector {
         self.metrics.get_summary()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_breakdown() {
+        let mut failures = FailureTally::default();
+        for _ in 0..3 {
+            failures.record(&NetworkTestError::SocksReply {
+                phase: Phase::Connect,
+                code: 0x05,
+            });
+        }
+        failures.record(&NetworkTestError::timeout(Phase::Exchange, "no reply"));
+
+        let breakdown = failures.breakdown();
+        assert_eq!(breakdown.total_failures, 4);
+        assert_eq!(
+            breakdown.categories[0].category,
+            "0x05 connection refused at CONNECT"
+        );
+        assert_eq!(breakdown.categories[0].percentage, 75.0);
+        assert_eq!(
+            breakdown.summary_lines()[0],
+            "75.0% of failures were 0x05 connection refused at CONNECT (3 of 4)"
+        );
+    }
+
+    #[test]
+    fn test_event_log_outages() {
+        let mut log = EventLog::new("tcp_stability");
+        log.recovered("a");
+        let timeout = FailureCategory::Timeout {
+            phase: Phase::Exchange,
+        };
+        log.category("a", timeout);
+        log.category("a", timeout);
+        log.failure("b", EventKind::DnsFailure, "NXDOMAIN");
+        log.failure("a", EventKind::ConnectionDrop, "reset");
+        log.reconnected("a", "after 1s");
+        log.category("a", timeout);
+
+        let events = log.into_events();
+        // Repeated timeouts fold into one event; recovery while healthy is ignored
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[0].count, 2);
+
+        let outages = outages(&events);
+        assert_eq!(outages.len(), 3);
+        assert_eq!(outages[0].target, "a");
+        assert_eq!(outages[0].failures, 3);
+        assert_eq!(outages[0].cause, "timeout: timeout at data exchange");
+        assert!(outages[0].end.is_some());
+        assert!(outages[1..].iter().all(|outage| outage.end.is_none()));
+    }
+}
@@ -1,21 +1,24 @@
+pub use crate::config::{OutputFormat, ReportAssertions};
+use crate::exporter::MetricRegistry;
+use crate::metrics::{
+    BandwidthMetrics, ConnectionPerfMetrics, DnsStabilityMetrics, FailureBreakdown,
+    FairnessMetrics, NetworkJitterMetrics,
+};
+use crate::tests::fairness::format_window_index;
+use crate::tests::network_jitter::r_factor_rating;
 use crate::{Metrics, NetworkTestError, Result};
 use chrono::Utc;
 use std::fs;
 use std::path::Path;
+use std::time::Duration;
+use tracing::info;
 
 #[derive(Debug, Clone)]
 pub struct Report {
     metrics: Metrics,
     output_format: OutputFormat,
     output_file: Option<String>,
-}
-
-#[derive(Debug, Clone)]
-pub enum OutputFormat {
-    Json,
-    Csv,
-    Html,
-    Text,
+    assertions: ReportAssertions,
 }
 
 impl Report {
@@ -24,6 +27,7 @@ impl Report {
             metrics,
             output_format: OutputFormat::Json,
             output_file: None,
+            assertions: ReportAssertions::default(),
         }
     }
 
@@ -37,17 +41,26 @@ impl Report {
         self
     }
 
+    pub fn with_assertions(mut self, assertions: ReportAssertions) -> Self {
+        self.assertions = assertions;
+        self
+    }
+
     pub fn generate_and_save(&self) -> Result<()> {
         let content = match self.output_format {
             OutputFormat::Json => self.generate_json()?,
             OutputFormat::Csv => self.generate_csv(),
             OutputFormat::Html => self.generate_html(),
             OutputFormat::Text => self.generate_text(),
+            OutputFormat::Influx => self.generate_influx(),
+            OutputFormat::OpenMetrics => self.generate_openmetrics(),
+            OutputFormat::Junit => self.generate_junit(),
+            OutputFormat::Markdown => self.generate_markdown(),
         };
 
         if let Some(ref file_path) = self.output_file {
             self.save_to_file(&content, file_path)?;
-            println!("Report saved to: {file_path}");
+            info!("Report saved to: {file_path}");
         } else {
             println!("{content}");
         }
@@ -105,6 +118,20 @@ impl Report {
             html.push_str(&self.generate_html_network_jitter());
         }
 
+        if self.metrics.latency_under_load.is_some() {
+            html.push_str(&self.generate_html_latency_under_load());
+        }
+
+        if self.metrics.max_tunnels.is_some() {
+            html.push_str(&self.generate_html_max_tunnels());
+        }
+
+        if self.metrics.proxy_dns.is_some() {
+            html.push_str(&self.generate_html_proxy_dns());
+        }
+
+        html.push_str(&self.generate_html_outages());
+
         html.push_str("</body>\n");
         html.push_str("</html>\n");
 
@@ -158,6 +185,7 @@ impl Report {
             ));
             text.push_str(&format!("Reconnections: {}\n", tcp.reconnections));
             text.push_str(&format!("Average RTT: {:?}\n", tcp.average_rtt));
+            self.push_text_failures(&mut text, &tcp.failures);
             text.push('\n');
         }
 
@@ -187,6 +215,27 @@ impl Report {
                 "Connection Interruptions: {}\n",
                 bandwidth.connection_interruptions
             ));
+            if let Some(ref fairness) = bandwidth.fairness {
+                text.push_str(&format!(
+                    "Jain's Fairness Index: {:.3} ({} streams)\n",
+                    fairness.jains_index, fairness.streams
+                ));
+                text.push_str(&format!(
+                    "Starvation Events: {}\n",
+                    fairness.starvation_events
+                ));
+                for stream in &fairness.per_stream {
+                    text.push_str(&format!(
+                        "  Stream {} ({}): {:.2} KB/s, {:.1}% share, {} starved windows\n",
+                        stream.stream_id,
+                        stream.username.as_deref().unwrap_or("-"),
+                        stream.average_throughput / 1024.0,
+                        stream.share_of_total,
+                        stream.starvation_events
+                    ));
+                }
+            }
+            self.push_text_failures(&mut text, &bandwidth.failures);
             text.push('\n');
         }
 
@@ -208,6 +257,42 @@ impl Report {
                 "Max Concurrent Successful: {}\n",
                 conn_perf.max_concurrent_successful
             ));
+            match conn_perf.saturation_knee {
+                Some(knee) => text.push_str(&format!(
+                    "Saturation Knee: {} ({})\n",
+                    knee,
+                    conn_perf.knee_reason.as_deref().unwrap_or("")
+                )),
+                None => text.push_str("Saturation Knee: not reached\n"),
+            }
+            for level in &conn_perf.concurrent_results {
+                text.push_str(&format!(
+                    "  Level {}: {}/{} ok ({:.1}%), p50 {:?}, p95 {:?}, p99 {:?}\n",
+                    level.concurrent_level,
+                    level.successful_connections,
+                    level.attempts,
+                    level.success_rate,
+                    level.median_time,
+                    level.p95_time,
+                    level.p99_time
+                ));
+            }
+            if let Some(rate) = conn_perf.max_sustained_rate {
+                text.push_str(&format!("Max Sustained Rate: {rate:.1} connections/sec\n"));
+            }
+            for step in &conn_perf.open_loop_results {
+                text.push_str(&format!(
+                    "  Offered {:.1}/s: achieved {:.1}/s, backlog max {} / end {}, p50 {:?}, p95 {:?}, p99 {:?}\n",
+                    step.offered_rate,
+                    step.achieved_rate,
+                    step.max_backlog,
+                    step.backlog_at_step_end,
+                    step.median_latency,
+                    step.p95_latency,
+                    step.p99_latency
+                ));
+            }
+            self.push_text_failures(&mut text, &conn_perf.failures);
             text.push('\n');
         }
 
@@ -223,6 +308,96 @@ impl Report {
                 "Average Query Time: {:?}\n",
                 dns.average_query_time
             ));
+            for resolver in &dns.per_resolver_metrics {
+                text.push_str(&format!(
+                    "  Resolver {}: {:.1}% ok, {:.1}% timeouts, p50 {:?}, p95 {:?}, p99 {:?}\n",
+                    resolver.resolver,
+                    resolver.success_rate,
+                    resolver.timeout_rate,
+                    resolver.median_query_time,
+                    resolver.p95_query_time,
+                    resolver.p99_query_time
+                ));
+            }
+            for record_type in &dns.per_type_metrics {
+                text.push_str(&format!(
+                    "  Type {}: {} queries over {} domain(s), {:.1}% ok, NODATA {}, avg {:?}, p95 {:?}\n",
+                    record_type.record_type,
+                    record_type.total_queries,
+                    record_type.domains,
+                    record_type.success_rate,
+                    record_type.nodata_responses,
+                    record_type.average_query_time,
+                    record_type.p95_query_time
+                ));
+            }
+            text.push_str(&format!(
+                "Answer Changes: {}, Unexpected Answers: {}, NXDOMAIN: {}, NODATA: {}\n",
+                dns.answer_changes,
+                dns.unexpected_answers,
+                dns.nxdomain_responses,
+                dns.nodata_responses
+            ));
+            text.push_str(&format!(
+                "UDP Associations: {} (avg setup {:?}, max {:?}), Stray Responses: {}\n",
+                dns.associations,
+                dns.average_association_time,
+                dns.max_association_time,
+                dns.stray_responses
+            ));
+            text.push_str(&format!(
+                "Transport: {}{}, UDP Queries: {}, TCP Queries: {}, TCP Connections: {} (avg connect {:?})\n",
+                dns.transport,
+                if dns.udp_fallback {
+                    " (fell back to TCP)"
+                } else {
+                    ""
+                },
+                dns.udp_queries,
+                dns.tcp_queries,
+                dns.tcp_connections,
+                dns.average_tcp_connect_time
+            ));
+            if dns.tls_handshakes > 0 {
+                text.push_str(&format!(
+                    "TLS Handshakes: {} ({} resumed), Average Handshake Time: {:?}\n",
+                    dns.tls_handshakes, dns.resumed_tls_sessions, dns.average_tls_handshake_time
+                ));
+            }
+            if let Some(ref cache) = dns.cache {
+                text.push_str(&format!(
+                    "Cache: est. hit ratio {:.1}%, hit median {:?} vs cold {:?} ({:?} faster), decremented TTLs {}, expiry probes {} ({} refetched)\n",
+                    cache.estimated_hit_ratio,
+                    cache.hit_median_query_time,
+                    cache.cold_median_query_time,
+                    cache.hit_latency_advantage,
+                    cache.decremented_ttl_responses,
+                    cache.expiry_probes,
+                    cache.expiry_refetches
+                ));
+            }
+            let mut domains: Vec<_> = dns.per_domain_metrics.values().collect();
+            domains.sort_by_key(|d| (d.domain.as_str(), d.record_type));
+            for domain in domains {
+                if domain.answer_changes == 0
+                    && domain.unexpected_answers == 0
+                    && domain.nxdomain_responses == 0
+                    && domain.nodata_responses == 0
+                {
+                    continue;
+                }
+                text.push_str(&format!(
+                    "  {} {}: {} answer sets, {} changes, unexpected [{}], NXDOMAIN {}, NODATA {}\n",
+                    domain.domain,
+                    domain.record_type,
+                    domain.answer_sets.len(),
+                    domain.answer_changes,
+                    domain.unexpected_addresses.join(", "),
+                    domain.nxdomain_responses,
+                    domain.nodata_responses
+                ));
+            }
+            self.push_text_failures(&mut text, &dns.failures);
             text.push('\n');
         }
 
@@ -238,6 +413,160 @@ impl Report {
             text.push_str(&format!("Successful Pings: {}\n", jitter.successful_pings));
             text.push_str(&format!("Average RTT: {:?}\n", jitter.average_rtt));
             text.push_str(&format!("Jitter: {:?}\n", jitter.jitter));
+            text.push_str(&format!("RFC 3550 Jitter: {:?}\n", jitter.rfc3550_jitter));
+            text.push_str(&format!(
+                "IPDV p50/p95/p99: {:?} / {:?} / {:?}\n",
+                jitter.ipdv_p50, jitter.ipdv_p95, jitter.ipdv_p99
+            ));
+            text.push_str(&format!(
+                "Voice Quality: R = {:.1}, MOS = {:.2} ({})\n",
+                jitter.r_factor,
+                jitter.mos,
+                r_factor_rating(jitter.r_factor)
+            ));
+            for mode in &jitter.modes {
+                text.push_str(&format!(
+                    "  {}: median RTT {:?}, jitter {:?}, RFC 3550 jitter {:?}, loss {:.2}%, MOS {:.2}, {} reconnect(s)\n",
+                    mode.mode.label(),
+                    mode.median_rtt,
+                    mode.jitter,
+                    mode.rfc3550_jitter,
+                    mode.packet_loss_rate,
+                    mode.mos,
+                    mode.reconnects
+                ));
+            }
+            if let Some(overhead) = jitter.setup_overhead {
+                text.push_str(&format!("Tunnel Setup Overhead: {overhead:?}\n"));
+            }
+            if let Some(ref one_way) = jitter.one_way_delay {
+                text.push_str(&format!(
+                    "Upstream Delay: median {:?}, jitter {:?}\n",
+                    one_way.upstream_median, one_way.upstream_jitter
+                ));
+                text.push_str(&format!(
+                    "Downstream Delay: median {:?}, jitter {:?}\n",
+                    one_way.downstream_median, one_way.downstream_jitter
+                ));
+                text.push_str(&format!("Slower Direction: {}\n", one_way.slower_direction));
+            }
+            let mut targets: Vec<_> = jitter.per_target_metrics.values().collect();
+            targets.sort_by_key(|target| target.target.as_str());
+            for target in targets {
+                text.push_str(&format!(
+                    "  {}: loss {:.2}%, p50 {:?}, p95 {:?}, p99 {:?}\n",
+                    target.target,
+                    target.packet_loss_rate,
+                    target.p50_rtt,
+                    target.p95_rtt,
+                    target.p99_rtt
+                ));
+            }
+            for pair in &jitter.loss_correlation {
+                text.push_str(&format!(
+                    "Loss Correlation {} / {}: {} joint loss(es), phi {}\n",
+                    pair.target_a,
+                    pair.target_b,
+                    pair.joint_losses,
+                    pair.correlation
+                        .map(|c| format!("{c:.2}"))
+                        .unwrap_or_else(|| "n/a".to_string())
+                ));
+            }
+            self.push_text_failures(&mut text, &jitter.failures);
+            text.push('\n');
+        }
+
+        if let Some(ref lul) = self.metrics.latency_under_load {
+            text.push_str("LATENCY UNDER LOAD TEST\n");
+            text.push_str("-----------------------\n");
+            text.push_str(&format!("Bufferbloat Grade: {}\n", lul.bufferbloat_grade));
+            text.push_str(&format!("Idle Median RTT: {:?}\n", lul.idle_median_rtt));
+            for (phase, loaded) in [
+                ("Upload", &lul.upload),
+                ("Download", &lul.download),
+                ("Duplex", &lul.duplex),
+            ] {
+                text.push_str(&format!(
+                    "{phase} RTT Inflation: +{:?} median, +{:?} p90, +{:?} p99\n",
+                    loaded.median_inflation, loaded.p90_inflation, loaded.p99_inflation
+                ));
+            }
+            self.push_text_failures(&mut text, &lul.failures);
+            text.push('\n');
+        }
+
+        if let Some(ref max_tunnels) = self.metrics.max_tunnels {
+            text.push_str("MAX SIMULTANEOUS TUNNELS TEST\n");
+            text.push_str("-----------------------------\n");
+            text.push_str(&format!("Tunnel Cap: {}\n", max_tunnels.max_tunnels));
+            for phase in &max_tunnels.phases {
+                let scope = phase.username.as_deref().unwrap_or("total");
+                text.push_str(&format!(
+                    "{scope}: limit {}{}, failure mode: {}, survived {}/{}\n",
+                    phase.limit,
+                    if phase.reached_cap {
+                        " (cap reached)"
+                    } else {
+                        ""
+                    },
+                    phase.failure_mode.as_deref().unwrap_or("none"),
+                    phase.tunnels_survived,
+                    phase.limit
+                ));
+            }
+            self.push_text_failures(&mut text, &max_tunnels.failures);
+            text.push('\n');
+        }
+
+        if let Some(ref proxy_dns) = self.metrics.proxy_dns {
+            text.push_str("PROXY DNS RESOLUTION TEST\n");
+            text.push_str("-------------------------\n");
+            text.push_str(&format!(
+                "Hostname Target: {}, Literal Target: {}, Rounds: {}\n",
+                proxy_dns.hostname_target, proxy_dns.literal_target, proxy_dns.rounds
+            ));
+            for path in &proxy_dns.paths {
+                text.push_str(&format!(
+                    "{}: {:.1}% ok, median {:?}, p95 {:?}",
+                    path.path, path.success_rate, path.median_connect_time, path.p95_connect_time
+                ));
+                if let Some(ref verdict) = path.verdict {
+                    text.push_str(&format!(
+                        ", cold DNS {:?}, warm DNS {:?}, {}",
+                        path.cold_overhead.unwrap_or_default(),
+                        path.warm_overhead.unwrap_or_default(),
+                        verdict
+                    ));
+                }
+                text.push('\n');
+            }
+            self.push_text_failures(&mut text, &proxy_dns.failures);
+            text.push('\n');
+        }
+
+        let outages = self.metrics.outages();
+        if !outages.is_empty() {
+            text.push_str("OUTAGE TIMELINE\n");
+            text.push_str("---------------\n");
+            for outage in &outages {
+                text.push_str(&format!(
+                    "{} -> {} ({}) [{}] {}: {} ({} failures)\n",
+                    outage.start.format("%H:%M:%S%.3f"),
+                    outage
+                        .end
+                        .map(|end| end.format("%H:%M:%S%.3f").to_string())
+                        .unwrap_or_else(|| "ongoing".to_string()),
+                    outage
+                        .duration
+                        .map(|d| format!("{d:.3?}"))
+                        .unwrap_or_else(|| "-".to_string()),
+                    outage.test,
+                    outage.target,
+                    outage.cause,
+                    outage.failures
+                ));
+            }
             text.push('\n');
         }
 
@@ -248,6 +577,1249 @@ impl Report {
         text
     }
 
+    fn generate_markdown(&self) -> String {
+        let metrics = &self.metrics;
+        let mut md = String::from("# Network Stability Test Report\n\n");
+
+        let mut overview = vec![
+            vec![
+                "Session ID".to_string(),
+                format!("`{}`", metrics.session_id),
+            ],
+            vec![
+                "Proxy Address".to_string(),
+                format!("`{}`", metrics.proxy_config.proxy_address),
+            ],
+            vec![
+                "Test Start Time".to_string(),
+                metrics
+                    .test_start_time
+                    .format("%Y-%m-%d %H:%M:%S UTC")
+                    .to_string(),
+            ],
+        ];
+        if let Some(end_time) = metrics.test_end_time {
+            overview.push(vec![
+                "Test End Time".to_string(),
+                end_time.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            ]);
+        }
+        if let Some(duration) = metrics.get_test_duration() {
+            overview.push(vec![
+                "Total Duration".to_string(),
+                format!("{duration:.2?}"),
+            ]);
+        }
+        if let Some(overall_score) = metrics.overall_score {
+            overview.push(vec![
+                "Overall Score".to_string(),
+                format!(
+                    "**{overall_score:.1}/100** ({})",
+                    self.get_rating(overall_score)
+                ),
+            ]);
+        }
+        md.push_str(&markdown_table(&["", ""], overview));
+
+        md.push_str(&self.generate_markdown_summary());
+
+        if let Some(ref tcp) = metrics.tcp_stability {
+            md.push_str("## TCP Stability\n\n");
+            md.push_str(&markdown_table(
+                &["Metric", "Value"],
+                vec![
+                    self.markdown_score(tcp.stability_score),
+                    vec!["Uptime".into(), format!("{:.2}%", tcp.uptime_percentage)],
+                    vec![
+                        "Heartbeats".into(),
+                        format!("{}/{} ok", tcp.successful_heartbeats, tcp.total_heartbeats),
+                    ],
+                    vec!["Reconnections".into(), tcp.reconnections.to_string()],
+                    vec![
+                        "Total Downtime".into(),
+                        format!("{:.2?}", tcp.total_downtime),
+                    ],
+                    vec![
+                        "RTT (min / avg / max)".into(),
+                        format!(
+                            "{:.2?} / {:.2?} / {:.2?}",
+                            tcp.min_rtt, tcp.average_rtt, tcp.max_rtt
+                        ),
+                    ],
+                ],
+            ));
+            self.push_markdown_failures(&mut md, &tcp.failures);
+        }
+
+        if let Some(ref bandwidth) = metrics.bandwidth {
+            md.push_str(&self.generate_markdown_bandwidth(bandwidth));
+        }
+
+        if let Some(ref conn_perf) = metrics.connection_perf {
+            md.push_str(&self.generate_markdown_connection_perf(conn_perf));
+        }
+
+        if let Some(ref dns) = metrics.dns_stability {
+            md.push_str(&self.generate_markdown_dns_stability(dns));
+        }
+
+        if let Some(ref jitter) = metrics.network_jitter {
+            md.push_str(&self.generate_markdown_network_jitter(jitter));
+        }
+
+        if let Some(ref lul) = metrics.latency_under_load {
+            md.push_str("## Latency Under Load\n\n");
+            md.push_str(&format!(
+                "Bufferbloat grade **{}**, idle median RTT {:.2?}, {} load stream(s).\n\n",
+                lul.bufferbloat_grade, lul.idle_median_rtt, lul.load_streams
+            ));
+            let rows = [
+                ("Upload", &lul.upload),
+                ("Download", &lul.download),
+                ("Duplex", &lul.duplex),
+            ]
+            .into_iter()
+            .map(|(phase, loaded)| {
+                vec![
+                    phase.to_string(),
+                    format!("{}/{}", loaded.lost_probes, loaded.probes),
+                    format!("{:.2?}", loaded.median_rtt),
+                    format!("{:.2?}", loaded.p99_rtt),
+                    format!("+{:.2?}", loaded.median_inflation),
+                    format!("+{:.2?}", loaded.p90_inflation),
+                    format!("+{:.2?}", loaded.p99_inflation),
+                    format!("{:.2} KB/s", loaded.throughput / 1024.0),
+                ]
+            })
+            .collect();
+            md.push_str(&markdown_table(
+                &[
+                    "Phase",
+                    "Lost Probes",
+                    "Median RTT",
+                    "P99 RTT",
+                    "Median Inflation",
+                    "P90 Inflation",
+                    "P99 Inflation",
+                    "Throughput",
+                ],
+                rows,
+            ));
+            self.push_markdown_failures(&mut md, &lul.failures);
+        }
+
+        if let Some(ref max_tunnels) = metrics.max_tunnels {
+            md.push_str("## Max Simultaneous Tunnels\n\n");
+            md.push_str(&format!("Tunnel cap: {}\n\n", max_tunnels.max_tunnels));
+            let rows = max_tunnels
+                .phases
+                .iter()
+                .map(|phase| {
+                    vec![
+                        phase.username.as_deref().unwrap_or("total").to_string(),
+                        phase.limit.to_string(),
+                        if phase.reached_cap { "yes" } else { "no" }.to_string(),
+                        phase.failure_mode.as_deref().unwrap_or("none").to_string(),
+                        format!("{}/{}", phase.tunnels_survived, phase.limit),
+                    ]
+                })
+                .collect();
+            md.push_str(&markdown_table(
+                &["Scope", "Limit", "Cap Reached", "Failure Mode", "Survived"],
+                rows,
+            ));
+            self.push_markdown_failures(&mut md, &max_tunnels.failures);
+        }
+
+        if let Some(ref proxy_dns) = metrics.proxy_dns {
+            md.push_str("## Proxy DNS Resolution\n\n");
+            md.push_str(&format!(
+                "Hostname target `{}`, literal target `{}`, {} round(s).\n\n",
+                proxy_dns.hostname_target, proxy_dns.literal_target, proxy_dns.rounds
+            ));
+            let rows = proxy_dns
+                .paths
+                .iter()
+                .map(|path| {
+                    vec![
+                        path.path.clone(),
+                        format!("{:.1}%", path.success_rate),
+                        format!("{:.2?}", path.median_connect_time),
+                        format!("{:.2?}", path.p95_connect_time),
+                        markdown_duration(path.cold_overhead),
+                        markdown_duration(path.warm_overhead),
+                        path.verdict.clone().unwrap_or_else(|| "-".to_string()),
+                    ]
+                })
+                .collect();
+            md.push_str(&markdown_table(
+                &[
+                    "Path",
+                    "Success",
+                    "Median Connect",
+                    "P95 Connect",
+                    "Cold DNS",
+                    "Warm DNS",
+                    "Verdict",
+                ],
+                rows,
+            ));
+            self.push_markdown_failures(&mut md, &proxy_dns.failures);
+        }
+
+        let outages = metrics.outages();
+        if !outages.is_empty() {
+            md.push_str("## Outages\n\n");
+            let rows = outages
+                .iter()
+                .map(|outage| {
+                    vec![
+                        outage.start.format("%H:%M:%S%.3f").to_string(),
+                        outage
+                            .end
+                            .map(|end| end.format("%H:%M:%S%.3f").to_string())
+                            .unwrap_or_else(|| "ongoing".to_string()),
+                        outage
+                            .duration
+                            .map(|d| format!("{d:.3?}"))
+                            .unwrap_or_else(|| "-".to_string()),
+                        outage.test.clone(),
+                        format!("`{}`", outage.target),
+                        outage.cause.clone(),
+                        outage.failures.to_string(),
+                    ]
+                })
+                .collect();
+            md.push_str(&markdown_table(
+                &[
+                    "Start", "End", "Duration", "Test", "Target", "Cause", "Failures",
+                ],
+                rows,
+            ));
+        }
+
+        md.push_str(&format!(
+            "_Report generated at {}_\n",
+            Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+        ));
+
+        md
+    }
+
+    fn generate_markdown_summary(&self) -> String {
+        let metrics = &self.metrics;
+        let scores = [
+            (
+                "TCP Stability",
+                metrics.tcp_stability.as_ref().map(|m| m.stability_score),
+            ),
+            (
+                "Bandwidth",
+                metrics.bandwidth.as_ref().map(|m| m.bandwidth_score),
+            ),
+            (
+                "Connection Performance",
+                metrics
+                    .connection_perf
+                    .as_ref()
+                    .map(|m| m.performance_score),
+            ),
+            (
+                "DNS Stability",
+                metrics.dns_stability.as_ref().map(|m| m.dns_score),
+            ),
+            (
+                "Network Jitter",
+                metrics
+                    .network_jitter
+                    .as_ref()
+                    .map(|m| m.network_quality_score),
+            ),
+        ];
+        let rows: Vec<Vec<String>> = scores
+            .into_iter()
+            .filter_map(|(test, score)| {
+                let score = score?;
+                Some(vec![
+                    test.to_string(),
+                    format!("{score:.1}"),
+                    self.get_rating(score).to_string(),
+                ])
+            })
+            .collect();
+        if rows.is_empty() {
+            return String::new();
+        }
+
+        format!(
+            "## Summary\n\n{}",
+            markdown_table(&["Test", "Score", "Rating"], rows)
+        )
+    }
+
+    fn generate_markdown_bandwidth(&self, bandwidth: &BandwidthMetrics) -> String {
+        let mut md = String::from("## Bandwidth\n\n");
+        md.push_str(&markdown_table(
+            &["Metric", "Value"],
+            vec![
+                self.markdown_score(bandwidth.bandwidth_score),
+                vec![
+                    "Upload (avg / max)".into(),
+                    format!(
+                        "{:.2} / {:.2} KB/s",
+                        bandwidth.average_upload_speed / 1024.0,
+                        bandwidth.max_upload_speed / 1024.0
+                    ),
+                ],
+                vec![
+                    "Download (avg / max)".into(),
+                    format!(
+                        "{:.2} / {:.2} KB/s",
+                        bandwidth.average_download_speed / 1024.0,
+                        bandwidth.max_download_speed / 1024.0
+                    ),
+                ],
+                vec![
+                    "Bytes Sent / Received".into(),
+                    format!(
+                        "{:.2} MB / {:.2} MB",
+                        bandwidth.total_bytes_sent as f64 / 1_048_576.0,
+                        bandwidth.total_bytes_received as f64 / 1_048_576.0
+                    ),
+                ],
+                vec![
+                    "Speed Consistency".into(),
+                    format!("{:.1}", bandwidth.speed_consistency_score),
+                ],
+                vec![
+                    "Connection Interruptions".into(),
+                    bandwidth.connection_interruptions.to_string(),
+                ],
+                vec![
+                    "Data Integrity Errors".into(),
+                    bandwidth.data_integrity_errors.to_string(),
+                ],
+            ],
+        ));
+
+        if let Some(ref fairness) = bandwidth.fairness {
+            md.push_str(&format!(
+                "### Fairness\n\nJain's index {:.3} over {} streams (worst window {}), {} starvation event(s).\n\n",
+                fairness.jains_index,
+                fairness.streams,
+                format_window_index(fairness.min_window_jains_index),
+                fairness.starvation_events
+            ));
+            let rows = fairness
+                .per_stream
+                .iter()
+                .map(|stream| {
+                    vec![
+                        stream.stream_id.to_string(),
+                        stream.username.clone().unwrap_or_else(|| "-".to_string()),
+                        format!("{:.2} KB/s", stream.average_throughput / 1024.0),
+                        format!("{:.1}%", stream.share_of_total),
+                        stream.starvation_events.to_string(),
+                        stream.reconnections.to_string(),
+                    ]
+                })
+                .collect();
+            md.push_str(&markdown_table(
+                &[
+                    "Stream",
+                    "User",
+                    "Throughput",
+                    "Share",
+                    "Starved Windows",
+                    "Reconnections",
+                ],
+                rows,
+            ));
+        }
+
+        self.push_markdown_failures(&mut md, &bandwidth.failures);
+        md
+    }
+
+    fn generate_markdown_connection_perf(&self, conn_perf: &ConnectionPerfMetrics) -> String {
+        let mut md = String::from("## Connection Performance\n\n");
+        md.push_str(&markdown_table(
+            &["Metric", "Value"],
+            vec![
+                self.markdown_score(conn_perf.performance_score),
+                vec![
+                    "Success Rate".into(),
+                    format!(
+                        "{:.2}% ({}/{})",
+                        conn_perf.success_rate,
+                        conn_perf.successful_connections,
+                        conn_perf.total_attempts
+                    ),
+                ],
+                vec![
+                    "Connection Time (avg / p95 / p99)".into(),
+                    format!(
+                        "{:.2?} / {:.2?} / {:.2?}",
+                        conn_perf.average_connection_time,
+                        conn_perf.p95_connection_time,
+                        conn_perf.p99_connection_time
+                    ),
+                ],
+                vec![
+                    "Max Concurrent Successful".into(),
+                    conn_perf.max_concurrent_successful.to_string(),
+                ],
+                vec![
+                    "Saturation Knee".into(),
+                    match conn_perf.saturation_knee {
+                        Some(knee) => format!(
+                            "{} ({})",
+                            knee,
+                            conn_perf.knee_reason.as_deref().unwrap_or("")
+                        ),
+                        None => "not reached".to_string(),
+                    },
+                ],
+            ],
+        ));
+
+        if !conn_perf.concurrent_results.is_empty() {
+            md.push_str("### Concurrency Levels\n\n");
+            let rows = conn_perf
+                .concurrent_results
+                .iter()
+                .map(|level| {
+                    vec![
+                        level.concurrent_level.to_string(),
+                        format!("{}/{}", level.successful_connections, level.attempts),
+                        format!("{:.1}%", level.success_rate),
+                        format!("{:.2?}", level.median_time),
+                        format!("{:.2?}", level.p95_time),
+                        format!("{:.2?}", level.p99_time),
+                    ]
+                })
+                .collect();
+            md.push_str(&markdown_table(
+                &["Level", "Successful", "Success", "P50", "P95", "P99"],
+                rows,
+            ));
+        }
+
+        if !conn_perf.open_loop_results.is_empty() {
+            md.push_str("### Open-Loop Rates\n\n");
+            if let Some(rate) = conn_perf.max_sustained_rate {
+                md.push_str(&format!(
+                    "Max sustained rate: {rate:.1} connections/sec\n\n"
+                ));
+            }
+            let rows = conn_perf
+                .open_loop_results
+                .iter()
+                .map(|step| {
+                    vec![
+                        format!("{:.1}/s", step.offered_rate),
+                        format!("{:.1}/s", step.achieved_rate),
+                        format!("{}/{}", step.successful_connections, step.scheduled),
+                        format!("{} / {}", step.max_backlog, step.backlog_at_step_end),
+                        format!("{:.2?}", step.median_latency),
+                        format!("{:.2?}", step.p95_latency),
+                        format!("{:.2?}", step.p99_latency),
+                    ]
+                })
+                .collect();
+            md.push_str(&markdown_table(
+                &[
+                    "Offered",
+                    "Achieved",
+                    "Successful",
+                    "Backlog (max / end)",
+                    "P50",
+                    "P95",
+                    "P99",
+                ],
+                rows,
+            ));
+        }
+
+        self.push_markdown_failures(&mut md, &conn_perf.failures);
+        md
+    }
+
+    fn generate_markdown_dns_stability(&self, dns: &DnsStabilityMetrics) -> String {
+        let mut md = String::from("## DNS Stability\n\n");
+        let mut overview = vec![
+            self.markdown_score(dns.dns_score),
+            vec![
+                "Success Rate".into(),
+                format!(
+                    "{:.2}% ({}/{})",
+                    dns.success_rate, dns.successful_queries, dns.total_queries
+                ),
+            ],
+            vec!["Timeout Rate".into(), format!("{:.2}%", dns.timeout_rate)],
+            vec![
+                "Query Time (min / avg / max)".into(),
+                format!(
+                    "{:.2?} / {:.2?} / {:.2?}",
+                    dns.min_query_time, dns.average_query_time, dns.max_query_time
+                ),
+            ],
+            vec![
+                "Transport".into(),
+                format!(
+                    "{}{}",
+                    dns.transport,
+                    if dns.udp_fallback {
+                        " (fell back to TCP)"
+                    } else {
+                        ""
+                    }
+                ),
+            ],
+            vec![
+                "Answer Changes / Unexpected Answers".into(),
+                format!("{} / {}", dns.answer_changes, dns.unexpected_answers),
+            ],
+            vec![
+                "NXDOMAIN / NODATA".into(),
+                format!("{} / {}", dns.nxdomain_responses, dns.nodata_responses),
+            ],
+        ];
+        if let Some(ref cache) = dns.cache {
+            overview.push(vec![
+                "Estimated Cache Hit Ratio".into(),
+                format!(
+                    "{:.1}% (hit median {:.2?} vs cold {:.2?})",
+                    cache.estimated_hit_ratio,
+                    cache.hit_median_query_time,
+                    cache.cold_median_query_time
+                ),
+            ]);
+        }
+        md.push_str(&markdown_table(&["Metric", "Value"], overview));
+
+        if !dns.per_resolver_metrics.is_empty() {
+            md.push_str("### Resolvers\n\n");
+            let rows = dns
+                .per_resolver_metrics
+                .iter()
+                .map(|resolver| {
+                    vec![
+                        format!("`{}`", resolver.resolver),
+                        resolver.total_queries.to_string(),
+                        format!("{:.1}%", resolver.success_rate),
+                        format!("{:.1}%", resolver.timeout_rate),
+                        format!("{:.2?}", resolver.median_query_time),
+                        format!("{:.2?}", resolver.p95_query_time),
+                        format!("{:.2?}", resolver.p99_query_time),
+                    ]
+                })
+                .collect();
+            md.push_str(&markdown_table(
+                &[
+                    "Resolver", "Queries", "Success", "Timeouts", "P50", "P95", "P99",
+                ],
+                rows,
+            ));
+        }
+
+        if !dns.per_type_metrics.is_empty() {
+            md.push_str("### Record Types\n\n");
+            let rows = dns
+                .per_type_metrics
+                .iter()
+                .map(|record_type| {
+                    vec![
+                        record_type.record_type.to_string(),
+                        record_type.domains.to_string(),
+                        record_type.total_queries.to_string(),
+                        format!("{:.1}%", record_type.success_rate),
+                        record_type.nodata_responses.to_string(),
+                        format!("{:.2?}", record_type.average_query_time),
+                        format!("{:.2?}", record_type.p95_query_time),
+                    ]
+                })
+                .collect();
+            md.push_str(&markdown_table(
+                &[
+                    "Type", "Domains", "Queries", "Success", "NODATA", "Average", "P95",
+                ],
+                rows,
+            ));
+        }
+
+        if !dns.per_domain_metrics.is_empty() {
+            md.push_str("### Domains\n\n");
+            let mut domains: Vec<_> = dns.per_domain_metrics.values().collect();
+            domains.sort_by_key(|d| (d.domain.as_str(), d.record_type));
+            let rows = domains
+                .into_iter()
+                .map(|domain| {
+                    vec![
+                        format!("`{}`", domain.domain),
+                        domain.record_type.to_string(),
+                        format!("{:.1}%", domain.success_rate),
+                        format!("{:.2?}", domain.average_query_time),
+                        domain.answer_sets.len().to_string(),
+                        domain.answer_changes.to_string(),
+                        if domain.unexpected_addresses.is_empty() {
+                            "-".to_string()
+                        } else {
+                            domain.unexpected_addresses.join(", ")
+                        },
+                        format!(
+                            "{} / {}",
+                            domain.nxdomain_responses, domain.nodata_responses
+                        ),
+                    ]
+                })
+                .collect();
+            md.push_str(&markdown_table(
+                &[
+                    "Domain",
+                    "Type",
+                    "Success",
+                    "Average",
+                    "Answer Sets",
+                    "Changes",
+                    "Unexpected",
+                    "NXDOMAIN / NODATA",
+                ],
+                rows,
+            ));
+        }
+
+        self.push_markdown_failures(&mut md, &dns.failures);
+        md
+    }
+
+    fn generate_markdown_network_jitter(&self, jitter: &NetworkJitterMetrics) -> String {
+        let mut md = String::from("## Network Jitter\n\n");
+        let mut overview = vec![
+            self.markdown_score(jitter.network_quality_score),
+            vec![
+                "Packet Loss".into(),
+                format!(
+                    "{:.2}% ({}/{} ok)",
+                    jitter.packet_loss_rate, jitter.successful_pings, jitter.total_pings
+                ),
+            ],
+            vec![
+                "RTT (avg / p95 / p99)".into(),
+                format!(
+                    "{:.2?} / {:.2?} / {:.2?}",
+                    jitter.average_rtt, jitter.p95_rtt, jitter.p99_rtt
+                ),
+            ],
+            vec![
+                "Jitter (mean / RFC 3550)".into(),
+                format!("{:.2?} / {:.2?}", jitter.jitter, jitter.rfc3550_jitter),
+            ],
+            vec![
+                "IPDV (p50 / p95 / p99)".into(),
+                format!(
+                    "{:.2?} / {:.2?} / {:.2?}",
+                    jitter.ipdv_p50, jitter.ipdv_p95, jitter.ipdv_p99
+                ),
+            ],
+            vec![
+                "Voice Quality".into(),
+                format!(
+                    "R = {:.1}, MOS = {:.2} ({})",
+                    jitter.r_factor,
+                    jitter.mos,
+                    r_factor_rating(jitter.r_factor)
+                ),
+            ],
+        ];
+        if let Some(overhead) = jitter.setup_overhead {
+            overview.push(vec![
+                "Tunnel Setup Overhead".into(),
+                format!("{overhead:.2?}"),
+            ]);
+        }
+        if let Some(ref one_way) = jitter.one_way_delay {
+            overview.push(vec![
+                "Upstream / Downstream Median".into(),
+                format!(
+                    "{:.2?} / {:.2?} (slower: {})",
+                    one_way.upstream_median, one_way.downstream_median, one_way.slower_direction
+                ),
+            ]);
+        }
+        md.push_str(&markdown_table(&["Metric", "Value"], overview));
+
+        if !jitter.modes.is_empty() {
+            md.push_str("### Ping Modes\n\n");
+            let rows = jitter
+                .modes
+                .iter()
+                .map(|mode| {
+                    vec![
+                        mode.mode.label().to_string(),
+                        format!("{:.2}%", mode.packet_loss_rate),
+                        format!("{:.2?}", mode.median_rtt),
+                        format!("{:.2?}", mode.p99_rtt),
+                        format!("{:.2?}", mode.jitter),
+                        format!("{:.2}", mode.mos),
+                        mode.reconnects.to_string(),
+                    ]
+                })
+                .collect();
+            md.push_str(&markdown_table(
+                &[
+                    "Mode",
+                    "Loss",
+                    "Median RTT",
+                    "P99 RTT",
+                    "Jitter",
+                    "MOS",
+                    "Reconnects",
+                ],
+                rows,
+            ));
+        }
+
+        if !jitter.per_target_metrics.is_empty() {
+            md.push_str("### Targets\n\n");
+            let mut targets: Vec<_> = jitter.per_target_metrics.values().collect();
+            targets.sort_by_key(|target| target.target.as_str());
+            let rows = targets
+                .into_iter()
+                .map(|target| {
+                    vec![
+                        format!("`{}`", target.target),
+                        format!("{}/{}", target.successful_pings, target.total_pings),
+                        format!("{:.2}%", target.packet_loss_rate),
+                        format!("{:.2?}", target.p50_rtt),
+                        format!("{:.2?}", target.p95_rtt),
+                        format!("{:.2?}", target.p99_rtt),
+                        format!("{:.2?}", target.jitter),
+                    ]
+                })
+                .collect();
+            md.push_str(&markdown_table(
+                &[
+                    "Target",
+                    "Successful",
+                    "Loss",
+                    "P50",
+                    "P95",
+                    "P99",
+                    "Jitter",
+                ],
+                rows,
+            ));
+        }
+
+        if !jitter.loss_correlation.is_empty() {
+            md.push_str("### Loss Correlation\n\n");
+            let rows = jitter
+                .loss_correlation
+                .iter()
+                .map(|pair| {
+                    vec![
+                        format!("`{}` / `{}`", pair.target_a, pair.target_b),
+                        pair.joint_losses.to_string(),
+                        pair.correlation
+                            .map(|c| format!("{c:.2}"))
+                            .unwrap_or_else(|| "n/a".to_string()),
+                    ]
+                })
+                .collect();
+            md.push_str(&markdown_table(&["Targets", "Joint Losses", "Phi"], rows));
+        }
+
+        self.push_markdown_failures(&mut md, &jitter.failures);
+        md
+    }
+
+    fn markdown_score(&self, score: f64) -> Vec<String> {
+        vec![
+            "Score".to_string(),
+            format!("**{score:.1}/100** ({})", self.get_rating(score)),
+        ]
+    }
+
+    fn push_markdown_failures(&self, md: &mut String, failures: &FailureBreakdown) {
+        if failures.categories.is_empty() {
+            return;
+        }
+
+        md.push_str("**Failure breakdown**\n\n");
+        for line in failures.summary_lines() {
+            md.push_str(&format!("- {line}\n"));
+        }
+        md.push('\n');
+    }
+
+    /// One line-protocol point per test, plus one per target, resolver,
+    /// phase or stream, all stamped with the session start time.
+    fn generate_influx(&self) -> String {
+        let metrics = &self.metrics;
+        let proxy = metrics.proxy_config.proxy_address.as_str();
+        let mut lines = Vec::new();
+
+        let outages = metrics.outages();
+        let end = metrics.test_end_time.unwrap_or_else(Utc::now);
+        let outage_time: Duration = outages
+            .iter()
+            .map(|outage| {
+                outage
+                    .duration
+                    .unwrap_or_else(|| (end - outage.start).to_std().unwrap_or_default())
+            })
+            .sum();
+        lines.push(
+            InfluxLine::new(proxy, "overall")
+                .text("session_id", &metrics.session_id)
+                .float("overall_score", metrics.overall_score)
+                .float(
+                    "duration_seconds",
+                    metrics.get_test_duration().map(|d| d.as_secs_f64()),
+                )
+                .int("outages", outages.len() as u64)
+                .millis("outage_time_ms", outage_time),
+        );
+
+        if let Some(ref tcp) = metrics.tcp_stability {
+            lines.push(
+                InfluxLine::new(proxy, "tcp_stability")
+                    .int("total_heartbeats", tcp.total_heartbeats)
+                    .int("successful_heartbeats", tcp.successful_heartbeats)
+                    .int("failed_heartbeats", tcp.failed_heartbeats)
+                    .int("reconnections", tcp.reconnections)
+                    .float("uptime_percentage", tcp.uptime_percentage)
+                    .millis("average_rtt_ms", tcp.average_rtt)
+                    .millis("min_rtt_ms", tcp.min_rtt)
+                    .millis("max_rtt_ms", tcp.max_rtt)
+                    .millis("total_downtime_ms", tcp.total_downtime)
+                    .float("stability_score", tcp.stability_score)
+                    .int("failures", tcp.failures.total_failures),
+            );
+        }
+
+        if let Some(ref bandwidth) = metrics.bandwidth {
+            let test = if bandwidth.fairness.is_some() {
+                "fairness"
+            } else {
+                "bandwidth"
+            };
+            let mut line = InfluxLine::new(proxy, test)
+                .int("total_bytes_sent", bandwidth.total_bytes_sent)
+                .int("total_bytes_received", bandwidth.total_bytes_received)
+                .float("average_upload_speed", bandwidth.average_upload_speed)
+                .float("average_download_speed", bandwidth.average_download_speed)
+                .float("max_upload_speed", bandwidth.max_upload_speed)
+                .float("max_download_speed", bandwidth.max_download_speed)
+                .float("min_upload_speed", bandwidth.min_upload_speed)
+                .float("min_download_speed", bandwidth.min_download_speed)
+                .int(
+                    "connection_interruptions",
+                    bandwidth.connection_interruptions as u64,
+                )
+                .int(
+                    "data_integrity_errors",
+                    bandwidth.data_integrity_errors as u64,
+                )
+                .float("bandwidth_score", bandwidth.bandwidth_score)
+                .int("failures", bandwidth.failures.total_failures);
+
+            if let Some(ref fairness) = bandwidth.fairness {
+                line = line
+                    .float("jains_index", fairness.jains_index)
+                    .float("min_window_jains_index", fairness.min_window_jains_index)
+                    .int("starvation_events", fairness.starvation_events);
+                for stream in &fairness.per_stream {
+                    lines.push(
+                        InfluxLine::new(proxy, test)
+                            .tag("stream", &stream.stream_id.to_string())
+                            .int("bytes_received", stream.bytes_received)
+                            .float("average_throughput", stream.average_throughput)
+                            .float("share_of_total", stream.share_of_total)
+                            .int("starvation_events", stream.starvation_events)
+                            .int("reconnections", stream.reconnections as u64),
+                    );
+                }
+            }
+            lines.push(line);
+        }
+
+        if let Some(ref perf) = metrics.connection_perf {
+            lines.push(
+                InfluxLine::new(proxy, "connection_perf")
+                    .int("total_attempts", perf.total_attempts as u64)
+                    .int("successful_connections", perf.successful_connections as u64)
+                    .int("failed_connections", perf.failed_connections as u64)
+                    .float("success_rate", perf.success_rate)
+                    .millis("average_connection_time_ms", perf.average_connection_time)
+                    .millis("median_connection_time_ms", perf.median_connection_time)
+                    .millis("p95_connection_time_ms", perf.p95_connection_time)
+                    .millis("p99_connection_time_ms", perf.p99_connection_time)
+                    .millis("max_connection_time_ms", perf.max_connection_time)
+                    .float("performance_score", perf.performance_score)
+                    .int("saturation_knee", perf.saturation_knee.map(|k| k as u64))
+                    .float("max_sustained_rate", perf.max_sustained_rate)
+                    .int("failures", perf.failures.total_failures),
+            );
+        }
+
+        if let Some(ref dns) = metrics.dns_stability {
+            lines.push(
+                InfluxLine::new(proxy, "dns_stability")
+                    .int("total_queries", dns.total_queries)
+                    .int("successful_queries", dns.successful_queries)
+                    .int("failed_queries", dns.failed_queries)
+                    .int("timeout_queries", dns.timeout_queries)
+                    .float("success_rate", dns.success_rate)
+                    .millis("average_query_time_ms", dns.average_query_time)
+                    .float("dns_score", dns.dns_score)
+                    .int("answer_changes", dns.answer_changes)
+                    .int("unexpected_answers", dns.unexpected_answers)
+                    .int("nxdomain_responses", dns.nxdomain_responses)
+                    .int("nodata_responses", dns.nodata_responses)
+                    .int("failures", dns.failures.total_failures),
+            );
+
+            for resolver in &dns.per_resolver_metrics {
+                lines.push(
+                    InfluxLine::new(proxy, "dns_stability")
+                        .tag("target", &resolver.resolver)
+                        .int("total_queries", resolver.total_queries)
+                        .float("success_rate", resolver.success_rate)
+                        .float("timeout_rate", resolver.timeout_rate)
+                        .millis("median_query_time_ms", resolver.median_query_time)
+                        .millis("p95_query_time_ms", resolver.p95_query_time)
+                        .millis("p99_query_time_ms", resolver.p99_query_time),
+                );
+            }
+
+            let mut domains: Vec<_> = dns.per_domain_metrics.values().collect();
+            domains.sort_by(|a, b| (&a.domain, a.record_type).cmp(&(&b.domain, b.record_type)));
+            for domain in domains {
+                lines.push(
+                    InfluxLine::new(proxy, "dns_stability")
+                        .tag("domain", &domain.domain)
+                        .tag("record_type", &domain.record_type.to_string())
+                        .int("total_queries", domain.total_queries)
+                        .float("success_rate", domain.success_rate)
+                        .millis("average_query_time_ms", domain.average_query_time)
+                        .int("answer_changes", domain.answer_changes)
+                        .int("unexpected_answers", domain.unexpected_answers),
+                );
+            }
+        }
+
+        if let Some(ref jitter) = metrics.network_jitter {
+            lines.push(
+                InfluxLine::new(proxy, "network_jitter")
+                    .int("total_pings", jitter.total_pings)
+                    .int("successful_pings", jitter.successful_pings)
+                    .int("failed_pings", jitter.failed_pings)
+                    .float("packet_loss_rate", jitter.packet_loss_rate)
+                    .millis("average_rtt_ms", jitter.average_rtt)
+                    .millis("median_rtt_ms", jitter.median_rtt)
+                    .millis("p95_rtt_ms", jitter.p95_rtt)
+                    .millis("p99_rtt_ms", jitter.p99_rtt)
+                    .millis("jitter_ms", jitter.jitter)
+                    .millis("rfc3550_jitter_ms", jitter.rfc3550_jitter)
+                    .float("r_factor", jitter.r_factor)
+                    .float("mos", jitter.mos)
+                    .float("network_quality_score", jitter.network_quality_score)
+                    .int("failures", jitter.failures.total_failures),
+            );
+
+            let mut targets: Vec<_> = jitter.per_target_metrics.values().collect();
+            targets.sort_by(|a, b| a.target.cmp(&b.target));
+            for target in targets {
+                lines.push(
+                    InfluxLine::new(proxy, "network_jitter")
+                        .tag("target", &target.target)
+                        .int("total_pings", target.total_pings)
+                        .float("packet_loss_rate", target.packet_loss_rate)
+                        .millis("average_rtt_ms", target.average_rtt)
+                        .millis("p50_rtt_ms", target.p50_rtt)
+                        .millis("p95_rtt_ms", target.p95_rtt)
+                        .millis("p99_rtt_ms", target.p99_rtt)
+                        .millis("jitter_ms", target.jitter),
+                );
+            }
+        }
+
+        if let Some(ref lul) = metrics.latency_under_load {
+            lines.push(
+                InfluxLine::new(proxy, "latency_under_load")
+                    .millis("idle_median_rtt_ms", lul.idle_median_rtt)
+                    .millis("idle_p90_rtt_ms", lul.idle_p90_rtt)
+                    .text("bufferbloat_grade", &lul.bufferbloat_grade)
+                    .int("failures", lul.failures.total_failures),
+            );
+
+            for (phase, loaded) in [
+                ("upload", &lul.upload),
+                ("download", &lul.download),
+                ("duplex", &lul.duplex),
+            ] {
+                lines.push(
+                    InfluxLine::new(proxy, "latency_under_load")
+                        .tag("phase", phase)
+                        .int("probes", loaded.probes)
+                        .int("lost_probes", loaded.lost_probes)
+                        .millis("median_rtt_ms", loaded.median_rtt)
+                        .millis("p90_rtt_ms", loaded.p90_rtt)
+                        .millis("p99_rtt_ms", loaded.p99_rtt)
+                        .millis("median_inflation_ms", loaded.median_inflation)
+                        .millis("p90_inflation_ms", loaded.p90_inflation)
+                        .float("throughput", loaded.throughput),
+                );
+            }
+        }
+
+        if let Some(ref tunnels) = metrics.max_tunnels {
+            for phase in &tunnels.phases {
+                lines.push(
+                    InfluxLine::new(proxy, "max_tunnels")
+                        .tag("scope", &phase.scope)
+                        .int("limit", phase.limit as u64)
+                        .flag("reached_cap", phase.reached_cap)
+                        .text_opt("failure_mode", phase.failure_mode.as_deref())
+                        .int("failed_attempts", phase.failed_attempts as u64)
+                        .millis("average_open_time_ms", phase.average_open_time)
+                        .int("tunnels_survived", phase.tunnels_survived as u64)
+                        .int("tunnels_dropped", phase.tunnels_dropped as u64),
+                );
+            }
+        }
+
+        if let Some(ref proxy_dns) = metrics.proxy_dns {
+            for path in &proxy_dns.paths {
+                lines.push(
+                    InfluxLine::new(proxy, "proxy_dns")
+                        .tag("path", &path.path)
+                        .int("attempts", path.attempts)
+                        .int("successful", path.successful)
+                        .float("success_rate", path.success_rate)
+                        .millis("median_connect_time_ms", path.median_connect_time)
+                        .millis("p95_connect_time_ms", path.p95_connect_time)
+                        .int("cache_misses", path.cache_misses)
+                        .int("resolution_failures", path.resolution_failures)
+                        .text_opt("verdict", path.verdict.as_deref()),
+                );
+            }
+        }
+
+        let timestamp = metrics
+            .test_start_time
+            .timestamp_nanos_opt()
+            .unwrap_or_default();
+        lines
+            .into_iter()
+            .filter_map(|line| line.finish(timestamp))
+            .collect()
+    }
+
+    fn generate_openmetrics(&self) -> String {
+        let mut registry = MetricRegistry::default();
+        registry.observe(
+            &self.metrics.proxy_config.proxy_address,
+            None,
+            &self.metrics,
+        );
+        registry.render_openmetrics()
+    }
+
+    /// One testsuite for the proxy with a testcase per test that ran; any
+    /// assertion the test's figures break is reported as its failure.
+    fn generate_junit(&self) -> String {
+        let metrics = &self.metrics;
+        let proxy = escape_xml(&metrics.proxy_config.proxy_address);
+        let cases = self.junit_cases();
+        let failed: Vec<Vec<String>> = cases
+            .iter()
+            .map(|case| case.failures(&self.assertions))
+            .collect();
+        let failures = failed.iter().filter(|f| !f.is_empty()).count();
+        let errors = metrics.errors.len();
+        let tests = cases.len() + errors;
+        let time = metrics.get_test_duration().map_or(0.0, |d| d.as_secs_f64());
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites name=\"nst\" tests=\"{tests}\" failures=\"{failures}\" errors=\"{errors}\" time=\"{time:.3}\">\n"
+        ));
+        xml.push_str(&format!(
+            "  <testsuite name=\"{proxy}\" tests=\"{tests}\" failures=\"{failures}\" errors=\"{errors}\" skipped=\"0\" time=\"{time:.3}\" timestamp=\"{}\">\n",
+            metrics.test_start_time.format("%Y-%m-%dT%H:%M:%S")
+        ));
+
+        xml.push_str("    <properties>\n");
+        let mut properties = vec![
+            ("session_id", metrics.session_id.clone()),
+            ("proxy_type", metrics.proxy_config.proxy_type.clone()),
+        ];
+        if let Some(score) = metrics.overall_score {
+            properties.push(("overall_score", format!("{score:.1}")));
+        }
+        for (name, value) in properties {
+            xml.push_str(&format!(
+                "      <property name=\"{name}\" value=\"{}\"/>\n",
+                escape_xml(&value)
+            ));
+        }
+        xml.push_str("    </properties>\n");
+
+        for (case, failures) in cases.iter().zip(&failed) {
+            xml.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"nst.{proxy}\" time=\"{:.3}\">\n",
+                case.name,
+                case.time.map_or(0.0, |d| d.as_secs_f64())
+            ));
+            if !failures.is_empty() {
+                xml.push_str(&format!(
+                    "      <failure message=\"{}\" type=\"AssertionError\">{}</failure>\n",
+                    escape_xml(&failures.join("; ")),
+                    escape_xml(&failures.join("\n"))
+                ));
+            }
+            xml.push_str(&format!(
+                "      <system-out>{}</system-out>\n",
+                escape_xml(&case.summary())
+            ));
+            xml.push_str("    </testcase>\n");
+        }
+
+        // A test that aborted has no figures to check, only the error
+        for error in &metrics.errors {
+            xml.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"nst.{proxy}\" time=\"0.000\">\n",
+                escape_xml(&error.test)
+            ));
+            xml.push_str(&format!(
+                "      <error message=\"{}\" type=\"{}\"/>\n",
+                escape_xml(&error.message),
+                escape_xml(&error.category)
+            ));
+            xml.push_str("    </testcase>\n");
+        }
+
+        xml.push_str("  </testsuite>\n");
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+
+    fn junit_cases(&self) -> Vec<JunitCase> {
+        let metrics = &self.metrics;
+        let mut cases = Vec::new();
+
+        if let Some(ref tcp) = metrics.tcp_stability {
+            cases.push(JunitCase {
+                name: "tcp_stability",
+                time: Some(tcp.test_duration),
+                score: Some(("stability_score", tcp.stability_score)),
+                p99: None,
+                loss: percentage(tcp.failed_heartbeats, tcp.total_heartbeats)
+                    .map(|loss| ("heartbeat_loss", loss)),
+            });
+        }
+
+        if let Some(ref bandwidth) = metrics.bandwidth {
+            cases.push(JunitCase {
+                name: if bandwidth.fairness.is_some() {
+                    "fairness"
+                } else {
+                    "bandwidth"
+                },
+                time: Some(bandwidth.test_duration),
+                score: Some(("bandwidth_score", bandwidth.bandwidth_score)),
+                p99: None,
+                loss: None,
+            });
+        }
+
+        if let Some(ref perf) = metrics.connection_perf {
+            cases.push(JunitCase {
+                name: "connection_perf",
+                time: None,
+                score: Some(("performance_score", perf.performance_score)),
+                p99: (perf.successful_connections > 0)
+                    .then_some(("p99_connection_time", perf.p99_connection_time)),
+                loss: (perf.total_attempts > 0)
+                    .then_some(("connection_failure_rate", 100.0 - perf.success_rate)),
+            });
+        }
+
+        if let Some(ref dns) = metrics.dns_stability {
+            cases.push(JunitCase {
+                name: "dns_stability",
+                time: Some(dns.test_duration),
+                score: Some(("dns_score", dns.dns_score)),
+                p99: dns
+                    .per_resolver_metrics
+                    .iter()
+                    .filter(|resolver| resolver.successful_queries > 0)
+                    .map(|resolver| resolver.p99_query_time)
+                    .max()
+                    .map(|p99| ("p99_query_time", p99)),
+                loss: (dns.total_queries > 0)
+                    .then_some(("query_failure_rate", 100.0 - dns.success_rate)),
+            });
+        }
+
+        if let Some(ref jitter) = metrics.network_jitter {
+            cases.push(JunitCase {
+                name: "network_jitter",
+                time: Some(jitter.test_duration),
+                score: Some(("network_quality_score", jitter.network_quality_score)),
+                p99: (jitter.successful_pings > 0).then_some(("p99_rtt", jitter.p99_rtt)),
+                loss: (jitter.total_pings > 0)
+                    .then_some(("packet_loss_rate", jitter.packet_loss_rate)),
+            });
+        }
+
+        if let Some(ref lul) = metrics.latency_under_load {
+            let phases = [&lul.upload, &lul.download, &lul.duplex];
+            let probes: u64 = phases.iter().map(|phase| phase.probes).sum();
+            let lost: u64 = phases.iter().map(|phase| phase.lost_probes).sum();
+            cases.push(JunitCase {
+                name: "latency_under_load",
+                time: Some(lul.phase_duration * 4),
+                score: None,
+                p99: phases
+                    .iter()
+                    .filter(|phase| phase.probes > phase.lost_probes)
+                    .map(|phase| phase.p99_rtt)
+                    .max()
+                    .map(|p99| ("p99_loaded_rtt", p99)),
+                loss: percentage(lost, probes).map(|loss| ("probe_loss", loss)),
+            });
+        }
+
+        if metrics.max_tunnels.is_some() {
+            cases.push(JunitCase {
+                name: "max_tunnels",
+                time: None,
+                score: None,
+                p99: None,
+                loss: None,
+            });
+        }
+
+        if let Some(ref proxy_dns) = metrics.proxy_dns {
+            let attempts: u64 = proxy_dns.paths.iter().map(|path| path.attempts).sum();
+            let successful: u64 = proxy_dns.paths.iter().map(|path| path.successful).sum();
+            cases.push(JunitCase {
+                name: "proxy_dns",
+                time: None,
+                score: None,
+                p99: None,
+                loss: percentage(attempts - successful, attempts)
+                    .map(|loss| ("connect_failure_rate", loss)),
+            });
+        }
+
+        cases
+    }
+
     fn generate_html_header(&self) -> String {
         format!(
             r#"
@@ -337,7 +1909,7 @@ impl Report {
                 <span class="label">Average RTT:</span>
                 <span class="value">{:?}</span>
             </div>
-        </div>
+        </div>{}
     </section>
 "#,
                 tcp.stability_score,
@@ -345,7 +1917,8 @@ impl Report {
                 tcp.total_heartbeats,
                 tcp.successful_heartbeats,
                 tcp.reconnections,
-                tcp.average_rtt
+                tcp.average_rtt,
+                self.generate_html_failures(&tcp.failures)
             )
         } else {
             String::new()
@@ -383,7 +1956,7 @@ impl Report {
                 <span class="label">Interruptions:</span>
                 <span class="value">{}</span>
             </div>
-        </div>
+        </div>{}{}
     </section>
 "#,
                 bandwidth.bandwidth_score,
@@ -391,15 +1964,163 @@ impl Report {
                 bandwidth.average_download_speed / 1024.0,
                 bandwidth.total_bytes_sent as f64 / 1_048_576.0,
                 bandwidth.total_bytes_received as f64 / 1_048_576.0,
-                bandwidth.connection_interruptions
+                bandwidth.connection_interruptions,
+                bandwidth
+                    .fairness
+                    .as_ref()
+                    .map(|f| self.generate_html_fairness(f))
+                    .unwrap_or_default(),
+                self.generate_html_failures(&bandwidth.failures)
             )
         } else {
             String::new()
         }
     }
 
+    fn generate_html_fairness(&self, fairness: &FairnessMetrics) -> String {
+        let mut rows = String::new();
+        for stream in &fairness.per_stream {
+            rows.push_str(&format!(
+                r#"
+                <tr>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{:.2} KB/s</td>
+                    <td>{:.2} KB/s</td>
+                    <td>{:.1}%</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                </tr>
+"#,
+                stream.stream_id,
+                stream.username.as_deref().unwrap_or("-"),
+                stream.average_throughput / 1024.0,
+                stream.min_throughput / 1024.0,
+                stream.share_of_total,
+                stream.starvation_events,
+                stream.reconnections
+            ));
+        }
+
+        format!(
+            r#"
+        <h3>Multi-Stream Fairness</h3>
+        <div class="metrics-grid">
+            <div class="metric">
+                <span class="label">Jain's Fairness Index:</span>
+                <span class="value">{:.3}</span>
+            </div>
+            <div class="metric">
+                <span class="label">Worst Window Index:</span>
+                <span class="value">{}</span>
+            </div>
+            <div class="metric">
+                <span class="label">Starvation Events:</span>
+                <span class="value">{}</span>
+            </div>
+        </div>
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Stream</th>
+                    <th>User</th>
+                    <th>Avg Throughput</th>
+                    <th>Min Throughput</th>
+                    <th>Share</th>
+                    <th>Starved</th>
+                    <th>Reconnects</th>
+                </tr>
+            </thead>
+            <tbody>{}
+            </tbody>
+        </table>"#,
+            fairness.jains_index,
+            format_window_index(fairness.min_window_jains_index),
+            fairness.starvation_events,
+            rows
+        )
+    }
+
     fn generate_html_connection_perf(&self) -> String {
         if let Some(ref conn_perf) = self.metrics.connection_perf {
+            let mut rows = String::new();
+            for level in &conn_perf.concurrent_results {
+                rows.push_str(&format!(
+                    r#"
+                <tr>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{:.1}%</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                </tr>
+"#,
+                    level.concurrent_level,
+                    level.attempts,
+                    level.success_rate,
+                    level.median_time,
+                    level.p95_time,
+                    level.p99_time
+                ));
+            }
+
+            let open_loop_table = if conn_perf.open_loop_results.is_empty() {
+                String::new()
+            } else {
+                let mut open_loop_rows = String::new();
+                for step in &conn_perf.open_loop_results {
+                    open_loop_rows.push_str(&format!(
+                        r#"
+                <tr>
+                    <td>{:.1}/s</td>
+                    <td>{:.1}/s</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                </tr>
+"#,
+                        step.offered_rate,
+                        step.achieved_rate,
+                        step.max_backlog,
+                        step.backlog_at_step_end,
+                        step.median_latency,
+                        step.p95_latency,
+                        step.p99_latency
+                    ));
+                }
+                format!(
+                    r#"
+        <h3>Open-Loop Connection Rate</h3>
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Offered</th>
+                    <th>Achieved</th>
+                    <th>Max Backlog</th>
+                    <th>End Backlog</th>
+                    <th>p50</th>
+                    <th>p95</th>
+                    <th>p99</th>
+                </tr>
+            </thead>
+            <tbody>{open_loop_rows}
+            </tbody>
+        </table>"#
+                )
+            };
+
+            let knee = match conn_perf.saturation_knee {
+                Some(knee) => format!(
+                    "{} ({})",
+                    knee,
+                    conn_perf.knee_reason.as_deref().unwrap_or("")
+                ),
+                None => "Not reached".to_string(),
+            };
+
             format!(
                 r#"
     <section class="test-section">
@@ -429,7 +2150,25 @@ impl Report {
                 <span class="label">Max Concurrent:</span>
                 <span class="value">{}</span>
             </div>
+            <div class="metric">
+                <span class="label">Saturation Knee:</span>
+                <span class="value">{}</span>
+            </div>
         </div>
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Concurrency</th>
+                    <th>Attempts</th>
+                    <th>Success Rate</th>
+                    <th>p50</th>
+                    <th>p95</th>
+                    <th>p99</th>
+                </tr>
+            </thead>
+            <tbody>{}
+            </tbody>
+        </table>{}{}
     </section>
 "#,
                 conn_perf.performance_score,
@@ -437,7 +2176,11 @@ impl Report {
                 conn_perf.total_attempts,
                 conn_perf.successful_connections,
                 conn_perf.average_connection_time,
-                conn_perf.max_concurrent_successful
+                conn_perf.max_concurrent_successful,
+                knee,
+                rows,
+                open_loop_table,
+                self.generate_html_failures(&conn_perf.failures)
             )
         } else {
             String::new()
@@ -446,6 +2189,187 @@ impl Report {
 
     fn generate_html_dns_stability(&self) -> String {
         if let Some(ref dns) = self.metrics.dns_stability {
+            let resolver_table = if dns.per_resolver_metrics.is_empty() {
+                String::new()
+            } else {
+                let mut rows = String::new();
+                for resolver in &dns.per_resolver_metrics {
+                    rows.push_str(&format!(
+                        r#"
+                <tr>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{:.1}%</td>
+                    <td>{:.1}%</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                </tr>
+"#,
+                        resolver.resolver,
+                        resolver.total_queries,
+                        resolver.success_rate,
+                        resolver.timeout_rate,
+                        resolver.median_query_time,
+                        resolver.p95_query_time,
+                        resolver.p99_query_time
+                    ));
+                }
+                format!(
+                    r#"
+        <h3>Resolver Comparison</h3>
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Resolver</th>
+                    <th>Queries</th>
+                    <th>Success Rate</th>
+                    <th>Timeout Rate</th>
+                    <th>p50</th>
+                    <th>p95</th>
+                    <th>p99</th>
+                </tr>
+            </thead>
+            <tbody>{rows}
+            </tbody>
+        </table>"#
+                )
+            };
+
+            let type_table = if dns.per_type_metrics.is_empty() {
+                String::new()
+            } else {
+                let mut rows = String::new();
+                for record_type in &dns.per_type_metrics {
+                    rows.push_str(&format!(
+                        r#"
+                <tr>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{:.1}%</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                </tr>
+"#,
+                        record_type.record_type,
+                        record_type.domains,
+                        record_type.total_queries,
+                        record_type.success_rate,
+                        record_type.answer_changes,
+                        record_type.nodata_responses,
+                        record_type.average_query_time,
+                        record_type.p95_query_time
+                    ));
+                }
+                format!(
+                    r#"
+        <h3>Record Types</h3>
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Type</th>
+                    <th>Domains</th>
+                    <th>Queries</th>
+                    <th>Success Rate</th>
+                    <th>Changes</th>
+                    <th>NODATA</th>
+                    <th>Avg Time</th>
+                    <th>p95</th>
+                </tr>
+            </thead>
+            <tbody>{rows}
+            </tbody>
+        </table>"#
+                )
+            };
+
+            let cache_section = match dns.cache {
+                Some(ref cache) => format!(
+                    r#"
+        <h3>Cache Behavior</h3>
+        <div class="metrics-grid">
+            <div class="metric">
+                <span class="label">Estimated Hit Ratio:</span>
+                <span class="value">{:.1}% ({} of {})</span>
+            </div>
+            <div class="metric">
+                <span class="label">Hit / Cold Median:</span>
+                <span class="value">{:?} / {:?}</span>
+            </div>
+            <div class="metric">
+                <span class="label">Hit Advantage:</span>
+                <span class="value">{:?}</span>
+            </div>
+            <div class="metric">
+                <span class="label">Decremented TTLs:</span>
+                <span class="value">{} ({} early resets)</span>
+            </div>
+            <div class="metric">
+                <span class="label">TTL-Expiry Probes:</span>
+                <span class="value">{} ({} refetched, {} stale)</span>
+            </div>
+        </div>"#,
+                    cache.estimated_hit_ratio,
+                    cache.estimated_hits,
+                    cache.warm_queries,
+                    cache.hit_median_query_time,
+                    cache.cold_median_query_time,
+                    cache.hit_latency_advantage,
+                    cache.decremented_ttl_responses,
+                    cache.early_ttl_resets,
+                    cache.expiry_probes,
+                    cache.expiry_refetches,
+                    cache.expiry_stale_answers
+                ),
+                None => String::new(),
+            };
+
+            let mut answer_rows = String::new();
+            let mut domains: Vec<_> = dns.per_domain_metrics.values().collect();
+            domains.sort_by_key(|d| (d.domain.as_str(), d.record_type));
+            for domain in domains {
+                let sets: Vec<String> = domain
+                    .answer_sets
+                    .iter()
+                    .map(|set| {
+                        let records = if set.records.is_empty() {
+                            &set.addresses
+                        } else {
+                            &set.records
+                        };
+                        format!("{} ({}x)", records.join(", "), set.observations)
+                    })
+                    .collect();
+                answer_rows.push_str(&format!(
+                    r#"
+                <tr>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                </tr>
+"#,
+                    domain.domain,
+                    domain.record_type,
+                    sets.join("<br>"),
+                    domain.answer_changes,
+                    domain.unexpected_addresses.join(", "),
+                    domain.nxdomain_responses,
+                    domain.nodata_responses,
+                    match (domain.min_ttl, domain.max_ttl) {
+                        (Some(min), Some(max)) => format!("{min}-{max}s"),
+                        _ => "-".to_string(),
+                    }
+                ));
+            }
+
             format!(
                 r#"
     <section class="test-section">
@@ -475,7 +2399,40 @@ impl Report {
                 <span class="label">Avg Query Time:</span>
                 <span class="value">{:?}</span>
             </div>
-        </div>
+            <div class="metric">
+                <span class="label">UDP Associations:</span>
+                <span class="value">{} (avg setup {:?})</span>
+            </div>
+            <div class="metric">
+                <span class="label">Stray Responses:</span>
+                <span class="value">{}</span>
+            </div>
+            <div class="metric">
+                <span class="label">Transport:</span>
+                <span class="value">{} (UDP {} / TCP {} queries)</span>
+            </div>
+            <div class="metric">
+                <span class="label">TLS Handshakes:</span>
+                <span class="value">{} ({} resumed, avg {:?})</span>
+            </div>
+        </div>{}{}{}
+        <h3>Answer Validation</h3>
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Domain</th>
+                    <th>Type</th>
+                    <th>Answer Sets</th>
+                    <th>Changes</th>
+                    <th>Unexpected</th>
+                    <th>NXDOMAIN</th>
+                    <th>NODATA</th>
+                    <th>TTL</th>
+                </tr>
+            </thead>
+            <tbody>{}
+            </tbody>
+        </table>{}
     </section>
 "#,
                 dns.dns_score,
@@ -483,7 +2440,25 @@ impl Report {
                 dns.total_queries,
                 dns.successful_queries,
                 dns.timeout_rate,
-                dns.average_query_time
+                dns.average_query_time,
+                dns.associations,
+                dns.average_association_time,
+                dns.stray_responses,
+                if dns.udp_fallback {
+                    format!("{} → tcp", dns.transport)
+                } else {
+                    dns.transport.to_string()
+                },
+                dns.udp_queries,
+                dns.tcp_queries,
+                dns.tls_handshakes,
+                dns.resumed_tls_sessions,
+                dns.average_tls_handshake_time,
+                resolver_table,
+                type_table,
+                cache_section,
+                answer_rows,
+                self.generate_html_failures(&dns.failures)
             )
         } else {
             String::new()
@@ -492,6 +2467,192 @@ impl Report {
 
     fn generate_html_network_jitter(&self) -> String {
         if let Some(ref jitter) = self.metrics.network_jitter {
+            let mut modes = String::new();
+            if !jitter.modes.is_empty() {
+                let mut rows = String::new();
+                for mode in &jitter.modes {
+                    rows.push_str(&format!(
+                        r#"
+                <tr>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{:.2}%</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                    <td>{:.2}</td>
+                    <td>{}</td>
+                </tr>
+"#,
+                        mode.mode.label(),
+                        mode.total_pings,
+                        mode.packet_loss_rate,
+                        mode.median_rtt,
+                        mode.p95_rtt,
+                        mode.jitter,
+                        mode.rfc3550_jitter,
+                        mode.mos,
+                        mode.reconnects
+                    ));
+                }
+                modes = format!(
+                    r#"
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Mode</th>
+                    <th>Pings</th>
+                    <th>Loss</th>
+                    <th>Median RTT</th>
+                    <th>p95 RTT</th>
+                    <th>Jitter</th>
+                    <th>RFC 3550 Jitter</th>
+                    <th>MOS</th>
+                    <th>Reconnects</th>
+                </tr>
+            </thead>
+            <tbody>{rows}
+            </tbody>
+        </table>"#
+                );
+            }
+            let mut target_rows = String::new();
+            let mut targets: Vec<_> = jitter.per_target_metrics.values().collect();
+            targets.sort_by_key(|target| target.target.as_str());
+            for target in targets {
+                target_rows.push_str(&format!(
+                    r#"
+                <tr>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{:.2}%</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                </tr>
+"#,
+                    target.target,
+                    target.total_pings,
+                    target.packet_loss_rate,
+                    target.p50_rtt,
+                    target.p95_rtt,
+                    target.p99_rtt,
+                    target.jitter
+                ));
+            }
+            let mut correlation_rows = String::new();
+            for pair in &jitter.loss_correlation {
+                correlation_rows.push_str(&format!(
+                    r#"
+                <tr>
+                    <td>{} / {}</td>
+                    <td>{}</td>
+                    <td>{} / {}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                </tr>
+"#,
+                    pair.target_a,
+                    pair.target_b,
+                    pair.slots,
+                    pair.losses_a,
+                    pair.losses_b,
+                    pair.joint_losses,
+                    pair.correlation
+                        .map(|c| format!("{c:.2}"))
+                        .unwrap_or_else(|| "n/a".to_string())
+                ));
+            }
+            let targets = if target_rows.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    r#"
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Target</th>
+                    <th>Pings</th>
+                    <th>Loss</th>
+                    <th>p50 RTT</th>
+                    <th>p95 RTT</th>
+                    <th>p99 RTT</th>
+                    <th>Jitter</th>
+                </tr>
+            </thead>
+            <tbody>{target_rows}
+            </tbody>
+        </table>"#
+                )
+            };
+            let correlations = if correlation_rows.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    r#"
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Target Pair</th>
+                    <th>Slots</th>
+                    <th>Losses</th>
+                    <th>Joint Losses</th>
+                    <th>Loss Correlation</th>
+                </tr>
+            </thead>
+            <tbody>{correlation_rows}
+            </tbody>
+        </table>"#
+                )
+            };
+            let one_way = jitter
+                .one_way_delay
+                .as_ref()
+                .map(|one_way| {
+                    format!(
+                        r#"
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Direction</th>
+                    <th>Median Delay</th>
+                    <th>p95 Delay</th>
+                    <th>Jitter</th>
+                </tr>
+            </thead>
+            <tbody>
+                <tr>
+                    <td>Upstream</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                </tr>
+                <tr>
+                    <td>Downstream</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                </tr>
+            </tbody>
+        </table>
+        <p>Slower direction: {}</p>"#,
+                        one_way.upstream_median,
+                        one_way.upstream_p95,
+                        one_way.upstream_jitter,
+                        one_way.downstream_median,
+                        one_way.downstream_p95,
+                        one_way.downstream_jitter,
+                        one_way.slower_direction
+                    )
+                })
+                .unwrap_or_default();
+            let setup_overhead = jitter
+                .setup_overhead
+                .map(|o| format!("{o:?}"))
+                .unwrap_or_else(|| "-".to_string());
+
             format!(
                 r#"
     <section class="test-section">
@@ -521,7 +2682,23 @@ impl Report {
                 <span class="label">Jitter:</span>
                 <span class="value">{:?}</span>
             </div>
-        </div>
+            <div class="metric">
+                <span class="label">RFC 3550 Jitter:</span>
+                <span class="value">{:?}</span>
+            </div>
+            <div class="metric">
+                <span class="label">IPDV p95:</span>
+                <span class="value">{:?}</span>
+            </div>
+            <div class="metric">
+                <span class="label">MOS (R-factor):</span>
+                <span class="value">{:.2} ({:.1}, {})</span>
+            </div>
+            <div class="metric">
+                <span class="label">Tunnel Setup Overhead:</span>
+                <span class="value">{}</span>
+            </div>
+        </div>{}{}{}{}{}
     </section>
 "#,
                 jitter.network_quality_score,
@@ -529,13 +2706,347 @@ impl Report {
                 jitter.total_pings,
                 jitter.successful_pings,
                 jitter.average_rtt,
-                jitter.jitter
+                jitter.jitter,
+                jitter.rfc3550_jitter,
+                jitter.ipdv_p95,
+                jitter.mos,
+                jitter.r_factor,
+                r_factor_rating(jitter.r_factor),
+                setup_overhead,
+                modes,
+                one_way,
+                targets,
+                correlations,
+                self.generate_html_failures(&jitter.failures)
             )
         } else {
             String::new()
         }
     }
 
+    fn generate_html_latency_under_load(&self) -> String {
+        if let Some(ref lul) = self.metrics.latency_under_load {
+            let mut rows = String::new();
+            for (phase, loaded) in [
+                ("Upload", &lul.upload),
+                ("Download", &lul.download),
+                ("Duplex", &lul.duplex),
+            ] {
+                rows.push_str(&format!(
+                    r#"
+                <tr>
+                    <td>{}</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                    <td>+{:?}</td>
+                    <td>+{:?}</td>
+                    <td>+{:?}</td>
+                    <td>{:.2} Mbps</td>
+                </tr>
+"#,
+                    phase,
+                    loaded.median_rtt,
+                    loaded.p99_rtt,
+                    loaded.median_inflation,
+                    loaded.p90_inflation,
+                    loaded.p99_inflation,
+                    loaded.throughput * 8.0 / 1_000_000.0
+                ));
+            }
+
+            format!(
+                r#"
+    <section class="test-section">
+        <h2>Latency Under Load Test</h2>
+        <div class="metrics-grid">
+            <div class="metric">
+                <span class="label">Bufferbloat Grade:</span>
+                <span class="value">{}</span>
+            </div>
+            <div class="metric">
+                <span class="label">Idle Median RTT:</span>
+                <span class="value">{:?}</span>
+            </div>
+            <div class="metric">
+                <span class="label">Load Streams:</span>
+                <span class="value">{}</span>
+            </div>
+        </div>
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Phase</th>
+                    <th>Median RTT</th>
+                    <th>p99 RTT</th>
+                    <th>Median Inflation</th>
+                    <th>p90 Inflation</th>
+                    <th>p99 Inflation</th>
+                    <th>Throughput</th>
+                </tr>
+            </thead>
+            <tbody>{}
+            </tbody>
+        </table>{}
+    </section>
+"#,
+                lul.bufferbloat_grade,
+                lul.idle_median_rtt,
+                lul.load_streams,
+                rows,
+                self.generate_html_failures(&lul.failures)
+            )
+        } else {
+            String::new()
+        }
+    }
+
+    fn generate_html_max_tunnels(&self) -> String {
+        if let Some(ref max_tunnels) = self.metrics.max_tunnels {
+            let mut rows = String::new();
+            for phase in &max_tunnels.phases {
+                rows.push_str(&format!(
+                    r#"
+                <tr>
+                    <td>{}</td>
+                    <td>{}{}</td>
+                    <td>{}</td>
+                    <td>{:?}</td>
+                    <td>{}/{}</td>
+                </tr>
+"#,
+                    phase.username.as_deref().unwrap_or("total"),
+                    phase.limit,
+                    if phase.reached_cap { " (cap)" } else { "" },
+                    phase.failure_mode.as_deref().unwrap_or("none"),
+                    phase.average_open_time,
+                    phase.tunnels_survived,
+                    phase.limit
+                ));
+            }
+
+            format!(
+                r#"
+    <section class="test-section">
+        <h2>Max Simultaneous Tunnels Test</h2>
+        <div class="metrics-grid">
+            <div class="metric">
+                <span class="label">Tunnel Cap:</span>
+                <span class="value">{}</span>
+            </div>
+            <div class="metric">
+                <span class="label">Heartbeat Interval:</span>
+                <span class="value">{:?}</span>
+            </div>
+        </div>
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Scope</th>
+                    <th>Limit</th>
+                    <th>Failure Mode</th>
+                    <th>Avg Open Time</th>
+                    <th>Survived</th>
+                </tr>
+            </thead>
+            <tbody>{}
+            </tbody>
+        </table>{}
+    </section>
+"#,
+                max_tunnels.max_tunnels,
+                max_tunnels.heartbeat_interval,
+                rows,
+                self.generate_html_failures(&max_tunnels.failures)
+            )
+        } else {
+            String::new()
+        }
+    }
+
+    fn generate_html_proxy_dns(&self) -> String {
+        if let Some(ref proxy_dns) = self.metrics.proxy_dns {
+            let overhead = |o: Option<Duration>| o.map(|o| format!("{o:?}")).unwrap_or_default();
+            let mut rows = String::new();
+            for path in &proxy_dns.paths {
+                rows.push_str(&format!(
+                    r#"
+                <tr>
+                    <td>{}</td>
+                    <td>{:.1}%</td>
+                    <td>{:?}</td>
+                    <td>{:?}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                </tr>
+"#,
+                    path.path,
+                    path.success_rate,
+                    path.median_connect_time,
+                    path.p95_connect_time,
+                    overhead(path.cold_overhead),
+                    overhead(path.warm_overhead),
+                    path.resolution_failures,
+                    path.verdict.as_deref().unwrap_or("baseline")
+                ));
+            }
+
+            format!(
+                r#"
+    <section class="test-section">
+        <h2>Proxy DNS Resolution Test</h2>
+        <div class="metrics-grid">
+            <div class="metric">
+                <span class="label">Hostname Target:</span>
+                <span class="value">{}</span>
+            </div>
+            <div class="metric">
+                <span class="label">Literal Target:</span>
+                <span class="value">{}</span>
+            </div>
+            <div class="metric">
+                <span class="label">Rounds:</span>
+                <span class="value">{} (every {:?})</span>
+            </div>
+        </div>
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Path</th>
+                    <th>Success Rate</th>
+                    <th>Median Setup</th>
+                    <th>p95 Setup</th>
+                    <th>Cold DNS</th>
+                    <th>Warm DNS</th>
+                    <th>Resolution Failures</th>
+                    <th>Caching</th>
+                </tr>
+            </thead>
+            <tbody>{}
+            </tbody>
+        </table>{}
+    </section>
+"#,
+                proxy_dns.hostname_target,
+                proxy_dns.literal_target,
+                proxy_dns.rounds,
+                proxy_dns.round_interval,
+                rows,
+                self.generate_html_failures(&proxy_dns.failures)
+            )
+        } else {
+            String::new()
+        }
+    }
+
+    fn generate_html_outages(&self) -> String {
+        let outages = self.metrics.outages();
+        if outages.is_empty() {
+            return String::new();
+        }
+
+        let mut rows = String::new();
+        for outage in &outages {
+            rows.push_str(&format!(
+                r#"
+                <tr>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                </tr>
+"#,
+                outage.start.format("%Y-%m-%d %H:%M:%S%.3f"),
+                outage
+                    .end
+                    .map(|end| end.format("%H:%M:%S%.3f").to_string())
+                    .unwrap_or_else(|| "ongoing".to_string()),
+                outage
+                    .duration
+                    .map(|d| format!("{d:.3?}"))
+                    .unwrap_or_else(|| "-".to_string()),
+                outage.test,
+                outage.target,
+                outage.cause,
+                outage.failures
+            ));
+        }
+
+        format!(
+            r#"
+    <section class="test-section">
+        <h2>Outage Timeline</h2>
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Start (UTC)</th>
+                    <th>End</th>
+                    <th>Duration</th>
+                    <th>Test</th>
+                    <th>Target</th>
+                    <th>Cause</th>
+                    <th>Failures</th>
+                </tr>
+            </thead>
+            <tbody>{rows}
+            </tbody>
+        </table>
+    </section>
+"#
+        )
+    }
+
+    fn generate_html_failures(&self, failures: &FailureBreakdown) -> String {
+        if failures.categories.is_empty() {
+            return String::new();
+        }
+
+        let mut rows = String::new();
+        for c in &failures.categories {
+            rows.push_str(&format!(
+                r#"
+                <tr>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{:.1}%</td>
+                </tr>
+"#,
+                c.category, c.count, c.percentage
+            ));
+        }
+
+        format!(
+            r#"
+        <h3>Failure Breakdown</h3>
+        <table class="data-table">
+            <thead>
+                <tr>
+                    <th>Category</th>
+                    <th>Count</th>
+                    <th>Share</th>
+                </tr>
+            </thead>
+            <tbody>{rows}
+            </tbody>
+        </table>"#
+        )
+    }
+
+    fn push_text_failures(&self, text: &mut String, failures: &FailureBreakdown) {
+        if failures.categories.is_empty() {
+            return;
+        }
+
+        text.push_str("Failure Breakdown:\n");
+        for line in failures.summary_lines() {
+            text.push_str(&format!("  {line}\n"));
+        }
+    }
+
     fn get_rating(&self, score: f64) -> &'static str {
         match score {
             s if s >= 90.0 => "Excellent",
@@ -556,3 +3067,264 @@ impl Report {
         Ok(())
     }
 }
+
+/// A point in the InfluxDB line protocol, measurement `nst`; fields
+/// without a value are left out.
+struct InfluxLine {
+    tags: String,
+    fields: Vec<String>,
+}
+
+impl InfluxLine {
+    fn new(proxy: &str, test: &str) -> Self {
+        Self {
+            tags: String::new(),
+            fields: Vec::new(),
+        }
+        .tag("proxy", proxy)
+        .tag("test", test)
+    }
+
+    fn tag(mut self, key: &str, value: &str) -> Self {
+        if !value.is_empty() {
+            self.tags
+                .push_str(&format!(",{}={}", key, escape_influx_tag(value)));
+        }
+        self
+    }
+
+    fn float(mut self, key: &str, value: impl Into<Option<f64>>) -> Self {
+        if let Some(value) = value.into().filter(|v| v.is_finite()) {
+            self.fields.push(format!("{key}={value}"));
+        }
+        self
+    }
+
+    fn int(mut self, key: &str, value: impl Into<Option<u64>>) -> Self {
+        if let Some(value) = value.into() {
+            self.fields.push(format!("{key}={value}i"));
+        }
+        self
+    }
+
+    fn millis(self, key: &str, value: Duration) -> Self {
+        self.float(key, value.as_secs_f64() * 1000.0)
+    }
+
+    fn flag(mut self, key: &str, value: bool) -> Self {
+        self.fields.push(format!("{key}={value}"));
+        self
+    }
+
+    fn text(self, key: &str, value: &str) -> Self {
+        self.text_opt(key, Some(value))
+    }
+
+    fn text_opt(mut self, key: &str, value: Option<&str>) -> Self {
+        if let Some(value) = value {
+            let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+            self.fields.push(format!("{key}=\"{escaped}\""));
+        }
+        self
+    }
+
+    fn finish(self, timestamp: i64) -> Option<String> {
+        if self.fields.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "nst{} {} {}\n",
+            self.tags,
+            self.fields.join(","),
+            timestamp
+        ))
+    }
+}
+
+/// A GitHub-flavored Markdown table followed by a blank line; cell text
+/// is escaped so a stray `|` cannot break the row.
+fn markdown_table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut table = format!("| {} |\n", headers.join(" | "));
+    table.push_str(&format!("|{}\n", "---|".repeat(headers.len())));
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|cell| escape_markdown_cell(cell)).collect();
+        table.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    table.push('\n');
+    table
+}
+
+fn markdown_duration(value: Option<Duration>) -> String {
+    value
+        .map(|d| format!("{d:.2?}"))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn escape_markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+/// The figures of one test that the JUnit assertions are checked against,
+/// each with the metric name quoted in failure messages.
+struct JunitCase {
+    name: &'static str,
+    time: Option<Duration>,
+    score: Option<(&'static str, f64)>,
+    p99: Option<(&'static str, Duration)>,
+    loss: Option<(&'static str, f64)>,
+}
+
+impl JunitCase {
+    fn failures(&self, assertions: &ReportAssertions) -> Vec<String> {
+        let mut failures = Vec::new();
+
+        if let (Some((metric, score)), Some(min)) = (self.score, assertions.min_score) {
+            if score < min {
+                failures.push(format!("{metric} {score:.1} is below the minimum of {min}"));
+            }
+        }
+        if let (Some((metric, p99)), Some(max)) = (self.p99, assertions.max_p99_ms) {
+            let p99 = p99.as_secs_f64() * 1000.0;
+            if p99 > max {
+                failures.push(format!(
+                    "{metric} {p99:.1}ms exceeds the maximum of {max}ms"
+                ));
+            }
+        }
+        if let (Some((metric, loss)), Some(max)) = (self.loss, assertions.max_loss_percent) {
+            if loss > max {
+                failures.push(format!("{metric} {loss:.2}% exceeds the maximum of {max}%"));
+            }
+        }
+
+        failures
+    }
+
+    fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some((metric, score)) = self.score {
+            parts.push(format!("{metric}={score:.1}"));
+        }
+        if let Some((metric, p99)) = self.p99 {
+            parts.push(format!("{metric}={:.1}ms", p99.as_secs_f64() * 1000.0));
+        }
+        if let Some((metric, loss)) = self.loss {
+            parts.push(format!("{metric}={loss:.2}%"));
+        }
+        parts.join(" ")
+    }
+}
+
+fn percentage(part: u64, total: u64) -> Option<f64> {
+    (total > 0).then(|| part as f64 / total as f64 * 100.0)
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_influx_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ',' | '=' | ' ' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::MetricsCollector;
+
+    #[test]
+    fn test_influx_line_escaping() {
+        let line = InfluxLine::new("proxy a,b", "dns_stability")
+            .tag("domain", "x=y")
+            .int("total_queries", 3)
+            .float("success_rate", f64::NAN)
+            .text("grade", "say \"hi\"")
+            .finish(42)
+            .unwrap();
+        assert_eq!(
+            line.trim_end(),
+            r#"nst,proxy=proxy\ a\,b,test=dns_stability,domain=x\=y total_queries=3i,grade="say \"hi\"" 42"#
+        );
+        assert!(InfluxLine::new("p", "t").finish(0).is_none());
+    }
+
+    #[test]
+    fn test_openmetrics_report_ends_with_eof() {
+        let report = Report::new(Metrics::new("127.0.0.1:1080".to_string()))
+            .with_format(OutputFormat::OpenMetrics);
+        assert!(report.generate_openmetrics().ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn test_junit_assertion_failures() {
+        let case = JunitCase {
+            name: "network_jitter",
+            time: None,
+            score: Some(("network_quality_score", 72.0)),
+            p99: Some(("p99_rtt", Duration::from_millis(250))),
+            loss: Some(("packet_loss_rate", 0.5)),
+        };
+        let assertions = ReportAssertions {
+            min_score: Some(80.0),
+            max_p99_ms: Some(200.0),
+            max_loss_percent: Some(1.0),
+        };
+        assert_eq!(
+            case.failures(&assertions),
+            vec![
+                "network_quality_score 72.0 is below the minimum of 80".to_string(),
+                "p99_rtt 250.0ms exceeds the maximum of 200ms".to_string(),
+            ]
+        );
+        assert!(case.failures(&ReportAssertions::default()).is_empty());
+        assert_eq!(escape_xml("a<b & \"c\""), "a&lt;b &amp; &quot;c&quot;");
+    }
+
+    #[test]
+    fn test_junit_reports_aborted_tests() {
+        let mut collector = MetricsCollector::new("127.0.0.1:1080".to_string());
+        collector.add_error(
+            "tcp_stability",
+            &NetworkTestError::Connection("refused <by peer>".to_string()),
+        );
+        let xml = Report::new(collector.finalize()).generate_junit();
+
+        assert!(xml.contains("tests=\"1\" failures=\"0\" errors=\"1\""));
+        assert!(xml.contains("<testcase name=\"tcp_stability\""));
+        assert!(xml.contains("refused &lt;by peer&gt;"));
+    }
+
+    #[test]
+    fn test_markdown_table_escapes_pipes() {
+        let table = markdown_table(
+            &["Domain", "Unexpected"],
+            vec![vec!["a|b".to_string(), "1.2.3.4\n5.6.7.8".to_string()]],
+        );
+        assert_eq!(
+            table,
+            "| Domain | Unexpected |\n|---|---|\n| a\\|b | 1.2.3.4 5.6.7.8 |\n\n"
+        );
+    }
+}
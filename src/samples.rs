@@ -0,0 +1,251 @@
+use crate::{NetworkTestError, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+/// Where raw samples are streamed, parsed from `ndjson` or `ndjson:PATH`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SampleSinkSpec {
+    NdjsonStdout,
+    NdjsonFile(PathBuf),
+}
+
+/// One raw measurement, written the moment it is taken.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Sample {
+    Heartbeat {
+        sequence: u64,
+        success: bool,
+        rtt_ms: Option<f64>,
+        error: Option<String>,
+    },
+    Speed {
+        direction: &'static str,
+        bytes: u64,
+        duration_ms: f64,
+        bytes_per_second: f64,
+    },
+    DnsQuery {
+        domain: String,
+        record_type: String,
+        success: bool,
+        query_time_ms: Option<f64>,
+        outcome: String,
+    },
+    Ping {
+        mode: String,
+        success: bool,
+        rtt_ms: Option<f64>,
+        error: Option<String>,
+    },
+    ConnectionAttempt {
+        success: bool,
+        connect_time_ms: Option<f64>,
+        error: Option<String>,
+    },
+    Drop {
+        reason: String,
+    },
+    Reconnect {
+        downtime_ms: f64,
+    },
+}
+
+#[derive(Serialize)]
+struct SampleRecord<'a> {
+    timestamp: DateTime<Utc>,
+    session_id: &'a str,
+    test: &'a str,
+    instance: usize,
+    target: &'a str,
+    #[serde(flatten)]
+    sample: &'a Sample,
+}
+
+/// Shared NDJSON writer; every test instance records through its own
+/// `SampleRecorder` so lines carry the test name and instance.
+#[derive(Clone)]
+pub struct SampleSink {
+    session_id: Arc<str>,
+    writer: Arc<Mutex<Option<Box<dyn Write + Send>>>>,
+    to_stdout: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct SampleRecorder {
+    sink: Option<SampleSink>,
+    test: &'static str,
+    instance: usize,
+    stdout_reserved: bool,
+}
+
+impl FromStr for SampleSinkSpec {
+    type Err = NetworkTestError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            None if s.eq_ignore_ascii_case("ndjson") => Ok(SampleSinkSpec::NdjsonStdout),
+            Some((format, path)) if format.eq_ignore_ascii_case("ndjson") && !path.is_empty() => {
+                Ok(SampleSinkSpec::NdjsonFile(PathBuf::from(path)))
+            }
+            _ => Err(NetworkTestError::Config(format!(
+                "Invalid event sink, expected ndjson or ndjson:PATH: {s}"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for SampleSinkSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SampleSinkSpec::NdjsonStdout => f.write_str("ndjson"),
+            SampleSinkSpec::NdjsonFile(path) => write!(f, "ndjson:{}", path.display()),
+        }
+    }
+}
+
+impl SampleSink {
+    pub fn open(spec: &SampleSinkSpec) -> Result<Self> {
+        let writer: Box<dyn Write + Send> = match spec {
+            SampleSinkSpec::NdjsonStdout => Box::new(io::stdout()),
+            SampleSinkSpec::NdjsonFile(path) => Box::new(BufWriter::new(File::create(path)?)),
+        };
+        Ok(Self {
+            session_id: uuid::Uuid::new_v4().to_string().into(),
+            writer: Arc::new(Mutex::new(Some(writer))),
+            to_stdout: *spec == SampleSinkSpec::NdjsonStdout,
+        })
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    pub fn recorder(&self, test: &'static str, instance: usize) -> SampleRecorder {
+        SampleRecorder {
+            sink: Some(self.clone()),
+            test,
+            instance,
+            stdout_reserved: false,
+        }
+    }
+
+    fn write(&self, record: &SampleRecord) {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(out) = writer.as_mut() else {
+            return;
+        };
+
+        // Each line is flushed so a reader sees samples as they happen
+        let written = serde_json::to_writer(&mut *out, record)
+            .map_err(io::Error::from)
+            .and_then(|_| out.write_all(b"\n"))
+            .and_then(|_| out.flush());
+        if let Err(e) = written {
+            warn!(
+                "Sample stream closed, no further samples are written: {}",
+                e
+            );
+            *writer = None;
+        }
+    }
+}
+
+impl SampleRecorder {
+    /// Marks stdout as taken by other machine-readable output, such as a report.
+    pub fn with_stdout_reserved(mut self, stdout_reserved: bool) -> Self {
+        self.stdout_reserved = stdout_reserved;
+        self
+    }
+
+    /// True when samples or a report go to stdout, which then carries nothing else.
+    pub fn owns_stdout(&self) -> bool {
+        self.stdout_reserved || self.sink.as_ref().is_some_and(|sink| sink.to_stdout)
+    }
+
+    pub fn record(&self, target: &str, sample: Sample) {
+        if let Some(sink) = self.sink.as_ref() {
+            sink.write(&SampleRecord {
+                timestamp: Utc::now(),
+                session_id: &sink.session_id,
+                test: self.test,
+                instance: self.instance,
+                target,
+                sample: &sample,
+            });
+        }
+    }
+}
+
+impl fmt::Debug for SampleSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SampleSink")
+            .field("session_id", &self.session_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for SampleRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SampleRecorder")
+            .field("test", &self.test)
+            .field("instance", &self.instance)
+            .field("enabled", &self.sink.is_some())
+            .finish()
+    }
+}
+
+pub fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sink_spec_parsing() {
+        assert_eq!(
+            "ndjson".parse::<SampleSinkSpec>().unwrap(),
+            SampleSinkSpec::NdjsonStdout
+        );
+        assert_eq!(
+            "ndjson:/tmp/samples.ndjson"
+                .parse::<SampleSinkSpec>()
+                .unwrap(),
+            SampleSinkSpec::NdjsonFile(PathBuf::from("/tmp/samples.ndjson"))
+        );
+        assert!("ndjson:".parse::<SampleSinkSpec>().is_err());
+        assert!("csv".parse::<SampleSinkSpec>().is_err());
+    }
+
+    #[test]
+    fn test_sample_record_is_flat() {
+        let sample = Sample::Ping {
+            mode: "tunnel".to_string(),
+            success: true,
+            rtt_ms: Some(1.5),
+            error: None,
+        };
+        let record = SampleRecord {
+            timestamp: Utc::now(),
+            session_id: "s",
+            test: "network_jitter",
+            instance: 1,
+            target: "127.0.0.1:8005",
+            sample: &sample,
+        };
+        let value = serde_json::to_value(&record).unwrap();
+        assert_eq!(value["kind"], "ping");
+        assert_eq!(value["test"], "network_jitter");
+        assert_eq!(value["rtt_ms"], 1.5);
+    }
+}
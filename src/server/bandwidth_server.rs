@@ -34,7 +34,7 @@ pub async fn handle_client(mut stream: TcpStream) -> Result<(), Box<dyn std::err
                         break;
                     }
                 } else if request_line.starts_with("POST /post") {
-                    if let Err(e) = handle_post_request(&mut stream, &request).await {
+                    if let Err(e) = handle_post_request(&mut stream, &buffer[..n]).await {
                         error!("Error handling POST request: {}", e);
                         break;
                     }
@@ -80,26 +80,42 @@ async fn handle_get_stream_bytes(
 
 async fn handle_post_request(
     stream: &mut TcpStream,
-    request: &str,
+    request: &[u8],
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let header_end = request
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|pos| pos + 4)
+        .unwrap_or(request.len());
+    let headers = String::from_utf8_lossy(&request[..header_end]);
+
     let mut content_length = 0;
 
     // Parse headers for content-length
-    for line in request.lines() {
+    for line in headers.lines() {
         if line.to_lowercase().starts_with("content-length:") {
             content_length = line[15..].trim().parse::<usize>().unwrap_or(0);
             break;
         }
     }
 
-    // For simplicity, assume POST body follows immediately in the same buffer
-    // In a real implementation, you'd need to handle cases where the body
-    // might come in separate reads
     debug!(
         "Received POST request with content-length: {}",
         content_length
     );
 
+    // Drain the rest of the body so it is not mistaken for the next request
+    let mut remaining = content_length.saturating_sub(request.len() - header_end);
+    let mut discard = [0u8; 8192];
+    while remaining > 0 {
+        let to_read = remaining.min(discard.len());
+        let n = stream.read(&mut discard[..to_read]).await?;
+        if n == 0 {
+            return Err("Connection closed while reading POST body".into());
+        }
+        remaining -= n;
+    }
+
     // Send response
     let response = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: keep-alive\r\n\r\n";
     stream.write_all(response.as_bytes()).await?;
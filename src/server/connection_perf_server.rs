@@ -1,4 +1,4 @@
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
 use tracing::debug;
 
@@ -16,7 +16,12 @@ pub async fn handle_client(mut stream: TcpStream) -> Result<(), Box<dyn std::err
             
             debug!("Connection performance test - received: {}", message);
             
-            if message == "PING" {
+            if message == "HOLD" {
+                // Hold-open mode: keep the tunnel alive and answer heartbeats until the client leaves
+                stream.write_all(b"HELD\n").await?;
+                debug!("Connection performance test - holding connection open");
+                return hold_open(stream).await;
+            } else if message == "PING" {
                 // Send PONG response
                 let response = b"PONG\n";
                 stream.write_all(response).await?;
@@ -38,3 +43,23 @@ pub async fn handle_client(mut stream: TcpStream) -> Result<(), Box<dyn std::err
     debug!("Connection performance test - heartbeat complete, closing");
     Ok(())
 }
+
+async fn hold_open(stream: TcpStream) -> Result<(), Box<dyn std::error::Error>> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim() == "PING" {
+            writer.write_all(b"PONG\n").await?;
+        } else {
+            writer.write_all(b"ERROR\n").await?;
+            debug!(
+                "Connection performance test - unexpected held message: {}",
+                line.trim()
+            );
+        }
+    }
+
+    debug!("Connection performance test - held connection closed by client");
+    Ok(())
+}
@@ -1,6 +1,14 @@
-use tokio::net::UdpSocket;
+use std::net::{Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio_rustls::rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
 use tracing::{debug, error, info, warn};
 
+const MAX_HTTP_HEADER: usize = 16 * 1024;
+
 pub async fn run_dns_server(port: u16) -> Result<(), String> {
     let bind_addr = format!("0.0.0.0:{port}");
     let socket = UdpSocket::bind(&bind_addr)
@@ -8,6 +16,12 @@ pub async fn run_dns_server(port: u16) -> Result<(), String> {
         .map_err(|e| format!("Failed to bind UDP socket: {e}"))?;
     info!("DNS stability server listening on UDP {}", bind_addr);
 
+    let listener = TcpListener::bind(&bind_addr)
+        .await
+        .map_err(|e| format!("Failed to bind TCP listener: {e}"))?;
+    info!("DNS stability server listening on TCP {}", bind_addr);
+    tokio::spawn(run_tcp_listener(listener));
+
     let mut buffer = [0u8; 512];
 
     loop {
@@ -15,73 +29,329 @@ pub async fn run_dns_server(port: u16) -> Result<(), String> {
             Ok((n, client_addr)) => {
                 debug!("Received DNS query from {}: {} bytes", client_addr, n);
 
-                if n < 12 {
-                    warn!("Invalid DNS query from {}: too short", client_addr);
-                    continue;
+                if let Some(response) = answer_query(&buffer[..n], client_addr) {
+                    if let Err(e) = socket.send_to(&response, client_addr).await {
+                        error!("Failed to send DNS response to {}: {}", client_addr, e);
+                    }
                 }
+            }
+            Err(e) => {
+                warn!("Error receiving DNS query: {}", e);
+            }
+        }
+    }
+}
 
-                // Parse basic DNS query
-                let query_id = u16::from_be_bytes([buffer[0], buffer[1]]);
-                let _flags = u16::from_be_bytes([buffer[2], buffer[3]]);
-                let questions = u16::from_be_bytes([buffer[4], buffer[5]]);
+async fn run_tcp_listener(listener: TcpListener) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, client_addr)) => {
+                debug!("New DNS over TCP connection from {}", client_addr);
+                tokio::spawn(async move {
+                    if let Err(e) = handle_tcp_client(stream, client_addr).await {
+                        debug!("DNS over TCP connection from {} ended: {}", client_addr, e);
+                    }
+                });
+            }
+            Err(e) => {
+                error!("Failed to accept DNS over TCP connection: {}", e);
+            }
+        }
+    }
+}
 
-                if questions == 0 {
-                    warn!("No questions in DNS query from {}", client_addr);
-                    continue;
-                }
+/// DoT and DoH share one TLS port with a self-signed certificate; ALPN
+/// picks the protocol, defaulting to DoT.
+pub async fn run_dns_tls_server(port: u16) -> Result<(), String> {
+    let acceptor = tls_acceptor()?;
+    let bind_addr = format!("0.0.0.0:{port}");
+    let listener = TcpListener::bind(&bind_addr)
+        .await
+        .map_err(|e| format!("Failed to bind TLS listener: {e}"))?;
+    info!("DNS over TLS/HTTPS server listening on {}", bind_addr);
 
-                // Create DNS response
-                let response_result = create_dns_response(query_id, &buffer[12..n]);
-                match response_result {
-                    Ok(response) => {
-                        if let Err(e) = socket.send_to(&response, client_addr).await {
-                            error!("Failed to send DNS response to {}: {}", client_addr, e);
-                        } else {
-                            debug!(
-                                "Sent DNS response to {} for query ID: {}",
-                                client_addr, query_id
-                            );
+    loop {
+        match listener.accept().await {
+            Ok((stream, client_addr)) => {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let stream = match acceptor.accept(stream).await {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            warn!("TLS handshake with {} failed: {}", client_addr, e);
+                            return;
                         }
+                    };
+
+                    let is_http = stream.get_ref().1.alpn_protocol() == Some(b"http/1.1");
+                    let result = if is_http {
+                        handle_https_client(stream, client_addr).await
+                    } else {
+                        handle_tcp_client(stream, client_addr).await
+                    };
+                    if let Err(e) = result {
+                        debug!("Encrypted DNS connection from {} ended: {}", client_addr, e);
                     }
-                    Err(e) => {
-                        error!("Failed to create DNS response: {}", e);
-                    }
-                }
+                });
             }
             Err(e) => {
-                warn!("Error receiving DNS query: {}", e);
+                error!("Failed to accept TLS connection: {}", e);
+            }
+        }
+    }
+}
+
+fn tls_acceptor() -> Result<TlsAcceptor, String> {
+    let certified =
+        rcgen::generate_simple_self_signed(vec!["localhost".to_string(), "127.0.0.1".to_string()])
+            .map_err(|e| format!("Failed to generate certificate: {e}"))?;
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der()));
+
+    let mut config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(vec![certified.cert.der().clone()], key)
+        .map_err(|e| format!("Invalid TLS certificate: {e}"))?;
+    config.alpn_protocols = vec![b"dot".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+// Messages are prefixed with a 2-byte length (RFC 1035 4.2.2); several
+// queries may be pipelined on one connection.
+async fn handle_tcp_client<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    client_addr: SocketAddr,
+) -> Result<(), std::io::Error> {
+    loop {
+        let mut length = [0u8; 2];
+        match stream.read_exact(&mut length).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+
+        let mut query = vec![0u8; u16::from_be_bytes(length) as usize];
+        stream.read_exact(&mut query).await?;
+        debug!(
+            "Received DNS over TCP query from {}: {} bytes",
+            client_addr,
+            query.len()
+        );
+
+        if let Some(response) = answer_query(&query, client_addr) {
+            let mut framed = Vec::with_capacity(response.len() + 2);
+            framed.extend_from_slice(&(response.len() as u16).to_be_bytes());
+            framed.extend_from_slice(&response);
+            stream.write_all(&framed).await?;
+            stream.flush().await?;
+        }
+    }
+}
+
+// RFC 8484 POST requests over a kept-alive HTTP/1.1 connection
+async fn handle_https_client<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    client_addr: SocketAddr,
+) -> Result<(), std::io::Error> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 1024];
+
+    loop {
+        let header_end = loop {
+            if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                break end + 4;
+            }
+            if buffer.len() > MAX_HTTP_HEADER {
+                return Err(std::io::Error::other("request headers too large"));
+            }
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(());
             }
+            buffer.extend_from_slice(&chunk[..n]);
+        };
+
+        let head = String::from_utf8_lossy(&buffer[..header_end]).into_owned();
+        let method = head
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let content_length = head
+            .split("\r\n")
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+
+        buffer.drain(..header_end);
+        while buffer.len() < content_length {
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(());
+            }
+            buffer.extend_from_slice(&chunk[..n]);
+        }
+        let body: Vec<u8> = buffer.drain(..content_length).collect();
+
+        debug!(
+            "Received DoH {} from {}: {} bytes",
+            method,
+            client_addr,
+            body.len()
+        );
+
+        let response = if method != "POST" {
+            http_response("405 Method Not Allowed", &[])
+        } else {
+            match answer_query(&body, client_addr) {
+                Some(answer) => http_response("200 OK", &answer),
+                None => http_response("400 Bad Request", &[]),
+            }
+        };
+        stream.write_all(&response).await?;
+        stream.flush().await?;
+    }
+}
+
+fn http_response(status: &str, body: &[u8]) -> Vec<u8> {
+    let mut response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/dns-message\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    response.extend_from_slice(body);
+    response
+}
+
+fn answer_query(query: &[u8], client_addr: SocketAddr) -> Option<Vec<u8>> {
+    if query.len() < 12 {
+        warn!("Invalid DNS query from {}: too short", client_addr);
+        return None;
+    }
+
+    // Parse basic DNS query
+    let query_id = u16::from_be_bytes([query[0], query[1]]);
+    let _flags = u16::from_be_bytes([query[2], query[3]]);
+    let questions = u16::from_be_bytes([query[4], query[5]]);
+
+    if questions == 0 {
+        warn!("No questions in DNS query from {}", client_addr);
+        return None;
+    }
+
+    // Create DNS response
+    match create_dns_response(query_id, &query[12..]) {
+        Ok(response) => {
+            debug!(
+                "Sent DNS response to {} for query ID: {}",
+                client_addr, query_id
+            );
+            Some(response)
+        }
+        Err(e) => {
+            error!("Failed to create DNS response: {}", e);
+            None
         }
     }
 }
 
 fn create_dns_response(query_id: u16, question: &[u8]) -> Result<Vec<u8>, String> {
+    // Question name ends at the root label, followed by QTYPE and QCLASS
+    let mut name_end = 0;
+    loop {
+        let len = *question.get(name_end).ok_or("Question name is truncated")? as usize;
+        if len & 0xc0 != 0 {
+            return Err("Compressed question names are not supported".to_string());
+        }
+        name_end += 1 + len;
+        if len == 0 {
+            break;
+        }
+    }
+    if question.len() < name_end + 4 {
+        return Err("Question type is truncated".to_string());
+    }
+    let qtype = u16::from_be_bytes([question[name_end], question[name_end + 1]]);
+    let rdata = answer_rdata(qtype);
+
     let mut response = Vec::new();
 
     // DNS Header
     response.extend_from_slice(&query_id.to_be_bytes()); // ID
     response.extend_from_slice(&[0x81, 0x80]); // Flags: response, recursion available
     response.extend_from_slice(&[0x00, 0x01]); // Questions: 1
-    response.extend_from_slice(&[0x00, 0x01]); // Answers: 1
+    response.extend_from_slice(&(rdata.is_some() as u16).to_be_bytes()); // Answers: 0 (NODATA) or 1
     response.extend_from_slice(&[0x00, 0x00]); // Authority RRs: 0
     response.extend_from_slice(&[0x00, 0x00]); // Additional RRs: 0
 
-    // Question section (copy from query)
-    response.extend_from_slice(question);
-
-    // Answer section
-    // Name pointer to question
-    response.extend_from_slice(&[0xc0, 0x0c]);
-    // Type A (0x0001)
-    response.extend_from_slice(&[0x00, 0x01]);
-    // Class IN (0x0001)
-    response.extend_from_slice(&[0x00, 0x01]);
-    // TTL (300 seconds)
-    response.extend_from_slice(&[0x00, 0x00, 0x01, 0x2c]);
-    // Data length (4 bytes for IPv4)
-    response.extend_from_slice(&[0x00, 0x04]);
-    // IP address (8.8.8.8 as example)
-    response.extend_from_slice(&[8, 8, 8, 8]);
+    // Question section (copy from query, dropping anything after it)
+    response.extend_from_slice(&question[..name_end + 4]);
+
+    if let Some(rdata) = rdata {
+        // Answer section
+        // Name pointer to question
+        response.extend_from_slice(&[0xc0, 0x0c]);
+        // Same type as asked
+        response.extend_from_slice(&qtype.to_be_bytes());
+        // Class IN (0x0001)
+        response.extend_from_slice(&[0x00, 0x01]);
+        // TTL (300 seconds)
+        response.extend_from_slice(&[0x00, 0x00, 0x01, 0x2c]);
+        // Data length
+        response.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        response.extend_from_slice(&rdata);
+    }
 
     Ok(response)
 }
+
+/// Fixed record data for each supported type; `None` answers with NODATA.
+fn answer_rdata(qtype: u16) -> Option<Vec<u8>> {
+    let mut rdata = Vec::new();
+    match qtype {
+        // A: 8.8.8.8
+        1 => rdata.extend_from_slice(&[8, 8, 8, 8]),
+        // CNAME
+        5 => encode_name(&mut rdata, "cname.nst-server.test"),
+        // MX: preference 10
+        15 => {
+            rdata.extend_from_slice(&10u16.to_be_bytes());
+            encode_name(&mut rdata, "mail.nst-server.test");
+        }
+        // TXT: one character-string
+        16 => {
+            let text = b"v=nst-server";
+            rdata.push(text.len() as u8);
+            rdata.extend_from_slice(text);
+        }
+        // AAAA: 2001:4860:4860::8888
+        28 => rdata
+            .extend_from_slice(&Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888).octets()),
+        // SRV: priority 0, weight 5, port 443
+        33 => {
+            rdata.extend_from_slice(&0u16.to_be_bytes());
+            rdata.extend_from_slice(&5u16.to_be_bytes());
+            rdata.extend_from_slice(&443u16.to_be_bytes());
+            encode_name(&mut rdata, "srv.nst-server.test");
+        }
+        // HTTPS: priority 1, target ".", alpn="h2"
+        65 => {
+            rdata.extend_from_slice(&1u16.to_be_bytes());
+            rdata.push(0);
+            rdata.extend_from_slice(&1u16.to_be_bytes()); // SvcParamKey: alpn
+            rdata.extend_from_slice(&3u16.to_be_bytes());
+            rdata.extend_from_slice(&[2, b'h', b'2']);
+        }
+        _ => return None,
+    }
+    Some(rdata)
+}
+
+fn encode_name(buf: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label.as_bytes());
+    }
+    buf.push(0);
+}
@@ -46,6 +46,8 @@ enum ServerMode {
     DnsStability,
     /// Network jitter test server only
     NetworkJitter,
+    /// DNS over TLS/HTTPS test server only
+    DnsTls,
 }
 
 #[derive(Debug, Clone)]
@@ -55,6 +57,7 @@ pub enum ServerType {
     ConnectionPerf,
     DnsStability,
     NetworkJitter,
+    DnsTls,
 }
 
 #[tokio::main]
@@ -99,6 +102,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let addr: SocketAddr = format!("{}:{}", args.host, args.port + 5).parse()?;
             start_server(addr, ServerType::NetworkJitter).await?;
         }
+        ServerMode::DnsTls => {
+            let addr: SocketAddr = format!("{}:{}", args.host, args.port + 6).parse()?;
+            start_server(addr, ServerType::DnsTls).await?;
+        }
     }
 
     Ok(())
@@ -117,6 +124,7 @@ async fn start_all_servers(host: &str, base_port: u16) -> Result<(), Box<dyn std
         ),
         (base_port + 4, ServerType::DnsStability, "DNS Stability"),
         (base_port + 5, ServerType::NetworkJitter, "Network Jitter"),
+        (base_port + 6, ServerType::DnsTls, "DNS over TLS/HTTPS"),
     ];
 
     let mut tasks = vec![];
@@ -166,6 +174,12 @@ async fn start_server(
                 .map_err(|e| format!("DNS server error: {e}"))?;
             Ok(())
         }
+        ServerType::DnsTls => {
+            dns_stability_server::run_dns_tls_server(addr.port())
+                .await
+                .map_err(|e| format!("DNS TLS server error: {e}"))?;
+            Ok(())
+        }
         _ => {
             use tokio::net::TcpListener;
 
@@ -205,8 +219,8 @@ async fn handle_connection(
         ServerType::Bandwidth => bandwidth_server::handle_client(stream).await,
         ServerType::ConnectionPerf => connection_perf_server::handle_client(stream).await,
         ServerType::NetworkJitter => network_jitter_server::handle_client(stream).await,
-        ServerType::DnsStability => {
-            // DNS server is handled separately as UDP, this should never be reached
+        ServerType::DnsStability | ServerType::DnsTls => {
+            // DNS servers run their own listeners, this should never be reached
             Ok(())
         }
     }
@@ -1,3 +1,4 @@
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
 use tracing::{debug, warn};
@@ -15,11 +16,20 @@ pub async fn handle_client(mut stream: TcpStream) -> Result<(), Box<dyn std::err
                 break;
             }
             Ok(_) => {
+                let received_at = epoch_micros();
                 let line = line.trim();
-                if line == "PING" {
-                    let response = b"PONG\n";
+                // "PING <seq> <timestamp>" is echoed back with the server's
+                // receive and send times appended, so clients can match
+                // replies on a long-lived connection and split the round trip
+                let response = if line == "PING" {
+                    Some("PONG\n".to_string())
+                } else {
+                    line.strip_prefix("PING ")
+                        .map(|payload| format!("PONG {payload} {received_at} {}\n", epoch_micros()))
+                };
 
-                    if let Err(e) = writer.write_all(response).await {
+                if let Some(response) = response {
+                    if let Err(e) = writer.write_all(response.as_bytes()).await {
                         warn!("Failed to send PONG response: {}", e);
                         break;
                     }
@@ -38,3 +48,10 @@ pub async fn handle_client(mut stream: TcpStream) -> Result<(), Box<dyn std::err
 
     Ok(())
 }
+
+fn epoch_micros() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_micros() as i64)
+        .unwrap_or_default()
+}
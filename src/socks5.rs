@@ -1,15 +1,61 @@
-use crate::{NetworkTestError, Result};
-use std::net::SocketAddr;
+use crate::{NetworkTestError, Phase, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpStream, UdpSocket};
+use tokio::net::{lookup_host, TcpStream, UdpSocket};
 use tracing::{debug, info};
 
+/// Where CONNECT target hostnames are resolved: by the proxy (socks5h, sent
+/// as ATYP 0x03) or by the client before CONNECT (socks5).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostResolution {
+    #[default]
+    Remote,
+    Local,
+}
+
+impl HostResolution {
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            HostResolution::Remote => "socks5h",
+            HostResolution::Local => "socks5",
+        }
+    }
+}
+
+impl fmt::Display for HostResolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostResolution::Remote => f.write_str("remote"),
+            HostResolution::Local => f.write_str("local"),
+        }
+    }
+}
+
+impl FromStr for HostResolution {
+    type Err = NetworkTestError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "remote" | "socks5h" => Ok(HostResolution::Remote),
+            "local" | "socks5" => Ok(HostResolution::Local),
+            _ => Err(NetworkTestError::Config(format!(
+                "Unknown host resolution '{s}', expected remote (socks5h) or local (socks5)"
+            ))),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Socks5Client {
     proxy_addr: SocketAddr,
     username: Option<String>,
     password: Option<String>,
     timeout: std::time::Duration,
+    resolution: HostResolution,
 }
 
 #[derive(Debug)]
@@ -26,6 +72,7 @@ impl Socks5Client {
             username: None,
             password: None,
             timeout: std::time::Duration::from_secs(5),
+            resolution: HostResolution::default(),
         }
     }
 
@@ -40,17 +87,29 @@ impl Socks5Client {
         self
     }
 
+    pub fn with_resolution(mut self, resolution: HostResolution) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
     pub async fn connect(&self, target_addr: &str) -> Result<TcpStream> {
+        let resolved;
+        let target_addr = match self.resolution {
+            HostResolution::Remote => target_addr,
+            HostResolution::Local => {
+                resolved = self.resolve_locally(target_addr).await?;
+                resolved.as_str()
+            }
+        };
+
         debug!("Connecting to SOCKS5 proxy at {}", self.proxy_addr);
 
         let mut stream = tokio::time::timeout(self.timeout, TcpStream::connect(self.proxy_addr))
             .await
             .map_err(|_| {
-                NetworkTestError::Timeout("Failed to connect to SOCKS5 proxy".to_string())
+                NetworkTestError::timeout(Phase::ProxyConnect, "Failed to connect to SOCKS5 proxy")
             })?
-            .map_err(|e| {
-                NetworkTestError::Connection(format!("Failed to connect to proxy: {e}"))
-            })?;
+            .map_err(|e| NetworkTestError::io(Phase::ProxyConnect, e))?;
 
         self.socks5_handshake(&mut stream).await?;
 
@@ -69,19 +128,17 @@ impl Socks5Client {
         let mut stream = tokio::time::timeout(self.timeout, TcpStream::connect(self.proxy_addr))
             .await
             .map_err(|_| {
-                NetworkTestError::Timeout("Failed to connect to SOCKS5 proxy".to_string())
+                NetworkTestError::timeout(Phase::ProxyConnect, "Failed to connect to SOCKS5 proxy")
             })?
-            .map_err(|e| {
-                NetworkTestError::Connection(format!("Failed to connect to proxy: {e}"))
-            })?;
+            .map_err(|e| NetworkTestError::io(Phase::ProxyConnect, e))?;
 
         self.socks5_handshake(&mut stream).await?;
 
         let relay_addr = self.socks5_udp_associate(&mut stream).await?;
 
-        let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| {
-            NetworkTestError::Connection(format!("Failed to bind UDP socket: {e}"))
-        })?;
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::UdpAssociate, e))?;
 
         info!(
             "Successfully created UDP association via SOCKS5 proxy, relay at {}",
@@ -95,6 +152,27 @@ impl Socks5Client {
         })
     }
 
+    async fn resolve_locally(&self, target_addr: &str) -> Result<String> {
+        let (host, port) = self.parse_address(target_addr)?;
+        if host.parse::<IpAddr>().is_ok() {
+            return Ok(target_addr.to_string());
+        }
+
+        let addr = tokio::time::timeout(self.timeout, lookup_host((host.as_str(), port)))
+            .await
+            .map_err(|_| {
+                NetworkTestError::timeout(Phase::Resolve, format!("Resolving {host} locally"))
+            })?
+            .map_err(|e| NetworkTestError::io(Phase::Resolve, e))?
+            .next()
+            .ok_or_else(|| {
+                NetworkTestError::protocol(Phase::Resolve, format!("No addresses for {host}"))
+            })?;
+        debug!("Resolved {} locally to {}", host, addr.ip());
+
+        Ok(addr.to_string())
+    }
+
     async fn socks5_handshake(&self, stream: &mut TcpStream) -> Result<()> {
         debug!("Performing SOCKS5 handshake");
 
@@ -105,14 +183,17 @@ impl Socks5Client {
         };
 
         let handshake = [0x05, 0x01, auth_method];
-        stream.write_all(&handshake).await?;
+        self.write_phase(stream, &handshake, Phase::MethodNegotiation)
+            .await?;
 
         let mut response = [0u8; 2];
-        stream.read_exact(&mut response).await?;
+        self.read_phase(stream, &mut response, Phase::MethodNegotiation)
+            .await?;
 
         if response[0] != 0x05 {
-            return Err(NetworkTestError::Socks5(
-                "Invalid SOCKS version in response".to_string(),
+            return Err(NetworkTestError::protocol(
+                Phase::MethodNegotiation,
+                format!("Invalid SOCKS version in response: {}", response[0]),
             ));
         }
 
@@ -124,16 +205,11 @@ impl Socks5Client {
                 debug!("Username/password authentication required");
                 self.authenticate(stream).await?;
             }
-            0xFF => {
-                return Err(NetworkTestError::Socks5(
-                    "No acceptable authentication methods".to_string(),
-                ));
-            }
-            _ => {
-                return Err(NetworkTestError::Socks5(format!(
-                    "Unknown authentication method: {}",
-                    response[1]
-                )));
+            code => {
+                return Err(NetworkTestError::SocksReply {
+                    phase: Phase::MethodNegotiation,
+                    code,
+                });
             }
         }
 
@@ -142,10 +218,10 @@ impl Socks5Client {
 
     async fn authenticate(&self, stream: &mut TcpStream) -> Result<()> {
         let username = self.username.as_ref().ok_or_else(|| {
-            NetworkTestError::Socks5("Username required for authentication".to_string())
+            NetworkTestError::Config("Username required for authentication".to_string())
         })?;
         let password = self.password.as_ref().ok_or_else(|| {
-            NetworkTestError::Socks5("Password required for authentication".to_string())
+            NetworkTestError::Config("Password required for authentication".to_string())
         })?;
 
         debug!("Authenticating with username/password");
@@ -157,21 +233,25 @@ impl Socks5Client {
         auth_request.push(password.len() as u8);
         auth_request.extend_from_slice(password.as_bytes());
 
-        stream.write_all(&auth_request).await?;
+        self.write_phase(stream, &auth_request, Phase::Authentication)
+            .await?;
 
         let mut response = [0u8; 2];
-        stream.read_exact(&mut response).await?;
+        self.read_phase(stream, &mut response, Phase::Authentication)
+            .await?;
 
         if response[0] != 0x01 {
-            return Err(NetworkTestError::Socks5(
-                "Invalid authentication response version".to_string(),
+            return Err(NetworkTestError::protocol(
+                Phase::Authentication,
+                format!("Invalid authentication response version: {}", response[0]),
             ));
         }
 
         if response[1] != 0x00 {
-            return Err(NetworkTestError::Socks5(
-                "Authentication failed".to_string(),
-            ));
+            return Err(NetworkTestError::SocksReply {
+                phase: Phase::Authentication,
+                code: response[1],
+            });
         }
 
         debug!("Authentication successful");
@@ -205,48 +285,27 @@ impl Socks5Client {
 
         connect_request.extend_from_slice(&port.to_be_bytes());
 
-        stream.write_all(&connect_request).await?;
+        self.write_phase(stream, &connect_request, Phase::Connect)
+            .await?;
 
         let mut response = [0u8; 4];
-        stream.read_exact(&mut response).await?;
+        self.read_phase(stream, &mut response, Phase::Connect)
+            .await?;
 
         if response[0] != 0x05 {
-            return Err(NetworkTestError::Socks5(
-                "Invalid SOCKS version in connect response".to_string(),
+            return Err(NetworkTestError::protocol(
+                Phase::Connect,
+                format!("Invalid SOCKS version in connect response: {}", response[0]),
             ));
         }
 
         match response[1] {
             0x00 => debug!("Connection established"),
-            0x01 => {
-                return Err(NetworkTestError::Socks5(
-                    "General SOCKS server failure".to_string(),
-                ))
-            }
-            0x02 => {
-                return Err(NetworkTestError::Socks5(
-                    "Connection not allowed by ruleset".to_string(),
-                ))
-            }
-            0x03 => return Err(NetworkTestError::Socks5("Network unreachable".to_string())),
-            0x04 => return Err(NetworkTestError::Socks5("Host unreachable".to_string())),
-            0x05 => return Err(NetworkTestError::Socks5("Connection refused".to_string())),
-            0x06 => return Err(NetworkTestError::Socks5("TTL expired".to_string())),
-            0x07 => {
-                return Err(NetworkTestError::Socks5(
-                    "Command not supported".to_string(),
-                ))
-            }
-            0x08 => {
-                return Err(NetworkTestError::Socks5(
-                    "Address type not supported".to_string(),
-                ))
-            }
-            _ => {
-                return Err(NetworkTestError::Socks5(format!(
-                    "Unknown error code: {}",
-                    response[1]
-                )))
+            code => {
+                return Err(NetworkTestError::SocksReply {
+                    phase: Phase::Connect,
+                    code,
+                })
             }
         }
 
@@ -254,22 +313,23 @@ impl Socks5Client {
         match addr_type {
             0x01 => {
                 let mut addr = [0u8; 6];
-                stream.read_exact(&mut addr).await?;
+                self.read_phase(stream, &mut addr, Phase::Connect).await?;
             }
             0x03 => {
                 let mut len = [0u8; 1];
-                stream.read_exact(&mut len).await?;
+                self.read_phase(stream, &mut len, Phase::Connect).await?;
                 let mut addr = vec![0u8; len[0] as usize + 2];
-                stream.read_exact(&mut addr).await?;
+                self.read_phase(stream, &mut addr, Phase::Connect).await?;
             }
             0x04 => {
                 let mut addr = [0u8; 18];
-                stream.read_exact(&mut addr).await?;
+                self.read_phase(stream, &mut addr, Phase::Connect).await?;
             }
             _ => {
-                return Err(NetworkTestError::Socks5(format!(
-                    "Unknown address type: {addr_type}"
-                )));
+                return Err(NetworkTestError::protocol(
+                    Phase::Connect,
+                    format!("Unknown address type: {addr_type}"),
+                ));
             }
         }
 
@@ -286,48 +346,30 @@ impl Socks5Client {
         request.extend_from_slice(&[0, 0, 0, 0]);
         request.extend_from_slice(&[0, 0]);
 
-        stream.write_all(&request).await?;
+        self.write_phase(stream, &request, Phase::UdpAssociate)
+            .await?;
 
         let mut response = [0u8; 4];
-        stream.read_exact(&mut response).await?;
+        self.read_phase(stream, &mut response, Phase::UdpAssociate)
+            .await?;
 
         if response[0] != 0x05 {
-            return Err(NetworkTestError::Socks5(
-                "Invalid SOCKS version in UDP associate response".to_string(),
+            return Err(NetworkTestError::protocol(
+                Phase::UdpAssociate,
+                format!(
+                    "Invalid SOCKS version in UDP associate response: {}",
+                    response[0]
+                ),
             ));
         }
 
         match response[1] {
             0x00 => debug!("UDP association established"),
-            0x01 => {
-                return Err(NetworkTestError::Socks5(
-                    "General SOCKS server failure".to_string(),
-                ))
-            }
-            0x02 => {
-                return Err(NetworkTestError::Socks5(
-                    "Connection not allowed by ruleset".to_string(),
-                ))
-            }
-            0x03 => return Err(NetworkTestError::Socks5("Network unreachable".to_string())),
-            0x04 => return Err(NetworkTestError::Socks5("Host unreachable".to_string())),
-            0x05 => return Err(NetworkTestError::Socks5("Connection refused".to_string())),
-            0x06 => return Err(NetworkTestError::Socks5("TTL expired".to_string())),
-            0x07 => {
-                return Err(NetworkTestError::Socks5(
-                    "Command not supported".to_string(),
-                ))
-            }
-            0x08 => {
-                return Err(NetworkTestError::Socks5(
-                    "Address type not supported".to_string(),
-                ))
-            }
-            _ => {
-                return Err(NetworkTestError::Socks5(format!(
-                    "Unknown error code: {}",
-                    response[1]
-                )))
+            code => {
+                return Err(NetworkTestError::SocksReply {
+                    phase: Phase::UdpAssociate,
+                    code,
+                })
             }
         }
 
@@ -335,14 +377,16 @@ impl Socks5Client {
         let relay_addr = match addr_type {
             0x01 => {
                 let mut addr = [0u8; 6];
-                stream.read_exact(&mut addr).await?;
+                self.read_phase(stream, &mut addr, Phase::UdpAssociate)
+                    .await?;
                 let ip = std::net::Ipv4Addr::new(addr[0], addr[1], addr[2], addr[3]);
                 let port = u16::from_be_bytes([addr[4], addr[5]]);
                 SocketAddr::new(ip.into(), port)
             }
             0x04 => {
                 let mut addr = [0u8; 18];
-                stream.read_exact(&mut addr).await?;
+                self.read_phase(stream, &mut addr, Phase::UdpAssociate)
+                    .await?;
                 let ip = std::net::Ipv6Addr::from([
                     addr[0], addr[1], addr[2], addr[3], addr[4], addr[5], addr[6], addr[7],
                     addr[8], addr[9], addr[10], addr[11], addr[12], addr[13], addr[14], addr[15],
@@ -351,15 +395,31 @@ impl Socks5Client {
                 SocketAddr::new(ip.into(), port)
             }
             _ => {
-                return Err(NetworkTestError::Socks5(format!(
-                    "Unsupported address type for UDP relay: {addr_type}"
-                )));
+                return Err(NetworkTestError::protocol(
+                    Phase::UdpAssociate,
+                    format!("Unsupported address type for UDP relay: {addr_type}"),
+                ));
             }
         };
 
         Ok(relay_addr)
     }
 
+    async fn read_phase(&self, stream: &mut TcpStream, buf: &mut [u8], phase: Phase) -> Result<()> {
+        tokio::time::timeout(self.timeout, stream.read_exact(buf))
+            .await
+            .map_err(|_| NetworkTestError::timeout(phase, "No reply from SOCKS5 proxy"))?
+            .map_err(|e| NetworkTestError::io(phase, e))?;
+        Ok(())
+    }
+
+    async fn write_phase(&self, stream: &mut TcpStream, buf: &[u8], phase: Phase) -> Result<()> {
+        stream
+            .write_all(buf)
+            .await
+            .map_err(|e| NetworkTestError::io(phase, e))
+    }
+
     fn parse_address(&self, addr: &str) -> Result<(String, u16)> {
         let parts: Vec<&str> = addr.rsplitn(2, ':').collect();
         if parts.len() != 2 {
@@ -371,9 +431,9 @@ impl Socks5Client {
         let port = parts[0]
             .parse::<u16>()
             .map_err(|_| NetworkTestError::Config(format!("Invalid port: {}", parts[0])))?;
-        let host = parts[1].to_string();
+        let host = parts[1].trim_start_matches('[').trim_end_matches(']');
 
-        Ok((host, port))
+        Ok((host.to_string(), port))
     }
 }
 
@@ -383,7 +443,7 @@ impl Socks5UdpRelay {
         self.socket
             .send_to(&packet, self.relay_addr)
             .await
-            .map_err(NetworkTestError::Io)?;
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
         Ok(())
     }
 
@@ -392,23 +452,26 @@ impl Socks5UdpRelay {
             .socket
             .recv_from(buf)
             .await
-            .map_err(NetworkTestError::Io)?;
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
 
         if n < 10 {
-            return Err(NetworkTestError::Connection(
-                "Invalid SOCKS5 UDP packet: too short".to_string(),
+            return Err(NetworkTestError::protocol(
+                Phase::Exchange,
+                "Invalid SOCKS5 UDP packet: too short",
             ));
         }
 
         if buf[0] != 0x00 || buf[1] != 0x00 {
-            return Err(NetworkTestError::Connection(
-                "Invalid SOCKS5 UDP packet: bad header".to_string(),
+            return Err(NetworkTestError::protocol(
+                Phase::Exchange,
+                "Invalid SOCKS5 UDP packet: bad header",
             ));
         }
 
         if buf[2] != 0x00 {
-            return Err(NetworkTestError::Connection(
-                "Fragmentation not supported".to_string(),
+            return Err(NetworkTestError::protocol(
+                Phase::Exchange,
+                "Fragmentation not supported",
             ));
         }
 
@@ -416,8 +479,9 @@ impl Socks5UdpRelay {
         let (header_len, target_addr) = match addr_type {
             0x01 => {
                 if n < 10 {
-                    return Err(NetworkTestError::Connection(
-                        "Invalid IPv4 UDP packet".to_string(),
+                    return Err(NetworkTestError::protocol(
+                        Phase::Exchange,
+                        "Invalid IPv4 UDP packet",
                     ));
                 }
                 let ip = std::net::Ipv4Addr::new(buf[4], buf[5], buf[6], buf[7]);
@@ -426,14 +490,16 @@ impl Socks5UdpRelay {
             }
             0x03 => {
                 if n < 5 {
-                    return Err(NetworkTestError::Connection(
-                        "Invalid domain UDP packet".to_string(),
+                    return Err(NetworkTestError::protocol(
+                        Phase::Exchange,
+                        "Invalid domain UDP packet",
                     ));
                 }
                 let domain_len = buf[4] as usize;
                 if n < 5 + domain_len + 2 {
-                    return Err(NetworkTestError::Connection(
-                        "Invalid domain UDP packet: too short".to_string(),
+                    return Err(NetworkTestError::protocol(
+                        Phase::Exchange,
+                        "Invalid domain UDP packet: too short",
                     ));
                 }
                 let domain = String::from_utf8_lossy(&buf[5..5 + domain_len]);
@@ -442,8 +508,9 @@ impl Socks5UdpRelay {
             }
             0x04 => {
                 if n < 22 {
-                    return Err(NetworkTestError::Connection(
-                        "Invalid IPv6 UDP packet".to_string(),
+                    return Err(NetworkTestError::protocol(
+                        Phase::Exchange,
+                        "Invalid IPv6 UDP packet",
                     ));
                 }
                 let ip = std::net::Ipv6Addr::from([
@@ -454,9 +521,10 @@ impl Socks5UdpRelay {
                 (22, format!("{ip}:{port}"))
             }
             _ => {
-                return Err(NetworkTestError::Connection(format!(
-                    "Unsupported address type: {addr_type}"
-                )));
+                return Err(NetworkTestError::protocol(
+                    Phase::Exchange,
+                    format!("Unsupported address type: {addr_type}"),
+                ));
             }
         };
 
@@ -508,9 +576,9 @@ impl Socks5UdpRelay {
         let port = parts[0]
             .parse::<u16>()
             .map_err(|_| NetworkTestError::Config(format!("Invalid port: {}", parts[0])))?;
-        let host = parts[1].to_string();
+        let host = parts[1].trim_start_matches('[').trim_end_matches(']');
 
-        Ok((host, port))
+        Ok((host.to_string(), port))
     }
 }
 
@@ -529,5 +597,9 @@ mod tests {
         let (host, port) = client.parse_address("192.168.1.1:443").unwrap();
         assert_eq!(host, "192.168.1.1");
         assert_eq!(port, 443);
+
+        let (host, port) = client.parse_address("[2001:db8::1]:8080").unwrap();
+        assert_eq!(host, "2001:db8::1");
+        assert_eq!(port, 8080);
     }
 }
@@ -1,4 +1,10 @@
-use crate::{NetworkTestError, Result, Socks5Client};
+use super::print_failure_breakdown;
+use crate::live::LivePanel;
+use crate::metrics::{
+    BandwidthMetrics, EventKind, EventLog, FailureCategory, FailureTally, TestEvent,
+};
+use crate::samples::{millis, Sample, SampleRecorder};
+use crate::{NetworkTestError, Phase, Result, Socks5Client};
 use rand::Rng;
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -11,6 +17,8 @@ pub struct BandwidthTest {
     target_addr: String,
     chunk_size: usize,
     test_duration: Duration,
+    live: LivePanel,
+    samples: SampleRecorder,
 }
 
 #[derive(Debug, Clone)]
@@ -24,6 +32,8 @@ pub struct BandwidthResult {
     pub download_samples: Vec<SpeedSample>,
     pub connection_interruptions: u32,
     pub data_integrity_errors: u32,
+    pub failures: FailureTally,
+    pub events: Vec<TestEvent>,
 }
 
 #[derive(Debug, Clone)]
@@ -46,10 +56,22 @@ impl BandwidthTest {
             target_addr: target_addr.to_string(),
             chunk_size,
             test_duration: Duration::from_secs(test_duration_sec),
+            live: LivePanel::default(),
+            samples: SampleRecorder::default(),
         }
     }
 
-    pub async fn run(&self) -> Result<()> {
+    pub fn with_live(mut self, live: LivePanel) -> Self {
+        self.live = live;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: SampleRecorder) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub async fn run(&self) -> Result<BandwidthResult> {
         info!("Starting bandwidth test");
         info!("Proxy: {}, Target: {}", self.proxy_addr, self.target_addr);
         info!(
@@ -57,6 +79,18 @@ impl BandwidthTest {
             self.chunk_size, self.test_duration
         );
 
+        let result = self.execute().await;
+        let _pause = self.live.finish(result.is_ok());
+        let result = result?;
+
+        if !self.samples.owns_stdout() {
+            self.print_results(&result);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn execute(&self) -> Result<BandwidthResult> {
         let proxy_addr = self
             .proxy_addr
             .parse()
@@ -64,14 +98,11 @@ impl BandwidthTest {
 
         let client = Socks5Client::new(proxy_addr).with_timeout(Duration::from_secs(10));
 
-        let result = self.run_bandwidth_test(&client).await?;
-
-        self.print_results(&result);
-
-        Ok(())
+        self.run_bandwidth_test(&client).await
     }
 
     async fn run_bandwidth_test(&self, client: &Socks5Client) -> Result<BandwidthResult> {
+        self.live.start(Some(self.test_duration));
         let start_time = Instant::now();
         let end_time = start_time + self.test_duration;
 
@@ -85,7 +116,10 @@ impl BandwidthTest {
             download_samples: Vec::new(),
             connection_interruptions: 0,
             data_integrity_errors: 0,
+            failures: FailureTally::default(),
+            events: Vec::new(),
         };
+        let mut events = EventLog::new("bandwidth").with_live(self.live.clone());
 
         let mut stream = client.connect(&self.target_addr).await?;
         info!("Connected to target via SOCKS5 proxy");
@@ -101,7 +135,7 @@ impl BandwidthTest {
             let chunk_start = Instant::now();
 
             match self
-                .perform_data_transfer(&mut stream, &mut result, chunk_start)
+                .perform_data_transfer(&mut stream, &mut result, &mut events, chunk_start)
                 .await
             {
                 Ok(_) => {
@@ -110,18 +144,40 @@ impl BandwidthTest {
                 Err(e) => {
                     warn!("Data transfer error: {}. Attempting to reconnect...", e);
                     result.connection_interruptions += 1;
-
+                    result.failures.record(&e);
+                    let reason = FailureCategory::from(&e).to_string();
+                    self.samples.record(
+                        &self.target_addr,
+                        Sample::Drop {
+                            reason: reason.clone(),
+                        },
+                    );
+                    events.failure(&self.target_addr, EventKind::ConnectionDrop, reason);
+
+                    let reconnect_start = Instant::now();
                     match client.connect(&self.target_addr).await {
                         Ok(new_stream) => {
                             stream = new_stream;
+                            events.reconnected(&self.target_addr, "transfer resumed");
+                            self.samples.record(
+                                &self.target_addr,
+                                Sample::Reconnect {
+                                    downtime_ms: millis(reconnect_start.elapsed()),
+                                },
+                            );
                             let http_request = self.create_http_request();
                             if let Err(e) = stream.write_all(http_request.as_bytes()).await {
                                 error!("Failed to send HTTP request after reconnection: {}", e);
+                                let e = NetworkTestError::io(Phase::Exchange, e);
+                                result.failures.record(&e);
+                                events.error(&self.target_addr, &e);
                                 break;
                             }
                         }
                         Err(e) => {
                             error!("Failed to reconnect: {}", e);
+                            result.failures.record(&e);
+                            events.error(&self.target_addr, &e);
                             break;
                         }
                     }
@@ -133,6 +189,7 @@ impl BandwidthTest {
 
         result.average_upload_speed = self.calculate_average_speed(&result.upload_samples);
         result.average_download_speed = self.calculate_average_speed(&result.download_samples);
+        result.events = events.into_events();
 
         Ok(result)
     }
@@ -141,6 +198,7 @@ impl BandwidthTest {
         &self,
         stream: &mut tokio::net::TcpStream,
         result: &mut BandwidthResult,
+        events: &mut EventLog,
         _chunk_start: Instant,
     ) -> Result<()> {
         let test_data = self.generate_test_data();
@@ -154,13 +212,21 @@ impl BandwidthTest {
             test_data.len()
         );
 
-        stream.write_all(upload_request.as_bytes()).await?;
-        stream.write_all(&test_data).await?;
+        stream
+            .write_all(upload_request.as_bytes())
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+        stream
+            .write_all(&test_data)
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
 
         let upload_duration = upload_start.elapsed();
         let upload_speed = test_data.len() as f64 / upload_duration.as_secs_f64();
 
         result.total_bytes_sent += test_data.len() as u64;
+        self.live.bytes(test_data.len() as u64);
+        self.record_speed("upload", test_data.len(), upload_duration, upload_speed);
         result.upload_samples.push(SpeedSample {
             timestamp: upload_start,
             bytes_per_second: upload_speed,
@@ -178,6 +244,7 @@ impl BandwidthTest {
                 Ok(Ok(n)) if n > 0 => {
                     response_buffer.extend_from_slice(&buffer[..n]);
                     bytes_read += n;
+                    self.live.bytes(n as u64);
 
                     if response_buffer.len() >= 4 && response_buffer.ends_with(b"\r\n\r\n") {
                         break;
@@ -195,10 +262,15 @@ impl BandwidthTest {
                     break;
                 }
                 Ok(Err(e)) => {
-                    return Err(NetworkTestError::Io(e));
+                    return Err(NetworkTestError::io(Phase::Exchange, e));
                 }
                 Err(_) => {
                     warn!("Download timeout");
+                    let category = FailureCategory::Timeout {
+                        phase: Phase::Exchange,
+                    };
+                    result.failures.record_category(category);
+                    events.category(&self.target_addr, category);
                     break;
                 }
             }
@@ -212,6 +284,7 @@ impl BandwidthTest {
         };
 
         result.total_bytes_received += bytes_read as u64;
+        self.record_speed("download", bytes_read, download_duration, download_speed);
         result.download_samples.push(SpeedSample {
             timestamp: download_start,
             bytes_per_second: download_speed,
@@ -221,8 +294,15 @@ impl BandwidthTest {
 
         if self.verify_response_integrity(&response_buffer) {
             debug!("Response integrity verified");
+            self.live.success();
+            events.recovered(&self.target_addr);
         } else {
             result.data_integrity_errors += 1;
+            events.failure(
+                &self.target_addr,
+                EventKind::IntegrityError,
+                "response failed integrity check",
+            );
             warn!("Data integrity error detected");
         }
 
@@ -252,6 +332,18 @@ impl BandwidthTest {
                 || response_str.contains("204 No Content"))
     }
 
+    fn record_speed(&self, direction: &'static str, bytes: usize, duration: Duration, speed: f64) {
+        self.samples.record(
+            &self.target_addr,
+            Sample::Speed {
+                direction,
+                bytes: bytes as u64,
+                duration_ms: millis(duration),
+                bytes_per_second: speed,
+            },
+        );
+    }
+
     fn create_http_request(&self) -> String {
         format!(
             "GET /stream-bytes/{} HTTP/1.1\r\nHost: {}\r\nConnection: keep-alive\r\nUser-Agent: NetworkStabilityTest/1.0\r\n\r\n",
@@ -354,46 +446,21 @@ impl BandwidthTest {
         );
         println!("  Data Integrity Errors: {}", result.data_integrity_errors);
 
-        let total_samples = result.upload_samples.len() + result.download_samples.len();
-        let error_rate = if total_samples > 0 {
-            (result.data_integrity_errors as f64 / total_samples as f64) * 100.0
-        } else {
-            0.0
-        };
+        let error_rate = self.calculate_error_rate(result);
         println!("  Error Rate: {error_rate:.2}%");
-
-        let stability_score = if total_samples > 0 {
-            let connection_stability = if result.connection_interruptions == 0 {
-                1.0
-            } else {
-                1.0 / (1.0 + result.connection_interruptions as f64 * 0.2)
-            };
-            let integrity_score = 1.0 - (error_rate / 100.0);
-            (connection_stability * integrity_score * 100.0).clamp(0.0, 100.0)
-        } else {
-            0.0
-        };
-
-        println!("  Bandwidth Stability Score: {stability_score:.1}/100");
+        println!(
+            "  Bandwidth Stability Score: {:.1}/100",
+            self.calculate_stability_score(result)
+        );
         println!();
 
-        let speed_consistency = if result.upload_samples.len() > 1 {
-            let mean = result.average_upload_speed;
-            let variance: f64 = result
-                .upload_samples
-                .iter()
-                .map(|s| (s.bytes_per_second - mean).powi(2))
-                .sum::<f64>()
-                / result.upload_samples.len() as f64;
-            let std_dev = variance.sqrt();
-            let coefficient_of_variation = if mean > 0.0 { std_dev / mean } else { 0.0 };
-            1.0 - coefficient_of_variation.min(1.0)
-        } else {
-            1.0
-        };
+        print_failure_breakdown(&result.failures);
 
         println!("Performance Metrics:");
-        println!("  Speed Consistency: {:.1}%", speed_consistency * 100.0);
+        println!(
+            "  Speed Consistency: {:.1}%",
+            self.calculate_speed_consistency(result) * 100.0
+        );
 
         if !result.upload_samples.is_empty() && !result.download_samples.is_empty() {
             let total_transfer_time: Duration = result
@@ -407,4 +474,79 @@ impl BandwidthTest {
             println!("  Average Transfer Time: {avg_transfer_time:?}");
         }
     }
+
+    fn calculate_error_rate(&self, result: &BandwidthResult) -> f64 {
+        let total_samples = result.upload_samples.len() + result.download_samples.len();
+        if total_samples > 0 {
+            (result.data_integrity_errors as f64 / total_samples as f64) * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    fn calculate_stability_score(&self, result: &BandwidthResult) -> f64 {
+        if result.upload_samples.is_empty() && result.download_samples.is_empty() {
+            return 0.0;
+        }
+
+        let connection_stability = if result.connection_interruptions == 0 {
+            1.0
+        } else {
+            1.0 / (1.0 + result.connection_interruptions as f64 * 0.2)
+        };
+        let integrity_score = 1.0 - (self.calculate_error_rate(result) / 100.0);
+        (connection_stability * integrity_score * 100.0).clamp(0.0, 100.0)
+    }
+
+    fn calculate_speed_consistency(&self, result: &BandwidthResult) -> f64 {
+        if result.upload_samples.len() <= 1 {
+            return 1.0;
+        }
+
+        let mean = result.average_upload_speed;
+        let variance: f64 = result
+            .upload_samples
+            .iter()
+            .map(|s| (s.bytes_per_second - mean).powi(2))
+            .sum::<f64>()
+            / result.upload_samples.len() as f64;
+        let std_dev = variance.sqrt();
+        let coefficient_of_variation = if mean > 0.0 { std_dev / mean } else { 0.0 };
+        1.0 - coefficient_of_variation.min(1.0)
+    }
+
+    pub fn to_metrics(&self, result: &BandwidthResult) -> BandwidthMetrics {
+        let speeds = |samples: &[SpeedSample]| {
+            if samples.is_empty() {
+                return (0.0, 0.0);
+            }
+            samples
+                .iter()
+                .map(|s| s.bytes_per_second)
+                .fold((f64::INFINITY, 0.0f64), |(min, max), speed| {
+                    (min.min(speed), max.max(speed))
+                })
+        };
+        let (min_upload_speed, max_upload_speed) = speeds(&result.upload_samples);
+        let (min_download_speed, max_download_speed) = speeds(&result.download_samples);
+
+        BandwidthMetrics {
+            test_duration: result.test_duration,
+            chunk_size: self.chunk_size,
+            total_bytes_sent: result.total_bytes_sent,
+            total_bytes_received: result.total_bytes_received,
+            average_upload_speed: result.average_upload_speed,
+            average_download_speed: result.average_download_speed,
+            max_upload_speed,
+            max_download_speed,
+            min_upload_speed,
+            min_download_speed,
+            speed_consistency_score: self.calculate_speed_consistency(result) * 100.0,
+            connection_interruptions: result.connection_interruptions,
+            data_integrity_errors: result.data_integrity_errors,
+            bandwidth_score: self.calculate_stability_score(result),
+            fairness: None,
+            failures: result.failures.breakdown(),
+        }
+    }
 }
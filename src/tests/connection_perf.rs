@@ -1,16 +1,37 @@
-use crate::{NetworkTestError, Result, Socks5Client};
+use super::print_failure_breakdown;
+use crate::config::ConcurrencyRamp;
+use crate::live::LivePanel;
+use crate::metrics::{
+    ConcurrentMetrics, ConnectionPerfMetrics, EventLog, FailureCategory, FailureTally,
+    OpenLoopMetrics, TestEvent,
+};
+use crate::samples::{millis, Sample, SampleRecorder};
+use crate::{NetworkTestError, Phase, Result, Socks5Client};
 use futures::future::join_all;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::time::{sleep, timeout};
+use tokio::time::{sleep, sleep_until, timeout};
 use tracing::{debug, info};
 
+/// Highest open-loop rate the launcher will attempt, in connections per second.
+const MAX_OPEN_LOOP_RATE: f64 = 100_000.0;
+
 #[derive(Debug, Clone)]
 pub struct ConnectionPerfTest {
     proxy_addr: String,
     target_addr: String,
     concurrent_connections: usize,
     total_connections: usize,
+    ramp: ConcurrencyRamp,
+    level_hold: Duration,
+    knee_min_success_rate: f64,
+    knee_p99_factor: f64,
+    open_loop_rates: Vec<f64>,
+    rate_step_duration: Duration,
+    live: LivePanel,
+    samples: SampleRecorder,
 }
 
 #[derive(Debug, Clone)]
@@ -24,21 +45,56 @@ pub struct ConnectionPerfResult {
     pub socks5_handshake_times: Vec<Duration>,
     pub target_connect_times: Vec<Duration>,
     pub concurrent_test_results: Vec<ConcurrentTestResult>,
+    pub saturation_knee: Option<SaturationKnee>,
+    pub open_loop_results: Vec<OpenLoopStepResult>,
+    pub failures: FailureTally,
     pub average_connection_time: Duration,
     pub average_heartbeat_time: Duration,
     pub average_total_time: Duration,
     pub min_connection_time: Duration,
     pub max_connection_time: Duration,
     pub connection_success_rate: f64,
+    pub events: Vec<TestEvent>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ConcurrentTestResult {
     pub concurrent_level: usize,
+    pub attempts: usize,
     pub successful_connections: usize,
     pub failed_connections: usize,
+    pub success_rate: f64,
     pub average_time: Duration,
+    pub median_time: Duration,
+    pub p95_time: Duration,
+    pub p99_time: Duration,
     pub total_time: Duration,
+    pub failures: FailureTally,
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenLoopStepResult {
+    pub offered_rate: f64,
+    pub achieved_rate: f64,
+    pub step_duration: Duration,
+    pub scheduled: usize,
+    pub successful_connections: usize,
+    pub failed_connections: usize,
+    pub max_backlog: usize,
+    pub backlog_at_step_end: usize,
+    pub max_launch_lag: Duration,
+    pub median_latency: Duration,
+    pub p95_latency: Duration,
+    pub p99_latency: Duration,
+    pub max_latency: Duration,
+    pub failures: FailureTally,
+}
+
+#[derive(Debug, Clone)]
+pub struct SaturationKnee {
+    pub concurrent_level: usize,
+    pub last_healthy_level: Option<usize>,
+    pub reason: String,
 }
 
 #[derive(Debug, Clone)]
@@ -49,10 +105,20 @@ struct ConnectionAttempt {
     pub heartbeat_time: Option<Duration>,
     pub socks5_time: Option<Duration>,
     pub target_time: Option<Duration>,
-    pub _error: Option<String>,
+    pub failure: Option<FailureCategory>,
     pub _timestamp: Instant,
 }
 
+impl ConnectionAttempt {
+    fn sample(&self) -> Sample {
+        Sample::ConnectionAttempt {
+            success: self.success,
+            connect_time_ms: self.connection_time.map(millis),
+            error: self.failure.map(|failure| failure.to_string()),
+        }
+    }
+}
+
 impl ConnectionPerfTest {
     pub fn new(proxy_addr: &str, target_addr: &str, concurrent: usize, total: usize) -> Self {
         Self {
@@ -60,10 +126,57 @@ impl ConnectionPerfTest {
             target_addr: target_addr.to_string(),
             concurrent_connections: concurrent,
             total_connections: total,
+            ramp: ConcurrencyRamp::up_to(concurrent),
+            level_hold: Duration::ZERO,
+            knee_min_success_rate: 95.0,
+            knee_p99_factor: 3.0,
+            open_loop_rates: Vec::new(),
+            rate_step_duration: Duration::from_secs(10),
+            live: LivePanel::default(),
+            samples: SampleRecorder::default(),
         }
     }
 
-    pub async fn run(&self) -> Result<()> {
+    pub fn with_ramp(mut self, ramp: ConcurrencyRamp) -> Self {
+        self.ramp = ramp;
+        self
+    }
+
+    pub fn with_level_hold(mut self, level_hold_ms: u64) -> Self {
+        self.level_hold = Duration::from_millis(level_hold_ms);
+        self
+    }
+
+    /// Non-finite and non-positive rates are dropped; the rest are clamped to
+    /// between one launch per step and `MAX_OPEN_LOOP_RATE`.
+    pub fn with_open_loop(mut self, rates: Vec<f64>, step_duration_sec: u64) -> Self {
+        self.rate_step_duration = Duration::from_secs(step_duration_sec.max(1));
+        let min_rate = 1.0 / self.rate_step_duration.as_secs_f64();
+        self.open_loop_rates = rates
+            .into_iter()
+            .filter(|rate| rate.is_finite() && *rate > 0.0)
+            .map(|rate| rate.clamp(min_rate, MAX_OPEN_LOOP_RATE))
+            .collect();
+        self
+    }
+
+    pub fn with_knee_thresholds(mut self, min_success_rate: f64, p99_factor: f64) -> Self {
+        self.knee_min_success_rate = min_success_rate;
+        self.knee_p99_factor = p99_factor;
+        self
+    }
+
+    pub fn with_live(mut self, live: LivePanel) -> Self {
+        self.live = live;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: SampleRecorder) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub async fn run(&self) -> Result<ConnectionPerfResult> {
         info!("Starting connection performance test");
         info!("Proxy: {}, Target: {}", self.proxy_addr, self.target_addr);
         info!(
@@ -78,22 +191,58 @@ impl ConnectionPerfTest {
 
         let client = Socks5Client::new(proxy_addr).with_timeout(Duration::from_secs(10));
 
-        let result = self.run_connection_perf_test(&client).await?;
+        let result = self.run_connection_perf_test(&client).await;
+        let _pause = self.live.finish(result.is_ok());
+        let result = result?;
+
+        if !self.samples.owns_stdout() {
+            self.print_results(&result);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn execute(&self) -> Result<ConnectionPerfResult> {
+        let proxy_addr = self
+            .proxy_addr
+            .parse()
+            .map_err(|e| NetworkTestError::Config(format!("Invalid proxy address: {e}")))?;
 
-        self.print_results(&result);
+        let client = Socks5Client::new(proxy_addr).with_timeout(Duration::from_secs(10));
 
-        Ok(())
+        self.run_connection_perf_test(&client).await
     }
 
     async fn run_connection_perf_test(
         &self,
         client: &Socks5Client,
     ) -> Result<ConnectionPerfResult> {
+        self.live.start(None);
         info!("Running sequential connection test");
-        let sequential_results = self.run_sequential_test(client).await;
+        let mut events = EventLog::new("connection_perf").with_live(self.live.clone());
+        let sequential_results = self.run_sequential_test(client, &mut events).await;
+
+        // Open-loop mode replaces the closed-loop concurrency ramp, since the ramp's
+        // batches wait for completions and so hide queueing inside the proxy.
+        let (concurrent_results, open_loop_results) = if self.open_loop_rates.is_empty() {
+            info!("Running concurrent connection tests");
+            (self.run_concurrent_tests(client).await, Vec::new())
+        } else {
+            info!("Running open-loop connection rate tests");
+            (Vec::new(), self.run_open_loop_tests(client).await)
+        };
+        let saturation_knee = self.detect_saturation_knee(&concurrent_results);
 
-        info!("Running concurrent connection tests");
-        let concurrent_results = self.run_concurrent_tests(client).await;
+        let mut failures = FailureTally::default();
+        for category in sequential_results.iter().filter_map(|r| r.failure) {
+            failures.record_category(category);
+        }
+        for level in &concurrent_results {
+            failures.merge(&level.failures);
+        }
+        for step in &open_loop_results {
+            failures.merge(&step.failures);
+        }
 
         let successful_connections = sequential_results.iter().filter(|r| r.success).count();
         let failed_connections = sequential_results.len() - successful_connections;
@@ -169,16 +318,26 @@ impl ConnectionPerfTest {
             socks5_handshake_times,
             target_connect_times,
             concurrent_test_results: concurrent_results,
+            saturation_knee,
+            open_loop_results,
+            failures,
             average_connection_time,
             average_heartbeat_time,
             average_total_time,
             min_connection_time,
             max_connection_time,
             connection_success_rate,
+            events: events.into_events(),
         })
     }
 
-    async fn run_sequential_test(&self, client: &Socks5Client) -> Vec<ConnectionAttempt> {
+    /// Only sequential attempts feed the event stream; failures under the
+    /// concurrency ramp are load effects rather than outages.
+    async fn run_sequential_test(
+        &self,
+        client: &Socks5Client,
+        events: &mut EventLog,
+    ) -> Vec<ConnectionAttempt> {
         let mut results = Vec::with_capacity(self.total_connections);
 
         for i in 0..self.total_connections {
@@ -190,6 +349,15 @@ impl ConnectionPerfTest {
 
             let _start_time = Instant::now();
             let result = self.attempt_single_connection(client).await;
+            self.samples.record(&self.target_addr, result.sample());
+            match result.failure {
+                Some(category) => events.category(&self.target_addr, category),
+                None if result.success => {
+                    self.live.rtt(result.total_time);
+                    events.recovered(&self.target_addr);
+                }
+                None => {}
+            }
 
             results.push(result);
 
@@ -203,41 +371,69 @@ impl ConnectionPerfTest {
 
     async fn run_concurrent_tests(&self, client: &Socks5Client) -> Vec<ConcurrentTestResult> {
         let mut results = Vec::new();
-        let concurrent_levels = vec![2, 5, 10, 20, 50];
+        let concurrent_levels = self.ramp.levels();
 
         for &concurrent_level in &concurrent_levels {
-            if concurrent_level > self.total_connections {
-                continue;
-            }
-
-            info!("Testing {} concurrent connections", concurrent_level);
+            info!(
+                "Testing {} concurrent connections (hold {:?})",
+                concurrent_level, self.level_hold
+            );
 
             let test_start = Instant::now();
+            let hold_until = test_start + self.level_hold;
             let mut tasks = Vec::new();
 
             for _ in 0..concurrent_level {
                 let client_clone = client.clone();
                 let target_addr = self.target_addr.clone();
+                let samples = self.samples.clone();
 
+                // Each worker keeps one connection attempt in flight until the hold time
+                // expires, so the level is sustained rather than fired as a single burst.
                 let task = tokio::spawn(async move {
-                    Self::attempt_single_connection_static(&client_clone, &target_addr).await
+                    let mut attempts = Vec::new();
+                    loop {
+                        let attempt =
+                            Self::attempt_single_connection_static(&client_clone, &target_addr)
+                                .await;
+                        samples.record(&target_addr, attempt.sample());
+                        attempts.push(attempt);
+                        if Instant::now() >= hold_until {
+                            break;
+                        }
+                    }
+                    attempts
                 });
 
                 tasks.push(task);
             }
 
-            let concurrent_results = join_all(tasks).await;
+            let concurrent_results: Vec<ConnectionAttempt> = join_all(tasks)
+                .await
+                .into_iter()
+                .filter_map(|r| r.ok())
+                .flatten()
+                .collect();
             let test_duration = test_start.elapsed();
 
-            let successful = concurrent_results
-                .iter()
-                .filter(|r| r.is_ok() && r.as_ref().unwrap().success)
-                .count();
-            let failed = concurrent_level - successful;
+            let mut failures = FailureTally::default();
+            for category in concurrent_results.iter().filter_map(|r| r.failure) {
+                failures.record_category(category);
+            }
+            for attempt in &concurrent_results {
+                if attempt.success {
+                    self.live.rtt(attempt.total_time);
+                } else {
+                    self.live.failure();
+                }
+            }
+
+            let attempts = concurrent_results.len().max(concurrent_level);
+            let successful = concurrent_results.iter().filter(|r| r.success).count();
+            let failed = attempts - successful;
 
             let successful_times: Vec<Duration> = concurrent_results
                 .iter()
-                .filter_map(|r| r.as_ref().ok())
                 .filter(|r| r.success)
                 .map(|r| r.total_time)
                 .collect();
@@ -250,16 +446,174 @@ impl ConnectionPerfTest {
 
             results.push(ConcurrentTestResult {
                 concurrent_level,
+                attempts,
                 successful_connections: successful,
                 failed_connections: failed,
+                success_rate: successful as f64 / attempts as f64 * 100.0,
                 average_time,
+                median_time: self.calculate_median(&successful_times),
+                p95_time: self.calculate_percentile(&successful_times, 95.0),
+                p99_time: self.calculate_percentile(&successful_times, 99.0),
                 total_time: test_duration,
+                failures,
             });
         }
 
         results
     }
 
+    async fn run_open_loop_tests(&self, client: &Socks5Client) -> Vec<OpenLoopStepResult> {
+        let mut results = Vec::new();
+
+        for &offered_rate in &self.open_loop_rates {
+            info!(
+                "Offering {:.1} connections/sec for {:?}",
+                offered_rate, self.rate_step_duration
+            );
+            results.push(self.run_open_loop_step(client, offered_rate).await);
+        }
+
+        results
+    }
+
+    async fn run_open_loop_step(
+        &self,
+        client: &Socks5Client,
+        offered_rate: f64,
+    ) -> OpenLoopStepResult {
+        let interval = Duration::try_from_secs_f64(1.0 / offered_rate)
+            .unwrap_or(self.rate_step_duration)
+            .clamp(
+                Duration::from_secs_f64(1.0 / MAX_OPEN_LOOP_RATE),
+                self.rate_step_duration,
+            );
+        let step_start = tokio::time::Instant::now();
+        let step_end = step_start + self.rate_step_duration;
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let mut max_backlog = 0;
+        let mut max_launch_lag = Duration::ZERO;
+        let mut tasks = Vec::new();
+
+        let mut scheduled = step_start;
+        while scheduled < step_end {
+            sleep_until(scheduled).await;
+            max_launch_lag = max_launch_lag.max(scheduled.elapsed());
+
+            let client_clone = client.clone();
+            let target_addr = self.target_addr.clone();
+            let in_flight_clone = in_flight.clone();
+            let samples = self.samples.clone();
+            max_backlog = max_backlog.max(in_flight.fetch_add(1, Ordering::SeqCst) + 1);
+
+            // Latency is taken from the scheduled start rather than the actual launch,
+            // so a launcher that falls behind still charges the delay to the proxy.
+            let task = tokio::spawn(async move {
+                let attempt =
+                    Self::attempt_single_connection_static(&client_clone, &target_addr).await;
+                in_flight_clone.fetch_sub(1, Ordering::SeqCst);
+                samples.record(&target_addr, attempt.sample());
+                (
+                    attempt.failure,
+                    scheduled.elapsed(),
+                    tokio::time::Instant::now(),
+                )
+            });
+            tasks.push(task);
+
+            scheduled += interval;
+        }
+
+        sleep_until(step_end).await;
+        let backlog_at_step_end = in_flight.load(Ordering::SeqCst);
+
+        let outcomes: Vec<(Option<FailureCategory>, Duration, tokio::time::Instant)> =
+            join_all(tasks)
+                .await
+                .into_iter()
+                .filter_map(|r| r.ok())
+                .collect();
+
+        let mut failures = FailureTally::default();
+        for category in outcomes.iter().filter_map(|(failure, _, _)| *failure) {
+            failures.record_category(category);
+        }
+        for (failure, latency, _) in &outcomes {
+            match failure {
+                Some(_) => self.live.failure(),
+                None => self.live.rtt(*latency),
+            }
+        }
+
+        let scheduled_count = outcomes.len();
+        let successful_connections = outcomes
+            .iter()
+            .filter(|(failure, _, _)| failure.is_none())
+            .count();
+        let completed_in_step = outcomes
+            .iter()
+            .filter(|(failure, _, finished)| failure.is_none() && *finished <= step_end)
+            .count();
+        let latencies: Vec<Duration> = outcomes
+            .iter()
+            .filter(|(failure, _, _)| failure.is_none())
+            .map(|(_, latency, _)| *latency)
+            .collect();
+
+        OpenLoopStepResult {
+            offered_rate,
+            achieved_rate: completed_in_step as f64 / self.rate_step_duration.as_secs_f64(),
+            step_duration: self.rate_step_duration,
+            scheduled: scheduled_count,
+            successful_connections,
+            failed_connections: scheduled_count - successful_connections,
+            max_backlog,
+            backlog_at_step_end,
+            max_launch_lag,
+            median_latency: self.calculate_median(&latencies),
+            p95_latency: self.calculate_percentile(&latencies, 95.0),
+            p99_latency: self.calculate_percentile(&latencies, 99.0),
+            max_latency: latencies.iter().max().copied().unwrap_or(Duration::ZERO),
+            failures,
+        }
+    }
+
+    fn detect_saturation_knee(&self, results: &[ConcurrentTestResult]) -> Option<SaturationKnee> {
+        let baseline_p99 = results
+            .iter()
+            .find(|r| r.successful_connections > 0)
+            .map(|r| r.p99_time)?;
+        let p99_limit = baseline_p99.mul_f64(self.knee_p99_factor);
+
+        let mut last_healthy_level = None;
+        for result in results {
+            let reason = if result.success_rate < self.knee_min_success_rate {
+                Some(format!(
+                    "success rate {:.1}% below {:.1}%",
+                    result.success_rate, self.knee_min_success_rate
+                ))
+            } else if result.p99_time > p99_limit {
+                Some(format!(
+                    "p99 setup latency {:?} exceeds {:.1}x baseline ({:?})",
+                    result.p99_time, self.knee_p99_factor, baseline_p99
+                ))
+            } else {
+                None
+            };
+
+            if let Some(reason) = reason {
+                return Some(SaturationKnee {
+                    concurrent_level: result.concurrent_level,
+                    last_healthy_level,
+                    reason,
+                });
+            }
+
+            last_healthy_level = Some(result.concurrent_level);
+        }
+
+        None
+    }
+
     async fn attempt_single_connection(&self, client: &Socks5Client) -> ConnectionAttempt {
         Self::attempt_single_connection_static(client, &self.target_addr).await
     }
@@ -293,7 +647,7 @@ impl ConnectionPerfTest {
                             heartbeat_time: Some(heartbeat_time),
                             socks5_time: Some(connection_time),
                             target_time: None,
-                            _error: None,
+                            failure: None,
                             _timestamp: timestamp,
                         }
                     }
@@ -308,7 +662,7 @@ impl ConnectionPerfTest {
                             heartbeat_time: None,
                             socks5_time: Some(connection_time),
                             target_time: None,
-                            _error: Some(format!("Heartbeat failed: {}", e)),
+                            failure: Some(FailureCategory::from(&e)),
                             _timestamp: timestamp,
                         }
                     }
@@ -324,7 +678,7 @@ impl ConnectionPerfTest {
                     heartbeat_time: None,
                     socks5_time: None,
                     target_time: None,
-                    _error: Some(e.to_string()),
+                    failure: Some(FailureCategory::from(&e)),
                     _timestamp: timestamp,
                 }
             }
@@ -338,7 +692,9 @@ impl ConnectionPerfTest {
                     heartbeat_time: None,
                     socks5_time: None,
                     target_time: None,
-                    _error: Some("Connection timeout".to_string()),
+                    failure: Some(FailureCategory::Timeout {
+                        phase: Phase::Connect,
+                    }),
                     _timestamp: timestamp,
                 }
             }
@@ -350,24 +706,103 @@ impl ConnectionPerfTest {
         let ping_message = b"PING\n";
         timeout(Duration::from_secs(5), stream.write_all(ping_message))
             .await
-            .map_err(|_| NetworkTestError::Timeout("Heartbeat send timeout".to_string()))?
-            .map_err(NetworkTestError::Io)?;
+            .map_err(|_| NetworkTestError::timeout(Phase::Exchange, "Heartbeat send timeout"))?
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
 
         // Read PONG response
         let mut buffer = [0u8; 64];
         let bytes_read = timeout(Duration::from_secs(5), stream.read(&mut buffer))
             .await
-            .map_err(|_| NetworkTestError::Timeout("Heartbeat response timeout".to_string()))?
-            .map_err(NetworkTestError::Io)?;
+            .map_err(|_| NetworkTestError::timeout(Phase::Exchange, "Heartbeat response timeout"))?
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+
+        if bytes_read == 0 {
+            return Err(NetworkTestError::io(
+                Phase::Exchange,
+                std::io::ErrorKind::UnexpectedEof.into(),
+            ));
+        }
 
         let response = std::str::from_utf8(&buffer[..bytes_read])
-            .map_err(|e| NetworkTestError::Connection(format!("Invalid response: {}", e)))?
+            .map_err(|e| {
+                NetworkTestError::protocol(Phase::Exchange, format!("Invalid response: {e}"))
+            })?
             .trim();
 
         if response == "PONG" {
             Ok(())
         } else {
-            Err(NetworkTestError::Connection(format!("Expected PONG, got: {}", response)))
+            Err(NetworkTestError::protocol(
+                Phase::Exchange,
+                format!("Expected PONG, got: {response}"),
+            ))
+        }
+    }
+
+    pub fn to_metrics(&self, result: &ConnectionPerfResult) -> ConnectionPerfMetrics {
+        ConnectionPerfMetrics {
+            total_attempts: result.total_attempts,
+            successful_connections: result.successful_connections,
+            failed_connections: result.failed_connections,
+            success_rate: result.connection_success_rate,
+            average_connection_time: result.average_connection_time,
+            min_connection_time: result.min_connection_time,
+            max_connection_time: result.max_connection_time,
+            median_connection_time: self.calculate_median(&result.connection_times),
+            p95_connection_time: self.calculate_percentile(&result.connection_times, 95.0),
+            p99_connection_time: self.calculate_percentile(&result.connection_times, 99.0),
+            connection_time_variance: self.calculate_variance(&result.connection_times),
+            max_concurrent_successful: result
+                .concurrent_test_results
+                .iter()
+                .filter(|r| r.failed_connections == 0)
+                .map(|r| r.concurrent_level)
+                .max()
+                .unwrap_or(0),
+            performance_score: self.calculate_performance_score(result),
+            concurrent_results: result
+                .concurrent_test_results
+                .iter()
+                .map(|r| ConcurrentMetrics {
+                    concurrent_level: r.concurrent_level,
+                    attempts: r.attempts,
+                    successful_connections: r.successful_connections,
+                    failed_connections: r.failed_connections,
+                    success_rate: r.success_rate,
+                    average_time: r.average_time,
+                    median_time: r.median_time,
+                    p95_time: r.p95_time,
+                    p99_time: r.p99_time,
+                    total_time: r.total_time,
+                })
+                .collect(),
+            saturation_knee: result.saturation_knee.as_ref().map(|k| k.concurrent_level),
+            last_healthy_level: result
+                .saturation_knee
+                .as_ref()
+                .and_then(|k| k.last_healthy_level),
+            knee_reason: result.saturation_knee.as_ref().map(|k| k.reason.clone()),
+            max_sustained_rate: self.max_sustained_rate(result),
+            failures: result.failures.breakdown(),
+            open_loop_results: result
+                .open_loop_results
+                .iter()
+                .map(|step| OpenLoopMetrics {
+                    offered_rate: step.offered_rate,
+                    achieved_rate: step.achieved_rate,
+                    step_duration: step.step_duration,
+                    scheduled: step.scheduled,
+                    successful_connections: step.successful_connections,
+                    failed_connections: step.failed_connections,
+                    max_backlog: step.max_backlog,
+                    backlog_at_step_end: step.backlog_at_step_end,
+                    max_launch_lag: step.max_launch_lag,
+                    median_latency: step.median_latency,
+                    p95_latency: step.p95_latency,
+                    p99_latency: step.p99_latency,
+                    max_latency: step.max_latency,
+                })
+                .collect(),
         }
     }
 
@@ -446,30 +881,76 @@ impl ConnectionPerfTest {
 
         if !result.concurrent_test_results.is_empty() {
             println!("Concurrent Connection Test Results:");
-            println!("  Level | Success | Failed | Success Rate | Avg Time | Total Time");
-            println!("  ------|---------|--------|--------------|----------|------------");
+            println!(
+                "  Level | Attempts | Success | Failed | Success Rate | Avg Time | P50 Time | P95 Time | P99 Time | Total Time"
+            );
+            println!(
+                "  ------|----------|---------|--------|--------------|----------|----------|----------|----------|-----------"
+            );
 
             for test_result in &result.concurrent_test_results {
-                let success_rate = if test_result.concurrent_level > 0 {
-                    test_result.successful_connections as f64 / test_result.concurrent_level as f64
-                        * 100.0
-                } else {
-                    0.0
-                };
-
                 println!(
-                    "  {:5} | {:7} | {:6} | {:9.1}% | {:8.0}ms | {:9.0}ms",
+                    "  {:5} | {:8} | {:7} | {:6} | {:11.1}% | {:6}ms | {:6}ms | {:6}ms | {:6}ms | {:8}ms",
                     test_result.concurrent_level,
+                    test_result.attempts,
                     test_result.successful_connections,
                     test_result.failed_connections,
-                    success_rate,
+                    test_result.success_rate,
                     test_result.average_time.as_millis(),
+                    test_result.median_time.as_millis(),
+                    test_result.p95_time.as_millis(),
+                    test_result.p99_time.as_millis(),
                     test_result.total_time.as_millis()
                 );
             }
             println!();
+
+            match &result.saturation_knee {
+                Some(knee) => {
+                    println!(
+                        "Saturation Knee: {} concurrent connections",
+                        knee.concurrent_level
+                    );
+                    println!("  Reason: {}", knee.reason);
+                    match knee.last_healthy_level {
+                        Some(level) => println!("  Last Healthy Level: {level}"),
+                        None => println!("  Last Healthy Level: none"),
+                    }
+                }
+                None => println!("Saturation Knee: not reached"),
+            }
+            println!();
         }
 
+        if !result.open_loop_results.is_empty() {
+            println!("Open-Loop Connection Rate Results:");
+            println!(
+                "  Offered/s | Achieved/s | Scheduled | Success | Failed | Max Backlog | End Backlog | P50 Latency | P95 Latency | P99 Latency"
+            );
+            println!(
+                "  ----------|------------|-----------|---------|--------|-------------|-------------|-------------|-------------|------------"
+            );
+
+            for step in &result.open_loop_results {
+                println!(
+                    "  {:9.1} | {:10.1} | {:9} | {:7} | {:6} | {:11} | {:11} | {:9}ms | {:9}ms | {:9}ms",
+                    step.offered_rate,
+                    step.achieved_rate,
+                    step.scheduled,
+                    step.successful_connections,
+                    step.failed_connections,
+                    step.max_backlog,
+                    step.backlog_at_step_end,
+                    step.median_latency.as_millis(),
+                    step.p95_latency.as_millis(),
+                    step.p99_latency.as_millis()
+                );
+            }
+            println!();
+        }
+
+        print_failure_breakdown(&result.failures);
+
         self.print_performance_analysis(result);
 
         let overall_score = self.calculate_performance_score(result);
@@ -508,7 +989,7 @@ impl ConnectionPerfTest {
         if let Some(best_concurrent) = result
             .concurrent_test_results
             .iter()
-            .filter(|r| r.successful_connections == r.concurrent_level)
+            .filter(|r| r.failed_connections == 0)
             .max_by_key(|r| r.concurrent_level)
         {
             println!(
@@ -517,6 +998,12 @@ impl ConnectionPerfTest {
             );
         }
 
+        if let Some(rate) = self.max_sustained_rate(result) {
+            println!("  ✓ Max sustained connection rate: {rate:.1}/s");
+        } else if !result.open_loop_results.is_empty() {
+            println!("  ✗ Max sustained connection rate: none of the offered rates");
+        }
+
         let time_variance = self.calculate_variance(&result.connection_times);
         if time_variance <= 0.1 {
             println!("  ✓ Connection time consistency: Excellent");
@@ -529,6 +1016,22 @@ impl ConnectionPerfTest {
         println!();
     }
 
+    // A rate is sustained when the proxy keeps up with the schedule: nearly every
+    // offered connection succeeds within the step and the backlog drains.
+    fn max_sustained_rate(&self, result: &ConnectionPerfResult) -> Option<f64> {
+        result
+            .open_loop_results
+            .iter()
+            .filter(|step| {
+                step.achieved_rate >= step.offered_rate * 0.95
+                    && step.backlog_at_step_end <= step.max_backlog.max(1) / 2
+            })
+            .map(|step| step.offered_rate)
+            .fold(None, |max: Option<f64>, rate| {
+                Some(max.map_or(rate, |max| max.max(rate)))
+            })
+    }
+
     fn calculate_median(&self, times: &[Duration]) -> Duration {
         if times.is_empty() {
             return Duration::ZERO;
@@ -538,7 +1041,7 @@ impl ConnectionPerfTest {
         sorted_times.sort();
 
         let mid = sorted_times.len() / 2;
-        if sorted_times.len() % 2 == 0 {
+        if sorted_times.len().is_multiple_of(2) {
             (sorted_times[mid - 1] + sorted_times[mid]) / 2
         } else {
             sorted_times[mid]
@@ -596,14 +1099,20 @@ impl ConnectionPerfTest {
             }
         };
 
-        let concurrent_score = if let Some(max_concurrent) = result
+        let concurrent_score = if !result.open_loop_results.is_empty() {
+            let max_offered = self.open_loop_rates.iter().copied().fold(0.0_f64, f64::max);
+            self.max_sustained_rate(result)
+                .map(|rate| rate / max_offered * 100.0)
+                .unwrap_or(0.0)
+        } else if let Some(max_concurrent) = result
             .concurrent_test_results
             .iter()
-            .filter(|r| r.successful_connections == r.concurrent_level)
+            .filter(|r| r.failed_connections == 0)
             .map(|r| r.concurrent_level)
             .max()
         {
-            (max_concurrent as f64 / 50.0 * 100.0).min(100.0)
+            let max_level = self.ramp.levels().last().copied().unwrap_or(1).max(1);
+            (max_concurrent as f64 / max_level as f64 * 100.0).min(100.0)
         } else {
             0.0
         };
@@ -611,3 +1120,18 @@ impl ConnectionPerfTest {
         (success_score * 0.4 + speed_score * 0.3 + consistency_score * 0.2 + concurrent_score * 0.1).clamp(0.0, 100.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_loop_rates_are_bounded() {
+        let test = ConnectionPerfTest::new("127.0.0.1:1080", "127.0.0.1:8003", 1, 1)
+            .with_open_loop(
+                vec![f64::INFINITY, f64::NAN, -5.0, 0.0, 1e12, 1e-300, 50.0],
+                10,
+            );
+        assert_eq!(test.open_loop_rates, vec![MAX_OPEN_LOOP_RATE, 0.1, 50.0]);
+    }
+}
@@ -1,37 +1,395 @@
-use crate::{NetworkTestError, Result, Socks5Client};
+use crate::dns::{
+    DnsHttpsSession, DnsMessage, DnsSession, DnsTcpSession, DnsTransport, DnsUdpSession,
+    RecordType, Resolution, RCODE_NOERROR, RCODE_NXDOMAIN,
+};
+use crate::live::LivePanel;
+use crate::metrics::{
+    AnswerSetMetrics, DnsCacheMetrics, DnsStabilityMetrics, DomainMetrics, EventLog,
+    FailureCategory, FailureTally, RecordTypeMetrics, ResolverMetrics, TestEvent,
+};
+use crate::samples::{millis, Sample, SampleRecorder};
+use crate::tests::print_failure_breakdown;
+use crate::tls;
+use crate::{NetworkTestError, Phase, Result, Socks5Client};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::time::{interval, timeout};
+use tokio::sync::Mutex;
+use tokio::time::interval;
+use tokio_rustls::TlsConnector;
 use tracing::{debug, info, warn};
 
 #[derive(Debug, Clone)]
 pub struct DnsStabilityTest {
     proxy_addr: String,
     domains: Vec<String>,
+    resolvers: Vec<String>,
+    expected_ips: HashMap<String, Vec<IpAddr>>,
+    record_types: HashMap<String, Vec<RecordType>>,
+    transport: DnsTransport,
+    tls_insecure: bool,
+    cache_analysis: bool,
     query_interval: Duration,
     test_duration: Duration,
+    live: LivePanel,
+    samples: SampleRecorder,
 }
 
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_CONSECUTIVE_TIMEOUTS: u32 = 3;
+const CACHE_BUST_EVERY: usize = 4;
+const EXPIRY_PROBE_MARGIN: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Clone)]
 pub struct DnsStabilityResult {
     pub total_queries: u64,
     pub successful_queries: u64,
     pub failed_queries: u64,
     pub timeout_queries: u64,
-    pub domain_results: std::collections::HashMap<String, DomainResult>,
+    pub domain_results: HashMap<String, DomainResult>,
+    pub resolver_results: Vec<ResolverResult>,
     pub average_query_time: Duration,
     pub min_query_time: Duration,
     pub max_query_time: Duration,
     pub success_rate: f64,
+    pub association_times: Vec<Duration>,
+    pub tcp_connect_times: Vec<Duration>,
+    pub tls_handshake_times: Vec<Duration>,
+    pub resumed_tls_sessions: u64,
+    pub udp_queries: u64,
+    pub tcp_queries: u64,
+    pub udp_fallback: bool,
+    pub stray_responses: u64,
+    pub cache: Option<CacheAnalysis>,
+    pub failures: FailureTally,
+    pub events: Vec<TestEvent>,
 }
 
 #[derive(Debug, Clone)]
 pub struct DomainResult {
     pub domain: String,
+    pub record_type: RecordType,
     pub total_queries: u64,
     pub successful_queries: u64,
     pub failed_queries: u64,
     pub average_query_time: Duration,
     pub query_times: Vec<Duration>,
+    pub answer_sets: Vec<AnswerSet>,
+    pub answer_changes: u64,
+    pub unexpected_answers: u64,
+    pub unexpected_addresses: Vec<IpAddr>,
+    pub nxdomain_responses: u64,
+    pub nodata_responses: u64,
+    pub min_ttl: Option<u32>,
+    pub max_ttl: Option<u32>,
+    last_answers: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnswerSet {
+    pub addresses: Vec<IpAddr>,
+    pub records: Vec<String>,
+    pub cname_chain: Vec<String>,
+    pub first_seen: Duration,
+    pub last_seen: Duration,
+    pub observations: u64,
+}
+
+#[derive(Debug, Clone)]
+pub enum DnsAnswer {
+    Records(Resolution),
+    NoData,
+    Rcode(u8),
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolverResult {
+    pub resolver: String,
+    pub total_queries: u64,
+    pub successful_queries: u64,
+    pub failed_queries: u64,
+    pub timeout_queries: u64,
+    pub query_times: Vec<Duration>,
+    pub failures: FailureTally,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QueryKind {
+    Regular,
+    /// Unique random subdomain, so no cache can hold the answer.
+    CacheBust,
+    /// Re-query of a name just after its last seen TTL ran out.
+    ExpiryProbe,
+}
+
+struct QueryCompletion {
+    kind: QueryKind,
+    resolver_index: usize,
+    domain: String,
+    record_type: RecordType,
+    transport: Option<DnsTransport>,
+    outcome: Result<(Duration, DnsAnswer)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CacheAnalysis {
+    pub cold_query_times: Vec<Duration>,
+    pub cold_failures: u64,
+    pub warm_samples: Vec<WarmSample>,
+    pub decremented_ttl_responses: u64,
+    pub early_ttl_resets: u64,
+    pub expiry_probes: u64,
+    pub expiry_refetches: u64,
+    pub expiry_stale_answers: u64,
+    pub expiry_query_times: Vec<Duration>,
+    ttls: HashMap<(usize, String), TtlTracker>,
+    pending_probes: Vec<ExpiryProbe>,
+    cache_busts: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WarmSample {
+    pub query_time: Duration,
+    pub decremented_ttl: bool,
+}
+
+#[derive(Debug, Clone)]
+struct TtlTracker {
+    full_ttl: u32,
+    ttl: u32,
+    seen_at: Instant,
+    probe_pending: bool,
+}
+
+#[derive(Debug, Clone)]
+struct ExpiryProbe {
+    due: Instant,
+    resolver_index: usize,
+    domain: String,
+    record_type: RecordType,
+}
+
+impl CacheAnalysis {
+    fn next_cache_bust(
+        &mut self,
+        targets: &[(String, RecordType)],
+        resolvers: usize,
+    ) -> (QueryKind, usize, String, RecordType) {
+        let resolver_index = self.cache_busts % resolvers;
+        let (domain, record_type) = &targets[(self.cache_busts / resolvers) % targets.len()];
+        self.cache_busts += 1;
+
+        let name = format!("nst-{:016x}.{}", rand::random::<u64>(), domain);
+        (QueryKind::CacheBust, resolver_index, name, *record_type)
+    }
+
+    fn due_probes(&mut self, now: Instant) -> Vec<ExpiryProbe> {
+        let (due, pending) = std::mem::take(&mut self.pending_probes)
+            .into_iter()
+            .partition(|probe| probe.due <= now);
+        self.pending_probes = pending;
+        due
+    }
+
+    /// Tracks the TTL of a repeated name per resolver. Anything below the
+    /// highest TTL seen so far was served from a cache.
+    fn observe(
+        &mut self,
+        resolver_index: usize,
+        domain: &str,
+        record_type: RecordType,
+        query_time: Duration,
+        ttl: u32,
+        end_time: Instant,
+    ) {
+        let now = Instant::now();
+        let tracker = match self
+            .ttls
+            .entry((resolver_index, target_label(domain, record_type)))
+        {
+            Entry::Vacant(entry) => entry.insert(TtlTracker {
+                full_ttl: ttl,
+                ttl,
+                seen_at: now,
+                probe_pending: false,
+            }),
+            Entry::Occupied(entry) => {
+                let tracker = entry.into_mut();
+                let remaining = tracker
+                    .ttl
+                    .saturating_sub(tracker.seen_at.elapsed().as_secs() as u32);
+                // A cached answer that turns fresh before expiry was evicted early
+                // or came from a different cache behind the same address
+                if tracker.ttl < tracker.full_ttl && remaining > 0 && ttl > remaining + 1 {
+                    self.early_ttl_resets += 1;
+                }
+
+                tracker.full_ttl = tracker.full_ttl.max(ttl);
+                let decremented_ttl = ttl < tracker.full_ttl;
+                if decremented_ttl {
+                    self.decremented_ttl_responses += 1;
+                }
+                self.warm_samples.push(WarmSample {
+                    query_time,
+                    decremented_ttl,
+                });
+
+                tracker.ttl = ttl;
+                tracker.seen_at = now;
+                tracker
+            }
+        };
+
+        let due = now + Duration::from_secs(ttl as u64) + EXPIRY_PROBE_MARGIN;
+        if !tracker.probe_pending && ttl > 0 && due < end_time {
+            tracker.probe_pending = true;
+            self.pending_probes.push(ExpiryProbe {
+                due,
+                resolver_index,
+                domain: domain.to_string(),
+                record_type,
+            });
+        }
+    }
+
+    fn record_probe(&mut self, completion: &QueryCompletion) {
+        match completion.kind {
+            QueryKind::CacheBust => match &completion.outcome {
+                Ok((query_time, _)) => self.cold_query_times.push(*query_time),
+                Err(_) => self.cold_failures += 1,
+            },
+            QueryKind::ExpiryProbe => {
+                self.expiry_probes += 1;
+                let key = (
+                    completion.resolver_index,
+                    target_label(&completion.domain, completion.record_type),
+                );
+                let Some(tracker) = self.ttls.get_mut(&key) else {
+                    return;
+                };
+                tracker.probe_pending = false;
+
+                let Ok((query_time, answer)) = &completion.outcome else {
+                    return;
+                };
+                self.expiry_query_times.push(*query_time);
+                if let DnsAnswer::Records(Resolution {
+                    min_ttl: Some(ttl), ..
+                }) = answer
+                {
+                    // After expiry a resolver has to go upstream, which resets the TTL
+                    if ttl + 1 >= tracker.full_ttl {
+                        self.expiry_refetches += 1;
+                    } else {
+                        self.expiry_stale_answers += 1;
+                    }
+                    tracker.full_ttl = tracker.full_ttl.max(*ttl);
+                    tracker.ttl = *ttl;
+                    tracker.seen_at = Instant::now();
+                }
+            }
+            QueryKind::Regular => {}
+        }
+    }
+}
+
+impl DomainResult {
+    fn new(domain: &str, record_type: RecordType) -> Self {
+        Self {
+            domain: domain.to_string(),
+            record_type,
+            total_queries: 0,
+            successful_queries: 0,
+            failed_queries: 0,
+            average_query_time: Duration::ZERO,
+            query_times: Vec::new(),
+            answer_sets: Vec::new(),
+            answer_changes: 0,
+            unexpected_answers: 0,
+            unexpected_addresses: Vec::new(),
+            nxdomain_responses: 0,
+            nodata_responses: 0,
+            min_ttl: None,
+            max_ttl: None,
+            last_answers: HashMap::new(),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        target_label(&self.domain, self.record_type)
+    }
+
+    fn record_answer(
+        &mut self,
+        resolver: &str,
+        resolution: Resolution,
+        seen_at: Duration,
+        expected: &[IpAddr],
+    ) {
+        if let Some(ttl) = resolution.min_ttl {
+            self.min_ttl = Some(self.min_ttl.map_or(ttl, |m| m.min(ttl)));
+            self.max_ttl = Some(self.max_ttl.map_or(ttl, |m| m.max(ttl)));
+        }
+
+        if !expected.is_empty() {
+            let unexpected: Vec<IpAddr> = resolution
+                .addresses
+                .iter()
+                .filter(|address| !expected.contains(address))
+                .copied()
+                .collect();
+            if !unexpected.is_empty() {
+                self.unexpected_answers += 1;
+                for address in unexpected {
+                    if !self.unexpected_addresses.contains(&address) {
+                        self.unexpected_addresses.push(address);
+                    }
+                }
+            }
+        }
+
+        // Compare per resolver so GeoDNS differences between resolvers aren't churn
+        let previous = self
+            .last_answers
+            .insert(resolver.to_string(), resolution.records.clone());
+        if previous.is_some_and(|previous| previous != resolution.records) {
+            self.answer_changes += 1;
+        }
+
+        match self.answer_sets.iter_mut().find(|set| {
+            set.records == resolution.records && set.cname_chain == resolution.cname_chain
+        }) {
+            Some(set) => {
+                set.last_seen = seen_at;
+                set.observations += 1;
+            }
+            None => self.answer_sets.push(AnswerSet {
+                addresses: resolution.addresses,
+                records: resolution.records,
+                cname_chain: resolution.cname_chain,
+                first_seen: seen_at,
+                last_seen: seen_at,
+                observations: 1,
+            }),
+        }
+    }
+}
+
+impl ResolverResult {
+    fn new(resolver: &str) -> Self {
+        Self {
+            resolver: resolver.to_string(),
+            total_queries: 0,
+            successful_queries: 0,
+            failed_queries: 0,
+            timeout_queries: 0,
+            query_times: Vec::new(),
+            failures: FailureTally::default(),
+        }
+    }
 }
 
 impl DnsStabilityTest {
@@ -44,15 +402,87 @@ impl DnsStabilityTest {
         Self {
             proxy_addr: proxy_addr.to_string(),
             domains,
+            resolvers: vec!["8.8.8.8".to_string()],
+            expected_ips: HashMap::new(),
+            record_types: HashMap::new(),
+            transport: DnsTransport::default(),
+            tls_insecure: false,
+            cache_analysis: false,
             query_interval: Duration::from_millis(query_interval_ms),
             test_duration: Duration::from_secs(test_duration_sec),
+            live: LivePanel::default(),
+            samples: SampleRecorder::default(),
         }
     }
 
-    pub async fn run(&self) -> Result<()> {
+    pub fn with_resolvers(mut self, resolvers: Vec<String>) -> Self {
+        if !resolvers.is_empty() {
+            self.resolvers = resolvers;
+        }
+        self
+    }
+
+    pub fn with_expected_ips(mut self, expected_ips: HashMap<String, Vec<IpAddr>>) -> Self {
+        self.expected_ips = expected_ips;
+        self
+    }
+
+    /// Domains missing from the map are queried for A records only.
+    pub fn with_record_types(mut self, record_types: HashMap<String, Vec<RecordType>>) -> Self {
+        self.record_types = record_types;
+        self
+    }
+
+    pub fn with_transport(mut self, transport: DnsTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    pub fn with_tls_insecure(mut self, tls_insecure: bool) -> Self {
+        self.tls_insecure = tls_insecure;
+        self
+    }
+
+    /// Interleaves cache-busting queries and TTL-expiry probes with the
+    /// regular ones to estimate how often answers come from a cache.
+    pub fn with_cache_analysis(mut self, cache_analysis: bool) -> Self {
+        self.cache_analysis = cache_analysis;
+        self
+    }
+
+    pub fn with_live(mut self, live: LivePanel) -> Self {
+        self.live = live;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: SampleRecorder) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub async fn run(&self) -> Result<DnsStabilityResult> {
+        let result = self.execute().await;
+        let _pause = self.live.finish(result.is_ok());
+        let result = result?;
+
+        if !self.samples.owns_stdout() {
+            self.print_results(&result);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn execute(&self) -> Result<DnsStabilityResult> {
         info!("Starting DNS stability test");
         info!("Proxy: {}", self.proxy_addr);
         info!("Domains: {:?}", self.domains);
+        let resolvers: Vec<String> = self
+            .resolvers
+            .iter()
+            .map(|resolver| self.transport.resolver_address(resolver))
+            .collect();
+        info!("Resolvers: {:?}", resolvers);
+        info!("Transport: {}", self.transport);
         info!(
             "Query interval: {:?}, Test duration: {:?}",
             self.query_interval, self.test_duration
@@ -65,29 +495,24 @@ impl DnsStabilityTest {
 
         let client = Socks5Client::new(proxy_addr).with_timeout(Duration::from_secs(10));
 
-        let result = self.run_dns_test(&client).await?;
-
-        self.print_results(&result);
-
-        Ok(())
+        self.run_dns_test(&client, &resolvers).await
     }
 
-    async fn run_dns_test(&self, client: &Socks5Client) -> Result<DnsStabilityResult> {
+    async fn run_dns_test(
+        &self,
+        client: &Socks5Client,
+        resolvers: &[String],
+    ) -> Result<DnsStabilityResult> {
+        self.live.start(Some(self.test_duration));
         let start_time = Instant::now();
         let end_time = start_time + self.test_duration;
 
-        let mut domain_results = std::collections::HashMap::new();
-        for domain in &self.domains {
+        let mut domain_results = HashMap::new();
+        let targets = self.query_targets();
+        for (domain, record_type) in &targets {
             domain_results.insert(
-                domain.clone(),
-                DomainResult {
-                    domain: domain.clone(),
-                    total_queries: 0,
-                    successful_queries: 0,
-                    failed_queries: 0,
-                    average_query_time: Duration::ZERO,
-                    query_times: Vec::new(),
-                },
+                target_label(domain, *record_type),
+                DomainResult::new(domain, *record_type),
             );
         }
 
@@ -96,48 +521,227 @@ impl DnsStabilityTest {
         let mut failed_queries = 0u64;
         let mut timeout_queries = 0u64;
         let mut all_query_times = Vec::new();
+        let mut failures = FailureTally::default();
+        let mut events = EventLog::new("dns_stability").with_live(self.live.clone());
+        let mut resolver_results: Vec<ResolverResult> = resolvers
+            .iter()
+            .map(|resolver| ResolverResult::new(resolver))
+            .collect();
 
-        let mut query_interval = interval(self.query_interval);
-        let mut domain_index = 0;
-
-        while Instant::now() < end_time {
-            query_interval.tick().await;
-
-            if self.domains.is_empty() {
-                break;
-            }
-
-            let domain = &self.domains[domain_index % self.domains.len()];
-            domain_index += 1;
-
-            let _query_start = Instant::now();
-            total_queries += 1;
-
-            let domain_result = domain_results.get_mut(domain).unwrap();
-            domain_result.total_queries += 1;
-
-            match self.perform_dns_query(client, domain).await {
-                Ok(query_time) => {
-                    successful_queries += 1;
-                    domain_result.successful_queries += 1;
-                    domain_result.query_times.push(query_time);
-                    all_query_times.push(query_time);
+        // Sessions are set up inside each query so a slow connect never stalls the loop
+        let sessions = Mutex::new(SessionPool::new(
+            self.transport,
+            resolvers.len(),
+            self.tls_insecure,
+        ));
+        let mut udp_queries = 0u64;
+        let mut tcp_queries = 0u64;
+        let mut consecutive_timeouts = 0u32;
+        let mut in_flight = FuturesUnordered::new();
+        let mut cache = self.cache_analysis.then(CacheAnalysis::default);
 
-                    debug!("DNS query for {} successful: {:?}", domain, query_time);
-                }
-                Err(NetworkTestError::Timeout(_)) => {
-                    timeout_queries += 1;
-                    domain_result.failed_queries += 1;
-                    warn!("DNS query for {} timed out", domain);
+        let mut query_interval = interval(self.query_interval);
+        let mut query_index = 0;
+
+        loop {
+            tokio::select! {
+                _ = query_interval.tick(), if Instant::now() < end_time && !targets.is_empty() => {
+                    // Rotate resolvers fastest so every target is asked of every resolver
+                    let resolver_index = query_index % resolvers.len();
+                    let (domain, record_type) =
+                        targets[(query_index / resolvers.len()) % targets.len()].clone();
+
+                    // A proxy that silently dropped a session only shows up as timeouts
+                    // A pool busy connecting is already being refreshed; retry next tick
+                    if consecutive_timeouts >= MAX_CONSECUTIVE_TIMEOUTS {
+                        if let Ok(mut pool) = sessions.try_lock() {
+                            info!("Re-establishing DNS sessions after repeated timeouts");
+                            pool.reset();
+                            consecutive_timeouts = 0;
+                        }
+                    }
+
+                    let mut batch = vec![(QueryKind::Regular, resolver_index, domain, record_type)];
+                    if let Some(cache) = cache.as_mut() {
+                        if query_index % CACHE_BUST_EVERY == 0 {
+                            batch.push(cache.next_cache_bust(&targets, resolvers.len()));
+                        }
+                        batch.extend(cache.due_probes(Instant::now()).into_iter().map(|probe| {
+                            (QueryKind::ExpiryProbe, probe.resolver_index, probe.domain, probe.record_type)
+                        }));
+                    }
+                    query_index += 1;
+
+                    for (kind, resolver_index, domain, record_type) in batch {
+                        in_flight.push(Self::run_query(
+                            &sessions,
+                            client,
+                            kind,
+                            resolver_index,
+                            resolvers[resolver_index].clone(),
+                            domain,
+                            record_type,
+                        ));
+                    }
                 }
-                Err(e) => {
-                    failed_queries += 1;
-                    domain_result.failed_queries += 1;
-                    warn!("DNS query for {} failed: {}", domain, e);
+                Some(completion) = in_flight.next(), if !in_flight.is_empty() => {
+                    match completion.transport {
+                        Some(DnsTransport::Udp) => udp_queries += 1,
+                        Some(_) => tcp_queries += 1,
+                        None => {}
+                    }
+                    if matches!(&completion.outcome, Err(e) if e.is_timeout()) {
+                        consecutive_timeouts += 1;
+                    } else {
+                        consecutive_timeouts = 0;
+                    }
+
+                    if completion.kind != QueryKind::Regular {
+                        debug!(
+                            "DNS {:?} query for {} via {}: {:?}",
+                            completion.kind,
+                            completion.domain,
+                            resolvers[completion.resolver_index],
+                            completion.outcome.as_ref().map(|(rtt, _)| rtt)
+                        );
+                        if let Some(cache) = cache.as_mut() {
+                            cache.record_probe(&completion);
+                        }
+                        continue;
+                    }
+
+                    let QueryCompletion {
+                        resolver_index,
+                        domain,
+                        record_type,
+                        outcome,
+                        ..
+                    } = completion;
+                    let resolver_result = &mut resolver_results[resolver_index];
+                    let domain_result = domain_results
+                        .get_mut(&target_label(&domain, record_type))
+                        .unwrap();
+
+                    let event_target = format!(
+                        "{} via {}",
+                        target_label(&domain, record_type),
+                        resolver_result.resolver
+                    );
+                    total_queries += 1;
+                    resolver_result.total_queries += 1;
+                    domain_result.total_queries += 1;
+
+                    match outcome {
+                        Ok((query_time, DnsAnswer::Records(resolution))) => {
+                            successful_queries += 1;
+                            domain_result.successful_queries += 1;
+                            domain_result.query_times.push(query_time);
+                            resolver_result.successful_queries += 1;
+                            resolver_result.query_times.push(query_time);
+                            all_query_times.push(query_time);
+                            self.live.rtt(query_time);
+                            self.record_query(
+                                &resolver_result.resolver,
+                                &domain,
+                                record_type,
+                                Some(query_time),
+                                None,
+                            );
+                            events.recovered(&event_target);
+
+                            debug!(
+                                "DNS {} query for {} via {} successful: {:?} -> {:?}",
+                                record_type, domain, resolver_result.resolver, query_time, resolution.records
+                            );
+                            if let (Some(cache), Some(ttl)) = (cache.as_mut(), resolution.min_ttl) {
+                                cache.observe(resolver_index, &domain, record_type, query_time, ttl, end_time);
+                            }
+                            domain_result.record_answer(
+                                &resolver_result.resolver,
+                                resolution,
+                                start_time.elapsed(),
+                                &self.expected_addresses(&domain, record_type),
+                            );
+                        }
+                        Ok((query_time, answer)) => {
+                            let category = match answer {
+                                DnsAnswer::Rcode(rcode) => {
+                                    if rcode == RCODE_NXDOMAIN {
+                                        domain_result.nxdomain_responses += 1;
+                                    }
+                                    FailureCategory::DnsRcode { rcode }
+                                }
+                                _ => {
+                                    domain_result.nodata_responses += 1;
+                                    FailureCategory::DnsNoData
+                                }
+                            };
+                            failed_queries += 1;
+                            domain_result.failed_queries += 1;
+                            resolver_result.failed_queries += 1;
+                            resolver_result.failures.record_category(category);
+                            failures.record_category(category);
+                            events.category(&event_target, category);
+                            self.record_query(
+                                &resolver_result.resolver,
+                                &domain,
+                                record_type,
+                                Some(query_time),
+                                Some(category.to_string()),
+                            );
+                            warn!(
+                                "DNS {} query for {} via {} returned {}",
+                                record_type, domain, resolver_result.resolver, category
+                            );
+                        }
+                        Err(e) if e.is_timeout() => {
+                            timeout_queries += 1;
+                            domain_result.failed_queries += 1;
+                            resolver_result.timeout_queries += 1;
+                            resolver_result.failures.record(&e);
+                            failures.record(&e);
+                            events.error(&event_target, &e);
+                            self.record_query(
+                                &resolver_result.resolver,
+                                &domain,
+                                record_type,
+                                None,
+                                Some(FailureCategory::from(&e).to_string()),
+                            );
+                            warn!(
+                                "DNS {} query for {} via {} timed out",
+                                record_type, domain, resolver_result.resolver
+                            );
+                        }
+                        Err(e) => {
+                            failed_queries += 1;
+                            failures.record(&e);
+                            domain_result.failed_queries += 1;
+                            resolver_result.failed_queries += 1;
+                            resolver_result.failures.record(&e);
+                            events.error(&event_target, &e);
+                            self.record_query(
+                                &resolver_result.resolver,
+                                &domain,
+                                record_type,
+                                None,
+                                Some(FailureCategory::from(&e).to_string()),
+                            );
+                            warn!(
+                                "DNS {} query for {} via {} failed: {}",
+                                record_type, domain, resolver_result.resolver, e
+                            );
+                        }
+                    }
                 }
+                else => break,
             }
         }
 
+        drop(in_flight);
+        let mut sessions = sessions.into_inner();
+        sessions.reset();
+
         for domain_result in domain_results.values_mut() {
             if !domain_result.query_times.is_empty() {
                 domain_result.average_query_time =
@@ -175,98 +779,131 @@ impl DnsStabilityTest {
             failed_queries,
             timeout_queries,
             domain_results,
+            resolver_results,
             average_query_time,
             min_query_time,
             max_query_time,
             success_rate,
+            association_times: sessions.association_times,
+            tcp_connect_times: sessions.tcp_connect_times,
+            tls_handshake_times: sessions.tls_handshake_times,
+            resumed_tls_sessions: sessions.resumed_tls_sessions,
+            udp_queries,
+            tcp_queries,
+            udp_fallback: sessions.udp_refused,
+            stray_responses: sessions.stray_responses,
+            cache,
+            failures,
+            events: events.into_events(),
         })
     }
 
-    async fn perform_dns_query(&self, client: &Socks5Client, domain: &str) -> Result<Duration> {
-        let query_start = Instant::now();
-
-        let dns_server = "8.8.8.8:53";
-
-        let query_result = timeout(
-            Duration::from_secs(5),
-            self.dns_query_via_proxy(client, dns_server, domain),
-        )
-        .await;
-
-        match query_result {
-            Ok(Ok(())) => Ok(query_start.elapsed()),
-            Ok(Err(e)) => Err(e),
-            Err(_) => Err(NetworkTestError::Timeout(format!(
-                "DNS query timeout for {domain}"
-            ))),
-        }
-    }
-
-    async fn dns_query_via_proxy(
-        &self,
+    async fn run_query(
+        sessions: &Mutex<SessionPool>,
         client: &Socks5Client,
-        dns_server: &str,
-        domain: &str,
-    ) -> Result<()> {
-        let udp_relay = client.udp_associate().await.map_err(|e| {
-            NetworkTestError::Connection(format!("Failed to create UDP association: {e}"))
-        })?;
-
-        let query_packet = self.create_dns_query_packet(domain)?;
-
-        udp_relay
-            .send_to(&query_packet, dns_server)
+        kind: QueryKind,
+        resolver_index: usize,
+        resolver: String,
+        domain: String,
+        record_type: RecordType,
+    ) -> QueryCompletion {
+        let session = sessions
+            .lock()
             .await
-            .map_err(|e| {
-                NetworkTestError::Connection(format!("Failed to send DNS query: {e}"))
-            })?;
-
-        let mut response_buffer = [0u8; 512];
-        let (bytes_read, _source_addr) =
-            udp_relay
-                .recv_from(&mut response_buffer)
+            .session_for(client, resolver_index, &resolver)
+            .await;
+        let transport = session.as_ref().ok().map(DnsSession::transport);
+        let outcome = match session {
+            Ok(session) => session
+                .query(&resolver, &domain, record_type.code(), QUERY_TIMEOUT)
                 .await
-                .map_err(|e| {
-                    NetworkTestError::Connection(format!("Failed to receive DNS response: {e}"))
-                })?;
+                .and_then(|(rtt, message)| {
+                    let answer = Self::classify_answer(&message, &domain, record_type)?;
+                    Ok((rtt, answer))
+                }),
+            Err(e) => Err(e),
+        };
 
-        if bytes_read < 12 {
-            return Err(NetworkTestError::Connection(
-                "Invalid DNS response".to_string(),
-            ));
+        QueryCompletion {
+            kind,
+            resolver_index,
+            domain,
+            record_type,
+            transport,
+            outcome,
         }
+    }
 
-        let response_code = response_buffer[3] & 0x0F;
-        if response_code != 0 {
-            return Err(NetworkTestError::Connection(format!(
-                "DNS query failed with code: {response_code}"
-            )));
+    fn query_targets(&self) -> Vec<(String, RecordType)> {
+        let mut targets = Vec::new();
+        for domain in &self.domains {
+            let mut record_types = self
+                .record_types
+                .get(domain)
+                .filter(|types| !types.is_empty())
+                .cloned()
+                .unwrap_or_else(|| vec![RecordType::A]);
+            record_types.sort();
+            record_types.dedup();
+
+            for record_type in record_types {
+                targets.push((domain.clone(), record_type));
+            }
         }
-
-        debug!("DNS query successful for domain: {}", domain);
-        Ok(())
+        targets
     }
 
-    fn create_dns_query_packet(&self, domain: &str) -> Result<Vec<u8>> {
-        let mut packet = Vec::new();
+    fn record_query(
+        &self,
+        resolver: &str,
+        domain: &str,
+        record_type: RecordType,
+        query_time: Option<Duration>,
+        failure: Option<String>,
+    ) {
+        self.samples.record(
+            resolver,
+            Sample::DnsQuery {
+                domain: domain.to_string(),
+                record_type: record_type.to_string(),
+                success: failure.is_none(),
+                query_time_ms: query_time.map(millis),
+                outcome: failure.unwrap_or_else(|| "ok".to_string()),
+            },
+        );
+    }
 
-        packet.extend_from_slice(&[
-            0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-        ]);
+    // Expected IPs are per domain; only the family matching the query applies
+    fn expected_addresses(&self, domain: &str, record_type: RecordType) -> Vec<IpAddr> {
+        let Some(expected) = self.expected_ips.get(domain) else {
+            return Vec::new();
+        };
+        expected
+            .iter()
+            .filter(|ip| match record_type {
+                RecordType::A => ip.is_ipv4(),
+                RecordType::Aaaa => ip.is_ipv6(),
+                _ => false,
+            })
+            .copied()
+            .collect()
+    }
 
-        for part in domain.split('.') {
-            if part.len() > 63 {
-                return Err(NetworkTestError::Config("Domain part too long".to_string()));
-            }
-            packet.push(part.len() as u8);
-            packet.extend_from_slice(part.as_bytes());
+    fn classify_answer(
+        message: &DnsMessage,
+        domain: &str,
+        record_type: RecordType,
+    ) -> Result<DnsAnswer> {
+        if message.rcode() != RCODE_NOERROR {
+            return Ok(DnsAnswer::Rcode(message.rcode()));
         }
-        packet.push(0);
 
-        packet.extend_from_slice(&[0x00, 0x01]);
-        packet.extend_from_slice(&[0x00, 0x01]);
+        let resolution = message.resolve(domain, record_type.code())?;
+        if resolution.records.is_empty() {
+            return Ok(DnsAnswer::NoData);
+        }
 
-        Ok(packet)
+        Ok(DnsAnswer::Records(resolution))
     }
 
     fn print_results(&self, result: &DnsStabilityResult) {
@@ -274,6 +911,7 @@ impl DnsStabilityTest {
         println!("Test Duration: {:?}", self.test_duration);
         println!("Query Interval: {:?}", self.query_interval);
         println!("Tested Domains: {}", self.domains.len());
+        println!("Query Targets: {}", result.domain_results.len());
         println!();
 
         println!("Overall Statistics:");
@@ -315,9 +953,9 @@ impl DnsStabilityTest {
         println!("  --------------------------|---------|---------|--------|----------");
 
         let mut sorted_domains: Vec<_> = result.domain_results.iter().collect();
-        sorted_domains.sort_by_key(|(domain, _)| domain.as_str());
+        sorted_domains.sort_by_key(|(label, _)| label.as_str());
 
-        for (domain, domain_result) in sorted_domains {
+        for (label, domain_result) in sorted_domains {
             let success_rate = if domain_result.total_queries > 0 {
                 domain_result.successful_queries as f64 / domain_result.total_queries as f64 * 100.0
             } else {
@@ -326,7 +964,7 @@ impl DnsStabilityTest {
 
             println!(
                 "  {:25} | {:7} | {:6.1}% | {:6} | {:7.0}ms",
-                self.truncate_domain(domain, 25),
+                self.truncate_domain(label, 25),
                 domain_result.total_queries,
                 success_rate,
                 domain_result.failed_queries,
@@ -335,12 +973,194 @@ impl DnsStabilityTest {
         }
         println!();
 
+        println!("Per-Type Results:");
+        println!("  Type  | Queries | Success | NODATA | Avg Time | p95");
+        println!("  ------|---------|---------|--------|----------|--------");
+        for record_type in self.type_metrics(result) {
+            println!(
+                "  {:5} | {:7} | {:6.1}% | {:6} | {:6.0}ms | {:4.0}ms",
+                record_type.record_type,
+                record_type.total_queries,
+                record_type.success_rate,
+                record_type.nodata_responses,
+                record_type.average_query_time.as_millis(),
+                record_type.p95_query_time.as_millis()
+            );
+        }
+        println!();
+
+        println!("Per-Resolver Results:");
+        println!(
+            "  Resolver              | Queries | Success | Timeouts | p50     | p95     | p99"
+        );
+        println!(
+            "  ----------------------|---------|---------|----------|---------|---------|--------"
+        );
+
+        for resolver in self.resolver_metrics(result) {
+            println!(
+                "  {:21} | {:7} | {:6.1}% | {:7.1}% | {:5.0}ms | {:5.0}ms | {:5.0}ms",
+                self.truncate_domain(&resolver.resolver, 21),
+                resolver.total_queries,
+                resolver.success_rate,
+                resolver.timeout_rate,
+                resolver.median_query_time.as_millis(),
+                resolver.p95_query_time.as_millis(),
+                resolver.p99_query_time.as_millis()
+            );
+        }
+        println!();
+
+        self.print_answer_validation(result);
+
+        if let Some(ref cache) = result.cache {
+            let cache = self.cache_metrics(cache);
+            println!("Cache Behavior:");
+            println!(
+                "  Cache-Busting Queries: {} ({} failed), median {:?}",
+                cache.cold_queries, cache.cold_failures, cache.cold_median_query_time
+            );
+            println!("  Repeated-Name Answers: {}", cache.warm_queries);
+            println!(
+                "  Estimated Hit Ratio: {:.1}% ({} hits)",
+                cache.estimated_hit_ratio, cache.estimated_hits
+            );
+            println!(
+                "  Hit Median: {:?}, Miss Median: {:?}, Hit Advantage: {:?}",
+                cache.hit_median_query_time,
+                cache.miss_median_query_time,
+                cache.hit_latency_advantage
+            );
+            println!(
+                "  Decremented-TTL Answers: {}, Early TTL Resets: {}",
+                cache.decremented_ttl_responses, cache.early_ttl_resets
+            );
+            println!(
+                "  TTL-Expiry Probes: {} ({} refetched, {} served past expiry), median {:?}",
+                cache.expiry_probes,
+                cache.expiry_refetches,
+                cache.expiry_stale_answers,
+                cache.expiry_median_query_time
+            );
+            println!();
+        }
+
+        println!("UDP Association:");
+        println!("  Associations Opened: {}", result.association_times.len());
+        println!(
+            "  Average Setup Time: {:?}",
+            average_duration(&result.association_times)
+        );
+        println!(
+            "  Max Setup Time: {:?}",
+            result
+                .association_times
+                .iter()
+                .max()
+                .copied()
+                .unwrap_or_default()
+        );
+        println!("  Stray Responses: {}", result.stray_responses);
+        println!();
+
+        println!("Transport:");
+        println!("  Configured: {}", self.transport);
+        if result.udp_fallback {
+            println!("  Proxy refused UDP ASSOCIATE, fell back to DNS over TCP");
+        }
+        println!("  UDP Queries: {}", result.udp_queries);
+        println!("  TCP Queries: {}", result.tcp_queries);
+        if !result.tcp_connect_times.is_empty() {
+            println!(
+                "  TCP Connections Opened: {}",
+                result.tcp_connect_times.len()
+            );
+            println!(
+                "  Average TCP Connect Time: {:?}",
+                average_duration(&result.tcp_connect_times)
+            );
+        }
+        if !result.tls_handshake_times.is_empty() {
+            println!(
+                "  TLS Handshakes: {} ({} resumed)",
+                result.tls_handshake_times.len(),
+                result.resumed_tls_sessions
+            );
+            println!(
+                "  Average TLS Handshake Time: {:?}",
+                average_duration(&result.tls_handshake_times)
+            );
+        }
+        println!();
+
+        print_failure_breakdown(&result.failures);
+
         self.print_dns_analysis(result);
 
         let dns_score = self.calculate_dns_score(result);
         println!("DNS Stability Score: {dns_score:.1}/100");
     }
 
+    fn print_answer_validation(&self, result: &DnsStabilityResult) {
+        println!("Answer Validation:");
+
+        let mut sorted_domains: Vec<_> = result.domain_results.iter().collect();
+        sorted_domains.sort_by_key(|(label, _)| label.as_str());
+
+        for (label, dr) in sorted_domains {
+            let ttl = match (dr.min_ttl, dr.max_ttl) {
+                (Some(min), Some(max)) => format!("TTL {min}-{max}s"),
+                _ => "no TTL".to_string(),
+            };
+            println!(
+                "  {}: {} answer set(s), {} change(s), {}",
+                label,
+                dr.answer_sets.len(),
+                dr.answer_changes,
+                ttl
+            );
+
+            for set in &dr.answer_sets {
+                let via = if set.cname_chain.is_empty() {
+                    String::new()
+                } else {
+                    format!(" via {}", set.cname_chain.join(" -> "))
+                };
+                println!(
+                    "    [{}]{} seen {}x ({:?} - {:?})",
+                    set.records.join(", "),
+                    via,
+                    set.observations,
+                    set.first_seen,
+                    set.last_seen
+                );
+            }
+
+            if dr.answer_changes > 0 {
+                println!("    ⚠ Answers changed between queries to the same resolver");
+            }
+            if dr.unexpected_answers > 0 {
+                let addresses: Vec<String> = dr
+                    .unexpected_addresses
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect();
+                println!(
+                    "    ✗ {} answer(s) outside expected addresses: {}",
+                    dr.unexpected_answers,
+                    addresses.join(", ")
+                );
+            }
+            if dr.nxdomain_responses > 0 || dr.nodata_responses > 0 {
+                println!(
+                    "    ✗ NXDOMAIN: {}, NODATA: {}",
+                    dr.nxdomain_responses, dr.nodata_responses
+                );
+            }
+        }
+        println!();
+    }
+
     fn print_dns_analysis(&self, result: &DnsStabilityResult) {
         println!("DNS Performance Analysis:");
 
@@ -452,7 +1272,267 @@ impl DnsStabilityTest {
 
         let consistency_score = self.calculate_domain_consistency(result) * 100.0;
 
-        (success_score * 0.4 + speed_score * 0.3 + timeout_score * 0.2 + consistency_score * 0.1).clamp(0.0, 100.0)
+        (success_score * 0.4 + speed_score * 0.3 + timeout_score * 0.2 + consistency_score * 0.1)
+            .clamp(0.0, 100.0)
+    }
+
+    fn calculate_percentile(&self, samples: &[Duration], percentile: f64) -> Duration {
+        if samples.is_empty() {
+            return Duration::ZERO;
+        }
+
+        let mut sorted_samples = samples.to_vec();
+        sorted_samples.sort();
+
+        let index = ((percentile / 100.0) * (sorted_samples.len() - 1) as f64).round() as usize;
+        sorted_samples[index.min(sorted_samples.len() - 1)]
+    }
+
+    fn resolver_metrics(&self, result: &DnsStabilityResult) -> Vec<ResolverMetrics> {
+        result
+            .resolver_results
+            .iter()
+            .map(|rr| {
+                let rate = |count: u64| {
+                    if rr.total_queries > 0 {
+                        count as f64 / rr.total_queries as f64 * 100.0
+                    } else {
+                        0.0
+                    }
+                };
+
+                ResolverMetrics {
+                    resolver: rr.resolver.clone(),
+                    total_queries: rr.total_queries,
+                    successful_queries: rr.successful_queries,
+                    failed_queries: rr.failed_queries,
+                    timeout_queries: rr.timeout_queries,
+                    success_rate: rate(rr.successful_queries),
+                    timeout_rate: rate(rr.timeout_queries),
+                    average_query_time: if rr.query_times.is_empty() {
+                        Duration::ZERO
+                    } else {
+                        rr.query_times.iter().sum::<Duration>() / rr.query_times.len() as u32
+                    },
+                    median_query_time: self.calculate_percentile(&rr.query_times, 50.0),
+                    p95_query_time: self.calculate_percentile(&rr.query_times, 95.0),
+                    p99_query_time: self.calculate_percentile(&rr.query_times, 99.0),
+                    failures: rr.failures.breakdown(),
+                }
+            })
+            .collect()
+    }
+
+    fn type_metrics(&self, result: &DnsStabilityResult) -> Vec<RecordTypeMetrics> {
+        let mut by_type: BTreeMap<RecordType, Vec<&DomainResult>> = BTreeMap::new();
+        for dr in result.domain_results.values() {
+            by_type.entry(dr.record_type).or_default().push(dr);
+        }
+
+        by_type
+            .into_iter()
+            .map(|(record_type, results)| {
+                let total_queries: u64 = results.iter().map(|dr| dr.total_queries).sum();
+                let successful_queries: u64 = results.iter().map(|dr| dr.successful_queries).sum();
+                let query_times: Vec<Duration> = results
+                    .iter()
+                    .flat_map(|dr| dr.query_times.iter().copied())
+                    .collect();
+
+                RecordTypeMetrics {
+                    record_type,
+                    domains: results.len(),
+                    total_queries,
+                    successful_queries,
+                    failed_queries: results.iter().map(|dr| dr.failed_queries).sum(),
+                    success_rate: if total_queries > 0 {
+                        successful_queries as f64 / total_queries as f64 * 100.0
+                    } else {
+                        0.0
+                    },
+                    average_query_time: average_duration(&query_times),
+                    p95_query_time: self.calculate_percentile(&query_times, 95.0),
+                    answer_changes: results.iter().map(|dr| dr.answer_changes).sum(),
+                    nxdomain_responses: results.iter().map(|dr| dr.nxdomain_responses).sum(),
+                    nodata_responses: results.iter().map(|dr| dr.nodata_responses).sum(),
+                }
+            })
+            .collect()
+    }
+
+    /// Hits are answers served with a decremented TTL, or in under half the
+    /// median time of the cache-busting queries.
+    fn cache_metrics(&self, cache: &CacheAnalysis) -> DnsCacheMetrics {
+        let cold_median = self.calculate_percentile(&cache.cold_query_times, 50.0);
+        let (hits, misses): (Vec<&WarmSample>, Vec<&WarmSample>) = cache
+            .warm_samples
+            .iter()
+            .partition(|sample| sample.decremented_ttl || sample.query_time * 2 < cold_median);
+        let hit_times: Vec<Duration> = hits.iter().map(|sample| sample.query_time).collect();
+        let miss_times: Vec<Duration> = misses.iter().map(|sample| sample.query_time).collect();
+        let hit_median = self.calculate_percentile(&hit_times, 50.0);
+
+        DnsCacheMetrics {
+            cold_queries: cache.cold_query_times.len() as u64 + cache.cold_failures,
+            cold_failures: cache.cold_failures,
+            cold_median_query_time: cold_median,
+            warm_queries: cache.warm_samples.len() as u64,
+            estimated_hits: hits.len() as u64,
+            estimated_hit_ratio: if cache.warm_samples.is_empty() {
+                0.0
+            } else {
+                hits.len() as f64 / cache.warm_samples.len() as f64 * 100.0
+            },
+            hit_median_query_time: hit_median,
+            miss_median_query_time: self.calculate_percentile(&miss_times, 50.0),
+            hit_latency_advantage: if hit_times.is_empty() {
+                Duration::ZERO
+            } else {
+                cold_median.saturating_sub(hit_median)
+            },
+            decremented_ttl_responses: cache.decremented_ttl_responses,
+            early_ttl_resets: cache.early_ttl_resets,
+            expiry_probes: cache.expiry_probes,
+            expiry_refetches: cache.expiry_refetches,
+            expiry_stale_answers: cache.expiry_stale_answers,
+            expiry_median_query_time: self.calculate_percentile(&cache.expiry_query_times, 50.0),
+        }
+    }
+
+    pub fn to_metrics(&self, result: &DnsStabilityResult) -> DnsStabilityMetrics {
+        let all_query_times: Vec<Duration> = result
+            .domain_results
+            .values()
+            .flat_map(|dr| dr.query_times.iter().copied())
+            .collect();
+        let query_time_variance = if all_query_times.is_empty() {
+            0.0
+        } else {
+            let mean = result.average_query_time.as_secs_f64() * 1000.0;
+            all_query_times
+                .iter()
+                .map(|t| (t.as_secs_f64() * 1000.0 - mean).powi(2))
+                .sum::<f64>()
+                / all_query_times.len() as f64
+        };
+
+        let per_domain_metrics = result
+            .domain_results
+            .iter()
+            .map(|(domain, dr)| {
+                (
+                    domain.clone(),
+                    DomainMetrics {
+                        domain: dr.domain.clone(),
+                        record_type: dr.record_type,
+                        total_queries: dr.total_queries,
+                        successful_queries: dr.successful_queries,
+                        failed_queries: dr.failed_queries,
+                        success_rate: if dr.total_queries > 0 {
+                            dr.successful_queries as f64 / dr.total_queries as f64 * 100.0
+                        } else {
+                            0.0
+                        },
+                        average_query_time: dr.average_query_time,
+                        min_query_time: dr.query_times.iter().min().copied().unwrap_or_default(),
+                        max_query_time: dr.query_times.iter().max().copied().unwrap_or_default(),
+                        answer_sets: dr
+                            .answer_sets
+                            .iter()
+                            .map(|set| AnswerSetMetrics {
+                                addresses: set.addresses.iter().map(|a| a.to_string()).collect(),
+                                records: set.records.clone(),
+                                cname_chain: set.cname_chain.clone(),
+                                first_seen: set.first_seen,
+                                last_seen: set.last_seen,
+                                observations: set.observations,
+                            })
+                            .collect(),
+                        answer_changes: dr.answer_changes,
+                        expected_addresses: self
+                            .expected_addresses(&dr.domain, dr.record_type)
+                            .iter()
+                            .map(|ip| ip.to_string())
+                            .collect(),
+                        unexpected_answers: dr.unexpected_answers,
+                        unexpected_addresses: dr
+                            .unexpected_addresses
+                            .iter()
+                            .map(|a| a.to_string())
+                            .collect(),
+                        nxdomain_responses: dr.nxdomain_responses,
+                        nodata_responses: dr.nodata_responses,
+                        min_ttl: dr.min_ttl,
+                        max_ttl: dr.max_ttl,
+                    },
+                )
+            })
+            .collect();
+
+        DnsStabilityMetrics {
+            test_duration: self.test_duration,
+            query_interval: self.query_interval,
+            domains_tested: self.domains.len(),
+            total_queries: result.total_queries,
+            successful_queries: result.successful_queries,
+            failed_queries: result.failed_queries,
+            timeout_queries: result.timeout_queries,
+            success_rate: result.success_rate,
+            timeout_rate: if result.total_queries > 0 {
+                result.timeout_queries as f64 / result.total_queries as f64 * 100.0
+            } else {
+                0.0
+            },
+            average_query_time: result.average_query_time,
+            min_query_time: result.min_query_time,
+            max_query_time: result.max_query_time,
+            query_time_variance,
+            domain_consistency_score: self.calculate_domain_consistency(result) * 100.0,
+            dns_score: self.calculate_dns_score(result),
+            per_domain_metrics,
+            per_resolver_metrics: self.resolver_metrics(result),
+            per_type_metrics: self.type_metrics(result),
+            cache: result.cache.as_ref().map(|cache| self.cache_metrics(cache)),
+            answer_changes: result
+                .domain_results
+                .values()
+                .map(|d| d.answer_changes)
+                .sum(),
+            unexpected_answers: result
+                .domain_results
+                .values()
+                .map(|d| d.unexpected_answers)
+                .sum(),
+            nxdomain_responses: result
+                .domain_results
+                .values()
+                .map(|d| d.nxdomain_responses)
+                .sum(),
+            nodata_responses: result
+                .domain_results
+                .values()
+                .map(|d| d.nodata_responses)
+                .sum(),
+            associations: result.association_times.len(),
+            average_association_time: average_duration(&result.association_times),
+            max_association_time: result
+                .association_times
+                .iter()
+                .max()
+                .copied()
+                .unwrap_or_default(),
+            stray_responses: result.stray_responses,
+            transport: self.transport,
+            udp_fallback: result.udp_fallback,
+            udp_queries: result.udp_queries,
+            tcp_queries: result.tcp_queries,
+            tcp_connections: result.tcp_connect_times.len(),
+            average_tcp_connect_time: average_duration(&result.tcp_connect_times),
+            tls_handshakes: result.tls_handshake_times.len(),
+            average_tls_handshake_time: average_duration(&result.tls_handshake_times),
+            resumed_tls_sessions: result.resumed_tls_sessions,
+            failures: result.failures.breakdown(),
+        }
     }
 
     fn truncate_domain(&self, domain: &str, max_len: usize) -> String {
@@ -463,3 +1543,294 @@ impl DnsStabilityTest {
         }
     }
 }
+
+/// Sessions reused across queries: one UDP association shared by every
+/// resolver, or one TCP/TLS connection per resolver.
+struct SessionPool {
+    transport: DnsTransport,
+    tls: Option<TlsConnector>,
+    udp: Option<Arc<DnsUdpSession>>,
+    streams: Vec<Option<DnsSession>>,
+    udp_refused: bool,
+    association_times: Vec<Duration>,
+    tcp_connect_times: Vec<Duration>,
+    tls_handshake_times: Vec<Duration>,
+    resumed_tls_sessions: u64,
+    stray_responses: u64,
+}
+
+impl SessionPool {
+    fn new(transport: DnsTransport, resolvers: usize, tls_insecure: bool) -> Self {
+        // One connector for the whole run so its session cache allows resumption
+        let tls = match transport {
+            DnsTransport::Dot => Some(tls::tls_connector(b"dot", tls_insecure)),
+            DnsTransport::Doh => Some(tls::tls_connector(b"http/1.1", tls_insecure)),
+            _ => None,
+        };
+
+        Self {
+            transport,
+            tls,
+            udp: None,
+            streams: vec![None; resolvers],
+            udp_refused: false,
+            association_times: Vec::new(),
+            tcp_connect_times: Vec::new(),
+            tls_handshake_times: Vec::new(),
+            resumed_tls_sessions: 0,
+            stray_responses: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        if let Some(udp) = self.udp.take() {
+            self.stray_responses += udp.stray_responses();
+        }
+        for stream in self.streams.iter_mut().filter_map(Option::take) {
+            self.stray_responses += stream.stray_responses();
+        }
+    }
+
+    async fn session_for(
+        &mut self,
+        client: &Socks5Client,
+        resolver_index: usize,
+        resolver: &str,
+    ) -> Result<DnsSession> {
+        let use_udp = match self.transport {
+            DnsTransport::Auto => !self.udp_refused,
+            DnsTransport::Udp => true,
+            _ => false,
+        };
+
+        if use_udp {
+            if let Some(stale) = self.udp.take_if(|udp| !udp.is_alive()) {
+                self.stray_responses += stale.stray_responses();
+                info!("Re-establishing UDP association");
+            }
+            if let Some(udp) = &self.udp {
+                return Ok(DnsSession::Udp(Arc::clone(udp)));
+            }
+
+            match DnsUdpSession::open(client).await {
+                Ok(opened) => {
+                    debug!(
+                        "UDP association established in {:?}",
+                        opened.association_time
+                    );
+                    self.association_times.push(opened.association_time);
+                    let opened = Arc::new(opened);
+                    self.udp = Some(Arc::clone(&opened));
+                    return Ok(DnsSession::Udp(opened));
+                }
+                Err(
+                    e @ NetworkTestError::SocksReply {
+                        phase: Phase::UdpAssociate,
+                        ..
+                    },
+                ) if self.transport == DnsTransport::Auto => {
+                    warn!("{}, falling back to DNS over TCP", e);
+                    self.udp_refused = true;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let slot = &mut self.streams[resolver_index];
+        if let Some(stale) = slot.take_if(|stream| !stream.is_alive()) {
+            self.stray_responses += stale.stray_responses();
+            info!("Reconnecting {} to {}", self.transport, resolver);
+        }
+        if let Some(stream) = slot {
+            return Ok(stream.clone());
+        }
+
+        let opened = match (&self.tls, self.transport) {
+            (Some(connector), DnsTransport::Dot) => DnsSession::Tcp(Arc::new(
+                DnsTcpSession::open_tls(client, resolver, connector).await?,
+            )),
+            (Some(connector), DnsTransport::Doh) => DnsSession::Https(Arc::new(
+                DnsHttpsSession::open(client, resolver, connector).await?,
+            )),
+            _ => DnsSession::Tcp(Arc::new(DnsTcpSession::open(client, resolver).await?)),
+        };
+
+        let connect_time = match &opened {
+            DnsSession::Tcp(session) => session.connect_time,
+            DnsSession::Https(session) => session.connect_time,
+            DnsSession::Udp(_) => Duration::ZERO,
+        };
+        self.tcp_connect_times.push(connect_time);
+        if let Some(handshake) = opened.tls_handshake() {
+            debug!(
+                "TLS handshake with {} took {:?} (resumed: {})",
+                resolver, handshake.handshake_time, handshake.resumed
+            );
+            self.tls_handshake_times.push(handshake.handshake_time);
+            if handshake.resumed {
+                self.resumed_tls_sessions += 1;
+            }
+        }
+        debug!(
+            "{} connection to {} established in {:?}",
+            opened.transport(),
+            resolver,
+            connect_time
+        );
+
+        *slot = Some(opened.clone());
+        Ok(opened)
+    }
+}
+
+fn target_label(domain: &str, record_type: RecordType) -> String {
+    format!("{domain} {record_type}")
+}
+
+fn average_duration(durations: &[Duration]) -> Duration {
+    if durations.is_empty() {
+        return Duration::ZERO;
+    }
+    durations.iter().sum::<Duration>() / durations.len() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completion(
+        kind: QueryKind,
+        resolver_index: usize,
+        outcome: Result<(Duration, DnsAnswer)>,
+    ) -> QueryCompletion {
+        QueryCompletion {
+            kind,
+            resolver_index,
+            domain: "example.com".to_string(),
+            record_type: RecordType::A,
+            transport: Some(DnsTransport::Udp),
+            outcome,
+        }
+    }
+
+    fn answer_with_ttl(ttl: u32) -> Result<(Duration, DnsAnswer)> {
+        Ok((
+            Duration::from_millis(5),
+            DnsAnswer::Records(Resolution {
+                cname_chain: Vec::new(),
+                addresses: Vec::new(),
+                records: Vec::new(),
+                min_ttl: Some(ttl),
+            }),
+        ))
+    }
+
+    #[test]
+    fn test_cache_observe_ttl_sequence() {
+        let mut cache = CacheAnalysis::default();
+        let end_time = Instant::now() + Duration::from_secs(3600);
+        let ms = Duration::from_millis;
+
+        for ttl in [300, 298, 297, 300, 300] {
+            cache.observe(0, "example.com", RecordType::A, ms(5), ttl, end_time);
+        }
+
+        // The first answer only sets the baseline
+        assert_eq!(cache.warm_samples.len(), 4);
+        let decremented: Vec<bool> = cache
+            .warm_samples
+            .iter()
+            .map(|sample| sample.decremented_ttl)
+            .collect();
+        assert_eq!(decremented, vec![true, true, false, false]);
+        assert_eq!(cache.decremented_ttl_responses, 2);
+        // 297 jumping back to 300 long before expiry
+        assert_eq!(cache.early_ttl_resets, 1);
+
+        // One probe per name, scheduled after its TTL runs out
+        assert_eq!(cache.pending_probes.len(), 1);
+        assert!(cache.due_probes(Instant::now()).is_empty());
+        let due = cache.due_probes(Instant::now() + Duration::from_secs(302));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].resolver_index, 0);
+
+        // Names expiring after the test ends aren't probed
+        cache.observe(1, "example.com", RecordType::A, ms(5), 300, Instant::now());
+        assert!(cache.pending_probes.is_empty());
+    }
+
+    #[test]
+    fn test_cache_expiry_probes() {
+        let mut cache = CacheAnalysis::default();
+        let end_time = Instant::now() + Duration::from_secs(3600);
+        for resolver_index in 0..3 {
+            cache.observe(
+                resolver_index,
+                "example.com",
+                RecordType::A,
+                Duration::from_millis(5),
+                300,
+                end_time,
+            );
+        }
+
+        // A full TTL means the resolver went upstream, a low one is stale
+        cache.record_probe(&completion(QueryKind::ExpiryProbe, 0, answer_with_ttl(299)));
+        cache.record_probe(&completion(QueryKind::ExpiryProbe, 1, answer_with_ttl(120)));
+        cache.record_probe(&completion(
+            QueryKind::ExpiryProbe,
+            2,
+            Err(NetworkTestError::timeout(Phase::Exchange, "timeout")),
+        ));
+        // Unknown names are counted but not judged
+        cache.record_probe(&completion(QueryKind::ExpiryProbe, 7, answer_with_ttl(300)));
+
+        assert_eq!(cache.expiry_probes, 4);
+        assert_eq!(cache.expiry_refetches, 1);
+        assert_eq!(cache.expiry_stale_answers, 1);
+        assert_eq!(cache.expiry_query_times.len(), 2);
+        assert!(cache.ttls.values().all(|tracker| !tracker.probe_pending));
+
+        cache.record_probe(&completion(QueryKind::CacheBust, 0, answer_with_ttl(60)));
+        cache.record_probe(&completion(
+            QueryKind::CacheBust,
+            0,
+            Err(NetworkTestError::timeout(Phase::Exchange, "timeout")),
+        ));
+        assert_eq!(cache.cold_query_times, vec![Duration::from_millis(5)]);
+        assert_eq!(cache.cold_failures, 1);
+    }
+
+    #[test]
+    fn test_cache_metrics_partition() {
+        let test = DnsStabilityTest::new("127.0.0.1:1080", Vec::new(), 1000, 1);
+        let ms = Duration::from_millis;
+        let warm = |query_time, decremented_ttl| WarmSample {
+            query_time,
+            decremented_ttl,
+        };
+        let cache = CacheAnalysis {
+            cold_query_times: vec![ms(40), ms(50), ms(60)],
+            cold_failures: 1,
+            // Hits: a decremented TTL, or faster than half the cold median
+            warm_samples: vec![
+                warm(ms(45), true),
+                warm(ms(10), false),
+                warm(ms(30), false),
+                warm(ms(40), false),
+            ],
+            decremented_ttl_responses: 1,
+            ..CacheAnalysis::default()
+        };
+
+        let metrics = test.cache_metrics(&cache);
+        assert_eq!(metrics.cold_queries, 4);
+        assert_eq!(metrics.cold_median_query_time, ms(50));
+        assert_eq!(metrics.warm_queries, 4);
+        assert_eq!(metrics.estimated_hits, 2);
+        assert!((metrics.estimated_hit_ratio - 50.0).abs() < 1e-9);
+        assert_eq!(metrics.hit_median_query_time, ms(45));
+        assert_eq!(metrics.miss_median_query_time, ms(40));
+        assert_eq!(metrics.hit_latency_advantage, ms(5));
+    }
+}
@@ -0,0 +1,562 @@
+use super::{print_failure_breakdown, read_http_response};
+use crate::live::LivePanel;
+use crate::metrics::{
+    BandwidthMetrics, EventClock, EventLog, FailureTally, FairnessMetrics, StreamFairnessMetrics,
+    TestEvent,
+};
+use crate::samples::{millis, Sample, SampleRecorder};
+use crate::{NetworkTestError, Phase, Result, Socks5Client};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::time::{interval, sleep, timeout};
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Clone)]
+pub struct FairnessTest {
+    proxy_addr: String,
+    target_addr: String,
+    streams: usize,
+    chunk_size: usize,
+    test_duration: Duration,
+    sample_interval: Duration,
+    starvation_threshold: f64,
+    credentials: Vec<(String, String)>,
+    live: LivePanel,
+    samples: SampleRecorder,
+}
+
+#[derive(Debug, Clone)]
+pub struct FairnessResult {
+    pub test_duration: Duration,
+    pub streams: Vec<StreamResult>,
+    pub window_jains_indexes: Vec<f64>,
+    pub events: Vec<TestEvent>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamResult {
+    pub stream_id: usize,
+    pub username: Option<String>,
+    pub bytes_received: u64,
+    pub window_throughputs: Vec<f64>,
+    pub starvation_events: u64,
+    pub reconnections: u32,
+    pub failures: FailureTally,
+}
+
+#[derive(Debug)]
+struct StreamCounters {
+    bytes_received: AtomicU64,
+    reconnections: AtomicU32,
+}
+
+impl StreamResult {
+    pub fn average_throughput(&self, test_duration: Duration) -> f64 {
+        if test_duration.is_zero() {
+            return 0.0;
+        }
+        self.bytes_received as f64 / test_duration.as_secs_f64()
+    }
+}
+
+impl FairnessResult {
+    pub fn failures(&self) -> FailureTally {
+        let mut failures = FailureTally::default();
+        for stream in &self.streams {
+            failures.merge(&stream.failures);
+        }
+        failures
+    }
+}
+
+/// Renders the worst-window index, which is missing when no window completed.
+pub fn format_window_index(index: Option<f64>) -> String {
+    index
+        .map(|index| format!("{index:.3}"))
+        .unwrap_or_else(|| "n/a".to_string())
+}
+
+impl FairnessTest {
+    pub fn new(
+        proxy_addr: &str,
+        target_addr: &str,
+        streams: usize,
+        chunk_size: usize,
+        test_duration_sec: u64,
+    ) -> Self {
+        Self {
+            proxy_addr: proxy_addr.to_string(),
+            target_addr: target_addr.to_string(),
+            streams: streams.max(1),
+            chunk_size: chunk_size.max(1),
+            test_duration: Duration::from_secs(test_duration_sec),
+            sample_interval: Duration::from_secs(1),
+            starvation_threshold: 0.1,
+            credentials: Vec::new(),
+            live: LivePanel::default(),
+            samples: SampleRecorder::default(),
+        }
+    }
+
+    pub fn with_credentials(mut self, credentials: Vec<(String, String)>) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    pub fn with_sample_interval(mut self, sample_interval_ms: u64) -> Self {
+        self.sample_interval = Duration::from_millis(sample_interval_ms.max(1));
+        self
+    }
+
+    pub fn with_starvation_threshold(mut self, fraction_of_fair_share: f64) -> Self {
+        self.starvation_threshold = fraction_of_fair_share.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_live(mut self, live: LivePanel) -> Self {
+        self.live = live;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: SampleRecorder) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub async fn run(&self) -> Result<FairnessResult> {
+        let result = self.execute().await;
+        let _pause = self.live.finish(result.is_ok());
+        let result = result?;
+
+        if !self.samples.owns_stdout() {
+            self.print_results(&result);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn execute(&self) -> Result<FairnessResult> {
+        info!("Starting multi-stream fairness test");
+        info!("Proxy: {}, Target: {}", self.proxy_addr, self.target_addr);
+        info!(
+            "Streams: {}, Chunk size: {} bytes, Test duration: {:?}, Credentials: {}",
+            self.streams,
+            self.chunk_size,
+            self.test_duration,
+            self.credentials.len()
+        );
+
+        let proxy_addr = self
+            .proxy_addr
+            .parse()
+            .map_err(|e| NetworkTestError::Config(format!("Invalid proxy address: {e}")))?;
+
+        let base_client = Socks5Client::new(proxy_addr).with_timeout(Duration::from_secs(10));
+
+        let start_time = Instant::now();
+        let deadline = start_time + self.test_duration;
+        let clock = EventClock::start();
+        self.live.start(Some(self.test_duration));
+
+        let mut counters = Vec::with_capacity(self.streams);
+        let mut usernames = Vec::with_capacity(self.streams);
+        let mut tasks = Vec::with_capacity(self.streams);
+
+        for stream_id in 0..self.streams {
+            let (client, username) = match self.credentials_for(stream_id) {
+                Some((username, password)) => (
+                    base_client
+                        .clone()
+                        .with_auth(username.clone(), password.clone()),
+                    Some(username.clone()),
+                ),
+                None => (base_client.clone(), None),
+            };
+
+            let stream_counters = Arc::new(StreamCounters {
+                bytes_received: AtomicU64::new(0),
+                reconnections: AtomicU32::new(0),
+            });
+            counters.push(Arc::clone(&stream_counters));
+            usernames.push(username);
+
+            let target_addr = self.target_addr.clone();
+            let chunk_size = self.chunk_size;
+            let events = EventLog::with_clock("fairness", clock).with_live(self.live.clone());
+            tasks.push(tokio::spawn(async move {
+                Self::run_stream(
+                    stream_id,
+                    &client,
+                    &target_addr,
+                    chunk_size,
+                    deadline,
+                    &stream_counters,
+                    events,
+                )
+                .await
+            }));
+        }
+
+        let mut streams: Vec<StreamResult> = usernames
+            .into_iter()
+            .enumerate()
+            .map(|(stream_id, username)| StreamResult {
+                stream_id,
+                username,
+                bytes_received: 0,
+                window_throughputs: Vec::new(),
+                starvation_events: 0,
+                reconnections: 0,
+                failures: FailureTally::default(),
+            })
+            .collect();
+        let mut window_jains_indexes = Vec::new();
+        let mut last_totals = vec![0u64; self.streams];
+
+        let mut sample_interval = interval(self.sample_interval);
+        sample_interval.tick().await;
+        let mut last_sample = Instant::now();
+
+        while Instant::now() < deadline {
+            sample_interval.tick().await;
+            let window = last_sample.elapsed();
+            last_sample = Instant::now();
+
+            let throughputs: Vec<f64> = counters
+                .iter()
+                .zip(last_totals.iter_mut())
+                .enumerate()
+                .map(|(stream_id, (c, last))| {
+                    let total = c.bytes_received.load(Ordering::Relaxed);
+                    let delta = total - *last;
+                    self.live.bytes(delta);
+                    *last = total;
+                    let throughput = delta as f64 / window.as_secs_f64();
+                    self.samples.record(
+                        &format!("stream {}", stream_id),
+                        Sample::Speed {
+                            direction: "download",
+                            bytes: delta,
+                            duration_ms: millis(window),
+                            bytes_per_second: throughput,
+                        },
+                    );
+                    throughput
+                })
+                .collect();
+
+            let fair_share = throughputs.iter().sum::<f64>() / throughputs.len() as f64;
+            if fair_share <= 0.0 {
+                debug!("No traffic in sampling window, skipping");
+                continue;
+            }
+
+            window_jains_indexes.push(Self::jains_index(&throughputs));
+
+            for (stream, &throughput) in streams.iter_mut().zip(throughputs.iter()) {
+                stream.window_throughputs.push(throughput);
+                if throughput < fair_share * self.starvation_threshold {
+                    stream.starvation_events += 1;
+                    warn!(
+                        "Stream {} starved: {:.2} KB/s against fair share {:.2} KB/s",
+                        stream.stream_id,
+                        throughput / 1024.0,
+                        fair_share / 1024.0
+                    );
+                }
+            }
+        }
+
+        let mut events = EventLog::with_clock("fairness", clock);
+        for (task, stream) in tasks.into_iter().zip(streams.iter_mut()) {
+            match task.await {
+                Ok((failures, stream_events)) => {
+                    stream.failures = failures;
+                    events.merge(stream_events);
+                }
+                Err(e) => warn!("Fairness stream task failed: {}", e),
+            }
+        }
+
+        for (stream, c) in streams.iter_mut().zip(counters.iter()) {
+            stream.bytes_received = c.bytes_received.load(Ordering::Relaxed);
+            stream.reconnections = c.reconnections.load(Ordering::Relaxed);
+        }
+
+        Ok(FairnessResult {
+            test_duration: start_time.elapsed(),
+            streams,
+            window_jains_indexes,
+            events: events.into_events(),
+        })
+    }
+
+    fn credentials_for(&self, stream_id: usize) -> Option<&(String, String)> {
+        if self.credentials.is_empty() {
+            None
+        } else {
+            self.credentials.get(stream_id % self.credentials.len())
+        }
+    }
+
+    async fn run_stream(
+        stream_id: usize,
+        client: &Socks5Client,
+        target_addr: &str,
+        chunk_size: usize,
+        deadline: Instant,
+        counters: &StreamCounters,
+        mut events: EventLog,
+    ) -> (FailureTally, EventLog) {
+        let event_target = format!("stream {stream_id}");
+        let host = target_addr
+            .rsplit_once(':')
+            .map(|(h, _)| h)
+            .unwrap_or(target_addr);
+        let request = format!(
+            "GET /stream-bytes/{chunk_size} HTTP/1.1\r\nHost: {host}\r\nConnection: keep-alive\r\nUser-Agent: NetworkStabilityTest/1.0\r\n\r\n"
+        );
+        let mut stream: Option<TcpStream> = None;
+        let mut failures = FailureTally::default();
+
+        while Instant::now() < deadline {
+            if stream.is_none() {
+                match client.connect(target_addr).await {
+                    Ok(s) => stream = Some(s),
+                    Err(e) => {
+                        failures.record(&e);
+                        events.error(&event_target, &e);
+                        warn!("Stream {} failed to connect: {}", stream_id, e);
+                        counters.reconnections.fetch_add(1, Ordering::Relaxed);
+                        sleep(Duration::from_millis(500)).await;
+                        continue;
+                    }
+                }
+            }
+            let Some(tcp_stream) = stream.as_mut() else {
+                continue;
+            };
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let transfer = async {
+                tcp_stream
+                    .write_all(request.as_bytes())
+                    .await
+                    .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+                read_http_response(tcp_stream, &counters.bytes_received).await
+            };
+
+            let outcome = timeout(remaining + Duration::from_secs(1), transfer).await;
+            match outcome {
+                Ok(Ok(_)) => {
+                    events.live().success();
+                    events.recovered(&event_target);
+                }
+                Ok(Err(e)) => {
+                    failures.record(&e);
+                    events.error(&event_target, &e);
+                    debug!("Stream {} transfer failed, reconnecting: {}", stream_id, e);
+                    counters.reconnections.fetch_add(1, Ordering::Relaxed);
+                    stream = None;
+                }
+                Err(_) => break,
+            }
+        }
+
+        (failures, events)
+    }
+
+    pub fn jains_index(throughputs: &[f64]) -> f64 {
+        if throughputs.is_empty() {
+            return 0.0;
+        }
+
+        let sum: f64 = throughputs.iter().sum();
+        let sum_of_squares: f64 = throughputs.iter().map(|x| x * x).sum();
+
+        if sum_of_squares == 0.0 {
+            return 0.0;
+        }
+
+        (sum * sum) / (throughputs.len() as f64 * sum_of_squares)
+    }
+
+    pub fn to_metrics(&self, result: &FairnessResult) -> BandwidthMetrics {
+        let total_bytes: u64 = result.streams.iter().map(|s| s.bytes_received).sum();
+        let averages: Vec<f64> = result
+            .streams
+            .iter()
+            .map(|s| s.average_throughput(result.test_duration))
+            .collect();
+        let jains_index = Self::jains_index(&averages);
+        let starvation_events: u64 = result.streams.iter().map(|s| s.starvation_events).sum();
+        let reconnections: u32 = result.streams.iter().map(|s| s.reconnections).sum();
+
+        let per_stream: Vec<StreamFairnessMetrics> = result
+            .streams
+            .iter()
+            .zip(averages.iter())
+            .map(|(s, &average_throughput)| StreamFairnessMetrics {
+                stream_id: s.stream_id,
+                username: s.username.clone(),
+                bytes_received: s.bytes_received,
+                average_throughput,
+                min_throughput: s
+                    .window_throughputs
+                    .iter()
+                    .copied()
+                    .fold(f64::INFINITY, f64::min)
+                    .min(average_throughput),
+                max_throughput: s.window_throughputs.iter().copied().fold(0.0f64, f64::max),
+                share_of_total: if total_bytes > 0 {
+                    s.bytes_received as f64 / total_bytes as f64 * 100.0
+                } else {
+                    0.0
+                },
+                starvation_events: s.starvation_events,
+                reconnections: s.reconnections,
+            })
+            .collect();
+
+        let aggregate_speed = averages.iter().sum::<f64>();
+        let connection_stability = if reconnections == 0 {
+            1.0
+        } else {
+            1.0 / (1.0 + reconnections as f64 * 0.2)
+        };
+
+        BandwidthMetrics {
+            test_duration: result.test_duration,
+            chunk_size: self.chunk_size,
+            total_bytes_sent: 0,
+            total_bytes_received: total_bytes,
+            average_upload_speed: 0.0,
+            average_download_speed: aggregate_speed,
+            max_upload_speed: 0.0,
+            max_download_speed: averages.iter().copied().fold(0.0f64, f64::max),
+            min_upload_speed: 0.0,
+            min_download_speed: averages.iter().copied().fold(f64::INFINITY, f64::min),
+            speed_consistency_score: jains_index * 100.0,
+            connection_interruptions: reconnections,
+            data_integrity_errors: 0,
+            bandwidth_score: (jains_index * connection_stability * 100.0).clamp(0.0, 100.0),
+            fairness: Some(FairnessMetrics {
+                streams: result.streams.len(),
+                sample_interval: self.sample_interval,
+                starvation_threshold: self.starvation_threshold,
+                jains_index,
+                min_window_jains_index: result
+                    .window_jains_indexes
+                    .iter()
+                    .copied()
+                    .reduce(f64::min),
+                starvation_events,
+                per_stream,
+            }),
+            failures: result.failures().breakdown(),
+        }
+    }
+
+    fn print_results(&self, result: &FairnessResult) {
+        let metrics = self.to_metrics(result);
+        let Some(ref fairness) = metrics.fairness else {
+            return;
+        };
+
+        println!("\n=== Multi-Stream Fairness Test Results ===");
+        println!("Test Duration: {:?}", result.test_duration);
+        println!("Streams: {}", self.streams);
+        println!("Credentials: {}", self.credentials.len());
+        println!("Sample Interval: {:?}", self.sample_interval);
+        println!();
+
+        println!("Aggregate Throughput:");
+        println!(
+            "  Total Received: {} ({:.2} MB)",
+            metrics.total_bytes_received,
+            metrics.total_bytes_received as f64 / 1_048_576.0
+        );
+        println!(
+            "  Aggregate Speed: {:.2} KB/s ({:.2} Mbps)",
+            metrics.average_download_speed / 1024.0,
+            (metrics.average_download_speed * 8.0) / 1_000_000.0
+        );
+        println!();
+
+        println!("Per-Stream Results:");
+        println!(
+            "  Stream | User         | Avg KB/s   | Min KB/s   | Share  | Starved | Reconnects"
+        );
+        println!(
+            "  -------|--------------|------------|------------|--------|---------|-----------"
+        );
+
+        for stream in &fairness.per_stream {
+            println!(
+                "  {:6} | {:12} | {:10.2} | {:10.2} | {:5.1}% | {:7} | {:10}",
+                stream.stream_id,
+                stream.username.as_deref().unwrap_or("-"),
+                stream.average_throughput / 1024.0,
+                stream.min_throughput / 1024.0,
+                stream.share_of_total,
+                stream.starvation_events,
+                stream.reconnections
+            );
+        }
+        println!();
+
+        print_failure_breakdown(&result.failures());
+
+        println!("Fairness Analysis:");
+        println!("  Jain's Fairness Index: {:.3}", fairness.jains_index);
+        println!(
+            "  Worst Window Fairness Index: {}",
+            format_window_index(fairness.min_window_jains_index)
+        );
+        println!(
+            "  Starvation Events: {} (below {:.0}% of fair share)",
+            fairness.starvation_events,
+            fairness.starvation_threshold * 100.0
+        );
+
+        if fairness.jains_index >= 0.95 {
+            println!("  ✓ Bandwidth Sharing: Fair");
+        } else if fairness.jains_index >= 0.8 {
+            println!("  ⚠ Bandwidth Sharing: Moderately Unfair");
+        } else {
+            println!("  ✗ Bandwidth Sharing: Unfair");
+        }
+        println!();
+
+        println!("Fairness Score: {:.1}/100", metrics.bandwidth_score);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jains_index() {
+        assert!((FairnessTest::jains_index(&[10.0, 10.0, 10.0, 10.0]) - 1.0).abs() < 1e-9);
+        assert!((FairnessTest::jains_index(&[10.0, 0.0, 0.0, 0.0]) - 0.25).abs() < 1e-9);
+        assert_eq!(FairnessTest::jains_index(&[]), 0.0);
+    }
+
+    #[test]
+    fn test_min_window_index_without_windows() {
+        let test = FairnessTest::new("127.0.0.1:1080", "127.0.0.1:8002", 2, 1024, 1);
+        let result = FairnessResult {
+            test_duration: Duration::from_millis(500),
+            streams: Vec::new(),
+            window_jains_indexes: Vec::new(),
+            events: Vec::new(),
+        };
+        let fairness = test.to_metrics(&result).fairness.unwrap();
+        assert_eq!(fairness.min_window_jains_index, None);
+        assert_eq!(format_window_index(fairness.min_window_jains_index), "n/a");
+    }
+}
@@ -0,0 +1,579 @@
+use super::{print_failure_breakdown, read_http_response};
+use crate::live::LivePanel;
+use crate::metrics::{
+    EventLog, FailureCategory, FailureTally, LatencyUnderLoadMetrics, LoadedLatencyMetrics,
+    TestEvent,
+};
+use crate::samples::{millis, Sample, SampleRecorder};
+use crate::{NetworkTestError, Phase, Result, Socks5Client};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::{interval, timeout};
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Clone)]
+pub struct LatencyUnderLoadTest {
+    proxy_addr: String,
+    load_target: String,
+    probe_target: String,
+    load_streams: usize,
+    chunk_size: usize,
+    phase_duration: Duration,
+    probe_interval: Duration,
+    live: LivePanel,
+    samples: SampleRecorder,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadPhase {
+    Idle,
+    Upload,
+    Download,
+    Duplex,
+}
+
+#[derive(Debug, Clone)]
+pub struct LatencyUnderLoadResult {
+    pub idle: PhaseResult,
+    pub upload: PhaseResult,
+    pub download: PhaseResult,
+    pub duplex: PhaseResult,
+    pub events: Vec<TestEvent>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PhaseResult {
+    pub phase: LoadPhase,
+    pub duration: Duration,
+    pub rtt_samples: Vec<Duration>,
+    pub lost_probes: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub failures: FailureTally,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StreamDirection {
+    Upload,
+    Download,
+}
+
+impl LoadPhase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LoadPhase::Idle => "Idle",
+            LoadPhase::Upload => "Upload",
+            LoadPhase::Download => "Download",
+            LoadPhase::Duplex => "Duplex",
+        }
+    }
+}
+
+impl LatencyUnderLoadResult {
+    pub fn failures(&self) -> FailureTally {
+        let mut failures = FailureTally::default();
+        for phase in [&self.idle, &self.upload, &self.download, &self.duplex] {
+            failures.merge(&phase.failures);
+        }
+        failures
+    }
+}
+
+impl PhaseResult {
+    fn new(phase: LoadPhase, duration: Duration) -> Self {
+        Self {
+            phase,
+            duration,
+            rtt_samples: Vec::new(),
+            lost_probes: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            failures: FailureTally::default(),
+        }
+    }
+
+    pub fn throughput(&self) -> f64 {
+        if self.duration.is_zero() {
+            return 0.0;
+        }
+        (self.bytes_sent + self.bytes_received) as f64 / self.duration.as_secs_f64()
+    }
+}
+
+impl LatencyUnderLoadTest {
+    pub fn new(
+        proxy_addr: &str,
+        load_target: &str,
+        probe_target: &str,
+        load_streams: usize,
+        phase_duration_sec: u64,
+    ) -> Self {
+        Self {
+            proxy_addr: proxy_addr.to_string(),
+            load_target: load_target.to_string(),
+            probe_target: probe_target.to_string(),
+            load_streams: load_streams.max(1),
+            chunk_size: 256 * 1024,
+            phase_duration: Duration::from_secs(phase_duration_sec),
+            probe_interval: Duration::from_millis(100),
+            live: LivePanel::default(),
+            samples: SampleRecorder::default(),
+        }
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    pub fn with_probe_interval(mut self, probe_interval_ms: u64) -> Self {
+        self.probe_interval = Duration::from_millis(probe_interval_ms.max(1));
+        self
+    }
+
+    pub fn with_live(mut self, live: LivePanel) -> Self {
+        self.live = live;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: SampleRecorder) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub async fn run(&self) -> Result<LatencyUnderLoadResult> {
+        let result = self.execute().await;
+        let _pause = self.live.finish(result.is_ok());
+        let result = result?;
+
+        if !self.samples.owns_stdout() {
+            self.print_results(&result);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn execute(&self) -> Result<LatencyUnderLoadResult> {
+        info!("Starting latency under load test");
+        info!(
+            "Proxy: {}, Load target: {}, Probe target: {}",
+            self.proxy_addr, self.load_target, self.probe_target
+        );
+        info!(
+            "Load streams: {}, Phase duration: {:?}, Probe interval: {:?}",
+            self.load_streams, self.phase_duration, self.probe_interval
+        );
+
+        let proxy_addr = self
+            .proxy_addr
+            .parse()
+            .map_err(|e| NetworkTestError::Config(format!("Invalid proxy address: {e}")))?;
+
+        let client = Socks5Client::new(proxy_addr).with_timeout(Duration::from_secs(10));
+
+        let probe_stream = client.connect(&self.probe_target).await?;
+        let mut probe = BufReader::new(probe_stream);
+        let mut events = EventLog::new("latency_under_load").with_live(self.live.clone());
+        self.live.start(Some(self.phase_duration * 4));
+
+        let idle = self
+            .run_phase(&client, &mut probe, &mut events, LoadPhase::Idle)
+            .await;
+        let upload = self
+            .run_phase(&client, &mut probe, &mut events, LoadPhase::Upload)
+            .await;
+        let download = self
+            .run_phase(&client, &mut probe, &mut events, LoadPhase::Download)
+            .await;
+        let duplex = self
+            .run_phase(&client, &mut probe, &mut events, LoadPhase::Duplex)
+            .await;
+
+        if idle.rtt_samples.is_empty() {
+            return Err(NetworkTestError::protocol(
+                Phase::Exchange,
+                "No idle RTT samples collected on the probe tunnel",
+            ));
+        }
+
+        Ok(LatencyUnderLoadResult {
+            idle,
+            upload,
+            download,
+            duplex,
+            events: events.into_events(),
+        })
+    }
+
+    async fn run_phase(
+        &self,
+        client: &Socks5Client,
+        probe: &mut BufReader<TcpStream>,
+        events: &mut EventLog,
+        phase: LoadPhase,
+    ) -> PhaseResult {
+        info!("Running {} phase", phase.name());
+
+        let directions = self.stream_directions(phase);
+        let bytes_sent = Arc::new(AtomicU64::new(0));
+        let bytes_received = Arc::new(AtomicU64::new(0));
+        let phase_start = Instant::now();
+        let deadline = phase_start + self.phase_duration;
+
+        let mut load_tasks = Vec::with_capacity(directions.len());
+        for direction in directions {
+            let client = client.clone();
+            let target = self.load_target.clone();
+            let chunk_size = self.chunk_size;
+            let bytes_sent = Arc::clone(&bytes_sent);
+            let bytes_received = Arc::clone(&bytes_received);
+
+            load_tasks.push(tokio::spawn(async move {
+                Self::run_load_stream(
+                    &client,
+                    &target,
+                    direction,
+                    chunk_size,
+                    deadline,
+                    &bytes_sent,
+                    &bytes_received,
+                )
+                .await
+            }));
+        }
+
+        let mut result = PhaseResult::new(phase, self.phase_duration);
+        let mut probe_interval = interval(self.probe_interval);
+        let mut sequence = 0u64;
+        let mut reported_bytes = 0u64;
+
+        while Instant::now() < deadline {
+            probe_interval.tick().await;
+            sequence += 1;
+
+            let transferred =
+                bytes_sent.load(Ordering::Relaxed) + bytes_received.load(Ordering::Relaxed);
+            self.live.bytes(transferred - reported_bytes);
+            reported_bytes = transferred;
+
+            let probe_result = timeout(Duration::from_secs(2), self.send_probe(probe)).await;
+            match probe_result {
+                Ok(Ok(rtt)) => {
+                    result.rtt_samples.push(rtt);
+                    self.live.rtt(rtt);
+                    self.record_probe(phase, Some(rtt), None);
+                    events.recovered(&self.probe_target);
+                    debug!("{} probe {} RTT: {:?}", phase.name(), sequence, rtt);
+                }
+                Ok(Err(e)) => {
+                    result.lost_probes += 1;
+                    result.failures.record(&e);
+                    events.error(&self.probe_target, &e);
+                    self.record_probe(phase, None, Some(FailureCategory::from(&e)));
+                    warn!("{} probe {} failed: {}", phase.name(), sequence, e);
+                    if let Err(e) = self.reopen_probe(client, probe).await {
+                        result.failures.record(&e);
+                        events.error(&self.probe_target, &e);
+                        warn!("Failed to reopen probe tunnel: {}", e);
+                    }
+                }
+                Err(_) => {
+                    result.lost_probes += 1;
+                    let category = FailureCategory::Timeout {
+                        phase: Phase::Exchange,
+                    };
+                    result.failures.record_category(category);
+                    events.category(&self.probe_target, category);
+                    self.record_probe(phase, None, Some(category));
+                    warn!("{} probe {} timed out", phase.name(), sequence);
+                    // A late reply would desynchronize the line protocol
+                    if let Err(e) = self.reopen_probe(client, probe).await {
+                        result.failures.record(&e);
+                        events.error(&self.probe_target, &e);
+                        warn!("Failed to reopen probe tunnel: {}", e);
+                    }
+                }
+            }
+        }
+
+        for task in load_tasks {
+            match task.await {
+                Ok(failures) => result.failures.merge(&failures),
+                Err(e) => warn!("Load stream task failed: {}", e),
+            }
+        }
+
+        result.duration = phase_start.elapsed();
+        result.bytes_sent = bytes_sent.load(Ordering::Relaxed);
+        result.bytes_received = bytes_received.load(Ordering::Relaxed);
+        result
+    }
+
+    fn stream_directions(&self, phase: LoadPhase) -> Vec<StreamDirection> {
+        match phase {
+            LoadPhase::Idle => Vec::new(),
+            LoadPhase::Upload => vec![StreamDirection::Upload; self.load_streams],
+            LoadPhase::Download => vec![StreamDirection::Download; self.load_streams],
+            LoadPhase::Duplex => {
+                let uploads = self.load_streams.div_ceil(2);
+                let downloads = self.load_streams.saturating_sub(uploads).max(1);
+                let mut directions = vec![StreamDirection::Upload; uploads];
+                directions.extend(vec![StreamDirection::Download; downloads]);
+                directions
+            }
+        }
+    }
+
+    async fn send_probe(&self, probe: &mut BufReader<TcpStream>) -> Result<Duration> {
+        let probe_start = Instant::now();
+        probe
+            .get_mut()
+            .write_all(b"PING\n")
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+
+        let mut line = String::new();
+        let n = probe
+            .read_line(&mut line)
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+        if n == 0 {
+            return Err(NetworkTestError::protocol(
+                Phase::Exchange,
+                "Probe tunnel closed by peer",
+            ));
+        }
+
+        if line.trim() != "PONG" {
+            return Err(NetworkTestError::protocol(
+                Phase::Exchange,
+                format!("Expected PONG, got: {}", line.trim()),
+            ));
+        }
+
+        Ok(probe_start.elapsed())
+    }
+
+    fn record_probe(
+        &self,
+        phase: LoadPhase,
+        rtt: Option<Duration>,
+        failure: Option<FailureCategory>,
+    ) {
+        self.samples.record(
+            &self.probe_target,
+            Sample::Ping {
+                mode: phase.name().to_lowercase(),
+                success: failure.is_none(),
+                rtt_ms: rtt.map(millis),
+                error: failure.map(|failure| failure.to_string()),
+            },
+        );
+    }
+
+    async fn reopen_probe(
+        &self,
+        client: &Socks5Client,
+        probe: &mut BufReader<TcpStream>,
+    ) -> Result<()> {
+        let stream = client.connect(&self.probe_target).await?;
+        *probe = BufReader::new(stream);
+        Ok(())
+    }
+
+    async fn run_load_stream(
+        client: &Socks5Client,
+        target: &str,
+        direction: StreamDirection,
+        chunk_size: usize,
+        deadline: Instant,
+        bytes_sent: &AtomicU64,
+        bytes_received: &AtomicU64,
+    ) -> FailureTally {
+        let host = target.rsplit_once(':').map(|(h, _)| h).unwrap_or(target);
+        let payload = vec![0x5Au8; chunk_size];
+        let mut stream: Option<TcpStream> = None;
+        let mut failures = FailureTally::default();
+
+        while Instant::now() < deadline {
+            if stream.is_none() {
+                match client.connect(target).await {
+                    Ok(s) => stream = Some(s),
+                    Err(e) => {
+                        failures.record(&e);
+                        warn!("Load stream failed to connect: {}", e);
+                        tokio::time::sleep(Duration::from_millis(500)).await;
+                        continue;
+                    }
+                }
+            }
+            let Some(tcp_stream) = stream.as_mut() else {
+                continue;
+            };
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let transfer = async {
+                match direction {
+                    StreamDirection::Upload => {
+                        let request = format!(
+                            "POST /post HTTP/1.1\r\nHost: {host}\r\nContent-Length: {chunk_size}\r\nConnection: keep-alive\r\n\r\n"
+                        );
+                        tcp_stream
+                            .write_all(request.as_bytes())
+                            .await
+                            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+                        tcp_stream
+                            .write_all(&payload)
+                            .await
+                            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+                        bytes_sent.fetch_add(chunk_size as u64, Ordering::Relaxed);
+                        read_http_response(tcp_stream, bytes_received).await?;
+                    }
+                    StreamDirection::Download => {
+                        let request = format!(
+                            "GET /stream-bytes/{chunk_size} HTTP/1.1\r\nHost: {host}\r\nConnection: keep-alive\r\n\r\n"
+                        );
+                        tcp_stream
+                            .write_all(request.as_bytes())
+                            .await
+                            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+                        bytes_sent.fetch_add(request.len() as u64, Ordering::Relaxed);
+                        read_http_response(tcp_stream, bytes_received).await?;
+                    }
+                }
+                Ok::<(), NetworkTestError>(())
+            };
+
+            let outcome = timeout(remaining + Duration::from_secs(1), transfer).await;
+            match outcome {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    failures.record(&e);
+                    debug!("Load stream transfer failed, reconnecting: {}", e);
+                    stream = None;
+                }
+                // Deadline reached mid-transfer; the phase is over
+                Err(_) => break,
+            }
+        }
+
+        failures
+    }
+
+    fn calculate_percentile(&self, samples: &[Duration], percentile: f64) -> Duration {
+        if samples.is_empty() {
+            return Duration::ZERO;
+        }
+
+        let mut sorted_samples = samples.to_vec();
+        sorted_samples.sort();
+
+        let index = ((percentile / 100.0) * (sorted_samples.len() - 1) as f64).round() as usize;
+        sorted_samples[index.min(sorted_samples.len() - 1)]
+    }
+
+    fn loaded_metrics(&self, idle_median: Duration, phase: &PhaseResult) -> LoadedLatencyMetrics {
+        let median_rtt = self.calculate_percentile(&phase.rtt_samples, 50.0);
+        let p90_rtt = self.calculate_percentile(&phase.rtt_samples, 90.0);
+        let p99_rtt = self.calculate_percentile(&phase.rtt_samples, 99.0);
+        let total_probes = phase.rtt_samples.len() as u64 + phase.lost_probes;
+
+        LoadedLatencyMetrics {
+            probes: total_probes,
+            lost_probes: phase.lost_probes,
+            median_rtt,
+            p90_rtt,
+            p99_rtt,
+            median_inflation: median_rtt.saturating_sub(idle_median),
+            p90_inflation: p90_rtt.saturating_sub(idle_median),
+            p99_inflation: p99_rtt.saturating_sub(idle_median),
+            throughput: phase.throughput(),
+        }
+    }
+
+    pub fn to_metrics(&self, result: &LatencyUnderLoadResult) -> LatencyUnderLoadMetrics {
+        let idle_median_rtt = self.calculate_percentile(&result.idle.rtt_samples, 50.0);
+        let idle_p90_rtt = self.calculate_percentile(&result.idle.rtt_samples, 90.0);
+
+        let upload = self.loaded_metrics(idle_median_rtt, &result.upload);
+        let download = self.loaded_metrics(idle_median_rtt, &result.download);
+        let duplex = self.loaded_metrics(idle_median_rtt, &result.duplex);
+
+        let worst_inflation = [&upload, &download, &duplex]
+            .iter()
+            .map(|m| m.p90_inflation)
+            .max()
+            .unwrap_or(Duration::ZERO);
+
+        LatencyUnderLoadMetrics {
+            phase_duration: self.phase_duration,
+            load_streams: self.load_streams,
+            idle_median_rtt,
+            idle_p90_rtt,
+            upload,
+            download,
+            duplex,
+            bufferbloat_grade: Self::grade_bufferbloat(worst_inflation).to_string(),
+            failures: result.failures().breakdown(),
+        }
+    }
+
+    pub fn grade_bufferbloat(inflation: Duration) -> &'static str {
+        match inflation.as_millis() {
+            0..=4 => "A+",
+            5..=29 => "A",
+            30..=59 => "B",
+            60..=199 => "C",
+            200..=399 => "D",
+            _ => "F",
+        }
+    }
+
+    fn print_results(&self, result: &LatencyUnderLoadResult) {
+        let metrics = self.to_metrics(result);
+
+        println!("\n=== Latency Under Load Test Results ===");
+        println!("Phase Duration: {:?}", self.phase_duration);
+        println!("Load Streams: {}", self.load_streams);
+        println!("Probe Interval: {:?}", self.probe_interval);
+        println!();
+
+        println!("Idle Latency:");
+        println!("  Probes: {}", result.idle.rtt_samples.len());
+        println!("  Median RTT: {:?}", metrics.idle_median_rtt);
+        println!("  90th Percentile: {:?}", metrics.idle_p90_rtt);
+        println!();
+
+        println!("Latency Under Load:");
+        println!("  Phase    | Probes | Lost | Median  | p90     | p99     | +Median | +p90    | +p99    | Throughput");
+        println!("  ---------|--------|------|---------|---------|---------|---------|---------|---------|-----------");
+
+        for (phase, loaded) in [
+            (&result.upload, &metrics.upload),
+            (&result.download, &metrics.download),
+            (&result.duplex, &metrics.duplex),
+        ] {
+            println!(
+                "  {:8} | {:6} | {:4} | {:5.0}ms | {:5.0}ms | {:5.0}ms | {:5.0}ms | {:5.0}ms | {:5.0}ms | {:.2} Mbps",
+                phase.phase.name(),
+                loaded.probes,
+                loaded.lost_probes,
+                loaded.median_rtt.as_millis(),
+                loaded.p90_rtt.as_millis(),
+                loaded.p99_rtt.as_millis(),
+                loaded.median_inflation.as_millis(),
+                loaded.p90_inflation.as_millis(),
+                loaded.p99_inflation.as_millis(),
+                loaded.throughput * 8.0 / 1_000_000.0
+            );
+        }
+        println!();
+
+        print_failure_breakdown(&result.failures());
+
+        println!("Bufferbloat Grade: {}", metrics.bufferbloat_grade);
+    }
+}
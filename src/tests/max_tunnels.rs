@@ -0,0 +1,468 @@
+use super::print_failure_breakdown;
+use crate::live::LivePanel;
+use crate::metrics::{FailureCategory, FailureTally, MaxTunnelsMetrics, TunnelLimitMetrics};
+use crate::samples::{millis, Sample, SampleRecorder};
+use crate::{NetworkTestError, Phase, Result, Socks5Client};
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Clone)]
+pub struct MaxTunnelsTest {
+    proxy_addr: String,
+    target_addr: String,
+    max_tunnels: usize,
+    open_interval: Duration,
+    heartbeat_interval: Duration,
+    connect_timeout: Duration,
+    failure_confirmations: usize,
+    credentials: Vec<(String, String)>,
+    live: LivePanel,
+    samples: SampleRecorder,
+}
+
+#[derive(Debug, Clone)]
+pub struct TunnelLimitResult {
+    pub scope: String,
+    pub username: Option<String>,
+    pub limit: usize,
+    pub reached_cap: bool,
+    pub failure_mode: Option<FailureCategory>,
+    pub failed_attempts: usize,
+    pub time_to_limit: Duration,
+    pub average_open_time: Duration,
+    pub tunnels_survived: usize,
+    pub tunnels_dropped: usize,
+    pub failures: FailureTally,
+}
+
+#[derive(Debug, Clone)]
+pub struct MaxTunnelsResult {
+    pub phases: Vec<TunnelLimitResult>,
+}
+
+impl MaxTunnelsTest {
+    pub fn new(proxy_addr: &str, target_addr: &str, max_tunnels: usize) -> Self {
+        Self {
+            proxy_addr: proxy_addr.to_string(),
+            target_addr: target_addr.to_string(),
+            max_tunnels: max_tunnels.max(1),
+            open_interval: Duration::from_millis(10),
+            heartbeat_interval: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(10),
+            failure_confirmations: 3,
+            credentials: Vec::new(),
+            live: LivePanel::default(),
+            samples: SampleRecorder::default(),
+        }
+    }
+
+    pub fn with_credentials(mut self, credentials: Vec<(String, String)>) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    pub fn with_open_interval(mut self, open_interval_ms: u64) -> Self {
+        self.open_interval = Duration::from_millis(open_interval_ms);
+        self
+    }
+
+    pub fn with_heartbeat_interval(mut self, heartbeat_interval_sec: u64) -> Self {
+        self.heartbeat_interval = Duration::from_secs(heartbeat_interval_sec.max(1));
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, connect_timeout_sec: u64) -> Self {
+        self.connect_timeout = Duration::from_secs(connect_timeout_sec.max(1));
+        self
+    }
+
+    pub fn with_live(mut self, live: LivePanel) -> Self {
+        self.live = live;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: SampleRecorder) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub async fn run(&self) -> Result<MaxTunnelsResult> {
+        let result = self.execute().await;
+        let _pause = self.live.finish(result.is_ok());
+        let result = result?;
+
+        if !self.samples.owns_stdout() {
+            self.print_results(&result);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn execute(&self) -> Result<MaxTunnelsResult> {
+        info!(
+            "Starting max tunnels test through proxy {} to {}",
+            self.proxy_addr, self.target_addr
+        );
+
+        let proxy_addr = self
+            .proxy_addr
+            .parse()
+            .map_err(|e| NetworkTestError::Config(format!("Invalid proxy address: {e}")))?;
+
+        self.live.start(None);
+        let mut phases = Vec::new();
+
+        if self.credentials.is_empty() {
+            let client = Socks5Client::new(proxy_addr).with_timeout(self.connect_timeout);
+            phases.push(self.discover_limit("total", None, &[client]).await);
+        } else {
+            let clients: Vec<Socks5Client> = self
+                .credentials
+                .iter()
+                .map(|(username, password)| {
+                    Socks5Client::new(proxy_addr)
+                        .with_timeout(self.connect_timeout)
+                        .with_auth(username.clone(), password.clone())
+                })
+                .collect();
+
+            for (client, (username, _)) in clients.iter().zip(&self.credentials) {
+                phases.push(
+                    self.discover_limit(
+                        "user",
+                        Some(username.clone()),
+                        std::slice::from_ref(client),
+                    )
+                    .await,
+                );
+            }
+
+            if clients.len() > 1 {
+                phases.push(self.discover_limit("total", None, &clients).await);
+            }
+        }
+
+        Ok(MaxTunnelsResult { phases })
+    }
+
+    async fn discover_limit(
+        &self,
+        scope: &str,
+        username: Option<String>,
+        clients: &[Socks5Client],
+    ) -> TunnelLimitResult {
+        info!(
+            "Discovering {} tunnel limit{}",
+            scope,
+            username
+                .as_ref()
+                .map(|u| format!(" for user {u}"))
+                .unwrap_or_default()
+        );
+
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let alive = Arc::new(AtomicUsize::new(0));
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let mut tunnels: Vec<JoinHandle<Option<FailureCategory>>> = Vec::new();
+        let mut failures = FailureTally::default();
+        let mut open_times = Vec::new();
+        let mut consecutive_failures = 0;
+        let mut failed_attempts = 0;
+        let mut failure_mode = None;
+        let start = Instant::now();
+        let mut time_to_limit = Duration::ZERO;
+
+        while tunnels.len() < self.max_tunnels {
+            let client = &clients[tunnels.len() % clients.len()];
+            let open_start = Instant::now();
+
+            match self.open_held_tunnel(client).await {
+                Ok(stream) => {
+                    let open_time = open_start.elapsed();
+                    open_times.push(open_time);
+                    self.live.rtt(open_time);
+                    self.samples.record(
+                        &self.target_addr,
+                        Sample::ConnectionAttempt {
+                            success: true,
+                            connect_time_ms: Some(millis(open_time)),
+                            error: None,
+                        },
+                    );
+                    consecutive_failures = 0;
+                    alive.fetch_add(1, Ordering::SeqCst);
+                    time_to_limit = start.elapsed();
+
+                    let handle = tokio::spawn(Self::heartbeat_loop(
+                        stream,
+                        self.heartbeat_interval,
+                        self.connect_timeout,
+                        stop_rx.clone(),
+                        alive.clone(),
+                        dropped.clone(),
+                    ));
+                    tunnels.push(handle);
+
+                    if tunnels.len().is_multiple_of(100) {
+                        info!("{} tunnels held open", tunnels.len());
+                    }
+                }
+                Err(mode) => {
+                    debug!("Tunnel {} failed: {}", tunnels.len() + 1, mode);
+                    failures.record_category(mode);
+                    self.live.failure();
+                    self.samples.record(
+                        &self.target_addr,
+                        Sample::ConnectionAttempt {
+                            success: false,
+                            connect_time_ms: None,
+                            error: Some(mode.to_string()),
+                        },
+                    );
+                    failed_attempts += 1;
+                    consecutive_failures += 1;
+
+                    if consecutive_failures >= self.failure_confirmations {
+                        warn!("New tunnels refused after {} open: {}", tunnels.len(), mode);
+                        failure_mode = Some(mode);
+                        break;
+                    }
+                    failure_mode = Some(mode);
+                }
+            }
+
+            sleep(self.open_interval).await;
+        }
+
+        let reached_cap = tunnels.len() >= self.max_tunnels;
+        if reached_cap {
+            failure_mode = None;
+        }
+
+        // Give every held tunnel at least one more heartbeat so tunnels the proxy
+        // silently dropped while refusing new ones are counted.
+        sleep(self.heartbeat_interval + self.heartbeat_interval / 2).await;
+
+        let limit = tunnels.len();
+        let tunnels_survived = alive.load(Ordering::SeqCst);
+        let tunnels_dropped = dropped.load(Ordering::SeqCst);
+
+        let _ = stop_tx.send(true);
+        for tunnel in tunnels {
+            if let Ok(Some(category)) = tunnel.await {
+                failures.record_category(category);
+            }
+        }
+
+        let average_open_time = if !open_times.is_empty() {
+            open_times.iter().sum::<Duration>() / open_times.len() as u32
+        } else {
+            Duration::ZERO
+        };
+
+        TunnelLimitResult {
+            scope: scope.to_string(),
+            username,
+            limit,
+            reached_cap,
+            failure_mode,
+            failed_attempts,
+            time_to_limit,
+            average_open_time,
+            tunnels_survived,
+            tunnels_dropped,
+            failures,
+        }
+    }
+
+    async fn open_held_tunnel(
+        &self,
+        client: &Socks5Client,
+    ) -> std::result::Result<BufReader<TcpStream>, FailureCategory> {
+        let stream = match timeout(self.connect_timeout, client.connect(&self.target_addr)).await {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(e)) => return Err(FailureCategory::from(&e)),
+            Err(_) => {
+                return Err(FailureCategory::Timeout {
+                    phase: Phase::Connect,
+                })
+            }
+        };
+
+        // A proxy may still accept CONNECT but never relay data once it is saturated,
+        // so a tunnel only counts once the hold-open handshake round-trips.
+        let mut stream = BufReader::new(stream);
+        match timeout(self.connect_timeout, Self::request_hold_open(&mut stream)).await {
+            Ok(Ok(())) => Ok(stream),
+            Ok(Err(e)) => Err(FailureCategory::from(&e)),
+            Err(_) => Err(FailureCategory::Timeout {
+                phase: Phase::Exchange,
+            }),
+        }
+    }
+
+    async fn request_hold_open(stream: &mut BufReader<TcpStream>) -> Result<()> {
+        stream
+            .get_mut()
+            .write_all(b"HOLD\n")
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+
+        let mut response = String::new();
+        let bytes_read = stream
+            .read_line(&mut response)
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+        if bytes_read == 0 {
+            return Err(NetworkTestError::io(
+                Phase::Exchange,
+                ErrorKind::UnexpectedEof.into(),
+            ));
+        }
+        if response.trim() != "HELD" {
+            return Err(NetworkTestError::protocol(
+                Phase::Exchange,
+                format!("Expected HELD, got: {}", response.trim()),
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn heartbeat_loop(
+        mut stream: BufReader<TcpStream>,
+        heartbeat_interval: Duration,
+        reply_timeout: Duration,
+        mut stop: watch::Receiver<bool>,
+        alive: Arc<AtomicUsize>,
+        dropped: Arc<AtomicUsize>,
+    ) -> Option<FailureCategory> {
+        loop {
+            tokio::select! {
+                _ = sleep(heartbeat_interval) => {}
+                _ = stop.changed() => break,
+            }
+
+            if let Err(e) = Self::send_heartbeat(&mut stream, reply_timeout).await {
+                debug!("Held tunnel dropped: {}", e);
+                alive.fetch_sub(1, Ordering::SeqCst);
+                dropped.fetch_add(1, Ordering::SeqCst);
+                return Some(FailureCategory::from(&e));
+            }
+        }
+
+        let _ = stream.get_mut().shutdown().await;
+        None
+    }
+
+    async fn send_heartbeat(
+        stream: &mut BufReader<TcpStream>,
+        reply_timeout: Duration,
+    ) -> Result<()> {
+        stream
+            .get_mut()
+            .write_all(b"PING\n")
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+
+        let mut response = String::new();
+        let bytes_read = timeout(reply_timeout, stream.read_line(&mut response))
+            .await
+            .map_err(|_| NetworkTestError::timeout(Phase::Exchange, "Heartbeat timeout"))?
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+
+        if bytes_read == 0 {
+            return Err(NetworkTestError::io(
+                Phase::Exchange,
+                ErrorKind::UnexpectedEof.into(),
+            ));
+        }
+        if response.trim() != "PONG" {
+            return Err(NetworkTestError::protocol(
+                Phase::Exchange,
+                format!("Expected PONG, got: {}", response.trim()),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn to_metrics(&self, result: &MaxTunnelsResult) -> MaxTunnelsMetrics {
+        MaxTunnelsMetrics {
+            max_tunnels: self.max_tunnels,
+            heartbeat_interval: self.heartbeat_interval,
+            phases: result
+                .phases
+                .iter()
+                .map(|phase| TunnelLimitMetrics {
+                    scope: phase.scope.clone(),
+                    username: phase.username.clone(),
+                    limit: phase.limit,
+                    reached_cap: phase.reached_cap,
+                    failure_mode: phase.failure_mode.as_ref().map(|m| m.to_string()),
+                    failed_attempts: phase.failed_attempts,
+                    time_to_limit: phase.time_to_limit,
+                    average_open_time: phase.average_open_time,
+                    tunnels_survived: phase.tunnels_survived,
+                    tunnels_dropped: phase.tunnels_dropped,
+                })
+                .collect(),
+            failures: {
+                let mut failures = FailureTally::default();
+                for phase in &result.phases {
+                    failures.merge(&phase.failures);
+                }
+                failures.breakdown()
+            },
+        }
+    }
+
+    fn print_results(&self, result: &MaxTunnelsResult) {
+        println!("\n=== Max Simultaneous Tunnels Test Results ===");
+        println!("Proxy: {}", self.proxy_addr);
+        println!("Target: {}", self.target_addr);
+        println!("Tunnel Cap: {}", self.max_tunnels);
+        println!("Heartbeat Interval: {:?}", self.heartbeat_interval);
+        println!();
+
+        for phase in &result.phases {
+            match &phase.username {
+                Some(username) => println!("Per-User Limit ({username}):"),
+                None => println!("Total Limit:"),
+            }
+
+            if phase.reached_cap {
+                println!("  Tunnels Held: {} (cap reached, no refusal)", phase.limit);
+            } else {
+                println!("  Tunnel Limit: {}", phase.limit);
+            }
+            if let Some(ref mode) = phase.failure_mode {
+                println!("  Failure Mode: {mode}");
+            }
+            println!("  Failed Attempts: {}", phase.failed_attempts);
+            println!("  Time To Limit: {:?}", phase.time_to_limit);
+            println!("  Average Open Time: {:?}", phase.average_open_time);
+            println!(
+                "  Existing Tunnels Survived: {}/{} ({} dropped)",
+                phase.tunnels_survived, phase.limit, phase.tunnels_dropped
+            );
+
+            if phase.limit > 0 && phase.tunnels_dropped == 0 {
+                println!("  ✓ Existing tunnels unaffected by the limit");
+            } else if phase.tunnels_dropped > 0 {
+                println!("  ✗ Proxy dropped existing tunnels under pressure");
+            }
+            println!();
+
+            print_failure_breakdown(&phase.failures);
+        }
+    }
+}
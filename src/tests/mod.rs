@@ -1,5 +1,81 @@
 pub mod bandwidth;
 pub mod connection_perf;
 pub mod dns_stability;
+pub mod fairness;
+pub mod latency_under_load;
+pub mod max_tunnels;
 pub mod network_jitter;
+pub mod proxy_dns;
 pub mod tcp_stability;
+
+use crate::metrics::FailureTally;
+use crate::{NetworkTestError, Phase, Result};
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+
+pub(crate) fn print_failure_breakdown(failures: &FailureTally) {
+    if failures.is_empty() {
+        return;
+    }
+
+    println!("Failure Breakdown:");
+    for line in failures.breakdown().summary_lines() {
+        println!("  {line}");
+    }
+    println!();
+}
+
+pub(crate) async fn read_http_response(
+    stream: &mut TcpStream,
+    bytes_received: &AtomicU64,
+) -> Result<usize> {
+    let mut header = Vec::with_capacity(256);
+    let mut byte = [0u8; 1];
+
+    while !header.ends_with(b"\r\n\r\n") {
+        let n = stream
+            .read(&mut byte)
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+        if n == 0 {
+            return Err(NetworkTestError::io(
+                Phase::Exchange,
+                ErrorKind::UnexpectedEof.into(),
+            ));
+        }
+        header.push(byte[0]);
+    }
+    bytes_received.fetch_add(header.len() as u64, Ordering::Relaxed);
+
+    let header_text = String::from_utf8_lossy(&header);
+    let content_length = header_text
+        .lines()
+        .find_map(|line| {
+            line.to_ascii_lowercase()
+                .strip_prefix("content-length:")
+                .and_then(|v| v.trim().parse::<usize>().ok())
+        })
+        .unwrap_or(0);
+
+    let mut remaining = content_length;
+    let mut buffer = [0u8; 16384];
+    while remaining > 0 {
+        let to_read = remaining.min(buffer.len());
+        let n = stream
+            .read(&mut buffer[..to_read])
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+        if n == 0 {
+            return Err(NetworkTestError::io(
+                Phase::Exchange,
+                ErrorKind::UnexpectedEof.into(),
+            ));
+        }
+        bytes_received.fetch_add(n as u64, Ordering::Relaxed);
+        remaining -= n;
+    }
+
+    Ok(header.len() + content_length)
+}
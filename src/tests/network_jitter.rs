@@ -1,19 +1,100 @@
-use crate::{NetworkTestError, Result, Socks5Client};
-use std::time::{Duration, Instant};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use crate::live::LivePanel;
+use crate::metrics::{
+    EventClock, EventLog, FailureCategory, FailureTally, LossCorrelationMetrics,
+    NetworkJitterMetrics, OneWayDelayMetrics, PingModeMetrics, TargetMetrics, TestEvent,
+};
+use crate::samples::{millis, Sample, SampleRecorder};
+use crate::tests::print_failure_breakdown;
+use crate::{NetworkTestError, Phase, Result, Socks5Client};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
 use tokio::time::{interval, timeout};
 use tracing::{debug, info, warn};
 
+const PING_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Consecutive unanswered tunnel pings after which the tunnel is reopened.
+const TUNNEL_STALL_LIMIT: u32 = 3;
+
+/// One direction counts as slower only when its median one-way delay
+/// exceeds the other's by this much and by half again.
+const ONE_WAY_ASYMMETRY_FLOOR: Duration = Duration::from_millis(1);
+
+/// G.113 equipment impairment and packet-loss robustness for G.711 with
+/// packet loss concealment, the reference codec for the E-model estimate.
+const G711_IE: f64 = 0.0;
+const G711_BPL: f64 = 25.1;
+
+/// How pings reach the jitter server: a fresh SOCKS5 CONNECT per ping, so
+/// the RTT includes tunnel setup, or timestamped pings over one long-lived
+/// tunnel per target, so the RTT covers only the proxied path.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PingMode {
+    #[default]
+    Connect,
+    Tunnel,
+}
+
+impl PingMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PingMode::Connect => "Connect-per-ping",
+            PingMode::Tunnel => "Persistent tunnel",
+        }
+    }
+}
+
+impl fmt::Display for PingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PingMode::Connect => f.pad("connect"),
+            PingMode::Tunnel => f.pad("tunnel"),
+        }
+    }
+}
+
+impl FromStr for PingMode {
+    type Err = NetworkTestError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "connect" => Ok(PingMode::Connect),
+            "tunnel" => Ok(PingMode::Tunnel),
+            _ => Err(NetworkTestError::Config(format!(
+                "Unknown ping mode '{s}', expected connect or tunnel"
+            ))),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct NetworkJitterTest {
     proxy_addr: String,
     targets: Vec<String>,
     ping_interval: Duration,
     test_duration: Duration,
+    modes: Vec<PingMode>,
+    live: LivePanel,
+    samples: SampleRecorder,
 }
 
 #[derive(Debug, Clone)]
 pub struct NetworkJitterResult {
+    /// One series per ping mode, in the order the modes were requested.
+    pub series: Vec<PingSeries>,
+    pub events: Vec<TestEvent>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PingSeries {
+    pub mode: PingMode,
     pub total_pings: u64,
     pub successful_pings: u64,
     pub failed_pings: u64,
@@ -24,8 +105,22 @@ pub struct NetworkJitterResult {
     pub max_rtt: Duration,
     pub median_rtt: Duration,
     pub jitter: Duration,
+    /// RFC 3550 smoothed interarrival jitter, averaged over targets by sample count.
+    pub rfc3550_jitter: Duration,
+    /// Absolute RTT differences between consecutive replies from the same target.
+    pub ipdv_samples: Vec<Duration>,
+    /// One-way delays from server timestamps; tunnel mode only.
+    pub upstream_delays: Vec<Duration>,
+    pub downstream_delays: Vec<Duration>,
+    pub upstream_jitter: Duration,
+    pub downstream_jitter: Duration,
     pub packet_loss_rate: f64,
-    pub target_results: std::collections::HashMap<String, TargetJitterResult>,
+    pub target_results: HashMap<String, TargetJitterResult>,
+    /// Tunnels reopened after an error or stall; always 0 in connect mode.
+    pub reconnects: u64,
+    /// Replies that arrived after their ping had already timed out.
+    pub late_replies: u64,
+    pub failures: FailureTally,
 }
 
 #[derive(Debug, Clone)]
@@ -37,7 +132,16 @@ pub struct TargetJitterResult {
     pub rtt_samples: Vec<Duration>,
     pub average_rtt: Duration,
     pub jitter: Duration,
+    pub rfc3550_jitter: Duration,
     pub packet_loss_rate: f64,
+    /// Whether each scheduled ping was lost, in schedule order.
+    pub loss_events: Vec<bool>,
+    pub timestamps: Vec<ProbeTimestamps>,
+    /// Estimated server clock minus client clock, in microseconds.
+    pub clock_offset: Option<i64>,
+    pub upstream_delays: Vec<Duration>,
+    pub downstream_delays: Vec<Duration>,
+    pub failures: FailureTally,
 }
 
 #[derive(Debug, Clone)]
@@ -45,7 +149,300 @@ struct PingResult {
     pub success: bool,
     pub rtt: Option<Duration>,
     pub _timestamp: Instant,
-    pub error: Option<String>,
+    pub timestamps: Option<ProbeTimestamps>,
+    pub failure: Option<FailureCategory>,
+}
+
+/// NTP-style timestamps of one tunnel ping, in microseconds since the Unix
+/// epoch: client send, server receive, server send, client receive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeTimestamps {
+    pub client_sent: i64,
+    pub server_received: i64,
+    pub server_sent: i64,
+    pub client_received: i64,
+}
+
+impl ProbeTimestamps {
+    /// Round trip minus the time the server held the ping.
+    pub fn network_delay(&self) -> i64 {
+        (self.client_received - self.client_sent) - (self.server_sent - self.server_received)
+    }
+
+    /// Server clock minus client clock, assuming a symmetric path.
+    pub fn clock_offset(&self) -> i64 {
+        ((self.server_received - self.client_sent) + (self.server_sent - self.client_received)) / 2
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pong {
+    sequence: u64,
+    /// Server receive and send times, absent from servers that only echo.
+    server_times: Option<(i64, i64)>,
+}
+
+/// Everything one target's probing schedule produced.
+#[derive(Debug)]
+struct TargetProbeLog {
+    target: String,
+    /// Ping outcomes per mode, in the order of `NetworkJitterTest::modes`.
+    pings: Vec<Vec<Result<PingResult>>>,
+    reconnects: u64,
+    late_replies: u64,
+    events: EventLog,
+}
+
+#[derive(Debug, Default)]
+struct TunnelProbe {
+    stream: Option<BufReader<TcpStream>>,
+    /// Survives a timed-out read so the rest of a half-received reply
+    /// isn't mistaken for a new line on the next ping.
+    line: String,
+    opened: bool,
+    stalled: u32,
+    reconnects: u64,
+    late_replies: u64,
+}
+
+impl NetworkJitterResult {
+    /// The series headline figures come from: tunnel probes when they ran,
+    /// since they measure the path rather than tunnel setup.
+    pub fn primary(&self) -> &PingSeries {
+        self.series(PingMode::Tunnel)
+            .unwrap_or_else(|| &self.series[0])
+    }
+
+    pub fn series(&self, mode: PingMode) -> Option<&PingSeries> {
+        self.series.iter().find(|series| series.mode == mode)
+    }
+
+    /// Median connect-per-ping RTT minus median in-tunnel RTT.
+    pub fn setup_overhead(&self) -> Option<Duration> {
+        let connect = self.series(PingMode::Connect)?;
+        let tunnel = self.series(PingMode::Tunnel)?;
+        (connect.successful_pings > 0 && tunnel.successful_pings > 0)
+            .then(|| connect.median_rtt.saturating_sub(tunnel.median_rtt))
+    }
+
+    pub fn failures(&self) -> FailureTally {
+        let mut failures = FailureTally::default();
+        for series in &self.series {
+            failures.merge(&series.failures);
+        }
+        failures
+    }
+}
+
+impl PingSeries {
+    fn new(mode: PingMode, targets: &[String]) -> Self {
+        let mut target_results = HashMap::new();
+        for target in targets {
+            target_results.insert(
+                target.clone(),
+                TargetJitterResult {
+                    target: target.clone(),
+                    total_pings: 0,
+                    successful_pings: 0,
+                    failed_pings: 0,
+                    rtt_samples: Vec::new(),
+                    average_rtt: Duration::ZERO,
+                    jitter: Duration::ZERO,
+                    rfc3550_jitter: Duration::ZERO,
+                    packet_loss_rate: 0.0,
+                    loss_events: Vec::new(),
+                    timestamps: Vec::new(),
+                    clock_offset: None,
+                    upstream_delays: Vec::new(),
+                    downstream_delays: Vec::new(),
+                    failures: FailureTally::default(),
+                },
+            );
+        }
+
+        Self {
+            mode,
+            total_pings: 0,
+            successful_pings: 0,
+            failed_pings: 0,
+            timeout_pings: 0,
+            rtt_samples: Vec::new(),
+            average_rtt: Duration::ZERO,
+            min_rtt: Duration::ZERO,
+            max_rtt: Duration::ZERO,
+            median_rtt: Duration::ZERO,
+            jitter: Duration::ZERO,
+            rfc3550_jitter: Duration::ZERO,
+            ipdv_samples: Vec::new(),
+            upstream_delays: Vec::new(),
+            downstream_delays: Vec::new(),
+            upstream_jitter: Duration::ZERO,
+            downstream_jitter: Duration::ZERO,
+            packet_loss_rate: 0.0,
+            target_results,
+            reconnects: 0,
+            late_replies: 0,
+            failures: FailureTally::default(),
+        }
+    }
+
+    fn record(&mut self, target: &str, ping: Result<PingResult>) {
+        self.total_pings += 1;
+        let target_result = self.target_results.get_mut(target).unwrap();
+        target_result.total_pings += 1;
+
+        match ping {
+            Ok(PingResult {
+                success: true,
+                rtt: Some(rtt),
+                timestamps,
+                ..
+            }) => {
+                self.successful_pings += 1;
+                target_result.successful_pings += 1;
+                target_result.loss_events.push(false);
+                target_result.timestamps.extend(timestamps);
+                target_result.rtt_samples.push(rtt);
+                self.rtt_samples.push(rtt);
+
+                debug!("{} ping to {} successful: {:?}", self.mode, target, rtt);
+            }
+            Ok(PingResult {
+                success: false,
+                failure: Some(failure),
+                ..
+            }) => {
+                if matches!(failure, FailureCategory::Timeout { .. }) {
+                    self.timeout_pings += 1;
+                } else {
+                    self.failed_pings += 1;
+                }
+                target_result.failed_pings += 1;
+                target_result.loss_events.push(true);
+                target_result.failures.record_category(failure);
+                self.failures.record_category(failure);
+                warn!("{} ping to {} failed: {}", self.mode, target, failure);
+            }
+            Err(e) => {
+                self.failed_pings += 1;
+                target_result.failed_pings += 1;
+                target_result.loss_events.push(true);
+                target_result.failures.record(&e);
+                self.failures.record(&e);
+                warn!("{} ping to {} error: {}", self.mode, target, e);
+            }
+            _ => {
+                self.failed_pings += 1;
+                target_result.failed_pings += 1;
+                target_result.loss_events.push(true);
+            }
+        }
+    }
+}
+
+/// ITU-T G.107 E-model transmission rating R for a G.711 call, using the
+/// simplified delay impairment of Cole and Rosenbluth.
+pub fn r_factor(one_way_delay: Duration, loss_rate: f64) -> f64 {
+    let delay_ms = one_way_delay.as_secs_f64() * 1000.0;
+    let delay_impairment = 0.024 * delay_ms
+        + if delay_ms > 177.3 {
+            0.11 * (delay_ms - 177.3)
+        } else {
+            0.0
+        };
+
+    let loss = loss_rate.clamp(0.0, 100.0);
+    let equipment_impairment = G711_IE + (95.0 - G711_IE) * loss / (loss + G711_BPL);
+
+    (93.2 - delay_impairment - equipment_impairment).clamp(0.0, 100.0)
+}
+
+/// G.107 Annex B conversion from R to estimated mean opinion score.
+pub fn mos_from_r_factor(r: f64) -> f64 {
+    if r <= 0.0 {
+        1.0
+    } else if r >= 100.0 {
+        4.5
+    } else {
+        (1.0 + 0.035 * r + 7.0e-6 * r * (r - 60.0) * (100.0 - r)).clamp(1.0, 4.5)
+    }
+}
+
+/// G.109 user satisfaction category for an R-factor.
+pub fn r_factor_rating(r: f64) -> &'static str {
+    if r >= 90.0 {
+        "very satisfied"
+    } else if r >= 80.0 {
+        "satisfied"
+    } else if r >= 70.0 {
+        "some users dissatisfied"
+    } else if r >= 60.0 {
+        "many users dissatisfied"
+    } else {
+        "nearly all users dissatisfied"
+    }
+}
+
+/// Which leg of the path is slower, judged by median one-way delay.
+pub fn slower_direction(upstream: Duration, downstream: Duration) -> &'static str {
+    let (slower, faster, direction) = if upstream > downstream {
+        (upstream, downstream, "upstream")
+    } else {
+        (downstream, upstream, "downstream")
+    };
+
+    if slower > faster + ONE_WAY_ASYMMETRY_FLOOR
+        && slower.as_secs_f64() > faster.as_secs_f64() * 1.5
+    {
+        direction
+    } else {
+        "balanced"
+    }
+}
+
+/// Phi coefficient between two loss series aligned by schedule slot; `None`
+/// when either target never or always lost pings over the common slots.
+pub fn loss_correlation(a: &[bool], b: &[bool]) -> Option<f64> {
+    let slots = a.len().min(b.len());
+    if slots == 0 {
+        return None;
+    }
+
+    let count = |events: &[bool]| events[..slots].iter().filter(|lost| **lost).count() as f64;
+    let (losses_a, losses_b) = (count(a), count(b));
+    let joint = a.iter().zip(b).filter(|(a, b)| **a && **b).count() as f64;
+    let n = slots as f64;
+
+    let covariance = joint / n - (losses_a / n) * (losses_b / n);
+    let variance_a = losses_a / n * (1.0 - losses_a / n);
+    let variance_b = losses_b / n * (1.0 - losses_b / n);
+    if variance_a == 0.0 || variance_b == 0.0 {
+        return None;
+    }
+
+    Some(covariance / (variance_a * variance_b).sqrt())
+}
+
+/// Parses "PONG <seq> <client sent> [<server received> <server sent>]".
+fn parse_pong(line: &str) -> Option<Pong> {
+    let mut fields = line.trim().strip_prefix("PONG ")?.split_whitespace();
+    let sequence = fields.next()?.parse().ok()?;
+    fields.next()?.parse::<i64>().ok()?;
+    let server_times = match (fields.next(), fields.next()) {
+        (Some(received), Some(sent)) => Some((received.parse().ok()?, sent.parse().ok()?)),
+        _ => None,
+    };
+    Some(Pong {
+        sequence,
+        server_times,
+    })
+}
+
+fn epoch_micros() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_micros() as i64)
+        .unwrap_or_default()
 }
 
 impl NetworkJitterTest {
@@ -60,16 +457,50 @@ impl NetworkJitterTest {
             targets,
             ping_interval: Duration::from_millis(ping_interval_ms),
             test_duration: Duration::from_secs(test_duration_sec),
+            modes: vec![PingMode::Connect, PingMode::Tunnel],
+            live: LivePanel::default(),
+            samples: SampleRecorder::default(),
+        }
+    }
+
+    pub fn with_modes(mut self, modes: Vec<PingMode>) -> Self {
+        if !modes.is_empty() {
+            self.modes = modes;
         }
+        let mut seen = HashSet::new();
+        self.modes.retain(|mode| seen.insert(*mode));
+        self
     }
 
-    pub async fn run(&self) -> Result<()> {
+    pub fn with_live(mut self, live: LivePanel) -> Self {
+        self.live = live;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: SampleRecorder) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub async fn run(&self) -> Result<NetworkJitterResult> {
+        let result = self.execute().await;
+        let _pause = self.live.finish(result.is_ok());
+        let result = result?;
+
+        if !self.samples.owns_stdout() {
+            self.print_results(&result);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn execute(&self) -> Result<NetworkJitterResult> {
         info!("Starting network jitter test");
         info!("Proxy: {}", self.proxy_addr);
         info!("Targets: {:?}", self.targets);
         info!(
-            "Ping interval: {:?}, Test duration: {:?}",
-            self.ping_interval, self.test_duration
+            "Ping interval: {:?}, Test duration: {:?}, Modes: {:?}",
+            self.ping_interval, self.test_duration, self.modes
         );
 
         let proxy_addr = self
@@ -79,102 +510,152 @@ impl NetworkJitterTest {
 
         let client = Socks5Client::new(proxy_addr).with_timeout(Duration::from_secs(10));
 
-        let result = self.run_jitter_test(&client).await?;
-
-        self.print_results(&result);
-
-        Ok(())
+        self.run_jitter_test(&client).await
     }
 
     async fn run_jitter_test(&self, client: &Socks5Client) -> Result<NetworkJitterResult> {
+        self.live.start(Some(self.test_duration));
         let start_time = Instant::now();
-        let end_time = start_time + self.test_duration;
+        let clock = EventClock::start();
+
+        // Each target runs its own schedule so a slow target cannot hold back
+        // the others, and every target is sampled at the full ping rate
+        let logs = join_all(
+            self.targets
+                .iter()
+                .map(|target| self.probe_target(client, target, start_time, clock)),
+        )
+        .await;
+        let mut events = EventLog::with_clock("network_jitter", clock);
 
-        let mut target_results = std::collections::HashMap::new();
-        for target in &self.targets {
-            target_results.insert(
-                target.clone(),
-                TargetJitterResult {
-                    target: target.clone(),
-                    total_pings: 0,
-                    successful_pings: 0,
-                    failed_pings: 0,
-                    rtt_samples: Vec::new(),
-                    average_rtt: Duration::ZERO,
-                    jitter: Duration::ZERO,
-                    packet_loss_rate: 0.0,
-                },
-            );
+        let mut series: Vec<PingSeries> = self
+            .modes
+            .iter()
+            .map(|mode| PingSeries::new(*mode, &self.targets))
+            .collect();
+
+        for log in logs {
+            events.merge(log.events);
+            for (series, pings) in series.iter_mut().zip(log.pings) {
+                for ping in pings {
+                    series.record(&log.target, ping);
+                }
+                if series.mode == PingMode::Tunnel {
+                    series.reconnects += log.reconnects;
+                    series.late_replies += log.late_replies;
+                }
+            }
+        }
+
+        for series in series.iter_mut() {
+            self.summarize(series);
         }
 
-        let mut total_pings = 0u64;
-        let mut successful_pings = 0u64;
-        let mut failed_pings = 0u64;
-        let mut timeout_pings = 0u64;
-        let mut all_rtt_samples = Vec::new();
+        Ok(NetworkJitterResult {
+            series,
+            events: events.into_events(),
+        })
+    }
+
+    async fn probe_target(
+        &self,
+        client: &Socks5Client,
+        target: &str,
+        start_time: Instant,
+        clock: EventClock,
+    ) -> TargetProbeLog {
+        let end_time = start_time + self.test_duration;
+        let mut pings: Vec<Vec<Result<PingResult>>> =
+            self.modes.iter().map(|_| Vec::new()).collect();
+        let mut tunnel = TunnelProbe::default();
+        let mut sequence = 0u64;
+        let mut events = EventLog::with_clock("network_jitter", clock).with_live(self.live.clone());
 
         let mut ping_interval = interval(self.ping_interval);
-        let mut target_index = 0;
 
         while Instant::now() < end_time {
             ping_interval.tick().await;
 
-            if self.targets.is_empty() {
-                break;
-            }
-
-            let target = &self.targets[target_index % self.targets.len()];
-            target_index += 1;
-
-            total_pings += 1;
-            let target_result = target_results.get_mut(target).unwrap();
-            target_result.total_pings += 1;
-
-            match self.perform_ping(client, target).await {
-                Ok(PingResult {
-                    success: true,
-                    rtt: Some(rtt),
-                    ..
-                }) => {
-                    successful_pings += 1;
-                    target_result.successful_pings += 1;
-                    target_result.rtt_samples.push(rtt);
-                    all_rtt_samples.push(rtt);
-
-                    debug!("Ping to {} successful: {:?}", target, rtt);
-                }
-                Ok(PingResult {
-                    success: false,
-                    error: Some(error),
-                    ..
-                }) => {
-                    if error.contains("timeout") {
-                        timeout_pings += 1;
-                    } else {
-                        failed_pings += 1;
+            for (mode, pings) in self.modes.iter().zip(pings.iter_mut()) {
+                let event_target = format!("{target} ({mode})");
+                let reconnects = tunnel.reconnects;
+                let ping = match mode {
+                    PingMode::Connect => self.perform_ping(client, target).await,
+                    PingMode::Tunnel => {
+                        sequence += 1;
+                        self.perform_tunnel_ping(client, target, &mut tunnel, sequence)
+                            .await
                     }
-                    target_result.failed_pings += 1;
-                    warn!("Ping to {} failed: {}", target, error);
-                }
-                Err(e) => {
-                    failed_pings += 1;
-                    target_result.failed_pings += 1;
-                    warn!("Ping to {} error: {}", target, e);
-                }
-                _ => {
-                    failed_pings += 1;
-                    target_result.failed_pings += 1;
+                };
+                if tunnel.reconnects > reconnects {
+                    events.reconnected(&event_target, "tunnel reopened");
                 }
+                let failure = match &ping {
+                    Ok(PingResult {
+                        success: true, rtt, ..
+                    }) => {
+                        if let Some(rtt) = rtt {
+                            self.live.rtt(*rtt);
+                        }
+                        events.recovered(&event_target);
+                        None
+                    }
+                    Ok(PingResult {
+                        failure: Some(failure),
+                        ..
+                    }) => {
+                        events.category(&event_target, *failure);
+                        Some(failure.to_string())
+                    }
+                    Err(e) => {
+                        events.error(&event_target, e);
+                        Some(FailureCategory::from(e).to_string())
+                    }
+                    Ok(_) => Some("no reply".to_string()),
+                };
+                self.samples.record(
+                    target,
+                    Sample::Ping {
+                        mode: mode.to_string(),
+                        success: failure.is_none(),
+                        rtt_ms: ping.as_ref().ok().and_then(|ping| ping.rtt).map(millis),
+                        error: failure,
+                    },
+                );
+                pings.push(ping);
             }
         }
 
-        for target_result in target_results.values_mut() {
+        TargetProbeLog {
+            target: target.to_string(),
+            pings,
+            reconnects: tunnel.reconnects,
+            late_replies: tunnel.late_replies,
+            events,
+        }
+    }
+
+    fn summarize(&self, series: &mut PingSeries) {
+        for target_result in series.target_results.values_mut() {
             if !target_result.rtt_samples.is_empty() {
                 target_result.average_rtt = target_result.rtt_samples.iter().sum::<Duration>()
                     / target_result.rtt_samples.len() as u32;
                 target_result.jitter = self.calculate_jitter(&target_result.rtt_samples);
+                target_result.rfc3550_jitter =
+                    self.calculate_rfc3550_jitter(&target_result.rtt_samples);
+                series
+                    .ipdv_samples
+                    .extend(self.calculate_ipdv(&target_result.rtt_samples));
             }
 
+            self.split_one_way_delays(target_result);
+            series
+                .upstream_delays
+                .extend(&target_result.upstream_delays);
+            series
+                .downstream_delays
+                .extend(&target_result.downstream_delays);
+
             target_result.packet_loss_rate = if target_result.total_pings > 0 {
                 target_result.failed_pings as f64 / target_result.total_pings as f64 * 100.0
             } else {
@@ -182,45 +663,58 @@ impl NetworkJitterTest {
             };
         }
 
-        let average_rtt = if !all_rtt_samples.is_empty() {
+        let all_rtt_samples = &series.rtt_samples;
+        series.average_rtt = if !all_rtt_samples.is_empty() {
             all_rtt_samples.iter().sum::<Duration>() / all_rtt_samples.len() as u32
         } else {
             Duration::ZERO
         };
 
-        let min_rtt = all_rtt_samples
+        series.min_rtt = all_rtt_samples
             .iter()
             .min()
             .copied()
             .unwrap_or(Duration::ZERO);
-        let max_rtt = all_rtt_samples
+        series.max_rtt = all_rtt_samples
             .iter()
             .max()
             .copied()
             .unwrap_or(Duration::ZERO);
-        let median_rtt = self.calculate_median(&all_rtt_samples);
-        let jitter = self.calculate_jitter(&all_rtt_samples);
+        series.median_rtt = self.calculate_median(all_rtt_samples);
+        // Samples are grouped by target, so jitter is taken per target and
+        // weighted by sample count rather than across target boundaries
+        let weighted = |jitter: fn(&TargetJitterResult) -> Duration| {
+            if all_rtt_samples.is_empty() {
+                return Duration::ZERO;
+            }
+            series
+                .target_results
+                .values()
+                .map(|tr| jitter(tr) * tr.rtt_samples.len() as u32)
+                .sum::<Duration>()
+                / all_rtt_samples.len() as u32
+        };
+        series.jitter = weighted(|tr| tr.jitter);
+        series.rfc3550_jitter = weighted(|tr| tr.rfc3550_jitter);
+
+        if !series.upstream_delays.is_empty() {
+            let one_way_jitter = |delays: fn(&TargetJitterResult) -> &Vec<Duration>| {
+                series
+                    .target_results
+                    .values()
+                    .map(|tr| self.calculate_rfc3550_jitter(delays(tr)) * delays(tr).len() as u32)
+                    .sum::<Duration>()
+                    / series.upstream_delays.len() as u32
+            };
+            series.upstream_jitter = one_way_jitter(|tr| &tr.upstream_delays);
+            series.downstream_jitter = one_way_jitter(|tr| &tr.downstream_delays);
+        }
 
-        let packet_loss_rate = if total_pings > 0 {
-            (failed_pings + timeout_pings) as f64 / total_pings as f64 * 100.0
+        series.packet_loss_rate = if series.total_pings > 0 {
+            (series.failed_pings + series.timeout_pings) as f64 / series.total_pings as f64 * 100.0
         } else {
             0.0
         };
-
-        Ok(NetworkJitterResult {
-            total_pings,
-            successful_pings,
-            failed_pings,
-            timeout_pings,
-            rtt_samples: all_rtt_samples,
-            average_rtt,
-            min_rtt,
-            max_rtt,
-            median_rtt,
-            jitter,
-            packet_loss_rate,
-            target_results,
-        })
     }
 
     async fn perform_ping(&self, client: &Socks5Client, target: &str) -> Result<PingResult> {
@@ -239,34 +733,37 @@ impl NetworkJitterTest {
                     success: true,
                     rtt: Some(rtt),
                     _timestamp: ping_start,
-                    error: None,
+                    timestamps: None,
+                    failure: None,
                 })
             }
             Ok(Err(e)) => Ok(PingResult {
                 success: false,
                 rtt: None,
                 _timestamp: ping_start,
-                error: Some(e.to_string()),
+                timestamps: None,
+                failure: Some(FailureCategory::from(&e)),
             }),
             Err(_) => Ok(PingResult {
                 success: false,
                 rtt: None,
                 _timestamp: ping_start,
-                error: Some("timeout".to_string()),
+                timestamps: None,
+                failure: Some(FailureCategory::Timeout {
+                    phase: Phase::Exchange,
+                }),
             }),
         }
     }
 
     async fn tcp_ping_via_proxy(&self, client: &Socks5Client, target: &str) -> Result<()> {
-        let mut stream = client.connect(target).await.map_err(|e| {
-            NetworkTestError::Connection(format!("Failed to connect to target: {e}"))
-        })?;
+        let mut stream = client.connect(target).await?;
 
         let ping_data = b"PING\n";
         stream
             .write_all(ping_data)
             .await
-            .map_err(NetworkTestError::Io)?;
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
 
         let mut response_buffer = [0u8; 64];
         let bytes_read = timeout(
@@ -274,12 +771,13 @@ impl NetworkJitterTest {
             stream.read(&mut response_buffer),
         )
         .await
-        .map_err(|_| NetworkTestError::Timeout("Response timeout".to_string()))?
-        .map_err(NetworkTestError::Io)?;
+        .map_err(|_| NetworkTestError::timeout(Phase::Exchange, "Response timeout"))?
+        .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
 
         if bytes_read == 0 {
-            return Err(NetworkTestError::Connection(
-                "Connection closed by peer".to_string(),
+            return Err(NetworkTestError::protocol(
+                Phase::Exchange,
+                "Connection closed by peer",
             ));
         }
 
@@ -287,6 +785,177 @@ impl NetworkJitterTest {
         Ok(())
     }
 
+    async fn perform_tunnel_ping(
+        &self,
+        client: &Socks5Client,
+        target: &str,
+        probe: &mut TunnelProbe,
+        sequence: u64,
+    ) -> Result<PingResult> {
+        let ping_start = Instant::now();
+
+        let outcome = self.tunnel_ping(client, target, probe, sequence).await;
+        match outcome {
+            Ok((rtt, timestamps)) => {
+                probe.stalled = 0;
+                Ok(PingResult {
+                    success: true,
+                    rtt: Some(rtt),
+                    _timestamp: ping_start,
+                    timestamps,
+                    failure: None,
+                })
+            }
+            Err(e) => {
+                let failure = FailureCategory::from(&e);
+                // A late reply is told apart by its sequence number, so a
+                // timeout alone does not spoil the tunnel; a silent one does
+                if matches!(failure, FailureCategory::Timeout { .. }) {
+                    probe.stalled += 1;
+                    if probe.stalled >= TUNNEL_STALL_LIMIT {
+                        debug!("Tunnel to {} stalled, reopening", target);
+                        probe.stream = None;
+                    }
+                } else {
+                    probe.stream = None;
+                }
+                Ok(PingResult {
+                    success: false,
+                    rtt: None,
+                    _timestamp: ping_start,
+                    timestamps: None,
+                    failure: Some(failure),
+                })
+            }
+        }
+    }
+
+    async fn tunnel_ping(
+        &self,
+        client: &Socks5Client,
+        target: &str,
+        probe: &mut TunnelProbe,
+        sequence: u64,
+    ) -> Result<(Duration, Option<ProbeTimestamps>)> {
+        if probe.stream.is_none() {
+            let stream = client.connect(target).await?;
+            if probe.opened {
+                probe.reconnects += 1;
+            }
+            probe.opened = true;
+            probe.stalled = 0;
+            probe.line.clear();
+            probe.stream = Some(BufReader::new(stream));
+            debug!("Opened persistent tunnel to {}", target);
+        }
+        let Some(stream) = probe.stream.as_mut() else {
+            unreachable!("tunnel opened above");
+        };
+
+        let sent_at = Instant::now();
+        let client_sent = epoch_micros();
+        stream
+            .get_mut()
+            .write_all(format!("PING {sequence} {client_sent}\n").as_bytes())
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+
+        let line = &mut probe.line;
+        let mut late_replies = 0;
+        let reply = timeout(PING_TIMEOUT, async {
+            loop {
+                let bytes_read = stream
+                    .read_line(line)
+                    .await
+                    .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
+                if bytes_read == 0 {
+                    return Err(NetworkTestError::protocol(
+                        Phase::Exchange,
+                        "Connection closed by peer",
+                    ));
+                }
+
+                let received = std::mem::take(line);
+                match parse_pong(&received) {
+                    Some(pong) if pong.sequence == sequence => {
+                        let rtt = sent_at.elapsed();
+                        let client_received = epoch_micros();
+                        let timestamps =
+                            pong.server_times.map(|(received, sent)| ProbeTimestamps {
+                                client_sent,
+                                server_received: received,
+                                server_sent: sent,
+                                client_received,
+                            });
+                        return Ok((rtt, timestamps));
+                    }
+                    Some(pong) if pong.sequence < sequence => late_replies += 1,
+                    _ => {
+                        return Err(NetworkTestError::protocol(
+                            Phase::Exchange,
+                            format!("Expected PONG {sequence}, got: {}", received.trim()),
+                        ));
+                    }
+                }
+            }
+        })
+        .await;
+        probe.late_replies += late_replies;
+
+        reply.map_err(|_| NetworkTestError::timeout(Phase::Exchange, "Response timeout"))?
+    }
+
+    /// Splits each round trip into upstream and downstream delay using the
+    /// clock offset of the minimum-delay ping, as NTP's clock filter does: the
+    /// least queued exchange is the one most likely to be symmetric.
+    fn split_one_way_delays(&self, target_result: &mut TargetJitterResult) {
+        let Some(best) = target_result
+            .timestamps
+            .iter()
+            .min_by_key(|ts| ts.network_delay())
+        else {
+            return;
+        };
+        let offset = best.clock_offset();
+
+        let delay = |micros: i64| Duration::from_micros(micros.max(0) as u64);
+        target_result.clock_offset = Some(offset);
+        target_result.upstream_delays = target_result
+            .timestamps
+            .iter()
+            .map(|ts| delay(ts.server_received - ts.client_sent - offset))
+            .collect();
+        target_result.downstream_delays = target_result
+            .timestamps
+            .iter()
+            .map(|ts| delay(ts.client_received - ts.server_sent + offset))
+            .collect();
+    }
+
+    /// RFC 3550 interarrival jitter: J += (|D| - J) / 16 over consecutive replies.
+    fn calculate_rfc3550_jitter(&self, rtt_samples: &[Duration]) -> Duration {
+        let jitter = rtt_samples.windows(2).fold(0.0, |jitter, pair| {
+            let transit_diff = pair[1].abs_diff(pair[0]).as_secs_f64();
+            jitter + (transit_diff - jitter) / 16.0
+        });
+        Duration::from_secs_f64(jitter)
+    }
+
+    fn calculate_ipdv(&self, rtt_samples: &[Duration]) -> Vec<Duration> {
+        rtt_samples
+            .windows(2)
+            .map(|pair| pair[1].abs_diff(pair[0]))
+            .collect()
+    }
+
+    /// R-factor and MOS, taking mouth-to-ear delay as half the median RTT plus
+    /// a jitter buffer of twice the RFC 3550 jitter.
+    fn voice_quality(&self, series: &PingSeries) -> (f64, f64) {
+        let one_way_delay = series.median_rtt / 2 + series.rfc3550_jitter * 2;
+        let r = r_factor(one_way_delay, series.packet_loss_rate);
+        (r, mos_from_r_factor(r))
+    }
+
     fn calculate_jitter(&self, rtt_samples: &[Duration]) -> Duration {
         if rtt_samples.len() < 2 {
             return Duration::ZERO;
@@ -317,7 +986,7 @@ impl NetworkJitterTest {
         sorted_samples.sort();
 
         let mid = sorted_samples.len() / 2;
-        if sorted_samples.len() % 2 == 0 {
+        if sorted_samples.len().is_multiple_of(2) {
             (sorted_samples[mid - 1] + sorted_samples[mid]) / 2
         } else {
             sorted_samples[mid]
@@ -336,6 +1005,153 @@ impl NetworkJitterTest {
         sorted_samples[index.min(sorted_samples.len() - 1)]
     }
 
+    fn mode_metrics(&self, series: &PingSeries) -> PingModeMetrics {
+        PingModeMetrics {
+            mode: series.mode,
+            total_pings: series.total_pings,
+            successful_pings: series.successful_pings,
+            packet_loss_rate: series.packet_loss_rate,
+            average_rtt: series.average_rtt,
+            median_rtt: series.median_rtt,
+            p95_rtt: self.calculate_percentile(&series.rtt_samples, 95.0),
+            p99_rtt: self.calculate_percentile(&series.rtt_samples, 99.0),
+            jitter: series.jitter,
+            rfc3550_jitter: series.rfc3550_jitter,
+            mos: self.voice_quality(series).1,
+            reconnects: series.reconnects,
+            late_replies: series.late_replies,
+        }
+    }
+
+    fn loss_correlations(&self, series: &PingSeries) -> Vec<LossCorrelationMetrics> {
+        let mut targets: Vec<&TargetJitterResult> = series.target_results.values().collect();
+        targets.sort_by_key(|tr| tr.target.as_str());
+
+        let mut correlations = Vec::new();
+        for (i, a) in targets.iter().enumerate() {
+            for b in &targets[i + 1..] {
+                let slots = a.loss_events.len().min(b.loss_events.len());
+                let losses = |tr: &TargetJitterResult| {
+                    tr.loss_events[..slots].iter().filter(|lost| **lost).count() as u64
+                };
+                correlations.push(LossCorrelationMetrics {
+                    target_a: a.target.clone(),
+                    target_b: b.target.clone(),
+                    slots: slots as u64,
+                    losses_a: losses(a),
+                    losses_b: losses(b),
+                    joint_losses: a
+                        .loss_events
+                        .iter()
+                        .zip(&b.loss_events)
+                        .filter(|(a, b)| **a && **b)
+                        .count() as u64,
+                    correlation: loss_correlation(&a.loss_events, &b.loss_events),
+                });
+            }
+        }
+        correlations
+    }
+
+    fn one_way_delay_metrics(&self, series: &PingSeries) -> Option<OneWayDelayMetrics> {
+        if series.upstream_delays.is_empty() {
+            return None;
+        }
+
+        let upstream_median = self.calculate_median(&series.upstream_delays);
+        let downstream_median = self.calculate_median(&series.downstream_delays);
+        Some(OneWayDelayMetrics {
+            samples: series.upstream_delays.len() as u64,
+            upstream_median,
+            upstream_p95: self.calculate_percentile(&series.upstream_delays, 95.0),
+            upstream_jitter: series.upstream_jitter,
+            downstream_median,
+            downstream_p95: self.calculate_percentile(&series.downstream_delays, 95.0),
+            downstream_jitter: series.downstream_jitter,
+            slower_direction: slower_direction(upstream_median, downstream_median).to_string(),
+        })
+    }
+
+    pub fn to_metrics(&self, result: &NetworkJitterResult) -> NetworkJitterMetrics {
+        let primary = result.primary();
+        let (r_factor, _) = self.voice_quality(primary);
+        let mean_rtt = primary.average_rtt.as_secs_f64() * 1000.0;
+        let rtt_variance = if primary.rtt_samples.is_empty() {
+            0.0
+        } else {
+            primary
+                .rtt_samples
+                .iter()
+                .map(|rtt| (rtt.as_secs_f64() * 1000.0 - mean_rtt).powi(2))
+                .sum::<f64>()
+                / primary.rtt_samples.len() as f64
+        };
+
+        NetworkJitterMetrics {
+            test_duration: self.test_duration,
+            ping_interval: self.ping_interval,
+            targets_tested: self.targets.len(),
+            primary_mode: primary.mode,
+            total_pings: primary.total_pings,
+            successful_pings: primary.successful_pings,
+            failed_pings: primary.failed_pings,
+            timeout_pings: primary.timeout_pings,
+            packet_loss_rate: primary.packet_loss_rate,
+            average_rtt: primary.average_rtt,
+            median_rtt: primary.median_rtt,
+            min_rtt: primary.min_rtt,
+            max_rtt: primary.max_rtt,
+            p95_rtt: self.calculate_percentile(&primary.rtt_samples, 95.0),
+            p99_rtt: self.calculate_percentile(&primary.rtt_samples, 99.0),
+            jitter: primary.jitter,
+            rfc3550_jitter: primary.rfc3550_jitter,
+            ipdv_p50: self.calculate_percentile(&primary.ipdv_samples, 50.0),
+            ipdv_p95: self.calculate_percentile(&primary.ipdv_samples, 95.0),
+            ipdv_p99: self.calculate_percentile(&primary.ipdv_samples, 99.0),
+            r_factor,
+            mos: mos_from_r_factor(r_factor),
+            rtt_variance,
+            latency_consistency_score: self.calculate_latency_consistency(primary),
+            target_consistency_score: self.calculate_target_consistency(primary),
+            network_quality_score: self.calculate_network_quality_score(primary),
+            per_target_metrics: primary
+                .target_results
+                .iter()
+                .map(|(target, target_result)| {
+                    (
+                        target.clone(),
+                        TargetMetrics {
+                            target: target.clone(),
+                            total_pings: target_result.total_pings,
+                            successful_pings: target_result.successful_pings,
+                            failed_pings: target_result.failed_pings,
+                            packet_loss_rate: target_result.packet_loss_rate,
+                            average_rtt: target_result.average_rtt,
+                            p50_rtt: self.calculate_percentile(&target_result.rtt_samples, 50.0),
+                            p95_rtt: self.calculate_percentile(&target_result.rtt_samples, 95.0),
+                            p99_rtt: self.calculate_percentile(&target_result.rtt_samples, 99.0),
+                            jitter: target_result.jitter,
+                            clock_offset_ms: target_result
+                                .clock_offset
+                                .map(|offset| offset as f64 / 1000.0),
+                        },
+                    )
+                })
+                .collect(),
+            modes: result
+                .series
+                .iter()
+                .map(|series| self.mode_metrics(series))
+                .collect(),
+            setup_overhead: result.setup_overhead(),
+            loss_correlation: self.loss_correlations(primary),
+            one_way_delay: result
+                .series(PingMode::Tunnel)
+                .and_then(|series| self.one_way_delay_metrics(series)),
+            failures: result.failures().breakdown(),
+        }
+    }
+
     fn print_results(&self, result: &NetworkJitterResult) {
         println!("\n=== Network Jitter Test Results ===");
         println!("Test Duration: {:?}", self.test_duration);
@@ -343,6 +1159,39 @@ impl NetworkJitterTest {
         println!("Tested Targets: {}", self.targets.len());
         println!();
 
+        for series in &result.series {
+            self.print_series(series);
+        }
+
+        if let (Some(connect), Some(tunnel)) = (
+            result.series(PingMode::Connect),
+            result.series(PingMode::Tunnel),
+        ) {
+            println!("Setup vs Path:");
+            println!(
+                "  Median RTT: {:?} per new tunnel, {:?} in tunnel",
+                connect.median_rtt, tunnel.median_rtt
+            );
+            if let Some(overhead) = result.setup_overhead() {
+                println!("  Tunnel Setup Overhead: {overhead:?}");
+            }
+            println!(
+                "  Jitter: {:?} setup + path, {:?} path only",
+                connect.jitter, tunnel.jitter
+            );
+            println!();
+        }
+
+        let primary = result.primary();
+        println!("Quality figures below use {} pings.", primary.mode);
+        self.print_network_quality_analysis(primary);
+
+        let network_score = self.calculate_network_quality_score(primary);
+        println!("Network Quality Score: {network_score:.1}/100");
+    }
+
+    fn print_series(&self, result: &PingSeries) {
+        println!("--- {} Pings ---", result.mode.label());
         println!("Overall Statistics:");
         println!("  Total Pings: {}", result.total_pings);
         println!(
@@ -373,6 +1222,10 @@ impl NetworkJitterTest {
             }
         );
         println!("  Packet Loss Rate: {:.2}%", result.packet_loss_rate);
+        if result.mode == PingMode::Tunnel {
+            println!("  Tunnel Reconnects: {}", result.reconnects);
+            println!("  Late Replies: {}", result.late_replies);
+        }
         println!();
 
         if result.successful_pings > 0 {
@@ -388,18 +1241,67 @@ impl NetworkJitterTest {
             println!("  95th Percentile: {p95_rtt:?}");
             println!("  99th Percentile: {p99_rtt:?}");
             println!();
+
+            println!("Delay Variation:");
+            println!("  RFC 3550 Jitter: {:?}", result.rfc3550_jitter);
+            println!(
+                "  IPDV p50/p95/p99: {:?} / {:?} / {:?}",
+                self.calculate_percentile(&result.ipdv_samples, 50.0),
+                self.calculate_percentile(&result.ipdv_samples, 95.0),
+                self.calculate_percentile(&result.ipdv_samples, 99.0)
+            );
+            let (r, mos) = self.voice_quality(result);
+            println!(
+                "  Voice Quality (G.711 E-model): R = {:.1}, MOS = {:.2} ({})",
+                r,
+                mos,
+                r_factor_rating(r)
+            );
+            println!();
+        }
+
+        if let Some(one_way) = self.one_way_delay_metrics(result) {
+            println!("One-Way Delay (server timestamps):");
+            println!(
+                "  Upstream: median {:?}, p95 {:?}, jitter {:?}",
+                one_way.upstream_median, one_way.upstream_p95, one_way.upstream_jitter
+            );
+            println!(
+                "  Downstream: median {:?}, p95 {:?}, jitter {:?}",
+                one_way.downstream_median, one_way.downstream_p95, one_way.downstream_jitter
+            );
+            let mut sorted_targets: Vec<_> = result.target_results.values().collect();
+            sorted_targets.sort_by_key(|tr| tr.target.as_str());
+            for target_result in sorted_targets {
+                if let Some(offset) = target_result.clock_offset {
+                    println!(
+                        "  Clock Offset ({}): {:+.3}ms",
+                        target_result.target,
+                        offset as f64 / 1000.0
+                    );
+                }
+            }
+            println!("  Slower Direction: {}", one_way.slower_direction);
+            println!("  (split assumes the least-delayed ping took equally long each way)");
+            println!();
         }
 
         println!("Per-Target Results:");
-        println!("  Target                    | Pings | Success | Loss% | Avg RTT | Jitter");
-        println!("  --------------------------|-------|---------|-------|---------|--------");
+        println!(
+            "  Target                    | Pings | Success | Loss% | Avg RTT | p50     | p95     | p99     | Jitter"
+        );
+        println!(
+            "  --------------------------|-------|---------|-------|---------|---------|---------|---------|--------"
+        );
 
         let mut sorted_targets: Vec<_> = result.target_results.iter().collect();
         sorted_targets.sort_by_key(|(target, _)| target.as_str());
 
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
         for (target, target_result) in sorted_targets {
+            let samples = &target_result.rtt_samples;
             println!(
-                "  {:25} | {:5} | {:6.1}% | {:4.1}% | {:6.0}ms | {:5.0}ms",
+                "  {:25} | {:5} | {:6.1}% | {:4.1}% | {:5.1}ms | {:5.1}ms | {:5.1}ms | {:5.1}ms | {:4.1}ms",
                 self.truncate_target(target, 25),
                 target_result.total_pings,
                 if target_result.total_pings > 0 {
@@ -408,19 +1310,45 @@ impl NetworkJitterTest {
                     0.0
                 },
                 target_result.packet_loss_rate,
-                target_result.average_rtt.as_millis(),
-                target_result.jitter.as_millis()
+                ms(target_result.average_rtt),
+                ms(self.calculate_percentile(samples, 50.0)),
+                ms(self.calculate_percentile(samples, 95.0)),
+                ms(self.calculate_percentile(samples, 99.0)),
+                ms(target_result.jitter)
             );
         }
         println!();
 
-        self.print_network_quality_analysis(result);
+        let correlations = self.loss_correlations(result);
+        if !correlations.is_empty() {
+            println!("Loss Correlation:");
+            for pair in &correlations {
+                println!(
+                    "  {} / {}: {} joint loss(es) ({} / {} of {} slots), correlation {}",
+                    pair.target_a,
+                    pair.target_b,
+                    pair.joint_losses,
+                    pair.losses_a,
+                    pair.losses_b,
+                    pair.slots,
+                    pair.correlation
+                        .map(|c| format!("{c:.2}"))
+                        .unwrap_or_else(|| "n/a".to_string())
+                );
+            }
+            if correlations
+                .iter()
+                .any(|pair| pair.correlation.is_some_and(|c| c >= 0.5))
+            {
+                println!("  ⚠ Losses coincide across targets: likely a shared bottleneck (proxy or local link)");
+            }
+            println!();
+        }
 
-        let network_score = self.calculate_network_quality_score(result);
-        println!("Network Quality Score: {network_score:.1}/100");
+        print_failure_breakdown(&result.failures);
     }
 
-    fn print_network_quality_analysis(&self, result: &NetworkJitterResult) {
+    fn print_network_quality_analysis(&self, result: &PingSeries) {
         println!("Network Quality Analysis:");
 
         if result.packet_loss_rate <= 0.1 {
@@ -479,7 +1407,7 @@ impl NetworkJitterTest {
         println!();
     }
 
-    fn calculate_latency_consistency(&self, result: &NetworkJitterResult) -> f64 {
+    fn calculate_latency_consistency(&self, result: &PingSeries) -> f64 {
         if result.rtt_samples.len() <= 1 {
             return 1.0;
         }
@@ -502,7 +1430,7 @@ impl NetworkJitterTest {
         (1.0 - coefficient_of_variation.min(1.0)).max(0.0)
     }
 
-    fn calculate_target_consistency(&self, result: &NetworkJitterResult) -> f64 {
+    fn calculate_target_consistency(&self, result: &PingSeries) -> f64 {
         if result.target_results.len() <= 1 {
             return 1.0;
         }
@@ -535,7 +1463,7 @@ impl NetworkJitterTest {
         (1.0 - coefficient_of_variation.min(1.0)).max(0.0)
     }
 
-    fn calculate_network_quality_score(&self, result: &NetworkJitterResult) -> f64 {
+    fn calculate_network_quality_score(&self, result: &PingSeries) -> f64 {
         let packet_loss_score = (100.0 - result.packet_loss_rate * 10.0).max(0.0);
 
         let latency_score = if result.average_rtt <= Duration::from_millis(50) {
@@ -577,3 +1505,106 @@ impl NetworkJitterTest {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pong() {
+        assert_eq!(
+            parse_pong("PONG 7 123456\n"),
+            Some(Pong {
+                sequence: 7,
+                server_times: None
+            })
+        );
+        assert_eq!(
+            parse_pong("PONG 7 100 250 260\n"),
+            Some(Pong {
+                sequence: 7,
+                server_times: Some((250, 260))
+            })
+        );
+        assert_eq!(parse_pong("PONG\n"), None);
+        assert_eq!(parse_pong("PONG x 1"), None);
+        assert_eq!(parse_pong("PING 7 1"), None);
+    }
+
+    #[test]
+    fn test_rfc3550_jitter() {
+        let test = NetworkJitterTest::new("127.0.0.1:1080", Vec::new(), 1000, 1);
+        let ms = Duration::from_millis;
+
+        assert_eq!(
+            test.calculate_rfc3550_jitter(&[ms(10), ms(10), ms(10)]),
+            ms(0)
+        );
+        // 16ms step: J = 16/16 = 1ms, then J = 1 + (16 - 1)/16
+        let jitter = test.calculate_rfc3550_jitter(&[ms(10), ms(26), ms(10)]);
+        assert!((jitter.as_secs_f64() * 1000.0 - 1.9375).abs() < 1e-6);
+        assert_eq!(
+            test.calculate_ipdv(&[ms(10), ms(26), ms(20)]),
+            vec![ms(16), ms(6)]
+        );
+    }
+
+    #[test]
+    fn test_e_model() {
+        let r = r_factor(Duration::ZERO, 0.0);
+        assert!((r - 93.2).abs() < 1e-9);
+        assert!((mos_from_r_factor(r) - 4.41).abs() < 0.01);
+
+        // Delay beyond 177.3ms and loss both cost rating
+        assert!(r_factor(Duration::from_millis(250), 0.0) < 80.0);
+        assert!(
+            r_factor(Duration::from_millis(20), 5.0) < r_factor(Duration::from_millis(20), 1.0)
+        );
+        assert_eq!(r_factor(Duration::from_secs(2), 50.0), 0.0);
+        assert_eq!(mos_from_r_factor(0.0), 1.0);
+        assert_eq!(r_factor_rating(93.2), "very satisfied");
+    }
+
+    #[test]
+    fn test_one_way_delay_split() {
+        let test = NetworkJitterTest::new("127.0.0.1:1080", Vec::new(), 1000, 1);
+        let mut target_result = PingSeries::new(PingMode::Tunnel, &["t".to_string()])
+            .target_results
+            .remove("t")
+            .unwrap();
+        // Server clock 1000us ahead; 10us each way at best, then 40us of
+        // queueing on the upstream leg
+        let ping = |sent: i64, up: i64, down: i64| ProbeTimestamps {
+            client_sent: sent,
+            server_received: sent + up + 1000,
+            server_sent: sent + up + 1005,
+            client_received: sent + up + 5 + down,
+        };
+        target_result.timestamps = vec![ping(0, 10, 10), ping(100, 50, 10)];
+        test.split_one_way_delays(&mut target_result);
+
+        let us = Duration::from_micros;
+        assert_eq!(target_result.clock_offset, Some(1000));
+        assert_eq!(target_result.upstream_delays, vec![us(10), us(50)]);
+        assert_eq!(target_result.downstream_delays, vec![us(10), us(10)]);
+
+        let ms = Duration::from_millis;
+        assert_eq!(slower_direction(ms(8), ms(3)), "upstream");
+        assert_eq!(slower_direction(ms(3), ms(8)), "downstream");
+        assert_eq!(slower_direction(us(500), us(100)), "balanced");
+        assert_eq!(slower_direction(ms(20), ms(18)), "balanced");
+    }
+
+    #[test]
+    fn test_loss_correlation() {
+        let a = [false, true, false, true, false, false];
+        assert!((loss_correlation(&a, &a).unwrap() - 1.0).abs() < 1e-9);
+
+        let inverse: Vec<bool> = a.iter().map(|lost| !lost).collect();
+        assert!((loss_correlation(&a, &inverse).unwrap() + 1.0).abs() < 1e-9);
+
+        // No variance in either series leaves correlation undefined
+        assert_eq!(loss_correlation(&a, &[false; 6]), None);
+        assert_eq!(loss_correlation(&[], &a), None);
+    }
+}
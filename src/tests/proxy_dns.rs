@@ -0,0 +1,487 @@
+use super::print_failure_breakdown;
+use crate::live::LivePanel;
+use crate::metrics::{EventLog, FailureTally, ProxyDnsMetrics, ResolutionPathMetrics, TestEvent};
+use crate::samples::{millis, Sample, SampleRecorder};
+use crate::{HostResolution, NetworkTestError, Result, Socks5Client};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::lookup_host;
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+/// Resolution overhead below this is indistinguishable from connect jitter.
+const CACHE_MISS_FLOOR: Duration = Duration::from_millis(5);
+
+#[derive(Debug, Clone)]
+pub struct ProxyDnsTest {
+    proxy_addr: String,
+    target_addr: String,
+    literal_addr: Option<String>,
+    resolutions: Vec<HostResolution>,
+    rounds: usize,
+    round_interval: Duration,
+    connect_timeout: Duration,
+    live: LivePanel,
+    samples: SampleRecorder,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnectPathResult {
+    /// `None` for the literal-IP baseline.
+    pub resolution: Option<HostResolution>,
+    pub attempts: u64,
+    pub successful: u64,
+    pub connect_times: Vec<Duration>,
+    /// Connect time minus the literal-IP connect time of the same round;
+    /// only the first round can be a cold lookup.
+    pub cold_overhead: Option<Duration>,
+    pub warm_overheads: Vec<Duration>,
+    /// Hostname CONNECTs that failed in a round where the literal IP worked.
+    pub resolution_failures: u64,
+    pub failures: FailureTally,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProxyDnsResult {
+    pub literal_addr: String,
+    pub literal: ConnectPathResult,
+    pub hostname: Vec<ConnectPathResult>,
+    pub events: Vec<TestEvent>,
+}
+
+impl ConnectPathResult {
+    fn new(resolution: Option<HostResolution>) -> Self {
+        Self {
+            resolution,
+            attempts: 0,
+            successful: 0,
+            connect_times: Vec::new(),
+            cold_overhead: None,
+            warm_overheads: Vec::new(),
+            resolution_failures: 0,
+            failures: FailureTally::default(),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self.resolution {
+            Some(resolution) => format!("{} ({})", resolution, resolution.scheme()),
+            None => "literal IP".to_string(),
+        }
+    }
+}
+
+impl ProxyDnsResult {
+    pub fn failures(&self) -> FailureTally {
+        let mut failures = self.literal.failures.clone();
+        for path in &self.hostname {
+            failures.merge(&path.failures);
+        }
+        failures
+    }
+}
+
+impl ProxyDnsTest {
+    pub fn new(proxy_addr: &str, target_addr: &str, rounds: usize, round_interval_ms: u64) -> Self {
+        Self {
+            proxy_addr: proxy_addr.to_string(),
+            target_addr: target_addr.to_string(),
+            literal_addr: None,
+            resolutions: vec![HostResolution::Remote, HostResolution::Local],
+            rounds: rounds.max(1),
+            round_interval: Duration::from_millis(round_interval_ms),
+            connect_timeout: Duration::from_secs(10),
+            live: LivePanel::default(),
+            samples: SampleRecorder::default(),
+        }
+    }
+
+    /// Literal address of the same service; resolved locally when not set.
+    pub fn with_literal_addr(mut self, literal_addr: Option<String>) -> Self {
+        self.literal_addr = literal_addr;
+        self
+    }
+
+    pub fn with_resolutions(mut self, resolutions: Vec<HostResolution>) -> Self {
+        if !resolutions.is_empty() {
+            self.resolutions = resolutions;
+        }
+        let mut seen = HashSet::new();
+        self.resolutions
+            .retain(|resolution| seen.insert(*resolution));
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, connect_timeout_sec: u64) -> Self {
+        self.connect_timeout = Duration::from_secs(connect_timeout_sec.max(1));
+        self
+    }
+
+    pub fn with_live(mut self, live: LivePanel) -> Self {
+        self.live = live;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: SampleRecorder) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub async fn run(&self) -> Result<ProxyDnsResult> {
+        let result = self.execute().await;
+        let _pause = self.live.finish(result.is_ok());
+        let result = result?;
+
+        if !self.samples.owns_stdout() {
+            self.print_results(&result);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn execute(&self) -> Result<ProxyDnsResult> {
+        info!(
+            "Starting proxy DNS test through proxy {} to {}",
+            self.proxy_addr, self.target_addr
+        );
+
+        let proxy_addr: SocketAddr = self
+            .proxy_addr
+            .parse()
+            .map_err(|e| NetworkTestError::Config(format!("Invalid proxy address: {e}")))?;
+        let literal_addr = self.literal_addr().await?;
+        info!("Literal-IP baseline: {}", literal_addr);
+        self.live.start(None);
+
+        let baseline_client = Socks5Client::new(proxy_addr).with_timeout(self.connect_timeout);
+        let hostname_clients: Vec<Socks5Client> = self
+            .resolutions
+            .iter()
+            .map(|resolution| baseline_client.clone().with_resolution(*resolution))
+            .collect();
+
+        let mut literal = ConnectPathResult::new(None);
+        let mut hostname: Vec<ConnectPathResult> = self
+            .resolutions
+            .iter()
+            .map(|resolution| ConnectPathResult::new(Some(*resolution)))
+            .collect();
+        let mut events = EventLog::new("proxy_dns").with_live(self.live.clone());
+
+        for round in 0..self.rounds {
+            let literal_time = self
+                .timed_connect(&baseline_client, &literal_addr, &mut literal, &mut events)
+                .await;
+
+            for (client, path) in hostname_clients.iter().zip(hostname.iter_mut()) {
+                let connect_time = self
+                    .timed_connect(client, &self.target_addr, path, &mut events)
+                    .await;
+                match (connect_time, literal_time) {
+                    (Some(connect_time), Some(literal_time)) => {
+                        let overhead = connect_time.saturating_sub(literal_time);
+                        if round == 0 {
+                            path.cold_overhead = Some(overhead);
+                        } else {
+                            path.warm_overheads.push(overhead);
+                        }
+                    }
+                    (None, Some(_)) => {
+                        path.resolution_failures += 1;
+                        warn!(
+                            "Round {}: CONNECT to {} failed with {} resolution while the literal IP worked",
+                            round + 1,
+                            self.target_addr,
+                            path.label()
+                        );
+                    }
+                    _ => {}
+                }
+            }
+
+            if round + 1 < self.rounds {
+                sleep(self.round_interval).await;
+            }
+        }
+
+        Ok(ProxyDnsResult {
+            literal_addr,
+            literal,
+            hostname,
+            events: events.into_events(),
+        })
+    }
+
+    async fn literal_addr(&self) -> Result<String> {
+        if let Some(ref literal_addr) = self.literal_addr {
+            return Ok(literal_addr.clone());
+        }
+
+        let addrs: Vec<SocketAddr> = lookup_host(self.target_addr.as_str())
+            .await
+            .map_err(|e| {
+                NetworkTestError::Config(format!("Cannot resolve {}: {e}", self.target_addr))
+            })?
+            .collect();
+        addrs
+            .iter()
+            .find(|addr| addr.is_ipv4())
+            .or(addrs.first())
+            .map(|addr| addr.to_string())
+            .ok_or_else(|| {
+                NetworkTestError::Config(format!("No addresses for {}", self.target_addr))
+            })
+    }
+
+    async fn timed_connect(
+        &self,
+        client: &Socks5Client,
+        target_addr: &str,
+        path: &mut ConnectPathResult,
+        events: &mut EventLog,
+    ) -> Option<Duration> {
+        let event_target = format!("{} ({})", target_addr, path.label());
+        path.attempts += 1;
+        let connect_start = Instant::now();
+
+        match client.connect(target_addr).await {
+            Ok(_stream) => {
+                let connect_time = connect_start.elapsed();
+                debug!(
+                    "CONNECT to {} ({}) took {:?}",
+                    target_addr,
+                    path.label(),
+                    connect_time
+                );
+                path.successful += 1;
+                path.connect_times.push(connect_time);
+                events.live().rtt(connect_time);
+                events.recovered(&event_target);
+                self.samples.record(
+                    &event_target,
+                    Sample::ConnectionAttempt {
+                        success: true,
+                        connect_time_ms: Some(millis(connect_time)),
+                        error: None,
+                    },
+                );
+                Some(connect_time)
+            }
+            Err(e) => {
+                debug!(
+                    "CONNECT to {} ({}) failed: {}",
+                    target_addr,
+                    path.label(),
+                    e
+                );
+                path.failures.record(&e);
+                events.error(&event_target, &e);
+                self.samples.record(
+                    &event_target,
+                    Sample::ConnectionAttempt {
+                        success: false,
+                        connect_time_ms: None,
+                        error: Some(e.to_string()),
+                    },
+                );
+                None
+            }
+        }
+    }
+
+    fn calculate_percentile(&self, samples: &[Duration], percentile: f64) -> Duration {
+        if samples.is_empty() {
+            return Duration::ZERO;
+        }
+
+        let mut sorted_samples = samples.to_vec();
+        sorted_samples.sort();
+
+        let index = ((percentile / 100.0) * (sorted_samples.len() - 1) as f64).round() as usize;
+        sorted_samples[index.min(sorted_samples.len() - 1)]
+    }
+
+    fn path_metrics(&self, path: &ConnectPathResult) -> ResolutionPathMetrics {
+        let warm = &path.warm_overheads;
+        let warm_overhead = (!warm.is_empty()).then(|| self.calculate_percentile(warm, 50.0));
+
+        // A warm lookup is slow when it costs a good part of the cold one
+        let miss_threshold = CACHE_MISS_FLOOR.max(path.cold_overhead.unwrap_or_default() / 2);
+        let cache_misses = warm.iter().filter(|o| **o > miss_threshold).count() as u64;
+
+        let verdict = path.resolution.map(|_| {
+            Self::judge_caching(path, warm.len() as u64, cache_misses, warm_overhead).to_string()
+        });
+
+        ResolutionPathMetrics {
+            path: path.label(),
+            resolution: path.resolution,
+            attempts: path.attempts,
+            successful: path.successful,
+            success_rate: if path.attempts > 0 {
+                path.successful as f64 / path.attempts as f64 * 100.0
+            } else {
+                0.0
+            },
+            median_connect_time: self.calculate_percentile(&path.connect_times, 50.0),
+            p95_connect_time: self.calculate_percentile(&path.connect_times, 95.0),
+            cold_overhead: path.cold_overhead,
+            warm_overhead,
+            cache_misses,
+            resolution_failures: path.resolution_failures,
+            verdict,
+        }
+    }
+
+    pub fn judge_caching(
+        path: &ConnectPathResult,
+        warm_rounds: u64,
+        cache_misses: u64,
+        warm_overhead: Option<Duration>,
+    ) -> &'static str {
+        if path.successful == 0 {
+            "resolution failing"
+        } else if path.resolution_failures > 0 {
+            "intermittent resolution failures"
+        } else if warm_rounds == 0 {
+            "insufficient data"
+        } else if warm_overhead.is_some_and(|o| o > CACHE_MISS_FLOOR)
+            && cache_misses * 2 > warm_rounds
+        {
+            "not caching"
+        } else if cache_misses > 0 {
+            "inconsistent caching"
+        } else {
+            "cached"
+        }
+    }
+
+    pub fn to_metrics(&self, result: &ProxyDnsResult) -> ProxyDnsMetrics {
+        ProxyDnsMetrics {
+            hostname_target: self.target_addr.clone(),
+            literal_target: result.literal_addr.clone(),
+            rounds: self.rounds,
+            round_interval: self.round_interval,
+            paths: std::iter::once(&result.literal)
+                .chain(&result.hostname)
+                .map(|path| self.path_metrics(path))
+                .collect(),
+            failures: result.failures().breakdown(),
+        }
+    }
+
+    fn print_results(&self, result: &ProxyDnsResult) {
+        let metrics = self.to_metrics(result);
+
+        println!("\n=== Proxy DNS Resolution Test Results ===");
+        println!("Proxy: {}", self.proxy_addr);
+        println!("Hostname Target: {}", self.target_addr);
+        println!("Literal Target: {}", result.literal_addr);
+        println!("Rounds: {}", self.rounds);
+        println!("Round Interval: {:?}", self.round_interval);
+        println!();
+
+        println!("CONNECT Setup Time:");
+        println!(
+            "  Path               | Attempts | Success | Median  | p95     | Cold DNS | Warm DNS"
+        );
+        println!(
+            "  -------------------|----------|---------|---------|---------|----------|---------"
+        );
+        for path in &metrics.paths {
+            let overhead = |o: Option<Duration>| {
+                o.map(|o| format!("{:6.1}ms", o.as_secs_f64() * 1000.0))
+                    .unwrap_or_else(|| format!("{:>8}", "-"))
+            };
+            println!(
+                "  {:18} | {:8} | {:6.1}% | {:5.0}ms | {:5.0}ms | {} | {}",
+                path.path,
+                path.attempts,
+                path.success_rate,
+                path.median_connect_time.as_millis(),
+                path.p95_connect_time.as_millis(),
+                overhead(path.cold_overhead),
+                overhead(path.warm_overhead)
+            );
+        }
+        println!();
+
+        println!("Resolution Behaviour:");
+        for path in metrics.paths.iter().filter(|p| p.resolution.is_some()) {
+            println!(
+                "  {}: {} ({} slow warm lookup(s), {} resolution failure(s))",
+                path.path,
+                path.verdict.as_deref().unwrap_or("-"),
+                path.cache_misses,
+                path.resolution_failures
+            );
+        }
+        println!();
+
+        print_failure_breakdown(&result.failures());
+
+        for path in metrics.paths.iter().filter(|p| p.resolution.is_some()) {
+            match path.verdict.as_deref() {
+                Some("cached") => println!("✓ {}: repeated lookups are cached", path.path),
+                Some(verdict) => println!("✗ {}: {}", path.path, verdict),
+                None => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolutions_deduplicated_in_order() {
+        let test =
+            ProxyDnsTest::new("127.0.0.1:1080", "example.com:80", 1, 0).with_resolutions(vec![
+                HostResolution::Local,
+                HostResolution::Remote,
+                HostResolution::Local,
+            ]);
+        assert_eq!(
+            test.resolutions,
+            vec![HostResolution::Local, HostResolution::Remote]
+        );
+    }
+
+    #[test]
+    fn test_judge_caching() {
+        let mut path = ConnectPathResult::new(Some(HostResolution::Remote));
+        let slow = Some(CACHE_MISS_FLOOR * 4);
+        assert_eq!(
+            ProxyDnsTest::judge_caching(&path, 0, 0, None),
+            "resolution failing"
+        );
+
+        path.successful = 10;
+        assert_eq!(
+            ProxyDnsTest::judge_caching(&path, 0, 0, None),
+            "insufficient data"
+        );
+        assert_eq!(ProxyDnsTest::judge_caching(&path, 9, 0, slow), "cached");
+        // A slow median only means "not caching" when most warm rounds miss
+        assert_eq!(
+            ProxyDnsTest::judge_caching(&path, 9, 5, slow),
+            "not caching"
+        );
+        assert_eq!(
+            ProxyDnsTest::judge_caching(&path, 9, 4, slow),
+            "inconsistent caching"
+        );
+        assert_eq!(
+            ProxyDnsTest::judge_caching(&path, 9, 5, Some(CACHE_MISS_FLOOR)),
+            "inconsistent caching"
+        );
+
+        path.resolution_failures = 1;
+        assert_eq!(
+            ProxyDnsTest::judge_caching(&path, 9, 0, None),
+            "intermittent resolution failures"
+        );
+    }
+}
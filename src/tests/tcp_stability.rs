@@ -1,4 +1,13 @@
-use crate::{NetworkTestError, Result, Socks5Client};
+use super::print_failure_breakdown;
+use crate::live::LivePanel;
+use crate::metrics::{
+    ConnectionDropMetrics, EventKind, EventLog, FailureCategory, FailureTally, TcpStabilityMetrics,
+    TestEvent,
+};
+use crate::samples::{millis, Sample, SampleRecorder};
+use crate::{NetworkTestError, Phase, Result, Socks5Client};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::time::{sleep, timeout};
@@ -10,6 +19,8 @@ pub struct TcpStabilityTest {
     target_addr: String,
     heartbeat_interval: Duration,
     test_duration: Duration,
+    live: LivePanel,
+    samples: SampleRecorder,
 }
 
 #[derive(Debug, Clone)]
@@ -22,12 +33,17 @@ pub struct TcpStabilityResult {
     pub average_rtt: Duration,
     pub max_rtt: Duration,
     pub min_rtt: Duration,
+    pub rtt_samples: Vec<Duration>,
     pub connection_drops: Vec<ConnectionDrop>,
+    pub failures: FailureTally,
+    pub events: Vec<TestEvent>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectionDrop {
-    pub timestamp: Instant,
+    pub timestamp: DateTime<Utc>,
+    /// Monotonic time from the start of the test to the drop.
+    pub offset: Duration,
     pub duration: Duration,
     pub reason: String,
 }
@@ -44,10 +60,34 @@ impl TcpStabilityTest {
             target_addr: target_addr.to_string(),
             heartbeat_interval: Duration::from_secs(heartbeat_interval_sec),
             test_duration: Duration::from_secs(test_duration_sec),
+            live: LivePanel::default(),
+            samples: SampleRecorder::default(),
         }
     }
 
-    pub async fn run(&self) -> Result<()> {
+    pub fn with_live(mut self, live: LivePanel) -> Self {
+        self.live = live;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: SampleRecorder) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub async fn run(&self) -> Result<TcpStabilityResult> {
+        let result = self.execute().await;
+        let _pause = self.live.finish(result.is_ok());
+        let result = result?;
+
+        if !self.samples.owns_stdout() {
+            self.print_results(&result);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn execute(&self) -> Result<TcpStabilityResult> {
         info!("Starting TCP stability test");
         info!("Proxy: {}, Target: {}", self.proxy_addr, self.target_addr);
         info!(
@@ -62,14 +102,11 @@ impl TcpStabilityTest {
 
         let client = Socks5Client::new(proxy_addr).with_timeout(Duration::from_secs(10));
 
-        let result = self.run_stability_test(&client).await?;
-
-        self.print_results(&result);
-
-        Ok(())
+        self.run_stability_test(&client).await
     }
 
     async fn run_stability_test(&self, client: &Socks5Client) -> Result<TcpStabilityResult> {
+        self.live.start(Some(self.test_duration));
         let start_time = Instant::now();
         let end_time = start_time + self.test_duration;
 
@@ -82,12 +119,19 @@ impl TcpStabilityTest {
             average_rtt: Duration::ZERO,
             max_rtt: Duration::ZERO,
             min_rtt: Duration::from_secs(u64::MAX),
+            rtt_samples: Vec::new(),
             connection_drops: Vec::new(),
+            failures: FailureTally::default(),
+            events: Vec::new(),
         };
+        let mut events = EventLog::new("tcp_stability").with_live(self.live.clone());
+        let target = self.target_addr.as_str();
 
         let mut rtt_sum = Duration::ZERO;
         let mut last_connection_attempt = Instant::now();
+        let mut drop_time = events.clock().now();
         let mut connection_broken = false;
+        let mut drop_reason = String::new();
 
         // Establish initial connection
         info!("Establishing initial connection...");
@@ -97,9 +141,8 @@ impl TcpStabilityTest {
                 Some(tcp_stream)
             }
             Err(e) => {
-                return Err(NetworkTestError::Connection(format!(
-                    "Failed to establish initial connection: {e}"
-                )));
+                warn!("Failed to establish initial connection: {}", e);
+                return Err(e);
             }
         };
 
@@ -116,16 +159,26 @@ impl TcpStabilityTest {
                         result.total_downtime += downtime;
 
                         result.connection_drops.push(ConnectionDrop {
-                            timestamp: last_connection_attempt,
+                            timestamp: drop_time.0,
+                            offset: drop_time.1,
                             duration: downtime,
-                            reason: "Connection lost - reconnected".to_string(),
+                            reason: format!("{drop_reason} - reconnected"),
                         });
+                        events.reconnected(target, format!("after {downtime:?} downtime"));
+                        self.samples.record(
+                            target,
+                            Sample::Reconnect {
+                                downtime_ms: millis(downtime),
+                            },
+                        );
 
                         info!("Reconnected after {:?} downtime", downtime);
                         connection_broken = false;
                     }
                     Err(e) => {
                         warn!("Failed to reconnect: {}", e);
+                        result.failures.record(&e);
+                        events.error(target, &e);
                         sleep(Duration::from_secs(1)).await;
                         continue;
                     }
@@ -149,6 +202,17 @@ impl TcpStabilityTest {
                         let rtt = heartbeat_start.elapsed();
                         result.successful_heartbeats += 1;
                         rtt_sum += rtt;
+                        result.rtt_samples.push(rtt);
+                        self.live.rtt(rtt);
+                        self.samples.record(
+                            target,
+                            Sample::Heartbeat {
+                                sequence: result.total_heartbeats,
+                                success: true,
+                                rtt_ms: Some(millis(rtt)),
+                                error: None,
+                            },
+                        );
 
                         if rtt > result.max_rtt {
                             result.max_rtt = rtt;
@@ -164,6 +228,14 @@ impl TcpStabilityTest {
                     }
                     Ok(Err(e)) => {
                         result.failed_heartbeats += 1;
+                        result.failures.record(&e);
+                        drop_reason = FailureCategory::from(&e).to_string();
+                        events.failure(target, EventKind::ConnectionDrop, drop_reason.as_str());
+                        self.record_heartbeat_failure(
+                            target,
+                            result.total_heartbeats,
+                            &drop_reason,
+                        );
                         warn!(
                             "Heartbeat {} failed, connection broken: {}",
                             result.total_heartbeats, e
@@ -171,9 +243,21 @@ impl TcpStabilityTest {
                         stream = None;
                         connection_broken = true;
                         last_connection_attempt = Instant::now();
+                        drop_time = events.clock().now();
                     }
                     Err(_) => {
+                        let category = FailureCategory::Timeout {
+                            phase: Phase::Exchange,
+                        };
                         result.failed_heartbeats += 1;
+                        result.failures.record_category(category);
+                        drop_reason = category.to_string();
+                        events.category(target, category);
+                        self.record_heartbeat_failure(
+                            target,
+                            result.total_heartbeats,
+                            &drop_reason,
+                        );
                         warn!(
                             "Heartbeat {} timed out, connection may be broken",
                             result.total_heartbeats
@@ -181,6 +265,7 @@ impl TcpStabilityTest {
                         stream = None;
                         connection_broken = true;
                         last_connection_attempt = Instant::now();
+                        drop_time = events.clock().now();
                     }
                 }
             }
@@ -188,6 +273,21 @@ impl TcpStabilityTest {
             sleep(self.heartbeat_interval).await;
         }
 
+        // A drop that never recovered lasts until the end of the test
+        if connection_broken {
+            let downtime = end_time
+                .min(Instant::now())
+                .saturating_duration_since(last_connection_attempt);
+            result.total_downtime += downtime;
+            result.connection_drops.push(ConnectionDrop {
+                timestamp: drop_time.0,
+                offset: drop_time.1,
+                duration: downtime,
+                reason: format!("{drop_reason} - not recovered"),
+            });
+            warn!("Connection still down at end of test after {:?}", downtime);
+        }
+
         if result.successful_heartbeats > 0 {
             result.average_rtt = rtt_sum / result.successful_heartbeats as u32;
         }
@@ -196,18 +296,45 @@ impl TcpStabilityTest {
             result.min_rtt = Duration::ZERO;
         }
 
+        result.events = events.into_events();
         Ok(result)
     }
 
+    /// A failed heartbeat is also where the connection is given up.
+    fn record_heartbeat_failure(&self, target: &str, sequence: u64, reason: &str) {
+        self.samples.record(
+            target,
+            Sample::Heartbeat {
+                sequence,
+                success: false,
+                rtt_ms: None,
+                error: Some(reason.to_string()),
+            },
+        );
+        self.samples.record(
+            target,
+            Sample::Drop {
+                reason: reason.to_string(),
+            },
+        );
+    }
+
     async fn send_heartbeat(&self, stream: &mut tokio::net::TcpStream, data: &str) -> Result<()> {
-        stream.write_all(data.as_bytes()).await?;
+        stream
+            .write_all(data.as_bytes())
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
 
         let mut buffer = [0u8; 1024];
-        let n = stream.read(&mut buffer).await?;
+        let n = stream
+            .read(&mut buffer)
+            .await
+            .map_err(|e| NetworkTestError::io(Phase::Exchange, e))?;
 
         if n == 0 {
-            return Err(NetworkTestError::Connection(
-                "Connection closed by peer".to_string(),
+            return Err(NetworkTestError::io(
+                Phase::Exchange,
+                std::io::ErrorKind::UnexpectedEof.into(),
             ));
         }
 
@@ -259,21 +386,17 @@ impl TcpStabilityTest {
             println!("  Total Downtime: {:?}", result.total_downtime);
             println!("  Connection Drops: {}", result.connection_drops.len());
 
-            let uptime_percentage = if self.test_duration > result.total_downtime {
-                ((self.test_duration - result.total_downtime).as_secs_f64()
-                    / self.test_duration.as_secs_f64())
-                    * 100.0
-            } else {
-                0.0
-            };
+            let uptime_percentage = self.calculate_uptime_percentage(result);
             println!("  Uptime: {uptime_percentage:.2}%");
 
             if result.connection_drops.len() <= 5 {
                 println!("\n  Connection Drop Details:");
                 for (i, drop) in result.connection_drops.iter().enumerate() {
                     println!(
-                        "    Drop {}: Duration {:?}, Reason: {}",
+                        "    Drop {}: At {} (+{:?}), Duration {:?}, Reason: {}",
                         i + 1,
+                        drop.timestamp.format("%H:%M:%S%.3f"),
+                        drop.offset,
                         drop.duration,
                         drop.reason
                     );
@@ -285,7 +408,25 @@ impl TcpStabilityTest {
 
         println!();
 
-        let stability_score = if result.total_heartbeats > 0 {
+        print_failure_breakdown(&result.failures);
+
+        let stability_score = self.calculate_stability_score(result);
+
+        println!("Overall Stability Score: {stability_score:.1}/100");
+    }
+
+    fn calculate_uptime_percentage(&self, result: &TcpStabilityResult) -> f64 {
+        if self.test_duration > result.total_downtime {
+            ((self.test_duration - result.total_downtime).as_secs_f64()
+                / self.test_duration.as_secs_f64())
+                * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    fn calculate_stability_score(&self, result: &TcpStabilityResult) -> f64 {
+        if result.total_heartbeats > 0 {
             let success_rate = result.successful_heartbeats as f64 / result.total_heartbeats as f64;
             let connection_stability = if result.reconnections == 0 {
                 1.0
@@ -295,8 +436,47 @@ impl TcpStabilityTest {
             (success_rate * connection_stability * 100.0).min(100.0)
         } else {
             0.0
+        }
+    }
+
+    pub fn to_metrics(&self, result: &TcpStabilityResult) -> TcpStabilityMetrics {
+        let mean_rtt = result.average_rtt.as_secs_f64() * 1000.0;
+        let rtt_variance = if result.rtt_samples.is_empty() {
+            0.0
+        } else {
+            result
+                .rtt_samples
+                .iter()
+                .map(|rtt| (rtt.as_secs_f64() * 1000.0 - mean_rtt).powi(2))
+                .sum::<f64>()
+                / result.rtt_samples.len() as f64
         };
 
-        println!("Overall Stability Score: {stability_score:.1}/100");
+        TcpStabilityMetrics {
+            test_duration: self.test_duration,
+            heartbeat_interval: self.heartbeat_interval,
+            total_heartbeats: result.total_heartbeats,
+            successful_heartbeats: result.successful_heartbeats,
+            failed_heartbeats: result.failed_heartbeats,
+            reconnections: result.reconnections,
+            total_downtime: result.total_downtime,
+            uptime_percentage: self.calculate_uptime_percentage(result),
+            average_rtt: result.average_rtt,
+            min_rtt: result.min_rtt,
+            max_rtt: result.max_rtt,
+            rtt_variance,
+            stability_score: self.calculate_stability_score(result),
+            connection_drops: result
+                .connection_drops
+                .iter()
+                .map(|drop| ConnectionDropMetrics {
+                    timestamp: drop.timestamp,
+                    offset: drop.offset,
+                    duration: drop.duration,
+                    reason: drop.reason.clone(),
+                })
+                .collect(),
+            failures: result.failures.breakdown(),
+        }
     }
 }
@@ -0,0 +1,143 @@
+use crate::{NetworkTestError, Phase, Result};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{
+    verify_tls12_signature, verify_tls13_signature, CryptoProvider,
+};
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{
+    ClientConfig, DigitallySignedStruct, HandshakeKind, RootCertStore, SignatureScheme,
+};
+use tokio_rustls::TlsConnector;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy)]
+pub struct TlsHandshake {
+    pub handshake_time: Duration,
+    pub resumed: bool,
+}
+
+/// Builds a connector for one ALPN protocol. Reusing the connector shares its
+/// session cache, so later handshakes to the same server can resume.
+pub fn tls_connector(alpn: &[u8], insecure: bool) -> TlsConnector {
+    let builder = ClientConfig::builder();
+    let mut config = if insecure {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate::default()))
+            .with_no_client_auth()
+    } else {
+        let roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        builder.with_root_certificates(roots).with_no_client_auth()
+    };
+    config.alpn_protocols = vec![alpn.to_vec()];
+
+    TlsConnector::from(Arc::new(config))
+}
+
+pub async fn handshake(
+    connector: &TlsConnector,
+    server: &str,
+    stream: TcpStream,
+) -> Result<(TlsStream<TcpStream>, TlsHandshake)> {
+    let host = server_host(server);
+    let name = ServerName::try_from(host.to_string())
+        .map_err(|e| NetworkTestError::Config(format!("Invalid TLS server name '{host}': {e}")))?;
+
+    let handshake_start = Instant::now();
+    let stream = tokio::time::timeout(HANDSHAKE_TIMEOUT, connector.connect(name, stream))
+        .await
+        .map_err(|_| {
+            NetworkTestError::timeout(Phase::TlsHandshake, format!("TLS handshake with {server}"))
+        })?
+        .map_err(|e| NetworkTestError::io(Phase::TlsHandshake, e))?;
+    let handshake_time = handshake_start.elapsed();
+
+    let resumed = stream.get_ref().1.handshake_kind() == Some(HandshakeKind::Resumed);
+    Ok((
+        stream,
+        TlsHandshake {
+            handshake_time,
+            resumed,
+        },
+    ))
+}
+
+/// Host part of a `host:port` address, without IPv6 brackets.
+pub fn server_host(server: &str) -> &str {
+    let host = match server.rsplit_once(':') {
+        Some((host, port)) if port.parse::<u16>().is_ok() => host,
+        _ => server,
+    };
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+/// For lab setups with self-signed certificates; signatures are still checked
+/// so the handshake itself stays honest.
+#[derive(Debug)]
+struct AcceptAnyCertificate {
+    provider: Arc<CryptoProvider>,
+}
+
+impl Default for AcceptAnyCertificate {
+    fn default() -> Self {
+        Self {
+            provider: Arc::new(tokio_rustls::rustls::crypto::ring::default_provider()),
+        }
+    }
+}
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}